@@ -3,10 +3,21 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::fs;
+#[cfg(any(unix, feature = "http"))]
+use std::path::Path;
 use std::path::PathBuf;
 
 use dump_syms::common;
-use dump_syms::mac::print_macho_architectures;
+#[cfg(unix)]
+use dump_syms::daemon;
+use dump_syms::doctor::{doctor, DoctorConfig};
+use dump_syms::inspect::inspect;
+use dump_syms::jobs::read_jobs;
+use dump_syms::list::list;
+#[cfg(feature = "http")]
+use dump_syms::server;
+use dump_syms::sym_diff::diff_sym;
 use dump_syms::utils;
 
 use dump_syms::dumper::{self, Config};
@@ -14,16 +25,97 @@ use dump_syms::dumper::{self, Config};
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum Action<'a> {
     Dump(Config<'a>),
+    /// Dump the modules listed in a job file
+    DumpJobs {
+        config: Config<'a>,
+        jobs_file: &'a str,
+    },
     ListArch,
+    List,
+    CleanCache(Config<'a>),
+    /// Dump the files requested on the socket
+    #[cfg(unix)]
+    Daemon(Config<'a>),
+    #[cfg(feature = "http")]
+    Fetch(Config<'a>),
+    Inspect,
+    Diff,
+    Doctor(DoctorConfig<'a>),
+    #[cfg(feature = "http")]
+    Upload {
+        url: &'a str,
+        auth_token: Option<&'a str>,
+        dry_run: bool,
+    },
+    #[cfg(feature = "http")]
+    Serve {
+        address: &'a str,
+    },
 }
 
 impl Action<'_> {
     pub(super) fn action(&self, filenames: &[&str]) -> common::Result<()> {
+        match self {
+            Self::CleanCache(config) => return dumper::clean_caches(config),
+            Self::Dump(config) if config.dry_run => {
+                for f in filenames {
+                    print!("{}", dumper::dry_run(config, f)?);
+                }
+                return Ok(());
+            }
+            Self::DumpJobs { config, jobs_file } => {
+                let jobs = read_jobs(jobs_file)?;
+                let report = dumper::dump_jobs(config, &jobs)?;
+                print!("{}", report);
+                report.result()?;
+                return match config.cache_limits.is_empty() {
+                    true => Ok(()),
+                    false => dumper::clean_caches(config),
+                };
+            }
+            #[cfg(unix)]
+            Self::Daemon(config) => return daemon::serve(config, Path::new(filenames[0])),
+            Self::Diff => return diff(filenames[0], filenames[1]),
+            Self::Doctor(config) => {
+                let report = doctor(config, filenames)?;
+                print!("{}", report);
+                anyhow::ensure!(report.errors() == 0, "{} check(s) failed", report.errors());
+                return Ok(());
+            }
+            #[cfg(feature = "http")]
+            Self::Serve { address } => return server::serve(Path::new(filenames[0]), address),
+            #[cfg(feature = "http")]
+            Self::Upload {
+                url,
+                auth_token,
+                dry_run,
+            } => {
+                for f in filenames {
+                    for path in get_sym_files(Path::new(f))? {
+                        if *dry_run {
+                            let data = fs::read(&path)?;
+                            let url = dump_syms::cache::get_upload_url(url, &data, &path)?;
+                            println!("{}: {}", path.display(), url);
+                        } else {
+                            dump_syms::cache::upload_sym_file(url, *auth_token, &path)?;
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+
         if filenames.len() == 1 {
             // no need to spawn a thread for one file
-            self.single_file(filenames[0])
+            self.single_file(filenames[0])?;
         } else {
-            self.several_files(filenames)
+            self.several_files(filenames)?;
+        }
+
+        match self {
+            Self::Dump(config) if !config.cache_limits.is_empty() => dumper::clean_caches(config),
+            _ => Ok(()),
         }
     }
 
@@ -34,9 +126,21 @@ impl Action<'_> {
                 let path = PathBuf::from(filename);
                 let filename = utils::get_filename(&path);
 
-                let buf = utils::read_file(&path);
-                print_macho_architectures(&buf, filename)
+                let buf = utils::read_file(&path)?;
+                list_arch(&buf, filename)
+            }
+            Self::List => {
+                print!("{}", list(&PathBuf::from(filename))?);
+                Ok(())
             }
+            #[cfg(feature = "http")]
+            Self::Fetch(config) => dumper::fetch(config, filename),
+            Self::Inspect => {
+                let buf = utils::read_file(filename)?;
+                print!("{}", inspect(&buf, filename)?);
+                Ok(())
+            }
+            _ => Ok(()),
         }
     }
 
@@ -48,8 +152,15 @@ impl Action<'_> {
                     let path = PathBuf::from(f);
                     let filename = utils::get_filename(&path);
 
-                    let buf = utils::read_file(&path);
-                    print_macho_architectures(&buf, filename)?;
+                    let buf = utils::read_file(&path)?;
+                    list_arch(&buf, filename)?;
+                }
+                Ok(())
+            }
+            // The other ones are run file by file
+            _ => {
+                for f in filenames {
+                    self.single_file(f)?;
                 }
                 Ok(())
             }
@@ -57,38 +168,88 @@ impl Action<'_> {
     }
 }
 
+/// Print the records which differ between two symbol files
+fn diff(left: &str, right: &str) -> common::Result<()> {
+    let left = String::from_utf8(fs::read(left)?)?;
+    let right = String::from_utf8(fs::read(right)?)?;
+    for line in diff_sym(&left, &right) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Get the symbol files in a directory (e.g. a symbol store) or the file itself
+#[cfg(feature = "http")]
+fn get_sym_files(path: &Path) -> common::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(get_sym_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "sym") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(feature = "macho")]
+fn list_arch(buf: &[u8], filename: String) -> common::Result<()> {
+    dump_syms::mac::print_macho_architectures(buf, filename)
+}
+
+#[cfg(not(feature = "macho"))]
+fn list_arch(_buf: &[u8], _filename: String) -> common::Result<()> {
+    anyhow::bail!("Mach-O support not enabled")
+}
+
 #[cfg(test)]
 mod tests {
 
+    use dump_syms::collector::{
+        CompilandFilter, GapFill, InnerPublics, ParseLimits, PublicFilter, Thunks,
+    };
+    use dump_syms::common::FailureKind;
+    use dump_syms::mapping::PathGlobs;
+    use dump_syms::metrics::{MetricsSink, ModuleMetrics};
+    use dump_syms::outlined::Outlined;
     use regex::Regex;
     use std::fs::{copy, read};
+    use std::sync::{Arc, Mutex};
     use tempfile::Builder;
 
     use super::*;
 
     // Read and process the input so it can be compared with the output
     fn read_input(input_path: &str) -> Vec<String> {
-        let generator_re = Regex::new(r"INFO GENERATOR mozilla/dump_syms XYZ\n").unwrap();
+        let generator_re = Regex::new(r"INFO GENERATOR mozilla/dump_syms XYZ").unwrap();
         let generator_string = format!(
-            "INFO GENERATOR mozilla/dump_syms {}\n",
+            "INFO GENERATOR mozilla/dump_syms {}",
             env!("CARGO_PKG_VERSION")
         );
         let basic = PathBuf::from(input_path);
         let data = read(basic).unwrap();
         let data = String::from_utf8(data).unwrap();
         let data = generator_re.replace(&data, generator_string);
-        data.split(|c: char| c == '\n')
-            .skip(1)
-            .map(String::from)
-            .collect()
+        data.split('\n').skip(1).map(String::from).collect()
     }
 
     fn read_output(output_path: &PathBuf) -> Vec<String> {
         let data = read(output_path).unwrap();
         let data = String::from_utf8(data).unwrap();
-        data.split(|c: char| c == '\n')
-            .skip(1)
-            .map(String::from)
+        data.split('\n').skip(1).map(String::from).collect()
+    }
+
+    // Remove the INFO GENERATOR record to compare outputs generated with other features
+    fn without_generator(lines: Vec<String>) -> Vec<String> {
+        lines
+            .into_iter()
+            .filter(|l| !l.starts_with("INFO GENERATOR"))
             .collect()
     }
 
@@ -103,17 +264,8 @@ mod tests {
 
         let action = Action::Dump(Config {
             output: tmp_out.clone().into(),
-            symbol_server: None,
-            debug_id: None,
-            code_id: None,
-            arch: common::get_compile_time_arch(),
             num_jobs: 1,
-            mapping_var: None,
-            mapping_src: None,
-            mapping_dest: None,
-            mapping_file: None,
-            check_cfi: false,
-            emit_inlines: false,
+            ..Default::default()
         });
 
         action.action(&[tmp_file.to_str().unwrap()]).unwrap();
@@ -125,6 +277,60 @@ mod tests {
         assert!(!data.contains("STACK CFI"));
     }
 
+    #[test]
+    fn test_dry_run() {
+        let tmp_dir = Builder::new().prefix("dry_run").tempdir().unwrap();
+        let store = tmp_dir.path().join("store");
+        let dll = "./test_data/windows/basic64.dll";
+        let full = "./test_data/linux/basic.full";
+
+        let config = Config {
+            output: dumper::Output::Store(store.clone()),
+            incremental: true,
+            dry_run: true,
+            ..Default::default()
+        };
+        Action::Dump(config).action(&[dll, full]).unwrap();
+        // Nothing is written
+        assert!(!store.exists());
+
+        let config = Config {
+            output: dumper::Output::Store(store.clone()),
+            incremental: true,
+            dry_run: true,
+            ..Default::default()
+        };
+        let out = dumper::dry_run(&config, dll).unwrap();
+        let sym = store.join("basic64.pdb/D09EA7D6D2C24C1EBFFE78B0C866BB7F2/basic64.sym");
+        assert_eq!(
+            out,
+            format!(
+                "{}:
+  format: pe
+  pdb: ./test_data/windows/basic64.pdb
+  module: basic64.pdb
+  debug id: D09EA7D6D2C24C1EBFFE78B0C866BB7F2
+  store: {}
+  action: dump
+",
+                dll,
+                sym.display()
+            )
+        );
+
+        // The symbols are up to date once they've been dumped
+        let action = Action::Dump(Config {
+            output: dumper::Output::Store(store),
+            ..Default::default()
+        });
+        action.action(&[dll]).unwrap();
+        let out = dumper::dry_run(&config, dll).unwrap();
+        assert!(out.ends_with("  action: skip, the symbols are up to date\n"));
+
+        let out = dumper::dry_run(&config, "./test_data/linux/basic.full.sym").unwrap();
+        assert!(out.ends_with("  action: fail, unsupported file format\n"));
+    }
+
     #[test]
     fn test_missing_cfi() {
         let tmp_dir = Builder::new().prefix("missing_cfi").tempdir().unwrap();
@@ -136,23 +342,553 @@ mod tests {
 
         let action = Action::Dump(Config {
             output: tmp_out.into(),
-            symbol_server: None,
-            debug_id: None,
-            code_id: None,
-            arch: common::get_compile_time_arch(),
             num_jobs: 1,
-            mapping_var: None,
-            mapping_src: None,
-            mapping_dest: None,
-            mapping_file: None,
             check_cfi: true,
-            emit_inlines: false,
+            ..Default::default()
         });
 
         let res = action.action(&[tmp_file.to_str().unwrap()]);
         assert!(res.is_err());
     }
 
+    #[derive(Default)]
+    struct Metrics(Mutex<Vec<ModuleMetrics>>);
+
+    impl MetricsSink for Metrics {
+        fn module_dumped(&self, metrics: &ModuleMetrics) {
+            self.0.lock().unwrap().push(metrics.clone());
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let tmp_dir = Builder::new().prefix("metrics").tempdir().unwrap();
+        let pdb = PathBuf::from("./test_data/windows/basic64.pdb");
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let sink = Arc::new(Metrics::default());
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            metrics: Some(Arc::clone(&sink) as Arc<dyn MetricsSink>),
+            ..Default::default()
+        });
+        action.action(&[pdb.to_str().unwrap()]).unwrap();
+
+        let data = String::from_utf8(read(tmp_out).unwrap()).unwrap();
+        let count = |prefix: &str| data.lines().filter(|l| l.starts_with(prefix)).count();
+        let metrics = sink.0.lock().unwrap();
+        assert_eq!(metrics.len(), 1);
+        let metrics = &metrics[0];
+        assert_eq!(metrics.name, "basic64.pdb");
+        assert_eq!(metrics.debug_id, "D09EA7D6D2C24C1EBFFE78B0C866BB7F2");
+        assert_eq!(metrics.functions, count("FUNC "));
+        assert_eq!(metrics.publics, count("PUBLIC "));
+        assert_eq!(metrics.cfi, count("STACK "));
+        assert_eq!(
+            metrics.lines,
+            data.lines()
+                .filter(|l| l
+                    .split(' ')
+                    .next()
+                    .unwrap()
+                    .chars()
+                    .all(|c| c.is_ascii_hexdigit()))
+                .count()
+        );
+        assert!(metrics.functions > 0 && metrics.lines > 0 && metrics.cfi > 0);
+    }
+
+    #[test]
+    fn test_metrics_sidecar() {
+        let tmp_dir = Builder::new().prefix("metrics_sidecar").tempdir().unwrap();
+        let store = tmp_dir.path().join("store");
+        let cache = tmp_dir.path().join("cache");
+        let sidecar =
+            store.join("basic64.pdb/D09EA7D6D2C24C1EBFFE78B0C866BB7F2/basic64.metrics.json");
+        let dump = || {
+            let action = Action::Dump(Config {
+                output: dumper::Output::Store(store.clone()),
+                sym_cache: Some(cache.to_str().unwrap()),
+                metrics_sidecar: true,
+                ..Default::default()
+            });
+            action.action(&["./test_data/windows/basic64.pdb"]).unwrap();
+            let mut metrics: serde_json::Value =
+                serde_json::from_slice(&read(&sidecar).unwrap()).unwrap();
+            let metrics = metrics.as_object_mut().unwrap();
+            for key in ["parse_ms", "store_ms"] {
+                assert!(metrics.remove(key).unwrap().is_u64());
+            }
+            metrics.clone()
+        };
+
+        let parsed = dump();
+        let sym = read(sidecar.with_file_name("basic64.sym")).unwrap();
+        assert_eq!(parsed["name"], "basic64.pdb");
+        assert_eq!(parsed["debug_id"], "D09EA7D6D2C24C1EBFFE78B0C866BB7F2");
+        assert_eq!(parsed["cpu"], "x86_64");
+        assert_eq!(parsed["size"], sym.len());
+        assert_eq!(
+            parsed["files"],
+            sym.split(|c| *c == b'\n')
+                .filter(|l| l.starts_with(b"FILE "))
+                .count()
+        );
+        assert!(parsed["functions"].as_u64().unwrap() > 0);
+
+        // The counters of the symbols from the cache are the same
+        assert_eq!(dump(), parsed);
+    }
+
+    #[test]
+    fn test_archive() {
+        use std::io::Write;
+
+        let tmp_dir = Builder::new().prefix("archive").tempdir().unwrap();
+        let zip_path = tmp_dir.path().join("artifacts.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+        for (name, path) in [
+            ("README.txt", "./test_data/windows/basic.cpp"),
+            ("bin/basic.full", "./test_data/linux/basic.full"),
+            ("bin/basic64.dll", "./test_data/windows/basic64.dll"),
+            ("pdb/basic64.pdb", "./test_data/windows/basic64.pdb"),
+        ] {
+            zip.start_file(name, Default::default()).unwrap();
+            zip.write_all(&read(path).unwrap()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        // An entry of the archive
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            ..Default::default()
+        });
+        let entry = format!("{}!/bin/basic.full", zip_path.display());
+        action.action(&[&entry]).unwrap();
+        let data = String::from_utf8(read(&tmp_out).unwrap()).unwrap();
+        assert!(
+            data.starts_with("MODULE Linux x86_64 20AD60B0B4C68177552708AA192E77390 basic.full")
+        );
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            ..Default::default()
+        });
+        let entry = format!("{}!bin/basic.so", zip_path.display());
+        assert!(action.action(&[&entry]).is_err());
+
+        // All the objects of the archive, the dll and the pdb are dumped together
+        let store = tmp_dir.path().join("store");
+        let action = Action::Dump(Config {
+            output: dumper::Output::Store(store.clone()),
+            ..Default::default()
+        });
+        action.action(&[zip_path.to_str().unwrap()]).unwrap();
+        assert!(store
+            .join("basic.full/20AD60B0B4C68177552708AA192E77390/basic.full.sym")
+            .exists());
+        let sym =
+            read(store.join("basic64.pdb/D09EA7D6D2C24C1EBFFE78B0C866BB7F2/basic64.sym")).unwrap();
+        let sym = String::from_utf8(sym).unwrap();
+        assert!(sym.contains("CODE_ID"));
+        assert!(sym.contains("STACK CFI"));
+
+        // Nothing to dump
+        let empty = tmp_dir.path().join("empty.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&empty).unwrap());
+        zip.start_file("README.txt", Default::default()).unwrap();
+        zip.finish().unwrap();
+        let e = action.action(&[empty.to_str().unwrap()]).unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::UnsupportedFormat);
+    }
+
+    #[test]
+    fn test_pair_pe_pdb() {
+        let tmp_dir = Builder::new().prefix("pair").tempdir().unwrap();
+        let bin = tmp_dir.path().join("bin");
+        let symbols = tmp_dir.path().join("symbols");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::create_dir_all(&symbols).unwrap();
+        for name in ["basic64", "basic-opt64"] {
+            let path = PathBuf::from("./test_data/windows").join(name);
+            copy(
+                path.with_extension("dll"),
+                bin.join(name).with_extension("dll"),
+            )
+            .unwrap();
+            copy(
+                path.with_extension("pdb"),
+                symbols.join(name).with_extension("pdb"),
+            )
+            .unwrap();
+        }
+        // The age of the pdb in the debug directory of the dll doesn't match
+        let dll = bin.join("basic-opt64.dll");
+        let mut data = read(&dll).unwrap();
+        let rsds = data.windows(4).position(|w| w == b"RSDS").unwrap();
+        data[rsds + 20] += 1;
+        std::fs::write(&dll, data).unwrap();
+
+        let store = tmp_dir.path().join("store");
+        let action = Action::Dump(Config {
+            output: dumper::Output::Store(store.clone()),
+            num_jobs: 2,
+            ..Default::default()
+        });
+        let files = [
+            bin.join("basic64.dll"),
+            dll,
+            symbols.join("basic64.pdb"),
+            symbols.join("basic-opt64.pdb"),
+        ];
+        let files: Vec<_> = files.iter().map(|f| f.to_str().unwrap()).collect();
+        action.action(&files).unwrap();
+
+        // The symbols are the ones of a pdb next to its dll
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            ..Default::default()
+        });
+        action.action(&["./test_data/windows/basic64.pdb"]).unwrap();
+        let sym =
+            read(store.join("basic64.pdb/D09EA7D6D2C24C1EBFFE78B0C866BB7F2/basic64.sym")).unwrap();
+        assert_eq!(sym, read(&tmp_out).unwrap());
+
+        let sym = String::from_utf8(
+            read(store.join("basic-opt64.pdb/2B02EEDFFB7C497B9F3A107E5193B3652/basic-opt64.sym"))
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(sym.contains("INFO CODE_ID"));
+        assert!(sym.contains("basic-opt64.dll"));
+        assert!(sym.contains("STACK CFI"));
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let tmp_dir = Builder::new().prefix("limits").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let dump = |limits| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                limits,
+                num_jobs: 1,
+                ..Default::default()
+            });
+            action.action(&[full.to_str().unwrap()])
+        };
+
+        let e = dump(ParseLimits {
+            max_symbols: Some(10),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::LimitExceeded);
+        assert_eq!(
+            e.to_string(),
+            "Limits exceeded: more than 10 symbols (--max-symbols)"
+        );
+
+        let e = dump(ParseLimits {
+            max_expansion: Some(0),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::LimitExceeded);
+
+        dump(ParseLimits {
+            max_symbols: Some(1000),
+            max_lines: Some(10000),
+            max_string_len: Some(1024),
+            max_expansion: Some(1),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_batch_partial_success() {
+        let tmp_dir = Builder::new().prefix("partial_success").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let cpp = PathBuf::from("./test_data/linux/basic.cpp");
+
+        let action = Action::Dump(Config {
+            output: dumper::Output::Store(tmp_dir.path().into()),
+            num_jobs: 2,
+            ..Default::default()
+        });
+
+        let e = action
+            .action(&[cpp.to_str().unwrap(), full.to_str().unwrap()])
+            .unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::PartialSuccess);
+        assert!(tmp_dir
+            .path()
+            .join("basic.full/20AD60B0B4C68177552708AA192E77390/basic.full.sym")
+            .exists());
+
+        let e = action
+            .action(&[cpp.to_str().unwrap(), cpp.to_str().unwrap()])
+            .unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::UnsupportedFormat);
+    }
+
+    #[test]
+    fn test_jobs_file() {
+        let tmp_dir = Builder::new().prefix("jobs_file").tempdir().unwrap();
+        let store = tmp_dir.path().join("store");
+        let jobs_file = tmp_dir.path().join("jobs.json");
+        std::fs::write(
+            &jobs_file,
+            r#"[
+                {"debug_file": "./test_data/linux/basic.full", "debug_id": "20AD60B0B4C68177552708AA192E77390"},
+                {"code_file": "missing.dll", "code_id": "5F3A1B2C8D34000", "debug_file": "C:\\missing.pdb"}
+            ]"#,
+        )
+        .unwrap();
+
+        let config = Config {
+            output: dumper::Output::Store(store.clone()),
+            ..Default::default()
+        };
+        let jobs = read_jobs(&jobs_file).unwrap();
+        let report = dumper::dump_jobs(&config, &jobs).unwrap();
+        assert_eq!(
+            report.to_string(),
+            "ok: ./test_data/linux/basic.full 20AD60B0B4C68177552708AA192E77390
+error: missing.dll 5F3A1B2C8D34000: Impossible to get the file from the symbol servers
+"
+        );
+        assert!(store
+            .join("basic.full/20AD60B0B4C68177552708AA192E77390/basic.full.sym")
+            .exists());
+
+        let action = Action::DumpJobs {
+            config,
+            jobs_file: jobs_file.to_str().unwrap(),
+        };
+        let e = action.action(&[]).unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::PartialSuccess);
+
+        // The symbols are written in a store only
+        let action = Action::DumpJobs {
+            config: Config::default(),
+            jobs_file: jobs_file.to_str().unwrap(),
+        };
+        let e = action.action(&[]).unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::Usage);
+    }
+
+    #[test]
+    fn test_ssqp_store_layout() {
+        let tmp_dir = Builder::new().prefix("ssqp").tempdir().unwrap();
+        let action = Action::Dump(Config {
+            output: dumper::Output::Store(tmp_dir.path().into()),
+            store_layout: dumper::StoreLayout::Ssqp,
+            ..Default::default()
+        });
+        action.action(&["./test_data/windows/basic64.pdb"]).unwrap();
+
+        assert!(tmp_dir
+            .path()
+            .join("basic64.pdb/d09ea7d6d2c24c1ebffe78b0c866bb7f2/basic64.sym")
+            .exists());
+    }
+
+    #[test]
+    fn test_source_url_template() {
+        let tmp_dir = Builder::new().prefix("source_url").tempdir().unwrap();
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let checkout = tmp_dir.path().join("checkout");
+        std::fs::create_dir_all(checkout.join(".git")).unwrap();
+        std::fs::write(checkout.join(".git/HEAD"), "abcdef\n").unwrap();
+
+        // The sources are mapped in the checkout to get its revision
+        let dest = format!("{}/src/{{1}}", checkout.display());
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            mapping_src: Some(vec!["/home/calixte/dev/mozilla/dump_syms.calixteman/(.*)"]),
+            mapping_dest: Some(vec![&dest]),
+            source_url_template: Some(
+                "https://raw.githubusercontent.com/org/repo/{rev}/{path}"
+                    .parse()
+                    .unwrap(),
+            ),
+            ..Default::default()
+        });
+        action.action(&["./test_data/linux/basic.full"]).unwrap();
+
+        let data = read_output(&tmp_out);
+        assert!(data.iter().any(|l| l
+            == "FILE 0 https://raw.githubusercontent.com/org/repo/abcdef/src/test_data/linux/basic.cpp"));
+        assert!(!data
+            .iter()
+            .any(|l| l.starts_with("FILE") && !l.contains("https://")));
+    }
+
+    #[test]
+    fn test_prune_lines() {
+        let tmp_dir = Builder::new().prefix("prune_lines").tempdir().unwrap();
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let dump = |globs: &[&str]| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                prune_lines: PathGlobs::new(globs).unwrap(),
+                ..Default::default()
+            });
+            action.action(&["./test_data/windows/basic64.pdb"]).unwrap();
+            read_output(&tmp_out)
+        };
+        let line_files = |data: &[String]| -> Vec<String> {
+            data.iter()
+                .filter(|l| {
+                    let first = l.split(' ').next().unwrap();
+                    l.contains(' ') && first.chars().all(|c| c.is_ascii_hexdigit())
+                })
+                .map(|l| l.rsplit(' ').next().unwrap().to_string())
+                .collect()
+        };
+
+        let full = dump(&[]);
+        let pruned = dump(&["**/msvc/**"]);
+        assert!(pruned[1].ends_with(" +prune-lines"));
+
+        // The files 1 to 5 are the MSVC headers
+        assert!(line_files(&full).iter().any(|f| f == "1"));
+        let files = line_files(&pruned);
+        assert!(!files.is_empty());
+        assert!(files
+            .iter()
+            .all(|f| !["1", "2", "3", "4", "5"].contains(&f.as_str())));
+
+        // The FUNC and FILE records are kept
+        let count =
+            |data: &[String], prefix: &str| data.iter().filter(|l| l.starts_with(prefix)).count();
+        assert_eq!(count(&full, "FUNC "), count(&pruned, "FUNC "));
+        assert_eq!(count(&full, "FILE "), count(&pruned, "FILE "));
+    }
+
+    #[test]
+    fn test_prune_lines_sym_cache() {
+        let tmp_dir = Builder::new()
+            .prefix("prune_lines_cache")
+            .tempdir()
+            .unwrap();
+        let cache = tmp_dir.path().join("cache");
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let dump = |globs: &[&str]| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                sym_cache: Some(cache.to_str().unwrap()),
+                prune_lines: PathGlobs::new(globs).unwrap(),
+                ..Default::default()
+            });
+            action.action(&["./test_data/linux/basic.full"]).unwrap();
+            read_output(&tmp_out)
+        };
+
+        let pruned = dump(&["**"]);
+        assert!(pruned[1].ends_with(" +prune-lines"));
+
+        // The pruned symbols in the cache aren't the ones without the globs
+        let basic = read_input("./test_data/linux/basic.full.sym");
+        assert_eq!(dump(&[]), basic);
+        assert_eq!(dump(&["**"]), pruned);
+    }
+
+    #[test]
+    fn test_compiland_filter() {
+        let tmp_dir = Builder::new().prefix("compilands").tempdir().unwrap();
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let dump = |include: &[&str], exclude: &[&str]| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                compiland_filter: CompilandFilter::new(include, exclude).unwrap(),
+                ..Default::default()
+            });
+            action.action(&["./test_data/windows/basic64.pdb"]).unwrap();
+            read_output(&tmp_out)
+        };
+        let funcs = |data: &[String]| -> Vec<String> {
+            data.iter()
+                .filter(|l| l.starts_with("FUNC "))
+                .map(|l| l.splitn(5, ' ').last().unwrap().to_string())
+                .collect()
+        };
+
+        let full = dump(&[], &[]);
+        assert!(funcs(&full).iter().any(|f| f == "__security_init_cookie()"));
+
+        // Only the functions of the project
+        let own = dump(&["basic64.obj"], &[]);
+        assert!(own[1].ends_with(" +compiland-filter"));
+        let own_funcs = funcs(&own);
+        assert!(own_funcs.iter().any(|f| f == "test1(int*)"));
+        assert!(!own_funcs.iter().any(|f| f == "__security_init_cookie()"));
+        assert!(!own
+            .iter()
+            .any(|l| l.starts_with("PUBLIC ") && l.ends_with(" __security_init_cookie")));
+
+        // Without the C runtime
+        let no_crt = dump(&[], &["libcmt.lib", "libvcruntime.lib", "libucrt.lib"]);
+        assert!(funcs(&no_crt).iter().any(|f| f == "test1(int*)"));
+        assert!(!funcs(&no_crt)
+            .iter()
+            .any(|f| f == "__security_init_cookie()"));
+        assert!(funcs(&no_crt).len() < funcs(&full).len());
+    }
+
+    #[test]
+    fn test_git_info() {
+        let tmp_dir = Builder::new().prefix("git_info").tempdir().unwrap();
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let checkout = tmp_dir.path().join("checkout");
+        std::fs::create_dir_all(checkout.join(".git")).unwrap();
+        std::fs::write(checkout.join(".git/HEAD"), "abcdef\n").unwrap();
+        std::fs::write(
+            checkout.join(".git/config"),
+            "[remote \"origin\"]\n\turl = https://github.com/org/repo.git\n",
+        )
+        .unwrap();
+
+        let dest = format!("{}/src/{{1}}", checkout.display());
+        let dump = |source_url_template: Option<&str>| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                mapping_src: Some(vec!["/home/calixte/dev/mozilla/dump_syms.calixteman/(.*)"]),
+                mapping_dest: Some(vec![&dest]),
+                source_url_template: source_url_template.map(|t| t.parse().unwrap()),
+                git_info: true,
+                ..Default::default()
+            });
+            action.action(&["./test_data/linux/basic.full"]).unwrap();
+            read_output(&tmp_out)
+        };
+
+        let data = dump(None);
+        assert!(data[1].ends_with(" +git-info"));
+        let file = format!(
+            "FILE 0 {}/src/test_data/linux/basic.cpp",
+            checkout.display()
+        );
+        let pos = data.iter().position(|l| *l == file).unwrap();
+        assert_eq!(
+            data[pos + 1],
+            "FILE_GIT 0 abcdef https://github.com/org/repo.git src/test_data/linux/basic.cpp"
+        );
+
+        // The checkouts are found before the files are rewritten
+        let data = dump(Some("https://src/{rev}/{path}"));
+        let pos = data
+            .iter()
+            .position(|l| l == "FILE 0 https://src/abcdef/src/test_data/linux/basic.cpp")
+            .unwrap();
+        assert!(data[pos + 1].starts_with("FILE_GIT 0 abcdef "));
+    }
+
     #[test]
     fn test_missing_pe_but_in_dir() {
         let tmp_dir = Builder::new().prefix("no_pe").tempdir().unwrap();
@@ -167,17 +903,8 @@ mod tests {
 
         let action = Action::Dump(Config {
             output: tmp_out.clone().into(),
-            symbol_server: None,
-            debug_id: None,
-            code_id: None,
-            arch: common::get_compile_time_arch(),
             num_jobs: 1,
-            mapping_var: None,
-            mapping_src: None,
-            mapping_dest: None,
-            mapping_file: None,
-            check_cfi: false,
-            emit_inlines: false,
+            ..Default::default()
         });
 
         action.action(&[tmp_pdb.to_str().unwrap()]).unwrap();
@@ -189,6 +916,135 @@ mod tests {
         assert!(data.contains("STACK CFI"));
     }
 
+    #[test]
+    fn test_missing_pdb_with_linker_map() {
+        let tmp_dir = Builder::new().prefix("linker_map").tempdir().unwrap();
+        let mozwer = PathBuf::from("./test_data/windows/mozwer.dll");
+        let tmp_dll = tmp_dir.path().join("mozwer.dll");
+        let tmp_map = tmp_dir.path().join("mozwer.map");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        copy(mozwer, &tmp_dll).unwrap();
+        std::fs::write(
+            &tmp_map,
+            r#"
+ Preferred load address is 0000000180000000
+
+  Address         Publics by Value              Rva+Base               Lib:Object
+
+ 0001:00000310       ?foo@@YAXXZ                0000000180001310 f   foo.obj
+ 0001:000003a0       _bar@8                     00000001800013a0 f   bar.obj
+"#,
+        )
+        .unwrap();
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            num_jobs: 1,
+            ..Default::default()
+        });
+
+        action.action(&[tmp_dll.to_str().unwrap()]).unwrap();
+
+        let data = read(tmp_out).unwrap();
+        let data = String::from_utf8(data).unwrap();
+
+        // The symbols are marked as incomplete without a pdb
+        assert!(data.contains("\nINFO PROVENANCE pe functions\n"));
+        // The placeholder function from the exception data gets a name
+        assert!(data.contains("\nFUNC 1310 64 0 foo(void)\n"));
+        // and the missing function is added up to the next symbol
+        assert!(data.contains("\nFUNC 13a0 20 8 bar\n"));
+    }
+
+    #[test]
+    fn test_linker_map_folded_functions() {
+        let tmp_dir = Builder::new().prefix("folded").tempdir().unwrap();
+        let tmp_dll = tmp_dir.path().join("mozwer.dll");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        copy("./test_data/windows/mozwer.dll", &tmp_dll).unwrap();
+        std::fs::write(
+            tmp_dir.path().join("mozwer.map"),
+            r#"
+ Preferred load address is 0000000180000000
+
+  Address         Publics by Value              Rva+Base               Lib:Object
+
+ 0001:00000310       ?foo@@YAXXZ                0000000180001310 f   foo.obj
+ 0001:00000310       ?baz@@YAXXZ                0000000180001310 f   baz.obj
+ 0001:000003c0       ?qux@@YAXXZ                00000001800013c0 f   qux.obj
+ 0001:000003c0       ?qux@@YAXXZ                00000001800013c0 f   qux2.obj
+"#,
+        )
+        .unwrap();
+
+        let dump = |priority: &str| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                num_jobs: 1,
+                priority: priority.parse().unwrap(),
+                ..Default::default()
+            });
+            action.action(&[tmp_dll.to_str().unwrap()]).unwrap();
+            String::from_utf8(read(&tmp_out).unwrap()).unwrap()
+        };
+        let data = dump("");
+
+        // The functions folded with /OPT:ICF are marked like in the PDB files
+        assert!(data.contains("\nFUNC m 1310 64 0 foo(void)\n"));
+        // but not the same function in two object files
+        assert!(data.contains("\nFUNC 13c0 21 0 qux(void)\n"));
+
+        // even when the function keeps another name
+        let data = dump("exception-data");
+        assert!(data.contains("\nFUNC m 1310 64 0 "));
+        assert!(!data.contains("foo(void)"));
+    }
+
+    #[test]
+    fn test_linker_map_gap_fill() {
+        let tmp_dir = Builder::new().prefix("gap_fill").tempdir().unwrap();
+        let tmp_dll = tmp_dir.path().join("mozwer.dll");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        copy("./test_data/windows/mozwer.dll", &tmp_dll).unwrap();
+        std::fs::write(
+            tmp_dir.path().join("mozwer.map"),
+            r#"
+ Preferred load address is 0000000180000000
+
+  Address         Publics by Value              Rva+Base               Lib:Object
+
+ 0001:00000310       ?foo@@YAXXZ                0000000180001310 f   foo.obj
+ 0001:000003a0       _bar@8                     00000001800013a0 f   bar.obj
+"#,
+        )
+        .unwrap();
+
+        let dump = |gap_fill| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                num_jobs: 1,
+                gap_fill,
+                ..Default::default()
+            });
+            action.action(&[tmp_dll.to_str().unwrap()]).unwrap();
+            String::from_utf8(read(&tmp_out).unwrap()).unwrap()
+        };
+
+        // The function without a size in the map is only a public symbol
+        let data = dump(GapFill::Off);
+        assert!(data.contains("\nFUNC 1310 64 0 foo(void)\n"));
+        assert!(data.contains("\nPUBLIC 13a0 8 bar\n"));
+        assert!(!data.contains("\nPUBLIC 1374 0 <unknown in mozwer.dll>\n"));
+
+        // and the code after the functions is unknown
+        let data = dump(GapFill::Unknown);
+        assert!(data.contains("\nPUBLIC 13a0 8 bar\n"));
+        assert!(data.contains("\nPUBLIC 1374 0 <unknown in mozwer.dll>\n"));
+    }
+
     #[test]
     fn test_elf_full() {
         let tmp_dir = Builder::new().prefix("full").tempdir().unwrap();
@@ -197,17 +1053,8 @@ mod tests {
 
         let action = Action::Dump(Config {
             output: tmp_out.clone().into(),
-            symbol_server: None,
-            debug_id: None,
-            code_id: None,
-            arch: common::get_compile_time_arch(),
             num_jobs: 1,
-            mapping_var: None,
-            mapping_src: None,
-            mapping_dest: None,
-            mapping_file: None,
-            check_cfi: false,
-            emit_inlines: false,
+            ..Default::default()
         });
 
         action.action(&[full.to_str().unwrap()]).unwrap();
@@ -219,64 +1066,1054 @@ mod tests {
     }
 
     #[test]
-    fn test_elf_full_with_inlines() {
-        let tmp_dir = Builder::new().prefix("full").tempdir().unwrap();
+    fn test_elf_full_map() {
+        let tmp_dir = Builder::new().prefix("full_map").tempdir().unwrap();
         let full = PathBuf::from("./test_data/linux/basic.full");
-        let tmp_out = tmp_dir.path().join("output.sym");
+        let tmp_out = tmp_dir.path().join("output.map");
 
         let action = Action::Dump(Config {
             output: tmp_out.clone().into(),
-            symbol_server: None,
-            debug_id: None,
-            code_id: None,
-            arch: common::get_compile_time_arch(),
+            format: dumper::OutputFormat::Map,
             num_jobs: 1,
-            mapping_var: None,
-            mapping_src: None,
-            mapping_dest: None,
-            mapping_file: None,
-            check_cfi: false,
-            emit_inlines: true,
+            ..Default::default()
         });
 
         action.action(&[full.to_str().unwrap()]).unwrap();
 
-        let new = read_output(&tmp_out);
-        let basic = read_input("./test_data/linux/basic.full.inlines.sym");
+        let data = read(tmp_out).unwrap();
+        let data = String::from_utf8(data).unwrap();
+        let lines: Vec<_> = data.lines().collect();
 
-        assert_eq!(basic, new);
+        assert!(lines.contains(&"000011e4 000000d9 foo(int)"));
+        assert!(lines.contains(&"00001040 0000002b _start"));
+        // Symbols without a size extend up to the next symbol
+        assert!(lines.contains(&"00001020 00000010 <.plt ELF section in basic.full>"));
+
+        let rvas: Vec<_> = lines
+            .iter()
+            .map(|l| u32::from_str_radix(&l[..8], 16).unwrap())
+            .collect();
+        assert!(rvas.windows(2).all(|w| w[0] < w[1]));
     }
 
     #[test]
-    fn test_elf_stripped_dbg() {
-        let tmp_dir = Builder::new().prefix("stripped_dbg").tempdir().unwrap();
-        let stripped = PathBuf::from("./test_data/linux/basic.stripped");
-        let dbg = PathBuf::from("./test_data/linux/basic.dbg");
-        let tmp_out = tmp_dir.path().join("output.sym");
+    fn test_store_formats() {
+        let tmp_dir = Builder::new().prefix("store_formats").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let store = tmp_dir.path().join("store");
+        let module = store.join("basic.full/20AD60B0B4C68177552708AA192E77390");
+
+        for (format, file) in [
+            (dumper::OutputFormat::Map, "basic.full.map"),
+            (dumper::OutputFormat::Json, "basic.full.json"),
+            (dumper::OutputFormat::Breakpad, "basic.full.sym"),
+        ] {
+            let action = Action::Dump(Config {
+                output: dumper::Output::Store(store.clone()),
+                format,
+                num_jobs: 1,
+                ..Default::default()
+            });
+            action.action(&[full.to_str().unwrap()]).unwrap();
+            assert!(module.join(file).exists());
+        }
+
+        // The Breakpad symbols are the only .sym file of the store
+        let sym = read(module.join("basic.full.sym")).unwrap();
+        assert!(sym.starts_with(b"MODULE Linux x86_64 20AD60B0B4C68177552708AA192E77390"));
+        assert_eq!(std::fs::read_dir(&module).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_elf_full_json() {
+        let tmp_dir = Builder::new().prefix("full_json").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let tmp_out = tmp_dir.path().join("output.json");
 
         let action = Action::Dump(Config {
             output: tmp_out.clone().into(),
-            symbol_server: None,
-            debug_id: None,
-            code_id: None,
-            arch: common::get_compile_time_arch(),
-            num_jobs: 2,
-            mapping_var: None,
-            mapping_src: None,
-            mapping_dest: None,
-            mapping_file: None,
-            check_cfi: false,
-            emit_inlines: true,
+            format: dumper::OutputFormat::Json,
+            num_jobs: 1,
+            ..Default::default()
         });
 
-        action
-            .action(&[stripped.to_str().unwrap(), dbg.to_str().unwrap()])
+        action.action(&[full.to_str().unwrap()]).unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&read(tmp_out).unwrap()).unwrap();
+        let basic = read_input("./test_data/linux/basic.full.sym");
+        let count = |prefix: &str| basic.iter().filter(|l| l.starts_with(prefix)).count();
+
+        assert_eq!(json["os"], "Linux");
+        assert_eq!(json["cpu"], "x86_64");
+        assert_eq!(json["debug_id"], "20AD60B0B4C68177552708AA192E77390");
+        assert_eq!(json["name"], "basic.full");
+        assert_eq!(json["code_id"], "B060AD20C6B47781552708AA192E7739FAC7C84A");
+        assert_eq!(json["files"].as_array().unwrap().len(), count("FILE "));
+        assert_eq!(json["functions"].as_array().unwrap().len(), count("FUNC "));
+        assert_eq!(json["publics"].as_array().unwrap().len(), count("PUBLIC "));
+        assert_eq!(
+            json["stack_cfi"].as_array().unwrap().len(),
+            count("STACK CFI INIT ")
+        );
+
+        let foo = json["functions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|f| f["name"] == "foo(int)")
             .unwrap();
+        assert_eq!(foo["address"], 0x11e4);
+        assert_eq!(foo["size"], 0xd9);
+        assert_eq!(foo["lines"][0]["address"], 0x11e4);
+        assert_eq!(foo["lines"][0]["file"], 0);
+    }
 
-        let re = Regex::new(r"<\.plt[\.a-zA-Z]* ELF section in [^>]*>").unwrap();
-        let new = read_output(&tmp_out);
-        let new: Vec<_> = new
-            .into_iter()
+    #[test]
+    fn test_elf_full_legacy_order() {
+        let tmp_dir = Builder::new().prefix("legacy_order").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            legacy_order: true,
+            num_jobs: 1,
+            ..Default::default()
+        });
+
+        action.action(&[full.to_str().unwrap()]).unwrap();
+
+        let new = read_output(&tmp_out);
+        let first_public = new.iter().position(|l| l.starts_with("PUBLIC")).unwrap();
+        let last_func = new.iter().rposition(|l| l.starts_with("FUNC")).unwrap();
+        assert!(last_func < first_public);
+        let generator = format!(
+            "INFO GENERATOR mozilla/dump_syms {} +legacy-order",
+            env!("CARGO_PKG_VERSION")
+        );
+        assert!(new.contains(&generator));
+
+        // Only the order changes
+        let mut new = without_generator(new);
+        let mut basic = without_generator(read_input("./test_data/linux/basic.full.sym"));
+        new.sort();
+        basic.sort();
+        assert_eq!(basic, new);
+    }
+
+    #[test]
+    fn test_module_name() {
+        let tmp_dir = Builder::new().prefix("module_name").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let cache_dir = tmp_dir.path().join("cache");
+        let basic = read_input("./test_data/linux/basic.full.sym");
+
+        // The second dump writes the cached symbols
+        for _ in 0..2 {
+            let action = Action::Dump(Config {
+                output: dumper::Output::Store(tmp_dir.path().into()),
+                module_name: Some("libbasic.so"),
+                sym_cache: cache_dir.to_str(),
+                num_jobs: 1,
+                ..Default::default()
+            });
+            action.action(&[full.to_str().unwrap()]).unwrap();
+
+            let out = tmp_dir
+                .path()
+                .join("libbasic.so/20AD60B0B4C68177552708AA192E77390/libbasic.so.sym");
+            let data = String::from_utf8(read(&out).unwrap()).unwrap();
+            assert_eq!(
+                data.lines().next().unwrap(),
+                "MODULE Linux x86_64 20AD60B0B4C68177552708AA192E77390 libbasic.so"
+            );
+            assert_eq!(basic, read_output(&out));
+            std::fs::remove_file(out).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_module_debug_id() {
+        let tmp_dir = Builder::new().prefix("module_debug_id").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let cache_dir = tmp_dir.path().join("cache");
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let basic = read_input("./test_data/linux/basic.full.sym");
+
+        // The id is in the Breakpad format, the second dump writes the cached symbols
+        for _ in 0..2 {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                module_debug_id: Some("0123456789abcdef0123456789abcdef1"),
+                sym_cache: cache_dir.to_str(),
+                num_jobs: 1,
+                ..Default::default()
+            });
+            action.action(&[full.to_str().unwrap()]).unwrap();
+
+            let data = String::from_utf8(read(&tmp_out).unwrap()).unwrap();
+            assert_eq!(
+                data.lines().next().unwrap(),
+                "MODULE Linux x86_64 0123456789ABCDEF0123456789ABCDEF1 basic.full"
+            );
+            assert_eq!(basic, read_output(&tmp_out));
+        }
+    }
+
+    #[test]
+    fn test_module_os_arch() {
+        let tmp_dir = Builder::new().prefix("module_os_arch").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let cache_dir = tmp_dir.path().join("cache");
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let basic = read_input("./test_data/linux/basic.full.sym");
+
+        for _ in 0..2 {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                module_os: Some("android"),
+                module_arch: Some("arm64"),
+                sym_cache: cache_dir.to_str(),
+                num_jobs: 1,
+                ..Default::default()
+            });
+            action.action(&[full.to_str().unwrap()]).unwrap();
+
+            let data = String::from_utf8(read(&tmp_out).unwrap()).unwrap();
+            assert_eq!(
+                data.lines().next().unwrap(),
+                "MODULE android arm64 20AD60B0B4C68177552708AA192E77390 basic.full"
+            );
+            assert_eq!(basic, read_output(&tmp_out));
+        }
+    }
+
+    #[test]
+    fn test_elf_full_sym_cache() {
+        let tmp_dir = Builder::new().prefix("sym_cache").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let cache_dir = tmp_dir.path().join("cache");
+        let basic = read_input("./test_data/linux/basic.full.sym");
+
+        for i in 0..2 {
+            let tmp_out = tmp_dir.path().join(format!("output{}.sym", i));
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                sym_cache: cache_dir.to_str(),
+                num_jobs: 1,
+                ..Default::default()
+            });
+
+            action.action(&[full.to_str().unwrap()]).unwrap();
+
+            let new = read_output(&tmp_out);
+            assert_eq!(basic, new);
+        }
+
+        let cached = std::fs::read_dir(cache_dir.join("20AD60B0B4C68177552708AA192E77390"))
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().path().extension().unwrap() == "sym")
+            .count();
+        assert_eq!(cached, 1);
+    }
+
+    #[test]
+    fn test_pe_without_pdb_sym_cache() {
+        let tmp_dir = Builder::new().prefix("pe_sym_cache").tempdir().unwrap();
+        let cache_dir = tmp_dir.path().join("cache");
+        let tmp_out = tmp_dir.path().join("output.sym");
+        // The pdb isn't next to the dll
+        let dll = tmp_dir.path().join("basic64.dll");
+        std::fs::copy("./test_data/windows/basic64.dll", &dll).unwrap();
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            sym_cache: cache_dir.to_str(),
+            ..Default::default()
+        });
+        action.action(&[dll.to_str().unwrap()]).unwrap();
+        let output = read_output(&tmp_out);
+        assert!(output.iter().any(|l| l.starts_with("INFO PROVENANCE pe ")));
+
+        // The symbols of the dll alone aren't cached
+        let cached = std::fs::read_dir(cache_dir.join("D09EA7D6D2C24C1EBFFE78B0C866BB7F2")).map_or(
+            0,
+            |entries| {
+                entries
+                    .filter(|e| e.as_ref().unwrap().path().extension().unwrap() == "sym")
+                    .count()
+            },
+        );
+        assert_eq!(cached, 0);
+    }
+
+    #[test]
+    fn test_pe_without_pdb_object_cache() {
+        let tmp_dir = Builder::new().prefix("pe_object_cache").tempdir().unwrap();
+        let cache_dir = tmp_dir.path().join("cache");
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let dll = tmp_dir.path().join("basic64.dll");
+        std::fs::copy("./test_data/windows/basic64.dll", &dll).unwrap();
+
+        let dump = || {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                sym_cache: cache_dir.to_str(),
+                ..Default::default()
+            });
+            action.action(&[dll.to_str().unwrap()]).unwrap();
+            read_output(&tmp_out)
+        };
+        let is_pe_only =
+            |output: &[String]| output.iter().any(|l| l.starts_with("INFO PROVENANCE pe "));
+
+        assert!(is_pe_only(&dump()));
+
+        // The pdb is there now: the dll alone isn't reused from the cache
+        std::fs::copy(
+            "./test_data/windows/basic64.pdb",
+            tmp_dir.path().join("basic64.pdb"),
+        )
+        .unwrap();
+        let output = dump();
+        assert!(!is_pe_only(&output));
+        assert!(output.iter().any(|l| l.starts_with("FILE ")));
+    }
+
+    #[test]
+    fn test_object_cache_mapping() {
+        let tmp_dir = Builder::new()
+            .prefix("object_cache_mapping")
+            .tempdir()
+            .unwrap();
+        let cache_dir = tmp_dir.path().join("cache");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let dump = |mapping: bool| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                sym_cache: cache_dir.to_str(),
+                mapping_src: mapping.then(|| vec![r".*\.h"]),
+                mapping_dest: mapping.then(|| vec!["same.h"]),
+                ..Default::default()
+            });
+            action.action(&["./test_data/windows/basic64.pdb"]).unwrap();
+            read_output(&tmp_out)
+        };
+
+        // The object is parsed without the mapping, it's applied on the cached one
+        dump(false);
+        let output = dump(true);
+        let files: Vec<_> = output.iter().filter(|l| l.starts_with("FILE ")).collect();
+        // The headers mapped to the same path are merged
+        assert_eq!(files.iter().filter(|l| l.ends_with(" same.h")).count(), 1);
+        for (n, file) in files.iter().enumerate() {
+            assert!(file.starts_with(&format!("FILE {} ", n)));
+        }
+        let line_files = output.iter().filter_map(|l| {
+            let fields: Vec<_> = l.split(' ').collect();
+            let is_line = fields.len() == 4
+                && fields
+                    .iter()
+                    .all(|f| f.chars().all(|c| c.is_ascii_hexdigit()));
+            is_line.then(|| fields[3].parse::<usize>().unwrap())
+        });
+        assert!(line_files.into_iter().all(|n| n < files.len()));
+        // The same as without the cache
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            mapping_src: Some(vec![r".*\.h"]),
+            mapping_dest: Some(vec!["same.h"]),
+            ..Default::default()
+        });
+        action.action(&["./test_data/windows/basic64.pdb"]).unwrap();
+        assert_eq!(read_output(&tmp_out), output);
+    }
+
+    #[test]
+    fn test_clean_cache_without_limits() {
+        let tmp_dir = Builder::new().prefix("clean_cache").tempdir().unwrap();
+        let cache_dir = tmp_dir.path().join("cache");
+        let server_cache = tmp_dir.path().join("server");
+        let downloaded = server_cache.join("basic64.pdb/ABCDEF1/basic64.pdb");
+        std::fs::create_dir_all(downloaded.parent().unwrap()).unwrap();
+        std::fs::write(&downloaded, b"pdb").unwrap();
+        let symbol_server = format!("SRV*{}*https://symbols.example.com", server_cache.display());
+
+        let action = Action::Dump(Config {
+            output: tmp_dir.path().join("output.sym").into(),
+            sym_cache: cache_dir.to_str(),
+            ..Default::default()
+        });
+        action.action(&["./test_data/linux/basic.full"]).unwrap();
+        assert!(cache_dir.join("20AD60B0B4C68177552708AA192E77390").exists());
+
+        let action = Action::CleanCache(Config {
+            sym_cache: cache_dir.to_str(),
+            symbol_server: Some(&symbol_server),
+            ..Default::default()
+        });
+        action.action(&[]).unwrap();
+        // The symbols cache is emptied, the downloads are kept
+        assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 0);
+        assert!(downloaded.exists());
+    }
+
+    #[test]
+    fn test_elf_full_object_cache() {
+        let tmp_dir = Builder::new().prefix("object_cache").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let cache_dir = tmp_dir.path().join("cache");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        // Fill the cache
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            sym_cache: cache_dir.to_str(),
+            num_jobs: 1,
+            ..Default::default()
+        });
+        action.action(&[full.to_str().unwrap()]).unwrap();
+
+        // Other output options reuse the parsed object
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            sym_cache: cache_dir.to_str(),
+            legacy_order: true,
+            num_jobs: 1,
+            ..Default::default()
+        });
+        action.action(&[full.to_str().unwrap()]).unwrap();
+
+        let mut new = without_generator(read_output(&tmp_out));
+        let mut basic = without_generator(read_input("./test_data/linux/basic.full.sym"));
+        new.sort();
+        basic.sort();
+        assert_eq!(basic, new);
+
+        let cached: Vec<_> = std::fs::read_dir(cache_dir.join("20AD60B0B4C68177552708AA192E77390"))
+            .unwrap()
+            .map(|e| e.unwrap().path().extension().unwrap().to_owned())
+            .filter(|e| e != "lock")
+            .collect();
+        assert_eq!(cached.iter().filter(|e| *e == "sym").count(), 2);
+        assert_eq!(cached.iter().filter(|e| *e == "bin").count(), 1);
+    }
+
+    #[test]
+    fn test_elf_full_incremental() {
+        let tmp_dir = Builder::new().prefix("incremental").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            incremental: true,
+            num_jobs: 1,
+            ..Default::default()
+        });
+
+        // Same debug id: the file is kept as is
+        let module = "MODULE Linux x86_64 20AD60B0B4C68177552708AA192E77390 basic.full\n";
+        std::fs::write(&tmp_out, module).unwrap();
+        action.action(&[full.to_str().unwrap()]).unwrap();
+        assert_eq!(std::fs::read_to_string(&tmp_out).unwrap(), module);
+
+        // Another debug id: the file is dumped
+        std::fs::write(&tmp_out, module.replace("20AD", "30AD")).unwrap();
+        action.action(&[full.to_str().unwrap()]).unwrap();
+
+        let basic = read_input("./test_data/linux/basic.full.sym");
+        let new = read_output(&tmp_out);
+        assert_eq!(basic, new);
+    }
+
+    #[test]
+    fn test_pe_store_incremental() {
+        let tmp_dir = Builder::new().prefix("pe_incremental").tempdir().unwrap();
+        let store = tmp_dir.path().join("store");
+        let dll = "./test_data/windows/basic64.dll";
+
+        let action = Action::Dump(Config {
+            output: dumper::Output::Store(store.clone()),
+            incremental: true,
+            ..Default::default()
+        });
+        action.action(&[dll]).unwrap();
+
+        // The symbols are stored with the name of the pdb file
+        let sym = store.join("basic64.pdb/D09EA7D6D2C24C1EBFFE78B0C866BB7F2/basic64.sym");
+        let module = "MODULE windows x86_64 D09EA7D6D2C24C1EBFFE78B0C866BB7F2 basic64.pdb\n";
+        assert!(std::fs::read_to_string(&sym).unwrap().starts_with(module));
+
+        // They're up to date: the second run doesn't dump the file again
+        std::fs::write(&sym, module).unwrap();
+        action.action(&[dll]).unwrap();
+        assert_eq!(std::fs::read_to_string(&sym).unwrap(), module);
+    }
+
+    #[test]
+    fn test_languages() {
+        let tmp_dir = Builder::new().prefix("languages").tempdir().unwrap();
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let dump = |file: &str, languages| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                languages,
+                num_jobs: 1,
+                ..Default::default()
+            });
+            action.action(&[file]).unwrap();
+            without_generator(read_output(&tmp_out))
+        };
+
+        for file in [
+            "./test_data/windows/basic64.pdb",
+            "./test_data/linux/basic.full",
+        ] {
+            let new = dump(file, true);
+            assert!(new.iter().any(|l| l == "LANGUAGE cpp"));
+
+            // Only the LANGUAGE records are added
+            let basic = dump(file, false);
+            let new: Vec<_> = new
+                .into_iter()
+                .filter(|l| !l.starts_with("LANGUAGE"))
+                .collect();
+            assert_eq!(basic, new);
+        }
+
+        // The assembly code of the PDB
+        let new = dump("./test_data/windows/basic64.pdb", true);
+        assert!(new.iter().any(|l| l == "LANGUAGE masm"));
+    }
+
+    #[test]
+    fn test_pdb_labels() {
+        let tmp_dir = Builder::new().prefix("labels").tempdir().unwrap();
+        let pdb = PathBuf::from("./test_data/windows/basic64.pdb");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            labels: true,
+            num_jobs: 1,
+            ..Default::default()
+        });
+
+        action.action(&[pdb.to_str().unwrap()]).unwrap();
+
+        let new = read_output(&tmp_out);
+        let label = new
+            .iter()
+            .position(|l| l == "LABEL 9a08 RestoreRcx")
+            .unwrap();
+        assert!(!new
+            .iter()
+            .any(|l| l.starts_with("LABEL") && l.contains("$LN")));
+
+        // The label is in the function containing it
+        let func = new[..label]
+            .iter()
+            .rfind(|l| l.starts_with("FUNC") || l.starts_with("PUBLIC"))
+            .unwrap();
+        let toks: Vec<_> = func.split_whitespace().collect();
+        let rva = u32::from_str_radix(toks[1], 16).unwrap();
+        let len = u32::from_str_radix(toks[2], 16).unwrap();
+        assert!(toks[0] == "FUNC" && rva < 0x9a08 && 0x9a08 < rva + len);
+
+        // Only the LABEL records are added
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            num_jobs: 1,
+            ..Default::default()
+        });
+        action.action(&[pdb.to_str().unwrap()]).unwrap();
+
+        let basic = without_generator(read_output(&tmp_out));
+        let new: Vec<_> = without_generator(new)
+            .into_iter()
+            .filter(|l| !l.starts_with("LABEL"))
+            .collect();
+        assert_eq!(basic, new);
+    }
+
+    #[test]
+    fn test_pdb_public_filter() {
+        let tmp_dir = Builder::new().prefix("publics").tempdir().unwrap();
+        let pdb = PathBuf::from("./test_data/windows/basic64.pdb");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            public_filter: PublicFilter::new(&["^RtlCaptureContext$"], &["^Rtl"], false).unwrap(),
+            num_jobs: 1,
+            ..Default::default()
+        });
+
+        action.action(&[pdb.to_str().unwrap()]).unwrap();
+
+        let new = read_output(&tmp_out);
+        let publics: Vec<_> = new.iter().filter(|l| l.starts_with("PUBLIC")).collect();
+        assert!(publics.iter().any(|l| l.ends_with(" RtlCaptureContext")));
+        assert!(!publics.iter().any(|l| l.ends_with(" RtlVirtualUnwind")));
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            num_jobs: 1,
+            ..Default::default()
+        });
+        action.action(&[pdb.to_str().unwrap()]).unwrap();
+
+        let basic = read_output(&tmp_out);
+        let removed: Vec<_> = basic
+            .iter()
+            .filter(|l| l.starts_with("PUBLIC") && !new.contains(l))
+            .collect();
+        assert!(!removed.is_empty());
+        assert!(removed
+            .iter()
+            .all(|l| l.split(' ').nth(3).unwrap().starts_with("Rtl")));
+
+        assert!(PublicFilter::new(&["("], &[], false).is_err());
+    }
+
+    #[test]
+    fn test_pdb_inner_publics() {
+        let tmp_dir = Builder::new().prefix("inner_publics").tempdir().unwrap();
+        let pdb = PathBuf::from("./test_data/windows/basic-opt32.pdb");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let dump = |inner_publics| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                inner_publics,
+                num_jobs: 1,
+                ..Default::default()
+            });
+            action.action(&[pdb.to_str().unwrap()]).unwrap();
+            read_output(&tmp_out)
+        };
+
+        // A label of _NLG_Call (at 12eab) is in the publics
+        let inner = "PUBLIC 12ead 0 _NLG_Return2";
+        assert!(dump(InnerPublics::Keep).iter().any(|l| l == inner));
+        assert!(!dump(InnerPublics::Nearest).iter().any(|l| l == inner));
+        assert!(!dump(InnerPublics::Drop).iter().any(|l| l == inner));
+    }
+
+    #[test]
+    fn test_pdb_priority() {
+        let tmp_dir = Builder::new().prefix("priority").tempdir().unwrap();
+        let pdb = PathBuf::from("./test_data/windows/basic-opt32.pdb");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let dump = |priority: &str| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                priority: priority.parse().unwrap(),
+                num_jobs: 1,
+                ..Default::default()
+            });
+            action.action(&[pdb.to_str().unwrap()]).unwrap();
+            read_output(&tmp_out)
+        };
+
+        // The function is named from the PDB or from its public symbol
+        let data = dump("");
+        assert!(data.iter().any(|l| {
+            l == "FUNC 6c20 3 0 test_array_empty_struct(Empty*, Empty[]*, Empty[][]*, Empty[][][]*)"
+        }));
+        let data = dump("publics");
+        assert!(data.iter().any(|l| l == "FUNC 6c20 3 0 test_array_empty_struct(Empty* const, Empty (* const)[34], Empty (* const)[34][56], Empty (* const)[34][56][78])"));
+    }
+
+    #[test]
+    fn test_corrupted_pe() {
+        let tmp_dir = Builder::new().prefix("corrupted").tempdir().unwrap();
+        let dll = tmp_dir.path().join("corrupted.dll");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        // The optional header of an x86 PE file is truncated
+        let mut data = b"MZ".to_vec();
+        data.resize(0x3c, 0);
+        data.extend(0x40u32.to_le_bytes());
+        data.extend(b"PE\0\0\x4c\x01\x05\0");
+        data.resize(0x54, 0);
+        data.extend(0xe0u16.to_le_bytes());
+        data.resize(0x76, 0);
+        fs::write(&dll, data).unwrap();
+
+        let action = Action::Dump(Config {
+            output: tmp_out.into(),
+            num_jobs: 1,
+            ..Default::default()
+        });
+
+        let e = action.action(&[dll.to_str().unwrap()]).unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::Internal);
+        assert!(e.to_string().contains("Unable to parse the PE file"));
+    }
+
+    #[test]
+    fn test_pdb_thunks() {
+        let tmp_dir = Builder::new().prefix("thunks").tempdir().unwrap();
+        let dll = PathBuf::from("./test_data/windows/basic32.dll");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let dump = |thunks| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                thunks,
+                num_jobs: 1,
+                ..Default::default()
+            });
+            action.action(&[dll.to_str().unwrap()]).unwrap();
+            read_output(&tmp_out)
+        };
+
+        let flagged = dump(Thunks::Flag);
+        assert!(flagged
+            .iter()
+            .any(|l| l == "FUNC 9b20 11 4 __security_check_cookie(unsigned int) [thunk]"));
+
+        let collapsed = dump(Thunks::Collapse);
+        assert!(collapsed
+            .iter()
+            .any(|l| l == "FUNC 9b20 11 4 <compiler thunk>"));
+        assert!(collapsed
+            .iter()
+            .any(|l| l == "FUNC 534f0 2d 0 <compiler thunk>"));
+    }
+
+    #[test]
+    fn test_pdb_merge_funclets() {
+        let tmp_dir = Builder::new().prefix("funclets").tempdir().unwrap();
+        let dll = PathBuf::from("./test_data/windows/basic64.dll");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            merge_funclets: true,
+            num_jobs: 1,
+            ..Default::default()
+        });
+        action.action(&[dll.to_str().unwrap()]).unwrap();
+        let output = read_output(&tmp_out);
+
+        assert!(output
+            .iter()
+            .any(|l| l == "FUNC 6952e c 0 UnDecorator::getDecoratedName()"));
+        assert!(output
+            .iter()
+            .any(|l| l
+                == "FUNC 69510 18 0 __DestructExceptionObject(EHExceptionRecord*, unsigned char)"));
+        assert!(!output
+            .iter()
+            .any(|l| l.contains("'::dtor$") || l.contains("$fin$") || l.contains("$filt$")));
+    }
+
+    #[test]
+    fn test_decl_line() {
+        let tmp_dir = Builder::new().prefix("decl").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/decl.full");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            ..Default::default()
+        });
+        action.action(&[full.to_str().unwrap()]).unwrap();
+        let output = read_output(&tmp_out);
+
+        assert!(output
+            .iter()
+            .any(|l| l.starts_with("FILE 0 ") && l.ends_with("decl.c")));
+        // nolines has no line rows, it gets the line of its declaration
+        let func = output
+            .iter()
+            .position(|l| l == "FUNC 1139 5 0 nolines")
+            .unwrap();
+        assert_eq!(output[func + 1], "1139 5 3 0");
+        assert_eq!(output[func + 2], "FUNC 113e 26 0 main");
+    }
+
+    #[test]
+    fn test_plt_entries() {
+        let tmp_dir = Builder::new().prefix("plt").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/split.full");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            ..Default::default()
+        });
+        action.action(&[full.to_str().unwrap()]).unwrap();
+        let output = read_output(&tmp_out);
+
+        // The resolver stub at the start of the lazy PLT isn't named
+        assert!(output.contains(&"PUBLIC 1020 0 <.plt ELF section in split.full>".to_string()));
+        for public in [
+            "PUBLIC 1030 0 printf@plt",
+            "PUBLIC 1040 0 fprintf@plt",
+            "PUBLIC 1050 0 strtol@plt",
+            "PUBLIC 1060 0 exit@plt",
+            // An entry of .plt.got
+            "PUBLIC 1070 0 __cxa_finalize@plt",
+        ] {
+            assert!(output.contains(&public.to_string()), "{}", public);
+        }
+    }
+
+    #[test]
+    fn test_outlined_functions() {
+        let tmp_dir = Builder::new().prefix("outlined").tempdir().unwrap();
+        let object = PathBuf::from("./test_data/linux/outlined.o");
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let dump = |outlined| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                outlined,
+                ..Default::default()
+            });
+            action.action(&[object.to_str().unwrap()]).unwrap();
+            read_output(&tmp_out)
+        };
+
+        let output = dump(Outlined::Keep);
+        assert!(output
+            .iter()
+            .any(|l| l == "PUBLIC 24 0 OUTLINED_FUNCTION_0"));
+
+        let output = dump(Outlined::Flag);
+        assert!(output
+            .iter()
+            .any(|l| l == "PUBLIC 24 0 OUTLINED_FUNCTION_0 [outlined]"));
+
+        let output = dump(Outlined::Callers);
+        assert!(output
+            .iter()
+            .any(|l| l == "PUBLIC 24 0 OUTLINED_FUNCTION_0 [outlined from foo, bar]"));
+        // Tail-called
+        assert!(output
+            .iter()
+            .any(|l| l == "PUBLIC 30 0 OUTLINED_FUNCTION_1 [outlined from bar]"));
+        // Never called
+        assert!(output
+            .iter()
+            .any(|l| l == "PUBLIC 38 0 OUTLINED_FUNCTION_2 [outlined]"));
+        assert!(output.iter().any(|l| l == "PUBLIC 0 0 foo"));
+    }
+
+    #[test]
+    fn test_merge_split_functions() {
+        let tmp_dir = Builder::new().prefix("split").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/split.full");
+        let tmp_out = tmp_dir.path().join("output.sym");
+        let dump = |merge_split_functions| {
+            let action = Action::Dump(Config {
+                output: tmp_out.clone().into(),
+                merge_split_functions,
+                ..Default::default()
+            });
+            action.action(&[full.to_str().unwrap()]).unwrap();
+            read_output(&tmp_out)
+        };
+
+        let output = dump(false);
+        assert!(output.iter().any(|l| l == "PUBLIC 109a 0 lookup.cold"));
+        assert!(output.iter().any(|l| l == "PUBLIC 1220 0 compute.part.0"));
+
+        let output = dump(true);
+        assert!(output.iter().any(|l| l == "PUBLIC 109a 0 lookup"));
+        assert!(output.iter().any(|l| l == "PUBLIC 1220 0 compute"));
+        assert!(!output
+            .iter()
+            .any(|l| l.contains(".cold") || l.contains(".part")));
+    }
+
+    #[cfg(feature = "disasm")]
+    #[test]
+    fn test_pdb_check_param_sizes() {
+        let tmp_dir = Builder::new().prefix("param_sizes").tempdir().unwrap();
+        let dll = PathBuf::from("./test_data/windows/basic32.dll");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            check_param_sizes: true,
+            num_jobs: 1,
+            ..Default::default()
+        });
+        action.action(&[dll.to_str().unwrap()]).unwrap();
+        let output = read_output(&tmp_out);
+
+        // The fastcall functions with their parameters in registers pop less
+        assert!(output
+            .iter()
+            .any(|l| l == "FUNC b760 17 8 @_EH4_LocalUnwind@16()"));
+        assert!(output
+            .iter()
+            .any(|l| l == "FUNC ac91 1 0 _guard_check_icall_nop(unsigned int)"));
+        assert!(output
+            .iter()
+            .any(|l| l == "FUNC 9b20 11 4 __security_check_cookie(unsigned int)"));
+    }
+
+    #[test]
+    fn test_elf_full_data() {
+        let tmp_dir = Builder::new().prefix("data").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            data_symbols: true,
+            num_jobs: 1,
+            ..Default::default()
+        });
+
+        action.action(&[full.to_str().unwrap()]).unwrap();
+
+        let new = read_output(&tmp_out);
+        assert!(new.iter().any(|l| l == "DATA 2000 4 _IO_stdin_used"));
+        assert!(new.iter().any(|l| l == "DATA 4028 1 completed.7452"));
+
+        // The DATA records are after the symbols and before the CFI
+        let first_data = new.iter().position(|l| l.starts_with("DATA")).unwrap();
+        let last_data = new.iter().rposition(|l| l.starts_with("DATA")).unwrap();
+        assert!(!new[..first_data].iter().any(|l| l.starts_with("STACK")));
+        assert!(!new[last_data..].iter().any(|l| l.starts_with("FUNC")));
+
+        let basic = without_generator(read_input("./test_data/linux/basic.full.sym"));
+        let new: Vec<_> = without_generator(new)
+            .into_iter()
+            .filter(|l| !l.starts_with("DATA"))
+            .collect();
+        assert_eq!(basic, new);
+    }
+
+    #[test]
+    fn test_elf_tls_data() {
+        let tmp_dir = Builder::new().prefix("tls").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/tls.full");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            data_symbols: true,
+            num_jobs: 1,
+            ..Default::default()
+        });
+
+        action.action(&[full.to_str().unwrap()]).unwrap();
+
+        // The TLS variables are in the TLS template
+        let new = read_output(&tmp_out);
+        assert!(new.iter().any(|l| l == "DATA 4010 4 global"));
+        assert!(new.iter().any(|l| l == "DATA t 3df0 4 counter"));
+        assert!(new.iter().any(|l| l == "DATA t 3e00 40 buffer"));
+    }
+
+    #[test]
+    fn test_elf_stabs() {
+        let tmp_dir = Builder::new().prefix("stabs").tempdir().unwrap();
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            ..Default::default()
+        });
+        action.action(&["./test_data/linux/stabs.full"]).unwrap();
+
+        // Without DWARF nor symbol table, the functions are the ones of the STABS
+        let new = read_output(&tmp_out);
+        let funcs: Vec<_> = new.iter().filter(|l| l.starts_with("FUNC")).collect();
+        assert_eq!(funcs, ["FUNC 1129 f 0 square", "FUNC 1138 24 0 main"]);
+        assert!(new.iter().any(|l| l == "FILE 0 stabs.c"));
+        assert!(new.iter().any(|l| l == "1147 d 8 0"));
+    }
+
+    #[test]
+    fn test_elf_full_with_inlines() {
+        let tmp_dir = Builder::new().prefix("full").tempdir().unwrap();
+        let full = PathBuf::from("./test_data/linux/basic.full");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            num_jobs: 1,
+            emit_inlines: true,
+            ..Default::default()
+        });
+
+        action.action(&[full.to_str().unwrap()]).unwrap();
+
+        let new = read_output(&tmp_out);
+        let basic = read_input("./test_data/linux/basic.full.inlines.sym");
+
+        assert_eq!(basic, new);
+    }
+
+    #[test]
+    fn test_macho_with_inlines() {
+        let tmp_dir = Builder::new().prefix("macho_inlines").tempdir().unwrap();
+        let object = PathBuf::from("./test_data/macos/inlines.o");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            emit_inlines: true,
+            ..Default::default()
+        });
+        action.action(&[object.to_str().unwrap()]).unwrap();
+        let output = read_output(&tmp_out);
+
+        let func = output
+            .iter()
+            .position(|l| l == "FUNC 10 21 0 caller")
+            .unwrap();
+        assert_eq!(
+            output[func - 3..func + 7],
+            [
+                "FILE 0 /src/inlines.c",
+                "INLINE_ORIGIN 0 mid",
+                "INLINE_ORIGIN 1 leaf",
+                "FUNC 10 21 0 caller",
+                // mid is called at line 13 of caller and leaf at line 9 of mid
+                "INLINE 0 13 0 0 11 14",
+                "INLINE 1 9 0 1 11 a",
+                "10 b 5 0",
+                "1b a 10 0",
+                "25 a 14 0",
+                "2f 2 15 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_elf_stripped_dbg() {
+        let tmp_dir = Builder::new().prefix("stripped_dbg").tempdir().unwrap();
+        let stripped = PathBuf::from("./test_data/linux/basic.stripped");
+        let dbg = PathBuf::from("./test_data/linux/basic.dbg");
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            num_jobs: 2,
+            emit_inlines: true,
+            ..Default::default()
+        });
+
+        action
+            .action(&[stripped.to_str().unwrap(), dbg.to_str().unwrap()])
+            .unwrap();
+
+        let re = Regex::new(r"<\.plt[\.a-zA-Z]* ELF section in [^>]*>").unwrap();
+        let new = read_output(&tmp_out);
+        let new: Vec<_> = new
+            .into_iter()
             .map(|s| re.replace(&s, "<.plt ELF section in>").to_string())
             .collect();
         let basic = read_input("./test_data/linux/basic.full.inlines.sym");
@@ -288,6 +2125,36 @@ mod tests {
         assert_eq!(basic, new);
     }
 
+    #[test]
+    fn test_elf_no_build_id() {
+        let tmp_dir = Builder::new().prefix("no_build_id").tempdir().unwrap();
+        let tmp_out = tmp_dir.path().join("output.sym");
+
+        let action = Action::Dump(Config {
+            output: tmp_out.clone().into(),
+            num_jobs: 2,
+            ..Default::default()
+        });
+        action
+            .action(&[
+                "./test_data/linux/nobuildid.stripped",
+                "./test_data/linux/nobuildid.dbg",
+            ])
+            .unwrap();
+
+        // The debug id of the debug file is nil, it's the one of the binary
+        let data = std::fs::read_to_string(&tmp_out).unwrap();
+        let data: Vec<_> = data.lines().collect();
+        assert_eq!(
+            data[0],
+            "MODULE Linux x86_64 AEF0B8EC643235674369B2F52003CFA00 nobuildid.stripped"
+        );
+        assert_eq!(data[1], "INFO CODE_ID ECB8F0AE326467354369B2F52003CFA0");
+        assert!(data
+            .iter()
+            .any(|l| l.starts_with("FUNC") && l.ends_with(" main")));
+    }
+
     #[test]
     fn test_elf_dbg_stripped() {
         let tmp_dir = Builder::new().prefix("stripped_dbg").tempdir().unwrap();
@@ -297,17 +2164,8 @@ mod tests {
 
         let action = Action::Dump(Config {
             output: tmp_out.clone().into(),
-            symbol_server: None,
-            debug_id: None,
-            code_id: None,
-            arch: common::get_compile_time_arch(),
             num_jobs: 2,
-            mapping_var: None,
-            mapping_src: None,
-            mapping_dest: None,
-            mapping_file: None,
-            check_cfi: false,
-            emit_inlines: false,
+            ..Default::default()
         });
 
         action
@@ -328,17 +2186,9 @@ mod tests {
 
         let action = Action::Dump(Config {
             output: tmp_out.clone().into(),
-            symbol_server: None,
-            debug_id: None,
-            code_id: None,
-            arch: common::get_compile_time_arch(),
             num_jobs: 1,
-            mapping_var: None,
-            mapping_src: None,
-            mapping_dest: None,
-            mapping_file: None,
-            check_cfi: false,
             emit_inlines: true,
+            ..Default::default()
         });
 
         action.action(&[full.to_str().unwrap()]).unwrap();
@@ -357,17 +2207,9 @@ mod tests {
 
         let action = Action::Dump(Config {
             output: tmp_out.clone().into(),
-            symbol_server: None,
-            debug_id: None,
-            code_id: None,
-            arch: common::get_compile_time_arch(),
             num_jobs: 1,
-            mapping_var: None,
-            mapping_src: None,
-            mapping_dest: None,
-            mapping_file: None,
-            check_cfi: false,
             emit_inlines: true,
+            ..Default::default()
         });
 
         action.action(&[minidebuginfo.to_str().unwrap()]).unwrap();
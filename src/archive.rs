@@ -0,0 +1,275 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use symbolic::common::ByteView;
+
+use crate::common::{self, FailureKind};
+
+/// The magic of the zip files (and so of the apk, aar and jar files)
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+/// The magic of the POSIX and GNU tar headers, at offset 257
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_BLOCK: usize = 512;
+
+/// The archives containing the files to dump
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Get the kind of the archive from its first bytes, none if it isn't one
+    pub fn of(path: &Path) -> Option<Self> {
+        if !path.is_file() {
+            return None;
+        }
+        let mut header = Vec::with_capacity(TAR_BLOCK);
+        File::open(path)
+            .ok()?
+            .take(TAR_BLOCK as u64)
+            .read_to_end(&mut header)
+            .ok()?;
+
+        if header.starts_with(ZIP_MAGIC) {
+            Some(Self::Zip)
+        } else if is_tar_header(&header) {
+            Some(Self::Tar)
+        } else if header.starts_with(GZIP_MAGIC) {
+            // A gzipped file which isn't a tar (e.g. a .pdb.gz) isn't an archive
+            let mut header = Vec::with_capacity(TAR_BLOCK);
+            GzDecoder::new(File::open(path).ok()?)
+                .take(TAR_BLOCK as u64)
+                .read_to_end(&mut header)
+                .ok()?;
+            is_tar_header(&header).then_some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+fn is_tar_header(header: &[u8]) -> bool {
+    header.len() == TAR_BLOCK && header[257..].starts_with(TAR_MAGIC)
+}
+
+/// Split a path such as `artifacts.zip!/bin/app.exe` into the path of the
+/// archive and the path of the entry in it. The archive is the first existing
+/// file before a `!`.
+pub fn split_path(file: &str) -> Option<(&str, &str)> {
+    file.match_indices('!').find_map(|(pos, _)| {
+        let archive = &file[..pos];
+        let entry = normalize(&file[pos + 1..]);
+        (!entry.is_empty() && Path::new(archive).is_file()).then_some((archive, entry))
+    })
+}
+
+/// The paths in the archives are relative
+fn normalize(entry: &str) -> &str {
+    let mut entry = entry.trim_start_matches('/');
+    while let Some(rest) = entry.strip_prefix("./") {
+        entry = rest.trim_start_matches('/');
+    }
+    entry
+}
+
+/// Read an entry of an archive, the archive is read as a stream and the entry
+/// is copied in an anonymous temporary file which is mapped in memory
+pub fn read_entry(archive: &Path, entry: &str) -> common::Result<ByteView<'static>> {
+    let entry = normalize(entry);
+    let mut data = None;
+    for_each_entry(archive, |name, reader| {
+        if data.is_none() && name == entry {
+            data = Some(map_entry(&[], reader)?);
+        }
+        Ok(data.is_none())
+    })?;
+
+    data.ok_or_else(|| {
+        common::failure(
+            FailureKind::Usage,
+            format!("No entry {} in {}", entry, archive.display()),
+        )
+    })
+}
+
+/// Copy the already read beginning of an entry and the rest of it in an anonymous
+/// temporary file which is mapped in memory: the entries may be several GiB.
+pub fn map_entry(head: &[u8], reader: &mut dyn Read) -> common::Result<ByteView<'static>> {
+    let mut file = tempfile::tempfile()?;
+    file.write_all(head)?;
+    io::copy(reader, &mut file)?;
+    Ok(ByteView::map_file(file)?)
+}
+
+/// Call `f` with the path and the content of each file of an archive, in their
+/// order in the archive, until it returns false. The content which isn't read
+/// by `f` is skipped.
+pub fn for_each_entry<F>(archive: &Path, mut f: F) -> common::Result<()>
+where
+    F: FnMut(&str, &mut dyn Read) -> common::Result<bool>,
+{
+    let kind = ArchiveKind::of(archive).ok_or_else(|| {
+        common::failure(
+            FailureKind::UnsupportedFormat,
+            format!("{} isn't a zip or a tar archive", archive.display()),
+        )
+    })?;
+    let file = File::open(archive)?;
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(BufReader::new(file))?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i)?;
+                if !entry.is_file() {
+                    continue;
+                }
+                let name = normalize(entry.name()).to_string();
+                if !f(&name, &mut entry)? {
+                    break;
+                }
+            }
+            Ok(())
+        }
+        ArchiveKind::Tar => for_each_tar_entry(BufReader::new(file), f),
+        ArchiveKind::TarGz => for_each_tar_entry(GzDecoder::new(BufReader::new(file)), f),
+    }
+}
+
+fn for_each_tar_entry<R, F>(reader: R, mut f: F) -> common::Result<()>
+where
+    R: Read,
+    F: FnMut(&str, &mut dyn Read) -> common::Result<bool>,
+{
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !matches!(
+            entry.header().entry_type(),
+            tar::EntryType::Regular | tar::EntryType::Continuous
+        ) {
+            continue;
+        }
+        // The GNU long names and the pax paths are handled by the tar crate
+        let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        if !f(normalize(&name), &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::fs;
+    use std::io::Write;
+    use tempfile::Builder;
+
+    /// Build a tar archive with a GNU long name for the names longer than 100 bytes
+    /// and a pax path for the ones starting with `pax/`
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut tar = tar::Builder::new(Vec::new());
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            if name.starts_with("pax/") {
+                tar.append_pax_extensions([("path", name.as_bytes())])
+                    .unwrap();
+                tar.append_data(&mut header, "truncated", *content).unwrap();
+            } else {
+                tar.append_data(&mut header, name, *content).unwrap();
+            }
+        }
+        tar.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_split_path() {
+        let tmp_dir = Builder::new().prefix("split").tempdir().unwrap();
+        let zip = tmp_dir.path().join("a!b.zip");
+        fs::write(&zip, ZIP_MAGIC).unwrap();
+        let zip = zip.to_str().unwrap();
+
+        let file = format!("{}!/bin/app.exe", zip);
+        assert_eq!(split_path(&file), Some((zip, "bin/app.exe")));
+        let file = format!("{}!./app.exe", zip);
+        assert_eq!(split_path(&file), Some((zip, "app.exe")));
+        assert_eq!(split_path(zip), None);
+        assert_eq!(split_path("nonexistent.zip!app.exe"), None);
+    }
+
+    #[test]
+    fn test_tar() {
+        let tmp_dir = Builder::new().prefix("tar").tempdir().unwrap();
+        let long = format!("{}/app.pdb", "dir".repeat(50));
+        let files: &[(&str, &[u8])] = &[
+            ("./bin/app.exe", b"exe"),
+            (&long, &[1; 1000]),
+            ("empty", b""),
+            ("pax/app.so", b"so"),
+        ];
+        let tar_path = tmp_dir.path().join("artifacts.tar");
+        fs::write(&tar_path, tar(files)).unwrap();
+        let tgz_path = tmp_dir.path().join("artifacts.tgz");
+        let mut gz = GzEncoder::new(
+            fs::File::create(&tgz_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        gz.write_all(&tar(files)).unwrap();
+        gz.finish().unwrap();
+
+        assert_eq!(ArchiveKind::of(&tar_path), Some(ArchiveKind::Tar));
+        assert_eq!(ArchiveKind::of(&tgz_path), Some(ArchiveKind::TarGz));
+        for path in [&tar_path, &tgz_path] {
+            let mut names = Vec::new();
+            for_each_entry(path, |name, _| {
+                names.push(name.to_string());
+                Ok(true)
+            })
+            .unwrap();
+            assert_eq!(
+                names,
+                vec!["bin/app.exe", long.as_str(), "empty", "pax/app.so"]
+            );
+
+            assert_eq!(read_entry(path, "/bin/app.exe").unwrap().as_slice(), b"exe");
+            assert_eq!(read_entry(path, &long).unwrap().as_slice(), [1; 1000]);
+            assert_eq!(read_entry(path, "pax/app.so").unwrap().as_slice(), b"so");
+            assert!(read_entry(path, "empty").unwrap().is_empty());
+            assert!(read_entry(path, "app.exe").is_err());
+        }
+    }
+
+    #[test]
+    fn test_zip() {
+        let tmp_dir = Builder::new().prefix("zip").tempdir().unwrap();
+        let path = tmp_dir.path().join("artifacts.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        zip.add_directory("bin", Default::default()).unwrap();
+        zip.start_file("bin/app.exe", Default::default()).unwrap();
+        zip.write_all(b"exe").unwrap();
+        zip.finish().unwrap();
+
+        assert_eq!(ArchiveKind::of(&path), Some(ArchiveKind::Zip));
+        assert_eq!(read_entry(&path, "bin/app.exe").unwrap().as_slice(), b"exe");
+
+        // Not an archive
+        let gz = tmp_dir.path().join("app.pdb.gz");
+        let mut enc = GzEncoder::new(fs::File::create(&gz).unwrap(), Default::default());
+        enc.write_all(&[0; 1000]).unwrap();
+        enc.finish().unwrap();
+        assert_eq!(ArchiveKind::of(&gz), None);
+        assert_eq!(ArchiveKind::of(tmp_dir.path()), None);
+    }
+}
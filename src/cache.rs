@@ -5,16 +5,20 @@
 
 use dirs::home_dir;
 use futures::{stream, StreamExt};
+use log::{info, warn};
 use reqwest::{self, blocking, header::USER_AGENT, Client};
 use std::fs::{self, File};
 use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use symbolic::common::ByteView;
 use tokio::runtime::Runtime;
 use url::Url;
 
-use crate::common;
-use crate::utils;
+use crate::common::{self, FailureKind};
+use crate::sym_cache;
+use crate::utils::{self, FileLock};
 
 const DEFAULT_STORE: &str = "https://msdl.microsoft.com/download/symbols";
 const DEFAULT_USER_AGENT: &str = "Microsoft-Symbol-Server/6.3.0.0";
@@ -25,6 +29,12 @@ pub struct SymbolServer {
     server: String,
 }
 
+impl SymbolServer {
+    pub fn url(&self) -> &str {
+        &self.server
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Job {
     cache: Option<PathBuf>,
@@ -76,9 +86,7 @@ fn parse_srv(path: &str) -> Option<SymbolServer> {
 }
 
 fn parse_sympath(path: &str) -> Vec<SymbolServer> {
-    path.split(|c| c == ';' || c == '\n')
-        .filter_map(parse_srv)
-        .collect()
+    path.split([';', '\n']).filter_map(parse_srv).collect()
 }
 
 fn read_config() -> Option<Vec<SymbolServer>> {
@@ -117,6 +125,14 @@ pub fn get_sym_servers(symbol_server: Option<&str>) -> Option<Vec<SymbolServer>>
     symbol_server.map_or_else(read_config, read_config_from_str)
 }
 
+/// Get the local caches of the symbol servers
+pub fn get_cache_dirs(servers: &[SymbolServer]) -> Vec<PathBuf> {
+    servers
+        .iter()
+        .filter_map(|s| s.cache.as_ref().map(PathBuf::from))
+        .collect()
+}
+
 fn copy_in_cache(path: Option<PathBuf>, data: &[u8]) -> bool {
     if data.is_empty() || data.starts_with(b"Symbol Not Found") {
         return false;
@@ -138,16 +154,49 @@ fn copy_in_cache(path: Option<PathBuf>, data: &[u8]) -> bool {
         }
     }
 
-    let output = File::create(&path)
-        .unwrap_or_else(|_| panic!("Cannot open file {} for writing", path.to_str().unwrap()));
+    // Write in a temporary file and rename it, so that the other processes never
+    // see a partially written file.
+    let mut tmp_path = path.clone().into_os_string();
+    tmp_path.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let output = File::create(&tmp_path).unwrap_or_else(|_| {
+        panic!(
+            "Cannot open file {} for writing",
+            tmp_path.to_str().unwrap()
+        )
+    });
     let mut output = BufWriter::new(output);
     output
         .write_all(data)
+        .and_then(|_| output.flush())
+        .unwrap_or_else(|_| panic!("Cannot write file {}", tmp_path.to_str().unwrap()));
+    fs::rename(&tmp_path, &path)
         .unwrap_or_else(|_| panic!("Cannot write file {}", path.to_str().unwrap()));
 
     true
 }
 
+/// Lock the cache paths of the jobs, so that several processes don't download
+/// the same file at the same time.
+fn lock_jobs(jobs: &[Job]) -> Vec<FileLock> {
+    let mut paths: Vec<_> = jobs.iter().filter_map(|j| j.cache.as_ref()).collect();
+    // Always lock in the same order to avoid deadlocks
+    paths.sort();
+    paths.dedup();
+
+    paths
+        .into_iter()
+        .filter_map(|path| match FileLock::lock(path) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                warn!("Unable to lock {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
 fn search_in_cache(
     servers: &[SymbolServer],
     id: &str,
@@ -157,6 +206,7 @@ fn search_in_cache(
     for cache in servers.iter().filter_map(|x| x.cache.as_ref()) {
         let path = PathBuf::from(cache).join(base).join(id).join(file_name);
         if path.exists() {
+            sym_cache::touch(&path);
             return Some(path);
         }
     }
@@ -255,11 +305,114 @@ fn fetch_data(jobs: Vec<Job>) -> Option<Vec<u8>> {
     }
 }
 
+/// A client for the quick requests to the symbol servers (probes without a
+/// body), an unresponsive server mustn't block the dump
+fn get_quick_client() -> reqwest::Result<blocking::Client> {
+    blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent(DEFAULT_USER_AGENT)
+        .build()
+}
+
+/// A client for the downloads: only the connection has a timeout, a big file
+/// (e.g. the symbols of xul) can take minutes to be received
+fn get_download_client() -> reqwest::Result<blocking::Client> {
+    blocking::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(None)
+        .user_agent(DEFAULT_USER_AGENT)
+        .build()
+}
+
+/// Check that a server answers, whatever the status of its response which is returned
+pub fn check_server(url: &str) -> common::Result<u16> {
+    anyhow::ensure!(Url::parse(url).is_ok(), "Invalid url: {}", url);
+    let status = get_quick_client()?.head(url).send()?.status();
+
+    Ok(status.as_u16())
+}
+
+/// Download the symbols already published for a module from the first server
+/// which has them.
+pub fn fetch_sym_file(servers: &[&str], file_name: &str, debug_id: &str) -> Option<Vec<u8>> {
+    let sym_path = utils::get_path_for_sym(file_name, debug_id);
+    let sym_path: Vec<_> = sym_path.iter().filter_map(|c| c.to_str()).collect();
+    let sym_path = sym_path.join("/");
+    let probe = get_quick_client().ok()?;
+    let client = get_download_client().ok()?;
+
+    for server in servers {
+        let url = format!("{}/{}", server.trim_end_matches('/'), sym_path);
+        // The short timeout is for the probe only, not for the download
+        match probe.head(&url).send() {
+            Ok(resp) if resp.status() == 200 => {}
+            _ => continue,
+        }
+        let resp = match client.get(&url).send() {
+            Ok(resp) if resp.status() == 200 => resp,
+            _ => continue,
+        };
+        if let Ok(buf) = resp.bytes() {
+            if sym_cache::has_debug_id(&buf, debug_id) {
+                info!("Use the published symbols from {}", url);
+                return Some(buf.to_vec());
+            }
+        }
+    }
+
+    None
+}
+
+/// Get the URL where a symbol file is uploaded, from its MODULE line
+pub fn get_upload_url(url: &str, data: &[u8], path: &Path) -> common::Result<String> {
+    let first_line = data.split(|c| *c == b'\n').next().unwrap_or_default();
+    let first_line = String::from_utf8_lossy(first_line);
+    let toks: Vec<_> = first_line.trim_end().splitn(5, ' ').collect();
+    anyhow::ensure!(
+        toks.len() == 5 && toks[0] == "MODULE",
+        "Invalid MODULE line in {}: {}",
+        path.display(),
+        first_line
+    );
+
+    let sym_path = utils::get_path_for_sym(toks[4], toks[3]);
+    let sym_path: Vec<_> = sym_path.iter().filter_map(|c| c.to_str()).collect();
+    Ok(format!(
+        "{}/{}",
+        url.trim_end_matches('/'),
+        sym_path.join("/")
+    ))
+}
+
+/// Upload a Breakpad symbol file to a server with the symbol store layout
+/// (`NAME/DEBUG_ID/NAME.sym`), the module name and the debug id are read
+/// from the MODULE line. The token is sent in the `Auth-Token` header.
+pub fn upload_sym_file(url: &str, auth_token: Option<&str>, path: &Path) -> common::Result<()> {
+    let data = fs::read(path)?;
+    let url = get_upload_url(url, &data, path)?;
+    let client = blocking::Client::new();
+    let mut request = client.put(&url).body(data);
+    if let Some(token) = auth_token {
+        request = request.header("Auth-Token", token);
+    }
+    let status = request.send()?.status();
+    if status.is_server_error() {
+        return Err(common::failure(
+            FailureKind::Network,
+            format!("Unable to upload {}: {}", url, status),
+        ));
+    }
+    anyhow::ensure!(status.is_success(), "Unable to upload {}: {}", url, status);
+    info!("Upload symbols at {}", url);
+
+    Ok(())
+}
+
 pub fn search_file(
     file_name: String,
     id: &str,
     sym_servers: Option<&Vec<SymbolServer>>,
-) -> (Option<Vec<u8>>, String) {
+) -> (Option<ByteView<'static>>, String) {
     if file_name.is_empty() {
         return (None, file_name);
     }
@@ -273,20 +426,83 @@ pub fn search_file(
 
     // Start with the caches
     if let Some(path) = search_in_cache(servers, id, &base, &file_name) {
-        return (Some(utils::read_file(path)), file_name);
+        return (utils::read_file(path).ok(), file_name);
     }
 
     // Try the symbol servers
     // Each job contains the path where to cache data (if one) and a query url
     let jobs = get_jobs(servers, id, &base, &file_name);
+
+    // Another process could have downloaded the file while we were waiting for the lock
+    let _locks = lock_jobs(&jobs);
+    if let Some(path) = search_in_cache(servers, id, &base, &file_name) {
+        return (utils::read_file(path).ok(), file_name);
+    }
+
     let buf = fetch_data(jobs);
 
     if let Some(buf) = buf {
         let path = PathBuf::from(&file_name);
         let buf = utils::read_cabinet(buf, path)
             .unwrap_or_else(|| panic!("Unable to read the file {} from the server", file_name));
-        (Some(buf), file_name)
+        (Some(ByteView::from_vec(buf)), file_name)
     } else {
         (None, file_name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_fetch_sym_file() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = format!("http://{}", listener.local_addr().unwrap());
+        let data = "MODULE windows x86_64 ABCDEF0 basic.pdb\nFUNC 1000 10 0 main\n";
+
+        // The probe then the download, each one on its own connection
+        let requests = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut headers = Vec::new();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() != 0 && !line.trim().is_empty() {
+                    headers.push(line.trim().to_lowercase());
+                    line.clear();
+                }
+                let body = if headers[0].starts_with("get ") {
+                    data
+                } else {
+                    ""
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    data.len(),
+                    body
+                )
+                .unwrap();
+                requests.push(headers);
+            }
+            requests
+        });
+
+        let sym = fetch_sym_file(&[&server], "basic.pdb", "ABCDEF0").unwrap();
+        assert_eq!(sym, data.as_bytes());
+
+        let requests = requests.join().unwrap();
+        assert_eq!(requests[0][0], "head /basic.pdb/abcdef0/basic.sym http/1.1");
+        assert_eq!(requests[1][0], "get /basic.pdb/abcdef0/basic.sym http/1.1");
+        for headers in requests {
+            assert!(headers
+                .iter()
+                .any(|h| h == "user-agent: microsoft-symbol-server/6.3.0.0"));
+        }
+    }
+}
@@ -0,0 +1,1447 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use log::{debug, warn};
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::io::Write;
+use std::ops::Range;
+use std::str::FromStr;
+use symbolic::common::{Arch, CpuFamily};
+use symbolic::debuginfo::dwarf::gimli::{
+    self, BaseAddresses, CfaRule, DebugFrame, EhFrame, EhFrameHdr, EndianSlice, Endianity,
+    Expression, FrameDescriptionEntry, Operation, Register, RegisterRule, UnwindContext,
+    UnwindSection,
+};
+use symbolic::debuginfo::dwarf::Dwarf;
+use symbolic::debuginfo::elf::ElfObject;
+
+use crate::common;
+use crate::symbol::Symbols;
+
+type Slice<'d> = EndianSlice<'d, gimli::RunTimeEndian>;
+
+// The register names used by the Breakpad stack walker, by DWARF register number
+static I386: &[&str] = &[
+    "$eax", "$ecx", "$edx", "$ebx", "$esp", "$ebp", "$esi", "$edi", "$eip", "$eflags", "$unused1",
+    "$st0", "$st1", "$st2", "$st3", "$st4", "$st5", "$st6", "$st7", "$unused2", "$unused3",
+    "$xmm0", "$xmm1", "$xmm2", "$xmm3", "$xmm4", "$xmm5", "$xmm6", "$xmm7", "$mm0", "$mm1", "$mm2",
+    "$mm3", "$mm4", "$mm5", "$mm6", "$mm7", "$fcw", "$fsw", "$mxcsr", "$es", "$cs", "$ss", "$ds",
+    "$fs", "$gs", "$unused4", "$unused5", "$tr", "$ldtr",
+];
+
+static X86_64: &[&str] = &[
+    "$rax", "$rdx", "$rcx", "$rbx", "$rsi", "$rdi", "$rbp", "$rsp", "$r8", "$r9", "$r10", "$r11",
+    "$r12", "$r13", "$r14", "$r15", "$rip", "$xmm0", "$xmm1", "$xmm2", "$xmm3", "$xmm4", "$xmm5",
+    "$xmm6", "$xmm7", "$xmm8", "$xmm9", "$xmm10", "$xmm11", "$xmm12", "$xmm13", "$xmm14", "$xmm15",
+    "$st0", "$st1", "$st2", "$st3", "$st4", "$st5", "$st6", "$st7", "$mm0", "$mm1", "$mm2", "$mm3",
+    "$mm4", "$mm5", "$mm6", "$mm7", "$rflags", "$es", "$cs", "$ss", "$ds", "$fs", "$gs",
+    "$unused1", "$unused2", "$fs.base", "$gs.base", "$unused3", "$unused4", "$tr", "$ldtr",
+    "$mxcsr", "$fcw", "$fsw",
+];
+
+static ARM: &[&str] = &[
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp", "lr",
+    "pc", "f0", "f1", "f2", "f3", "f4", "f5", "f6", "f7", "fps", "cpsr", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9",
+    "s10", "s11", "s12", "s13", "s14", "s15", "s16", "s17", "s18", "s19", "s20", "s21", "s22",
+    "s23", "s24", "s25", "s26", "s27", "s28", "s29", "s30", "s31", "f0", "f1", "f2", "f3", "f4",
+    "f5", "f6", "f7",
+];
+
+static ARM64: &[&str] = &[
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14",
+    "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27",
+    "x28", "x29", "x30", "sp", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "v0", "v1", "v2", "v3", "v4", "v5",
+    "v6", "v7", "v8", "v9", "v10", "v11", "v12", "v13", "v14", "v15", "v16", "v17", "v18", "v19",
+    "v20", "v21", "v22", "v23", "v24", "v25", "v26", "v27", "v28", "v29", "v30", "v31",
+];
+
+static MIPS: &[&str] = &[
+    "$zero", "$at", "$v0", "$v1", "$a0", "$a1", "$a2", "$a3", "$t0", "$t1", "$t2", "$t3", "$t4",
+    "$t5", "$t6", "$t7", "$s0", "$s1", "$s2", "$s3", "$s4", "$s5", "$s6", "$s7", "$t8", "$t9",
+    "$k0", "$k1", "$gp", "$sp", "$fp", "$ra", "$lo", "$hi", "$pc", "$f0", "$f2", "$f3", "$f4",
+    "$f5", "$f6", "$f7", "$f8", "$f9", "$f10", "$f11", "$f12", "$f13", "$f14", "$f15", "$f16",
+    "$f17", "$f18", "$f19", "$f20", "$f21", "$f22", "$f23", "$f24", "$f25", "$f26", "$f27", "$f28",
+    "$f29", "$f30", "$f31", "$fcsr", "$fir",
+];
+
+fn register_name(arch: Arch, register: Register) -> Option<&'static str> {
+    let names = match arch.cpu_family() {
+        CpuFamily::Intel32 => I386,
+        CpuFamily::Amd64 => X86_64,
+        CpuFamily::Arm64 | CpuFamily::Arm64_32 => ARM64,
+        CpuFamily::Arm32 => ARM,
+        CpuFamily::Mips32 | CpuFamily::Mips64 => MIPS,
+        _ => return None,
+    };
+    names
+        .get(register.0 as usize)
+        .copied()
+        .filter(|name| !name.is_empty())
+}
+
+/// An unwind section (.debug_frame or .eh_frame) and what's needed to read it
+struct UnwindInfo<S> {
+    arch: Arch,
+    load_address: u64,
+    section: S,
+    bases: BaseAddresses,
+    /// Emit a STACK SIGNAL record for the signal frames (extension)
+    signal_frames: bool,
+    /// The number of DWARF expressions which can't be written as Breakpad expressions
+    untranslated: Cell<usize>,
+    /// The number of entries which can't be parsed
+    invalid: Cell<usize>,
+}
+
+impl<S> UnwindInfo<S> {
+    fn new(elf: &ElfObject, section: S, bases: BaseAddresses, signal_frames: bool) -> Self {
+        Self {
+            arch: elf.arch(),
+            load_address: elf.load_address(),
+            section,
+            bases,
+            signal_frames,
+            untranslated: Cell::new(0),
+            invalid: Cell::new(0),
+        }
+    }
+}
+
+/// Write the STACK CFI records of an ELF file from .debug_frame and .eh_frame.
+///
+/// The FDEs of .eh_frame are read in address order from the binary-search table of
+/// .eh_frame_hdr when there's one, so the section isn't scanned, else they're sorted
+/// after the scan. An error in .debug_frame doesn't prevent to read .eh_frame, and
+/// the entries which can't be parsed are skipped.
+/// With `signal_frames`, a `STACK SIGNAL address size` record follows the INIT
+/// record of the signal frames (the CIEs with an `S` augmentation).
+pub fn write_elf_cfi<W: Write>(
+    elf: &ElfObject,
+    signal_frames: bool,
+    out: &mut W,
+) -> common::Result<()> {
+    let arch = elf.arch();
+    let endian = elf.endianity();
+    let address_size = arch.cpu_family().pointer_size().map(|size| size as u8);
+
+    let mut untranslated = 0;
+    let mut invalid = 0;
+    let debug_frame_result = match elf.section("debug_frame") {
+        Some(section) => {
+            let patched = patch_augmentations(&section.data, endian, false);
+            let data = patched.as_deref().unwrap_or(&section.data);
+            let mut frame = DebugFrame::new(data, endian);
+            if let Some(address_size) = address_size {
+                frame.set_address_size(address_size);
+            }
+            let info = UnwindInfo::new(elf, frame, BaseAddresses::default(), signal_frames);
+            let fdes = scan_fdes(&info, &get_entries(data, endian, false));
+            let result = write_fdes(&info, &fdes, out);
+            untranslated += info.untranslated.get();
+            invalid += info.invalid.get();
+            result
+        }
+        None => Ok(()),
+    };
+
+    if let Some(section) = elf.section("eh_frame") {
+        let patched = patch_augmentations(&section.data, endian, true);
+        let data = patched.as_deref().unwrap_or(&section.data);
+        let mut frame = EhFrame::new(data, endian);
+        if let Some(address_size) = address_size {
+            frame.set_address_size(address_size);
+        }
+
+        // The bases of the pointers relative to the text and the data (the GOT)
+        let mut bases = BaseAddresses::default().set_eh_frame(section.address);
+        if let Some(text) = elf.section("text") {
+            bases = bases.set_text(text.address);
+        }
+        if let Some(got) = elf.section("got.plt").or_else(|| elf.section("got")) {
+            bases = bases.set_got(got.address);
+        }
+        let hdr = elf.section("eh_frame_hdr");
+        if let Some(hdr) = &hdr {
+            bases = bases.set_eh_frame_hdr(hdr.address);
+        }
+        let info = UnwindInfo::new(elf, frame, bases, signal_frames);
+
+        let fdes = match &hdr {
+            Some(hdr) => get_eh_frame_hdr_fdes(&info, &hdr.data, endian, address_size.unwrap_or(8)),
+            None => None,
+        };
+        let fdes = match fdes {
+            Some(fdes) => fdes,
+            None => scan_fdes(&info, &get_entries(data, endian, true)),
+        };
+        let result = write_fdes(&info, &fdes, out);
+        untranslated += info.untranslated.get();
+        invalid += info.invalid.get();
+        result?;
+    }
+
+    if untranslated != 0 {
+        warn!(
+            "CFI: {} rules with a DWARF expression can't be translated to Breakpad expressions",
+            untranslated
+        );
+    }
+    if invalid != 0 {
+        warn!("CFI: {} entries can't be parsed", invalid);
+    }
+
+    debug_frame_result
+}
+
+/// An entry (CIE or FDE) of an unwind section
+struct Entry {
+    offset: usize,
+    is_cie: bool,
+    /// The offset of the data after the CIE id or the CIE pointer
+    body: usize,
+    end: usize,
+}
+
+/// Get the entries of an unwind section from their lengths, without parsing them,
+/// so an entry which can't be parsed doesn't prevent to read the next ones.
+fn get_entries(data: &[u8], endian: gimli::RunTimeEndian, eh_frame: bool) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while let Some(length) = data.get(offset..offset + 4).map(|b| endian.read_u32(b)) {
+        // The 64-bit DWARF format has a 64-bit length and CIE id
+        let (length, start, id_size) = if length == 0xffff_ffff {
+            match data.get(offset + 4..offset + 12) {
+                Some(b) => (endian.read_u64(b), offset + 12, 8),
+                None => break,
+            }
+        } else {
+            (u64::from(length), offset + 4, 4)
+        };
+        let end = match usize::try_from(length)
+            .ok()
+            .and_then(|l| start.checked_add(l))
+        {
+            Some(end) if end <= data.len() => end,
+            _ => break,
+        };
+        // The terminator of .eh_frame or some padding
+        if length < id_size as u64 {
+            offset = end;
+            continue;
+        }
+
+        let id = if id_size == 8 {
+            endian.read_u64(&data[start..start + 8])
+        } else {
+            u64::from(endian.read_u32(&data[start..start + 4]))
+        };
+        let is_cie = match (eh_frame, id_size) {
+            (true, _) => id == 0,
+            (false, 8) => id == u64::MAX,
+            (false, _) => id == 0xffff_ffff,
+        };
+        entries.push(Entry {
+            offset,
+            is_cie,
+            body: start + id_size,
+            end,
+        });
+        offset = end;
+    }
+
+    entries
+}
+
+/// Remove the augmentation characters unknown by gimli from the CIEs, when it's
+/// possible to know how to read their augmentation data: they must be after the
+/// ones with data (`L`, `P` and `R`), or be one of the AArch64 ones without data
+/// (`B` for the return addresses signed with the B key and `G` for the frames with
+/// tagged memory). The augmentation string is shortened in place and the CIE is
+/// padded with DW_CFA_nop so the offsets of the entries don't change.
+/// None is returned when there isn't anything to change.
+fn patch_augmentations(
+    data: &[u8],
+    endian: gimli::RunTimeEndian,
+    eh_frame: bool,
+) -> Option<Vec<u8>> {
+    let mut patched: Option<Vec<u8>> = None;
+    for entry in get_entries(data, endian, eh_frame) {
+        if !entry.is_cie {
+            continue;
+        }
+        // The version and the NUL-terminated augmentation string
+        let start = entry.body + 1;
+        let cie = match data.get(start..entry.end) {
+            Some(cie) => cie,
+            None => continue,
+        };
+        let augmentation = match cie.iter().position(|b| *b == 0) {
+            Some(len) => &cie[..len],
+            None => continue,
+        };
+        if augmentation.first() != Some(&b'z') {
+            continue;
+        }
+
+        let known = |c: &u8| matches!(c, b'z' | b'L' | b'P' | b'R' | b'S');
+        if augmentation.iter().all(known) {
+            continue;
+        }
+        let last_with_data = augmentation
+            .iter()
+            .rposition(|c| matches!(c, b'L' | b'P' | b'R'))
+            .unwrap_or(0);
+        let decodable = augmentation
+            .iter()
+            .enumerate()
+            .all(|(i, c)| known(c) || i > last_with_data || matches!(c, b'B' | b'G'));
+        if !decodable {
+            continue;
+        }
+
+        let mut cie_data: Vec<u8> = augmentation.iter().copied().filter(known).collect();
+        cie_data.extend_from_slice(&cie[augmentation.len()..]);
+        cie_data.resize(cie.len(), 0);
+        patched.get_or_insert_with(|| data.to_vec())[start..entry.end].copy_from_slice(&cie_data);
+    }
+
+    patched
+}
+
+/// Get the FDEs of an unwind section sorted by address
+fn scan_fdes<'d, S>(
+    info: &UnwindInfo<S>,
+    entries: &[Entry],
+) -> Vec<FrameDescriptionEntry<Slice<'d>>>
+where
+    S: UnwindSection<Slice<'d>>,
+{
+    let mut fdes = Vec::new();
+    for entry in entries.iter().filter(|entry| !entry.is_cie) {
+        let offset = S::Offset::from(entry.offset);
+        match info
+            .section
+            .fde_from_offset(&info.bases, offset, S::cie_from_offset)
+        {
+            Ok(fde) => fdes.push(fde),
+            Err(_) => info.invalid.set(info.invalid.get() + 1),
+        }
+    }
+    fdes.sort_by_key(|fde| fde.initial_address());
+
+    fdes
+}
+
+/// Get the FDEs of .eh_frame from the table of .eh_frame_hdr, which is sorted by address.
+/// None is returned if there's no table.
+fn get_eh_frame_hdr_fdes<'d>(
+    info: &UnwindInfo<EhFrame<Slice<'d>>>,
+    hdr: &'d [u8],
+    endian: gimli::RunTimeEndian,
+    address_size: u8,
+) -> Option<Vec<FrameDescriptionEntry<Slice<'d>>>> {
+    let hdr = EhFrameHdr::new(hdr, endian)
+        .parse(&info.bases, address_size)
+        .ok()?;
+    let table = hdr.table()?;
+
+    let mut fdes = Vec::new();
+    let mut entries = table.iter(&info.bases);
+    loop {
+        match entries.next() {
+            Ok(Some((_, pointer))) => {
+                // A bad entry is skipped like a bad FDE in the scan
+                let fde = table.pointer_to_offset(pointer).and_then(|offset| {
+                    info.section
+                        .fde_from_offset(&info.bases, offset, EhFrame::cie_from_offset)
+                });
+                match fde {
+                    Ok(fde) => fdes.push(fde),
+                    Err(_) => info.invalid.set(info.invalid.get() + 1),
+                }
+            }
+            Ok(None) => break,
+            // The table can't be read (e.g. an unsupported encoding), the section is scanned
+            Err(_) => return None,
+        }
+    }
+
+    Some(fdes)
+}
+
+fn write_fdes<'d, S, W>(
+    info: &UnwindInfo<S>,
+    fdes: &[FrameDescriptionEntry<Slice<'d>>],
+    out: &mut W,
+) -> common::Result<()>
+where
+    S: UnwindSection<Slice<'d>>,
+    W: Write,
+{
+    // The unwind context is reused for all the FDEs
+    let mut ctx = UnwindContext::new();
+    for fde in fdes {
+        write_fde(info, &mut ctx, fde, out)?;
+    }
+    Ok(())
+}
+
+fn write_fde<'d, S, W>(
+    info: &UnwindInfo<S>,
+    ctx: &mut UnwindContext<Slice<'d>>,
+    fde: &FrameDescriptionEntry<Slice<'d>>,
+    out: &mut W,
+) -> common::Result<()>
+where
+    S: UnwindSection<Slice<'d>>,
+    W: Write,
+{
+    // Breakpad has a special name for the register of the return address
+    let ra = fde.cie().return_address_register();
+    let encoding = fde.cie().encoding();
+
+    // All the rows are collected to get the end of the range of the INIT record
+    // A FDE with invalid instructions is skipped
+    let invalid = |e: gimli::Error| {
+        let rva = fde.initial_address().wrapping_sub(info.load_address);
+        debug!(rva = rva; "CFI: the unwind rows of the FDE can't be read: {}", e);
+        info.invalid.set(info.invalid.get() + 1);
+    };
+    let mut table = match fde.rows(&info.section, &info.bases, ctx) {
+        Ok(table) => table,
+        Err(e) => {
+            invalid(e);
+            return Ok(());
+        }
+    };
+    let mut rows = Vec::new();
+    loop {
+        match table.next_row() {
+            Ok(None) => break,
+            Ok(Some(row)) => rows.push(row.clone()),
+            Err(gimli::Error::UnknownCallFrameInstruction(_)) => continue,
+            Err(gimli::Error::TooManyRegisterRules) => continue,
+            Err(e) => {
+                invalid(e);
+                return Ok(());
+            }
+        }
+    }
+
+    let (first, last) = match (rows.first(), rows.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Ok(()),
+    };
+    let start = first.start_address();
+    let length = last.end_address() - start;
+    // The entries at address 0 are the ones of the discarded functions
+    if start < info.load_address {
+        return Ok(());
+    }
+
+    // Only the rules which changed since the previous row are written
+    let mut rule_cache = HashMap::new();
+    let mut cfa_cache = None;
+    for row in &rows {
+        let mut written = false;
+        let mut line = Vec::new();
+
+        if row.start_address() == start {
+            write!(
+                line,
+                "STACK CFI INIT {:x} {:x}",
+                start - info.load_address,
+                length
+            )?;
+        } else {
+            write!(
+                line,
+                "STACK CFI {:x}",
+                row.start_address() - info.load_address
+            )?;
+        }
+
+        if cfa_cache != Some(row.cfa()) {
+            cfa_cache = Some(row.cfa());
+            written |= write_cfa_rule(&mut line, info, encoding, row.cfa())?;
+        }
+
+        let mut ra_written = false;
+        for (register, rule) in row.registers() {
+            if rule_cache.get(register) != Some(&rule) {
+                rule_cache.insert(*register, rule);
+                ra_written |= *register == ra;
+                written |= write_register_rule(&mut line, info, encoding, *register, rule, ra)?;
+            }
+        }
+        // On MIPS, the return address is in $ra when there's no rule for it
+        if row.start_address() == start
+            && !ra_written
+            && matches!(info.arch, Arch::Mips | Arch::Mips64)
+        {
+            write!(line, " .ra: $ra")?;
+        }
+
+        if written {
+            out.write_all(&line)?;
+            writeln!(out)?;
+            if row.start_address() == start && info.signal_frames && fde.is_signal_trampoline() {
+                writeln!(
+                    out,
+                    "STACK SIGNAL {:x} {:x}",
+                    start - info.load_address,
+                    length
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_cfa_rule<S, W: Write>(
+    out: &mut W,
+    info: &UnwindInfo<S>,
+    encoding: gimli::Encoding,
+    rule: &CfaRule<Slice>,
+) -> common::Result<bool> {
+    let rule = match rule {
+        CfaRule::RegisterAndOffset { register, offset } => {
+            match register_name(info.arch, *register) {
+                Some(register) => format!("{} {} +", register, offset),
+                None => return Ok(false),
+            }
+        }
+        CfaRule::Expression(expr) => match translate_expression(expr, encoding, info.arch, false) {
+            Some(expr) => expr,
+            None => {
+                info.untranslated.set(info.untranslated.get() + 1);
+                return Ok(false);
+            }
+        },
+    };
+
+    write!(out, " .cfa: {}", rule)?;
+    Ok(true)
+}
+
+fn write_register_rule<S, W: Write>(
+    out: &mut W,
+    info: &UnwindInfo<S>,
+    encoding: gimli::Encoding,
+    register: Register,
+    rule: &RegisterRule<Slice>,
+    ra: Register,
+) -> common::Result<bool> {
+    let arch = info.arch;
+    let rule = match rule {
+        RegisterRule::SameValue => match register_name(arch, register) {
+            Some(register) => register.to_string(),
+            None => return Ok(false),
+        },
+        RegisterRule::Offset(offset) => format!(".cfa {} + ^", offset),
+        RegisterRule::ValOffset(offset) => format!(".cfa {} +", offset),
+        RegisterRule::Register(register) => match register_name(arch, *register) {
+            Some(register) => register.to_string(),
+            None => return Ok(false),
+        },
+        // The expressions of the register rules get the CFA on the stack, and
+        // the result is the address of the saved value or the value itself
+        RegisterRule::Expression(expr) | RegisterRule::ValExpression(expr) => {
+            match translate_expression(expr, encoding, arch, true) {
+                Some(expr) if matches!(rule, RegisterRule::Expression(_)) => format!("{} ^", expr),
+                Some(expr) => expr,
+                None => {
+                    info.untranslated.set(info.untranslated.get() + 1);
+                    return Ok(false);
+                }
+            }
+        }
+        _ => return Ok(false),
+    };
+
+    let name = if register == ra {
+        ".ra"
+    } else {
+        match register_name(arch, register) {
+            Some(name) => name,
+            None => return Ok(false),
+        }
+    };
+
+    write!(out, " {}: {}", name, rule)?;
+    Ok(true)
+}
+
+/// A token of a Breakpad postfix expression
+#[derive(Debug, PartialEq)]
+enum Token {
+    Constant(i64),
+    Name(&'static str),
+    Operator(&'static str),
+}
+
+/// A Breakpad postfix expression being built from the operations of a DWARF expression
+struct Postfix {
+    tokens: Vec<Token>,
+    /// The number of values on the stack
+    depth: usize,
+    /// The CFA is at the bottom of the stack but isn't used yet
+    cfa_pending: bool,
+}
+
+impl Postfix {
+    fn push(&mut self, token: Token) {
+        self.tokens.push(token);
+        self.depth += 1;
+    }
+
+    /// Check that an operation has its `n` operands on the stack, the pending
+    /// CFA is written when the operation uses it.
+    fn pop(&mut self, n: usize) -> Option<()> {
+        if self.depth < n && self.cfa_pending {
+            self.cfa_pending = false;
+            self.tokens.insert(0, Token::Name(".cfa"));
+            self.depth += 1;
+        }
+        self.depth = self.depth.checked_sub(n)?;
+        Some(())
+    }
+
+    fn apply(&mut self, op: &'static str, operands: usize) -> Option<()> {
+        self.pop(operands)?;
+        self.tokens.push(Token::Operator(op));
+        self.depth += 1;
+        Some(())
+    }
+
+    fn finish(self) -> Option<String> {
+        let tokens = match self.depth {
+            0 if self.cfa_pending => vec![Token::Name(".cfa")],
+            1 => self.tokens,
+            _ => return None,
+        };
+        let tokens: Vec<_> = tokens
+            .iter()
+            .map(|token| match token {
+                Token::Constant(value) => value.to_string(),
+                Token::Name(name) | Token::Operator(name) => name.to_string(),
+            })
+            .collect();
+        Some(tokens.join(" "))
+    }
+}
+
+/// Translate a DWARF expression to a Breakpad postfix expression.
+///
+/// Only the operations of the stack walker can be translated: the constants, the
+/// registers (plus an offset), the CFA, the arithmetic, the dereference of a
+/// pointer and the alignment (an `and` with a negative power of two).
+/// The CFA is on the stack when evaluating the expressions of the register rules
+/// (`with_cfa`).
+fn translate_expression(
+    expr: &Expression<Slice>,
+    encoding: gimli::Encoding,
+    arch: Arch,
+    with_cfa: bool,
+) -> Option<String> {
+    let mut postfix = Postfix {
+        tokens: Vec::new(),
+        depth: 0,
+        cfa_pending: with_cfa,
+    };
+
+    let mut operations = expr.operations(encoding);
+    while let Some(operation) = operations.next().ok()? {
+        match operation {
+            Operation::UnsignedConstant { value } => {
+                postfix.push(Token::Constant(i64::try_from(value).ok()?));
+            }
+            Operation::SignedConstant { value } => postfix.push(Token::Constant(value)),
+            Operation::RegisterOffset {
+                register,
+                offset,
+                base_type,
+            } if base_type.0 == 0 => {
+                postfix.push(Token::Name(register_name(arch, register)?));
+                if offset != 0 {
+                    postfix.push(Token::Constant(offset));
+                    postfix.apply("+", 2)?;
+                }
+            }
+            Operation::CallFrameCFA if with_cfa => postfix.push(Token::Name(".cfa")),
+            Operation::PlusConstant { value } => {
+                postfix.push(Token::Constant(i64::try_from(value).ok()?));
+                postfix.apply("+", 2)?;
+            }
+            Operation::Plus => postfix.apply("+", 2)?,
+            Operation::Minus => postfix.apply("-", 2)?,
+            Operation::Mul => postfix.apply("*", 2)?,
+            Operation::Div => postfix.apply("/", 2)?,
+            Operation::Mod => postfix.apply("%", 2)?,
+            Operation::Neg => match postfix.tokens.last_mut() {
+                Some(Token::Constant(value)) => *value = value.checked_neg()?,
+                _ => return None,
+            },
+            // x & -2^n is the alignment of x on 2^n: x 2^n @
+            Operation::And => match postfix.tokens.last_mut() {
+                Some(Token::Constant(value))
+                    if *value < 0 && value.unsigned_abs().is_power_of_two() =>
+                {
+                    *value = value.checked_neg()?;
+                    postfix.apply("@", 2)?;
+                }
+                _ => return None,
+            },
+            Operation::Deref {
+                base_type,
+                size,
+                space: false,
+            } if base_type.0 == 0 && size == encoding.address_size => postfix.apply("^", 1)?,
+            Operation::Nop => {}
+            _ => return None,
+        }
+    }
+
+    postfix.finish()
+}
+
+/// The rule at the start of a function, before it changes the stack: the
+/// return address is on the top of the stack or in the link register
+fn get_leaf_rule(arch: Arch) -> Option<&'static str> {
+    match arch.cpu_family() {
+        CpuFamily::Amd64 => Some(".cfa: $rsp 8 + .ra: .cfa -8 + ^"),
+        CpuFamily::Intel32 => Some(".cfa: $esp 4 + .ra: .cfa -4 + ^"),
+        CpuFamily::Arm64 | CpuFamily::Arm64_32 => Some(".cfa: sp 0 + .ra: x30"),
+        CpuFamily::Arm32 => Some(".cfa: sp 0 + .ra: lr"),
+        _ => None,
+    }
+}
+
+/// Get the range of a STACK CFI INIT record
+fn get_init_range(line: &str) -> Option<Range<u32>> {
+    let mut fields = line.strip_prefix("STACK CFI INIT ")?.split(' ');
+    let start = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let len = u32::from_str_radix(fields.next()?, 16).ok()?;
+    Some(start..start.saturating_add(len))
+}
+
+/// Get the ranges of the STACK CFI INIT records, they're the extents of the
+/// functions described by the FDEs when the CFI comes from an ELF file.
+pub fn get_init_ranges(stack: &str) -> Vec<Range<u32>> {
+    stack.lines().filter_map(get_init_range).collect()
+}
+
+/// Add a STACK CFI INIT record with the rule of a function entry on the parts of
+/// the code not covered by the CFI, they're mainly the small leaf functions which
+/// don't touch the stack. The ranges are split at the function starts and all the
+/// records are sorted by address.
+pub fn add_leaf_cfi(stack: &str, arch: Arch, code: &[Range<u32>], starts: &[u32]) -> String {
+    let rule = match get_leaf_rule(arch) {
+        Some(rule) => rule,
+        None => return stack.to_string(),
+    };
+
+    // The records of a function, with its range
+    let mut blocks: Vec<(Range<u32>, String)> = Vec::new();
+    for line in stack.lines() {
+        let range = get_init_range(line);
+        match (range, blocks.last_mut()) {
+            (None, Some((_, block))) => {
+                block.push_str(line);
+                block.push('\n');
+            }
+            (range, _) => blocks.push((range.unwrap_or(0..0), format!("{}\n", line))),
+        }
+    }
+
+    let mut covered: Vec<_> = blocks.iter().map(|(range, _)| range.clone()).collect();
+    covered.sort_by_key(|range| range.start);
+
+    let mut leaves = Vec::new();
+    for section in code {
+        let mut pos = section.start;
+        let mut gaps = Vec::new();
+        for range in covered
+            .iter()
+            .filter(|r| r.end > section.start && r.start < section.end)
+        {
+            if range.start > pos {
+                gaps.push(pos..range.start);
+            }
+            pos = pos.max(range.end);
+        }
+        if pos < section.end {
+            gaps.push(pos..section.end);
+        }
+
+        for gap in gaps {
+            let mut start = gap.start;
+            let inner = starts.iter().filter(|s| **s > gap.start && **s < gap.end);
+            for end in inner.copied().chain(std::iter::once(gap.end)) {
+                leaves.push((
+                    start..end,
+                    format!("STACK CFI INIT {:x} {:x} {}\n", start, end - start, rule),
+                ));
+                start = end;
+            }
+        }
+    }
+
+    if leaves.is_empty() {
+        return stack.to_string();
+    }
+    blocks.extend(leaves);
+    blocks.sort_by_key(|(range, _)| range.start);
+    blocks.into_iter().map(|(_, block)| block).collect()
+}
+
+/// Get the size of the standard x86 prologue at the start of a function and the
+/// offset of its `push ebp`: `push ebp; mov ebp, esp`, maybe after the hotpatch
+/// `mov edi, edi`.
+fn get_frame_pointer_prologue(code: &[u8]) -> Option<(u32, u32)> {
+    let (push, code) = match code {
+        [0x8b, 0xff, rest @ ..] => (2, rest),
+        _ => (0, code),
+    };
+    match code {
+        [0x55, 0x8b, 0xec, ..] | [0x55, 0x89, 0xe5, ..] => Some((push, push + 3)),
+        _ => None,
+    }
+}
+
+/// The frame of an x86 function, from its S_FRAMEPROC record in a PDB
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WinFrame {
+    /// The size of the locals
+    pub locals_size: u32,
+    /// The size of the callee-saved registers pushed in the prologue
+    pub saved_regs_size: u32,
+    /// EBP is the frame pointer, else the function is compiled with FPO
+    pub has_frame_pointer: bool,
+}
+
+/// Get the STACK WIN records of the x86 functions, for the PDBs without frame
+/// data (e.g. some of the ones written by lld-link).
+///
+/// The frame of a function in `frames` chooses its record: an FPO function gets an
+/// FPO record with the sizes of its locals and of its saved registers. A function
+/// setting up a frame pointer (or without a known frame) gets the records that
+/// MSVC writes for its prologue: the return address is on the top of the stack at
+/// its start, then above the saved ebp and after the prologue it's found from ebp.
+pub fn get_frame_pointer_win(
+    code: &[(u32, &[u8])],
+    symbols: &Symbols,
+    frames: &HashMap<u32, WinFrame>,
+) -> String {
+    let mut stack = String::new();
+    for sym in symbols.values().filter(|sym| sym.len != 0) {
+        let frame = frames.get(&sym.rva);
+        if let Some(frame) = frame.filter(|f| !f.has_frame_pointer) {
+            stack.push_str(&format!(
+                "STACK WIN 0 {:x} {:x} 0 0 {:x} {:x} {:x} 0 0 0\n",
+                sym.rva, sym.len, sym.parameter_size, frame.saved_regs_size, frame.locals_size
+            ));
+            continue;
+        }
+
+        let bytes = code.iter().find_map(|(rva, data)| {
+            let start = sym.rva.checked_sub(*rva)? as usize;
+            data.get(start..start.saturating_add(sym.len as usize).min(data.len()))
+        });
+        let (push, prologue) = match bytes.and_then(get_frame_pointer_prologue) {
+            Some(prologue) if prologue.1 < sym.len => prologue,
+            _ => continue,
+        };
+        let programs = [
+            (0, 0, "$T0 .raSearch = $eip $T0 ^ = $esp $T0 4 + ="),
+            (
+                push + 1,
+                4,
+                "$T0 .raSearch = $eip $T0 ^ = $esp $T0 4 + = $ebp $T0 4 - ^ =",
+            ),
+            (
+                prologue,
+                4,
+                "$T0 $ebp 4 + = $eip $T0 ^ = $esp $T0 4 + = $ebp $T0 4 - ^ =",
+            ),
+        ];
+        let locals_size = frame.map_or(0, |f| f.locals_size);
+        for (offset, saved_regs, program) in programs {
+            stack.push_str(&format!(
+                "STACK WIN 4 {:x} {:x} {:x} 0 {:x} {:x} {:x} 0 1 {}\n",
+                sym.rva + offset,
+                sym.len - offset,
+                prologue - offset,
+                sym.parameter_size,
+                saved_regs,
+                locals_size,
+                program
+            ));
+        }
+    }
+    stack
+}
+
+/// Get the rules of a STACK CFI record by register
+fn parse_rules(rules: &str) -> HashMap<&str, String> {
+    let mut map = HashMap::new();
+    let mut register = None;
+    for token in rules.split_whitespace() {
+        if let Some(name) = token.strip_suffix(':') {
+            register = Some(name);
+            map.insert(name, String::new());
+        } else if let Some(expr) = register.and_then(|r| map.get_mut(r)) {
+            if !expr.is_empty() {
+                expr.push(' ');
+            }
+            expr.push_str(token);
+        }
+    }
+    map
+}
+
+/// The STACK CFI records of a function
+struct CfiBlock<'a> {
+    range: Range<u32>,
+    rules: &'a str,
+    /// The rules at the end of the range
+    state: HashMap<&'a str, String>,
+    /// The STACK CFI records after the INIT one and the STACK SIGNAL one
+    lines: Vec<&'a str>,
+    signal: bool,
+}
+
+/// Merge a STACK CFI INIT record into the previous function when it's adjacent
+/// and the rules at the end of the previous one are the same as its initial rules,
+/// so a sequence of functions with the same unwind rules gets only one INIT record.
+/// The signal frames aren't merged and the other lines (e.g. STACK WIN) are kept as is.
+pub fn merge_cfi(stack: &str) -> String {
+    let mut blocks: Vec<Result<CfiBlock, &str>> = Vec::new();
+    for line in stack.lines() {
+        let init = line.strip_prefix("STACK CFI INIT ").and_then(|init| {
+            let mut fields = init.splitn(3, ' ');
+            let start = u32::from_str_radix(fields.next()?, 16).ok()?;
+            let len = u32::from_str_radix(fields.next()?, 16).ok()?;
+            Some((
+                start..start.checked_add(len)?,
+                fields.next().unwrap_or_default(),
+            ))
+        });
+        if let Some((range, rules)) = init {
+            blocks.push(Ok(CfiBlock {
+                range,
+                rules,
+                state: parse_rules(rules),
+                lines: Vec::new(),
+                signal: false,
+            }));
+            continue;
+        }
+
+        match (line.strip_prefix("STACK CFI "), blocks.last_mut()) {
+            (Some(delta), Some(Ok(block))) => {
+                let rules = delta.split_once(' ').map(|(_, rules)| rules);
+                block.state.extend(parse_rules(rules.unwrap_or_default()));
+                block.lines.push(line);
+            }
+            (None, Some(Ok(block))) if line.starts_with("STACK SIGNAL ") => {
+                block.signal = true;
+                block.lines.push(line);
+            }
+            _ => blocks.push(Err(line)),
+        }
+    }
+
+    let mut merged: Vec<Result<CfiBlock, &str>> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        if let (Some(Ok(prev)), Ok(block)) = (merged.last_mut(), &block) {
+            if !prev.signal
+                && !block.signal
+                && prev.range.end == block.range.start
+                && prev.state == parse_rules(block.rules)
+            {
+                prev.range.end = block.range.end;
+                prev.state.clone_from(&block.state);
+                prev.lines.extend_from_slice(&block.lines);
+                continue;
+            }
+        }
+        merged.push(block);
+    }
+
+    let mut out = String::with_capacity(stack.len());
+    for block in merged {
+        match block {
+            Ok(block) => {
+                out.push_str(&format!(
+                    "STACK CFI INIT {:x} {:x} {}\n",
+                    block.range.start,
+                    block.range.end - block.range.start,
+                    block.rules
+                ));
+                for line in block.lines {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            }
+            Err(line) => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// How the STACK CFI records are validated once they're generated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CfiValidation {
+    /// No validation
+    #[default]
+    Off,
+    /// Warn about the invalid records
+    Warn,
+    /// Fail on the invalid records
+    Strict,
+}
+
+impl FromStr for CfiValidation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "strict" => Ok(Self::Strict),
+            _ => anyhow::bail!("Invalid CFI validation mode: {}", s),
+        }
+    }
+}
+
+/// Check that the rules of a STACK CFI record are well-formed: a list of
+/// `register: expression` where each postfix expression leaves one value on the stack.
+/// The registers with a rule are added to `defined`.
+fn check_rules<'a>(rules: &'a str, defined: &mut Vec<&'a str>) -> Result<(), String> {
+    let mut register: Option<&str> = None;
+    let mut depth = 0usize;
+    let end_rule = |register: Option<&str>, depth: usize| match register {
+        Some(register) if depth != 1 => Err(format!("invalid expression for {}", register)),
+        None if depth != 0 => Err("expression without a register".to_string()),
+        _ => Ok(()),
+    };
+
+    for token in rules.split_whitespace() {
+        if let Some(name) = token.strip_suffix(':') {
+            end_rule(register, depth)?;
+            if name.is_empty() {
+                return Err("empty register name".to_string());
+            }
+            register = Some(name);
+            defined.push(name);
+            depth = 0;
+            continue;
+        }
+        depth = match token {
+            "+" | "-" | "*" | "/" | "%" | "@" => depth
+                .checked_sub(1)
+                .filter(|d| *d != 0)
+                .ok_or_else(|| format!("missing operand for {}", token))?,
+            "^" if depth == 0 => return Err("missing operand for ^".to_string()),
+            "^" => depth,
+            _ => depth + 1,
+        };
+    }
+
+    end_rule(register, depth)
+}
+
+/// Check the STACK CFI records for their internal consistency: the INIT ranges
+/// are in one of the sections of the module and don't overlap, they define the
+/// `.cfa` and `.ra` rules, the following records are in the range of their INIT
+/// and all the expressions are well-formed.
+/// An INIT record with only a .cfa rule is the one of an outermost frame, it
+/// doesn't need a .ra rule. The STACK WIN records aren't checked.
+pub fn validate_cfi(stack: &str, sections: &[Range<u32>]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut inits: Vec<Range<u32>> = Vec::new();
+    let parse_hex = |s: Option<&str>| s.and_then(|s| u32::from_str_radix(s, 16).ok());
+
+    for line in stack.lines() {
+        let mut problem = |what: String| problems.push(format!("{}: {}", line, what));
+        if let Some(init) = line.strip_prefix("STACK CFI INIT ") {
+            let mut fields = init.splitn(3, ' ');
+            let (start, len) = match (parse_hex(fields.next()), parse_hex(fields.next())) {
+                (Some(start), Some(len)) => (start, len),
+                _ => {
+                    problem("invalid range".to_string());
+                    continue;
+                }
+            };
+            let range = start..start.saturating_add(len);
+            if !sections.is_empty()
+                && !sections
+                    .iter()
+                    .any(|s| s.start <= range.start && range.end <= s.end)
+            {
+                problem("the range isn't in a section of the module".to_string());
+            }
+
+            let mut defined = Vec::new();
+            match check_rules(fields.next().unwrap_or_default(), &mut defined) {
+                Ok(()) => {
+                    // The outermost frames (e.g. `_start`) have an undefined return
+                    // address, so their INIT record has only a .cfa rule
+                    let outermost = defined == [".cfa"];
+                    for register in &[".cfa", ".ra"] {
+                        if !defined.contains(register) && !outermost {
+                            problem(format!("no {} rule", register));
+                        }
+                    }
+                }
+                Err(e) => problem(e),
+            }
+            inits.push(range);
+        } else if let Some(record) = line.strip_prefix("STACK CFI ") {
+            let mut fields = record.splitn(2, ' ');
+            match (parse_hex(fields.next()), inits.last()) {
+                (Some(address), Some(init)) if init.contains(&address) => {}
+                (Some(_), Some(_)) => problem("the address isn't in the INIT range".to_string()),
+                (Some(_), None) => problem("there isn't any INIT record before".to_string()),
+                (None, _) => problem("invalid address".to_string()),
+            }
+            if let Err(e) = check_rules(fields.next().unwrap_or_default(), &mut Vec::new()) {
+                problem(e);
+            }
+        }
+    }
+
+    inits.sort_by_key(|range| range.start);
+    for pair in inits.windows(2) {
+        if pair[1].start < pair[0].end {
+            problems.push(format!(
+                "STACK CFI INIT {:x} {:x} overlaps STACK CFI INIT {:x} {:x}",
+                pair[1].start,
+                pair[1].end - pair[1].start,
+                pair[0].start,
+                pair[0].end - pair[0].start
+            ));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symbolic::debuginfo::Object;
+
+    #[test]
+    fn test_eh_frame_hdr() {
+        let data = std::fs::read("./test_data/linux/basic.full").unwrap();
+        let elf = match Object::parse(&data).unwrap() {
+            Object::Elf(elf) => elf,
+            _ => unreachable!(),
+        };
+        let endian = elf.endianity();
+        let eh_frame = elf.section("eh_frame").unwrap();
+        let hdr = elf.section("eh_frame_hdr").unwrap();
+        let bases = BaseAddresses::default()
+            .set_eh_frame(eh_frame.address)
+            .set_eh_frame_hdr(hdr.address);
+        let info = UnwindInfo::new(&elf, EhFrame::new(&eh_frame.data, endian), bases, false);
+
+        // The table has the same FDEs as the section, in address order
+        let from_table: Vec<_> = get_eh_frame_hdr_fdes(&info, &hdr.data, endian, 8)
+            .unwrap()
+            .iter()
+            .map(|fde| fde.initial_address())
+            .collect();
+        let from_scan: Vec<_> = scan_fdes(&info, &get_entries(&eh_frame.data, endian, true))
+            .iter()
+            .map(|fde| fde.initial_address())
+            .collect();
+        assert!(!from_table.is_empty());
+        assert!(from_table.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(from_table, from_scan);
+
+        let mut out = Vec::new();
+        write_elf_cfi(&elf, false, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let starts: Vec<_> = out
+            .lines()
+            .filter_map(|l| l.strip_prefix("STACK CFI INIT "))
+            .map(|l| u64::from_str_radix(l.split(' ').next().unwrap(), 16).unwrap())
+            .collect();
+        assert_eq!(starts.len(), from_table.len());
+        assert!(starts.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_augmentations() {
+        // A CIE with a 3-character augmentation and an absolute FDE address
+        // encoding (DW_EH_PE_udata4), and a FDE for 0x1000..0x1010
+        let eh_frame = |augmentation: &[u8; 3]| {
+            let mut data = vec![20, 0, 0, 0, 0, 0, 0, 0, 1];
+            data.extend_from_slice(augmentation);
+            // The alignment factors, the return address register, the augmentation data,
+            // DW_CFA_def_cfa rsp 8, DW_CFA_offset rip 1 and DW_CFA_nop
+            data.extend_from_slice(&[0, 1, 0x78, 0x10, 1, 0x03, 0x0c, 0x07, 0x08, 0x90, 0x01, 0]);
+            data.extend_from_slice(&[16, 0, 0, 0, 28, 0, 0, 0]);
+            data.extend_from_slice(&0x1000u32.to_le_bytes());
+            data.extend_from_slice(&0x10u32.to_le_bytes());
+            // DW_CFA_advance_loc 1 and DW_CFA_def_cfa_offset 16
+            data.extend_from_slice(&[0, 0x41, 0x0e, 0x10]);
+            data.extend_from_slice(&[0, 0, 0, 0]);
+            data
+        };
+        let endian = gimli::RunTimeEndian::Little;
+        let write = |data: &[u8], signal_frames| {
+            let info = UnwindInfo {
+                arch: Arch::Amd64,
+                load_address: 0,
+                section: EhFrame::new(data, endian),
+                bases: BaseAddresses::default(),
+                signal_frames,
+                untranslated: Cell::new(0),
+                invalid: Cell::new(0),
+            };
+            let fdes = scan_fdes(&info, &get_entries(data, endian, true));
+            let mut out = Vec::new();
+            write_fdes(&info, &fdes, &mut out).unwrap();
+            (String::from_utf8(out).unwrap(), info.invalid.get())
+        };
+        let cfi = "STACK CFI INIT 1000 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+                   STACK CFI 1001 .cfa: $rsp 16 +\n";
+
+        let entries = get_entries(&eh_frame(b"zRS"), endian, true);
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_cie && !entries[1].is_cie);
+        assert_eq!((entries[1].offset, entries[1].body), (24, 32));
+
+        // The return address signed with the B key on AArch64
+        let data = eh_frame(b"zRB");
+        assert_eq!(write(&data, false), (String::new(), 1));
+        let patched = patch_augmentations(&data, endian, true).unwrap();
+        assert_eq!(&patched[9..13], b"zR\0\x01");
+        assert_eq!(patched.len(), data.len());
+        assert_eq!(write(&patched, false), (cfi.to_string(), 0));
+
+        // An unknown augmentation can be skipped after the ones with data
+        assert!(patch_augmentations(&eh_frame(b"zRX"), endian, true).is_some());
+        assert!(patch_augmentations(&eh_frame(b"zXR"), endian, true).is_none());
+
+        // A signal frame
+        let data = eh_frame(b"zRS");
+        assert!(patch_augmentations(&data, endian, true).is_none());
+        assert_eq!(write(&data, false), (cfi.to_string(), 0));
+        assert_eq!(
+            write(&data, true).0,
+            "STACK CFI INIT 1000 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+             STACK SIGNAL 1000 10\n\
+             STACK CFI 1001 .cfa: $rsp 16 +\n"
+        );
+    }
+
+    #[test]
+    fn test_translate_expression() {
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 1,
+            address_size: 8,
+        };
+        let translate = |bytes: &[u8], with_cfa| {
+            let expr = Expression(EndianSlice::new(bytes, gimli::RunTimeEndian::Little));
+            translate_expression(&expr, encoding, Arch::Amd64, with_cfa)
+        };
+
+        // The CFA of a realigned frame: DW_OP_breg6 -8; DW_OP_deref
+        assert_eq!(
+            translate(&[0x76, 0x78, 0x06], false).as_deref(),
+            Some("$rbp -8 + ^")
+        );
+        // The CFA isn't written when it's not used: DW_OP_breg6 0
+        assert_eq!(translate(&[0x76, 0x00], true).as_deref(), Some("$rbp"));
+        // DW_OP_lit16; DW_OP_minus on the CFA
+        assert_eq!(translate(&[0x40, 0x1c], true).as_deref(), Some(".cfa 16 -"));
+        // DW_OP_call_frame_cfa; DW_OP_plus_uconst 8
+        assert_eq!(
+            translate(&[0x9c, 0x23, 0x08], true).as_deref(),
+            Some(".cfa 8 +")
+        );
+        // DW_OP_breg7 0; DW_OP_const1s -32; DW_OP_and
+        assert_eq!(
+            translate(&[0x77, 0x00, 0x09, 0xe0, 0x1a], false).as_deref(),
+            Some("$rsp 32 @")
+        );
+        // The PLT entries: DW_OP_breg7 8; DW_OP_breg16 0; DW_OP_lit15; DW_OP_and;
+        // DW_OP_lit11; DW_OP_ge; DW_OP_lit3; DW_OP_shl; DW_OP_plus
+        assert_eq!(
+            translate(
+                &[0x77, 0x08, 0x80, 0x00, 0x3f, 0x1a, 0x3b, 0x2a, 0x33, 0x24, 0x22],
+                false
+            ),
+            None
+        );
+        // The operands are missing
+        assert_eq!(translate(&[0x22], false), None);
+        // The value is in a register: DW_OP_reg6
+        assert_eq!(translate(&[0x56], false), None);
+    }
+
+    #[test]
+    fn test_leaf_cfi() {
+        let stack = "STACK CFI INIT 1010 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+                     STACK CFI 1011 .cfa: $rsp 16 +\n\
+                     STACK CFI INIT 1030 8 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n";
+        let code = [0x1000..0x1040, 0x2000..0x2004];
+        let stack = add_leaf_cfi(stack, Arch::Amd64, &code, &[0x1000, 0x1004, 0x1030]);
+
+        assert_eq!(
+            stack,
+            "STACK CFI INIT 1000 4 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+             STACK CFI INIT 1004 c .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+             STACK CFI INIT 1010 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+             STACK CFI 1011 .cfa: $rsp 16 +\n\
+             STACK CFI INIT 1020 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+             STACK CFI INIT 1030 8 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+             STACK CFI INIT 1038 8 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+             STACK CFI INIT 2000 4 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n"
+        );
+
+        // The records are kept as is when the architecture isn't supported
+        let stack = "STACK CFI INIT 1010 10 .cfa: $sp 0 +\n";
+        assert_eq!(add_leaf_cfi(stack, Arch::Ppc, &code, &[]), stack);
+    }
+
+    #[test]
+    fn test_frame_pointer_win() {
+        let buf = std::fs::read("./test_data/windows/basic32.dll").unwrap();
+        let object = Object::parse(&buf).unwrap();
+        let code = crate::symbol::get_pe_code(&object);
+        let mut symbols = Symbols::new();
+        for (rva, len, parameter_size) in [(0x6bf8, 8, 0), (0x6c00, 0x17, 8)] {
+            symbols.insert(
+                rva,
+                crate::symbol::Symbol {
+                    rva,
+                    len,
+                    parameter_size,
+                    ..Default::default()
+                },
+            );
+        }
+
+        // The padding before test2 isn't a function with a frame pointer
+        assert_eq!(
+            get_frame_pointer_win(&code, &symbols, &HashMap::new()),
+            "STACK WIN 4 6c00 17 3 0 8 0 0 0 1 $T0 .raSearch = $eip $T0 ^ = $esp $T0 4 + =\n\
+             STACK WIN 4 6c01 16 2 0 8 4 0 0 1 $T0 .raSearch = $eip $T0 ^ = $esp $T0 4 + = $ebp $T0 4 - ^ =\n\
+             STACK WIN 4 6c03 14 0 0 8 4 0 0 1 $T0 $ebp 4 + = $eip $T0 ^ = $esp $T0 4 + = $ebp $T0 4 - ^ =\n"
+        );
+
+        // The frames from S_FRAMEPROC give the locals, and an FPO function gets
+        // an FPO record whatever its code
+        let mut frames = HashMap::new();
+        frames.insert(
+            0x6c00,
+            WinFrame {
+                locals_size: 0x10,
+                saved_regs_size: 0,
+                has_frame_pointer: true,
+            },
+        );
+        frames.insert(
+            0x6bf8,
+            WinFrame {
+                locals_size: 0x20,
+                saved_regs_size: 8,
+                has_frame_pointer: false,
+            },
+        );
+        assert_eq!(
+            get_frame_pointer_win(&code, &symbols, &frames),
+            "STACK WIN 0 6bf8 8 0 0 0 8 20 0 0 0\n\
+             STACK WIN 4 6c00 17 3 0 8 0 10 0 1 $T0 .raSearch = $eip $T0 ^ = $esp $T0 4 + =\n\
+             STACK WIN 4 6c01 16 2 0 8 4 10 0 1 $T0 .raSearch = $eip $T0 ^ = $esp $T0 4 + = $ebp $T0 4 - ^ =\n\
+             STACK WIN 4 6c03 14 0 0 8 4 10 0 1 $T0 $ebp 4 + = $eip $T0 ^ = $esp $T0 4 + = $ebp $T0 4 - ^ =\n"
+        );
+    }
+
+    #[test]
+    fn test_frame_pointer_prologue() {
+        assert_eq!(
+            get_frame_pointer_prologue(&[0x55, 0x8b, 0xec, 0x5d]),
+            Some((0, 3))
+        );
+        assert_eq!(
+            get_frame_pointer_prologue(&[0x8b, 0xff, 0x55, 0x89, 0xe5]),
+            Some((2, 5))
+        );
+        assert_eq!(get_frame_pointer_prologue(&[0x8b, 0xff, 0xc3]), None);
+        assert_eq!(get_frame_pointer_prologue(&[0x55]), None);
+    }
+
+    #[test]
+    fn test_validate_cfi() {
+        let sections = [0x1000..0x2000, 0x3000..0x3010];
+        let stack = "STACK CFI INIT 1010 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+                     STACK CFI 1011 .cfa: $rsp 16 + $rbp: .cfa -16 + ^\n\
+                     STACK CFI INIT 1020 8 .cfa: $rsp 32 @ .ra: x30\n\
+                     STACK CFI INIT 3000 10 .cfa: $rsp 8 +\n\
+                     STACK WIN 4 1000 10 0 0 0 0 0 0 1 $T0 .raSearch =\n";
+        assert!(validate_cfi(stack, &sections).is_empty());
+
+        let stack = "STACK CFI 1000 .cfa: $rsp 8 +\n\
+                     STACK CFI INIT 1010 10 .cfa: $rsp 8 + $rbx: .cfa -16 + ^\n\
+                     STACK CFI 1020 .cfa: $rsp + .ra: ^\n\
+                     STACK CFI INIT 1018 10 .cfa: $rsp 8 + 1 .ra: .cfa\n\
+                     STACK CFI INIT 1ff8 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n";
+        assert_eq!(
+            validate_cfi(stack, &sections),
+            vec![
+                "STACK CFI 1000 .cfa: $rsp 8 +: there isn't any INIT record before",
+                "STACK CFI INIT 1010 10 .cfa: $rsp 8 + $rbx: .cfa -16 + ^: no .ra rule",
+                "STACK CFI 1020 .cfa: $rsp + .ra: ^: the address isn't in the INIT range",
+                "STACK CFI 1020 .cfa: $rsp + .ra: ^: missing operand for +",
+                "STACK CFI INIT 1018 10 .cfa: $rsp 8 + 1 .ra: .cfa: invalid expression for .cfa",
+                "STACK CFI INIT 1ff8 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^: the range isn't in a section of the module",
+                "STACK CFI INIT 1018 10 overlaps STACK CFI INIT 1010 10",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_cfi() {
+        let stack = "STACK CFI INIT 1000 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+                     STACK CFI INIT 1010 4 .ra: .cfa -8 + ^ .cfa: $rsp 8 +\n\
+                     STACK CFI INIT 1014 c .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+                     STACK CFI 1018 .cfa: $rsp 16 + $rbp: .cfa -16 + ^\n\
+                     STACK CFI INIT 1020 10 .cfa: $rsp 16 +  $rbp: .cfa -16 + ^ .ra: .cfa -8 + ^\n\
+                     STACK CFI 1021 .cfa: $rsp 8 +\n\
+                     STACK CFI INIT 1030 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+                     STACK CFI INIT 1040 8 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+                     STACK WIN 4 1048 10 0 0 0 0 0 0 1 $T0 .raSearch =\n\
+                     STACK CFI INIT 1048 8 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n";
+
+        // The rules at the end of a function are the ones at the start of the next one
+        // (the rule of $rbp is still there in 1030)
+        assert_eq!(
+            merge_cfi(stack),
+            "STACK CFI INIT 1000 30 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+             STACK CFI 1018 .cfa: $rsp 16 + $rbp: .cfa -16 + ^\n\
+             STACK CFI 1021 .cfa: $rsp 8 +\n\
+             STACK CFI INIT 1030 18 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+             STACK WIN 4 1048 10 0 0 0 0 0 0 1 $T0 .raSearch =\n\
+             STACK CFI INIT 1048 8 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n"
+        );
+    }
+}
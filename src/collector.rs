@@ -3,19 +3,41 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+#[cfg(feature = "pdb")]
 use goblin::pe::exception::ExceptionData;
+use hashbrown::{hash_map, HashMap, HashSet};
 use log::{error, warn};
+use regex::Regex;
 use std::collections::btree_map;
+use std::ops::Range;
+use std::str::FromStr;
+#[cfg(feature = "pdb")]
+use symbolic::common::Arch;
 use symbolic::common::{Language, Name, NameMangling};
-use symbolic::debuginfo::{Function, Object, ObjectDebugSession};
+#[cfg(feature = "pdb")]
+use symbolic::debuginfo::pe::{PeObject, SectionTable};
+use symbolic::debuginfo::{FileInfo, Function, Object, ObjectDebugSession};
 use symbolic::demangle::Demangle;
 
 use super::source::SourceFiles;
-use super::symbol::{should_skip_symbol, ContainsSymbol, ParsedWinFuncName, Symbol, Symbols};
-use crate::common::{self, demangle_options};
+use super::symbol::{
+    should_skip_symbol, ContainsSymbol, DataSymbol, DataSymbols, Label, ParsedWinFuncName, Symbol,
+    Symbols,
+};
+use crate::cfi::CfiValidation;
+use crate::common::{self, DemangleCache, FailureKind, InputEncoding};
+use crate::dwarf::{DeclLine, DwarfName, LineFlags};
 use crate::inline_origins::InlineOrigins;
 use crate::line::{InlineAddressRange, InlineSite, Lines};
+use crate::mapping::PathGlobs;
+use crate::outlined::Outlined;
 use crate::platform::Platform;
+use crate::progress;
+use crate::stabs::StabsFunction;
+#[cfg(feature = "pdb")]
+use crate::windows::fastlink::ObjectFunction;
+#[cfg(feature = "pdb")]
+use crate::windows::{self, map::LinkerMap};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Type {
@@ -23,6 +45,524 @@ pub enum Type {
     DebugInfo,
 }
 
+/// What is collected in addition to the FUNC and PUBLIC records
+#[derive(Clone, Debug, Default)]
+pub struct CollectOptions {
+    /// Collect the inlined functions to emit INLINE and INLINE_ORIGIN records
+    pub inlines: bool,
+    /// Collect the labels inside the functions to emit LABEL records (extension)
+    pub labels: bool,
+    /// Collect the source languages of the functions to emit LANGUAGE records (extension)
+    pub languages: bool,
+    /// Collect the global and static variables to emit DATA records (extension)
+    pub data: bool,
+    /// Collect the functions of the object files referenced by a /DEBUG:FASTLINK PDB
+    pub fastlink_objects: bool,
+    /// The filter for the PUBLIC records
+    pub publics: PublicFilter,
+    /// The filter for the modules of the PDB files
+    pub compilands: CompilandFilter,
+    /// What to do with the compiler thunks
+    pub thunks: Thunks,
+    /// What to do with the functions of the LLVM machine outliner
+    pub outlined: Outlined,
+    /// Give the name of their parent function to the funclets
+    pub merge_funclets: bool,
+    /// Give the name of their parent function to the parts of the split functions
+    pub merge_split_functions: bool,
+    /// Synthesize the CFI of the code without unwind info (leaf functions)
+    pub leaf_cfi: bool,
+    /// Mark the signal frames with a STACK SIGNAL record (extension)
+    pub signal_frames: bool,
+    /// Merge the adjacent functions with the same unwind rules in one STACK CFI INIT record
+    pub merge_cfi: bool,
+    /// How to validate the STACK CFI records
+    pub validate_cfi: CfiValidation,
+    /// Look for the functions in the code without symbols nor unwind info
+    pub discover_functions: bool,
+    /// Check the x86 parameter sizes against the `ret n` ending the functions
+    pub check_param_sizes: bool,
+    /// What covers the code between the functions
+    pub gap_fill: GapFill,
+    /// Log the code of the executable sections not covered by a symbol
+    pub report_gaps: bool,
+    /// Where the public symbol after the last one is added
+    pub sentinel: Sentinel,
+    /// The name of that symbol instead of `<unknown in module>`
+    pub sentinel_name: Option<String>,
+    /// What to do with the public symbols inside a function
+    pub inner_publics: InnerPublics,
+    /// Which source names a symbol when several of them have one for its address
+    pub priority: SourcePriority,
+    /// The hard limits for the untrusted files
+    pub limits: ParseLimits,
+    /// The encoding of the strings which aren't UTF-8
+    pub encoding: InputEncoding,
+}
+
+impl CollectOptions {
+    /// The names of the enabled options which change the output, like the flags,
+    /// they're written in the INFO GENERATOR record
+    pub fn features(&self) -> Vec<&'static str> {
+        let publics = &self.publics;
+        let flags = [
+            (self.inlines, "inlines"),
+            (self.labels, "labels"),
+            (self.languages, "languages"),
+            (self.data, "data"),
+            (self.fastlink_objects, "fastlink-objects"),
+            (
+                !publics.include.is_empty() || !publics.exclude.is_empty() || publics.no_default,
+                "public-filter",
+            ),
+            (!self.compilands.is_empty(), "compiland-filter"),
+            (self.thunks == Thunks::Flag, "thunks=flag"),
+            (self.thunks == Thunks::Collapse, "thunks=collapse"),
+            (self.outlined == Outlined::Flag, "outlined=flag"),
+            (self.outlined == Outlined::Callers, "outlined=callers"),
+            (self.merge_funclets, "merge-funclets"),
+            (self.merge_split_functions, "merge-split-functions"),
+            (self.leaf_cfi, "leaf-cfi"),
+            (self.signal_frames, "signal-frames"),
+            (self.merge_cfi, "merge-cfi"),
+            (self.discover_functions, "discover-functions"),
+            (self.check_param_sizes, "check-param-sizes"),
+            (self.gap_fill == GapFill::Off, "gap-fill=off"),
+            (self.gap_fill == GapFill::Unknown, "gap-fill=unknown"),
+            (
+                self.sentinel == Sentinel::SectionEnd,
+                "sentinel=section-end",
+            ),
+            (self.sentinel == Sentinel::Off, "sentinel=off"),
+            (self.sentinel_name.is_some(), "sentinel-name"),
+            (
+                self.inner_publics == InnerPublics::Drop,
+                "inner-publics=drop",
+            ),
+            (
+                self.inner_publics == InnerPublics::Keep,
+                "inner-publics=keep",
+            ),
+            (self.priority != SourcePriority::default(), "priority"),
+            (!self.encoding.is_auto(), "input-encoding"),
+        ];
+        flags
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+/// The hard limits on what's collected from a file, so a hostile one can't
+/// exhaust the memory: a dump exceeding one of them fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// The maximum number of symbols (FUNC and PUBLIC records)
+    pub max_symbols: Option<usize>,
+    /// The maximum number of line records
+    pub max_lines: Option<usize>,
+    /// The maximum length of a symbol name or of a source path
+    pub max_string_len: Option<usize>,
+    /// The maximum ratio between the size of the symbols and the size of the file
+    pub max_expansion: Option<u64>,
+}
+
+impl ParseLimits {
+    fn exceeded(what: String) -> anyhow::Error {
+        common::failure(
+            FailureKind::LimitExceeded,
+            format!("Limits exceeded: {}", what),
+        )
+    }
+
+    pub(crate) fn check_symbols(&self, count: usize) -> common::Result<()> {
+        match self.max_symbols {
+            Some(max) if count > max => Err(Self::exceeded(format!(
+                "more than {} symbols (--max-symbols)",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_lines(&self, count: usize) -> common::Result<()> {
+        match self.max_lines {
+            Some(max) if count > max => Err(Self::exceeded(format!(
+                "more than {} line records (--max-lines)",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_string(&self, s: &str) -> common::Result<()> {
+        match self.max_string_len {
+            Some(max) if s.len() > max => Err(Self::exceeded(format!(
+                "a string of {} bytes is longer than {} (--max-string-length): {}...",
+                s.len(),
+                max,
+                s.chars().take(32).collect::<String>()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_expansion(&self, size: u64, file_size: u64) -> common::Result<()> {
+        match self.max_expansion {
+            Some(max) if size > file_size.saturating_mul(max) => Err(Self::exceeded(format!(
+                "the symbols ({} bytes) are more than {} times the size of the file ({} bytes) (--max-expansion)",
+                size, max, file_size
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What to do with the thunks inserted by the compiler (Control Flow Guard checks,
+/// stack cookie checks, stack probes, ...)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Thunks {
+    /// Keep their names
+    #[default]
+    Keep,
+    /// Add a ` [thunk]` suffix to their names
+    Flag,
+    /// Give them all the same name: `<compiler thunk>`
+    Collapse,
+}
+
+impl FromStr for Thunks {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "flag" => Ok(Self::Flag),
+            "collapse" => Ok(Self::Collapse),
+            _ => anyhow::bail!("Invalid thunks mode: {}", s),
+        }
+    }
+}
+
+/// What covers the code between the functions (padding, stripped code, ...)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GapFill {
+    /// The functions of the linker map without a size extend up to the next symbol
+    #[default]
+    Extend,
+    /// Keep the exact ranges: the functions without a size are PUBLIC records
+    Off,
+    /// Keep the exact ranges and start an `<unknown>` symbol after the functions
+    Unknown,
+}
+
+impl FromStr for GapFill {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "extend" => Ok(Self::Extend),
+            "off" => Ok(Self::Off),
+            "unknown" => Ok(Self::Unknown),
+            _ => anyhow::bail!("Invalid gap fill policy: {}", s),
+        }
+    }
+}
+
+/// Where the public symbol ending the last function of a PE file is added
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Sentinel {
+    /// Just after the last symbol
+    #[default]
+    AfterLast,
+    /// At the end of the section of the last symbol
+    SectionEnd,
+    /// Nowhere, some tools take it for a real export
+    Off,
+}
+
+impl FromStr for Sentinel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "after-last" => Ok(Self::AfterLast),
+            "section-end" => Ok(Self::SectionEnd),
+            "off" => Ok(Self::Off),
+            _ => anyhow::bail!("Invalid sentinel policy: {}", s),
+        }
+    }
+}
+
+/// What to do with the public symbols inside a function, they could be counted
+/// twice by the tools reading the symbols
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InnerPublics {
+    /// Skip the ones inside the symbol before them when they're collected
+    #[default]
+    Nearest,
+    /// Drop all the ones inside a function
+    Drop,
+    /// Keep them all
+    Keep,
+}
+
+impl FromStr for InnerPublics {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Self::Nearest),
+            "drop" => Ok(Self::Drop),
+            "keep" => Ok(Self::Keep),
+            _ => anyhow::bail!("Invalid inner publics policy: {}", s),
+        }
+    }
+}
+
+/// A source of the names of the symbols
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolSource {
+    /// The functions of the PDB or of the DWARF
+    DebugInfo,
+    /// The functions of the linker map next to a PE file without PDB
+    LinkerMap,
+    /// The public symbols exported by the PE file
+    Exports,
+    /// The public symbols of the PDB or of the symbol table
+    Publics,
+    /// The functions of the exception data, they're `<unknown in module>`
+    ExceptionData,
+}
+
+impl SymbolSource {
+    const ALL: [Self; 5] = [
+        Self::DebugInfo,
+        Self::LinkerMap,
+        Self::Exports,
+        Self::Publics,
+        Self::ExceptionData,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::DebugInfo => "debug-info",
+            Self::LinkerMap => "linker-map",
+            Self::Exports => "exports",
+            Self::Publics => "publics",
+            Self::ExceptionData => "exception-data",
+        }
+    }
+}
+
+impl FromStr for SymbolSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::ALL.iter().find(|source| source.name() == s) {
+            Some(source) => Ok(*source),
+            None => anyhow::bail!("Invalid symbol source: {}", s),
+        }
+    }
+}
+
+/// The order of the sources naming a symbol when they disagree about an address,
+/// the first one wins. By default: `debug-info,linker-map,exports,publics,exception-data`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourcePriority(Vec<SymbolSource>);
+
+impl Default for SourcePriority {
+    fn default() -> Self {
+        Self(SymbolSource::ALL.to_vec())
+    }
+}
+
+impl SourcePriority {
+    /// Whether the name from the first source wins over the one from the second
+    pub fn prefers(&self, a: SymbolSource, b: SymbolSource) -> bool {
+        let pos = |source| self.0.iter().position(|s| *s == source);
+        pos(a) < pos(b)
+    }
+}
+
+impl FromStr for SourcePriority {
+    type Err = anyhow::Error;
+
+    /// A comma-separated list of sources, the missing ones come after the
+    /// others in the default order
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sources = Vec::new();
+        for name in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let source = name.parse()?;
+            if sources.contains(&source) {
+                anyhow::bail!("The symbol source {} is twice in the priority", name);
+            }
+            sources.push(source);
+        }
+        for source in SymbolSource::ALL {
+            if !sources.contains(&source) {
+                sources.push(source);
+            }
+        }
+        Ok(Self(sources))
+    }
+}
+
+// The prefixes of the names of the compiler thunks
+const THUNKS: [&str; 12] = [
+    "__guard_check_icall",
+    "__guard_dispatch_icall",
+    "_guard_check_icall",
+    "_guard_dispatch_icall",
+    "__guard_xfg_",
+    "__security_check_cookie",
+    "@__security_check_cookie",
+    "__GSHandlerCheck",
+    "__chkstk",
+    "_chkstk",
+    "_alloca_probe",
+    "_RTC_Check",
+];
+
+fn is_thunk(name: &str) -> bool {
+    let name = name.split('(').next().unwrap_or_default();
+    THUNKS.iter().any(|prefix| name.starts_with(prefix))
+}
+
+// The C++ exception handling funclets (catch blocks and unwind actions) and the
+// SEH ones (__except filters and __finally blocks)
+const FUNCLETS: [&str; 7] = [
+    "catch$", "dtor$", "unwind$", "filt$", "fin$", "filter$", "finally$",
+];
+
+/// Get the name of the function containing a funclet, they're named like
+/// `` `Parent'::`1'::catch$0 `` and the SEH ones in C functions like `Parent$filt$0`.
+fn get_funclet_parent(name: &str) -> Option<&str> {
+    let (parent, funclet) = match name.strip_prefix('`') {
+        Some(name) => {
+            let (parent, funclet) = name.split_once("'::`")?;
+            (parent, funclet.split_once("'::")?.1)
+        }
+        None => {
+            let kind = name.trim_end_matches(|c: char| c.is_ascii_digit());
+            let start = kind.strip_suffix('$')?.rfind('$')?;
+            (&name[..start], &name[start + 1..])
+        }
+    };
+    let kind = funclet.trim_end_matches(|c: char| c.is_ascii_digit());
+    if !parent.is_empty() && FUNCLETS.contains(&kind) {
+        Some(parent)
+    } else {
+        None
+    }
+}
+
+// The parts of the functions split by GCC and Clang: the cold blocks (`foo.cold`)
+// and the outlined part of a partially inlined function (`foo.part.0`)
+const SPLIT_PARTS: [&str; 2] = ["cold", "part"];
+
+fn strip_split_suffix(name: &str) -> Option<&str> {
+    // The demangled C++ names have them as clones: `foo(int) [clone .cold]`
+    let (parent, part) = match name
+        .strip_suffix(']')
+        .and_then(|name| name.rsplit_once(" [clone ."))
+    {
+        Some((parent, clone)) => (parent, clone.split('.').next().unwrap()),
+        None => {
+            let base = name.trim_end_matches(|c: char| c.is_ascii_digit());
+            let base = if base.len() < name.len() {
+                base.strip_suffix('.')?
+            } else {
+                base
+            };
+            base.rsplit_once('.')?
+        }
+    };
+    (!parent.is_empty() && SPLIT_PARTS.contains(&part)).then_some(parent)
+}
+
+/// Get the name of the function a part has been split from, a part can be split
+/// again (`foo.part.0.cold`).
+fn get_split_parent(name: &str) -> Option<&str> {
+    let mut parent = strip_split_suffix(name)?;
+    while let Some(name) = strip_split_suffix(parent) {
+        parent = name;
+    }
+    Some(parent)
+}
+
+/// Select the public symbols to emit, the regexes are matched on the raw (mangled) names
+#[derive(Clone, Debug, Default)]
+pub struct PublicFilter {
+    /// Keep the matching symbols, even if they're skipped by the other rules
+    pub include: Vec<Regex>,
+    /// Skip the matching symbols
+    pub exclude: Vec<Regex>,
+    /// Don't skip the constants by default (`??_C`, `__real@`, ...)
+    pub no_default: bool,
+}
+
+impl PublicFilter {
+    pub fn new(include: &[&str], exclude: &[&str], no_default: bool) -> common::Result<Self> {
+        let parse = |res: &[&str]| -> common::Result<Vec<Regex>> {
+            res.iter().map(|re| Ok(Regex::new(re)?)).collect()
+        };
+
+        Ok(Self {
+            include: parse(include)?,
+            exclude: parse(exclude)?,
+            no_default,
+        })
+    }
+
+    pub fn should_skip(&self, name: &str) -> bool {
+        if self.include.iter().any(|re| re.is_match(name)) {
+            return false;
+        }
+        if self.exclude.iter().any(|re| re.is_match(name)) {
+            return true;
+        }
+        !self.no_default && should_skip_symbol(name)
+    }
+}
+
+/// Select the modules (compilands) of a PDB to dump, the globs are matched on the
+/// object and the library paths of the modules and on their file names
+#[derive(Clone, Debug, Default)]
+pub struct CompilandFilter {
+    /// Keep only the matching modules when it isn't empty
+    pub include: PathGlobs,
+    /// Skip the matching modules
+    pub exclude: PathGlobs,
+}
+
+impl CompilandFilter {
+    pub fn new(include: &[&str], exclude: &[&str]) -> common::Result<Self> {
+        Ok(Self {
+            include: PathGlobs::new(include)?,
+            exclude: PathGlobs::new(exclude)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn should_skip(&self, object: &str, library: &str) -> bool {
+        let is_match = |globs: &PathGlobs| {
+            [object, library]
+                .iter()
+                .filter(|path| !path.is_empty())
+                .any(|path| {
+                    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+                    globs.is_match(path) || globs.is_match(name)
+                })
+        };
+        (!self.include.is_empty() && !is_match(&self.include)) || is_match(&self.exclude)
+    }
+}
+
 // What we have.
 // Each function has an address, a size, a list of lines and a list of inlinees (which are functions)
 // - address: the address where the function start: first_range.begin
@@ -33,8 +573,26 @@ pub enum Type {
 #[derive(Debug)]
 pub struct Collector {
     pub platform: Platform,
-    pub collect_inlines: bool,
+    pub options: CollectOptions,
     pub syms: Symbols,
+    pub data: DataSymbols,
+    /// The names exported by the PE file, by rva
+    pub exports: HashMap<u32, Vec<String>>,
+    /// The functions split by a binary layout optimization, by rva
+    pub fragments: HashMap<u32, Vec<Range<u32>>>,
+    /// The is_stmt and prologue_end flags of the DWARF line rows
+    pub line_flags: Option<LineFlags>,
+    /// The function names resolved from the DWARF references, by rva
+    pub dwarf_names: HashMap<u32, DwarfName>,
+    /// The declarations of the DWARF functions, by rva, for the ones without line rows
+    pub decl_lines: HashMap<u32, DeclLine>,
+    pub demangled: DemangleCache,
+    /// The rvas of the symbols named from the linker map
+    pub mapped: HashSet<u32>,
+    /// The rvas of the old versions of the functions edited with Edit and Continue
+    pub superseded: HashSet<u32>,
+    /// The rvas of the functions of the modules skipped by the compiland filter
+    pub skipped: HashSet<u32>,
 }
 
 impl Collector {
@@ -53,38 +611,52 @@ impl Collector {
         println!();
     }
 
-    fn demangle(name: &Name) -> String {
-        let name = common::fix_symbol_name(name);
+    fn demangle(cache: &mut DemangleCache, name: &Name, rva: u32) -> String {
         if let Language::C = name.language() {
-            return name.as_str().to_string();
+            return common::fix_symbol_name(name).as_str().to_string();
         }
 
-        match name.demangle(demangle_options()) {
-            Some(demangled) => demangled,
-            None => {
-                let aname = name.as_str();
-                warn!("Didn't manage to demangle {:?}", name);
-                aname.to_string()
-            }
-        }
+        cache.demangle(name).unwrap_or_else(|fixed| {
+            warn!(category = "demangle", rva = rva; "Didn't manage to demangle {:?}", fixed);
+            fixed
+        })
     }
 
-    fn demangle_str(name: &str) -> String {
+    fn demangle_str(cache: &mut DemangleCache, name: &str, rva: u32) -> String {
         let lang = Name::new(name, NameMangling::Mangled, Language::Unknown).detect_language();
         if lang == Language::Unknown {
             return name.to_string();
         }
 
         let name = Name::new(name, NameMangling::Mangled, lang);
-        let name = common::fix_symbol_name(&name);
+        cache.demangle(&name).unwrap_or_else(|fixed| {
+            warn!(category = "demangle", rva = rva; "Didn't manage to demangle {}", fixed);
+            fixed
+        })
+    }
 
-        match name.demangle(demangle_options()) {
-            Some(demangled) => demangled,
-            None => {
-                warn!("Didn't manage to demangle {}", name);
-                name.to_string()
+    /// The name of a function is the one symbolic found in the symbol table or in
+    /// the debug info, unless it's only a DW_AT_name and the whole chain of the
+    /// DWARF references gives a better one.
+    fn get_function_name(&mut self, fun: &Function) -> String {
+        if fun.name.mangling() != NameMangling::Mangled || fun.name.as_str().is_empty() {
+            if let Some(name) = self.dwarf_names.get(&(fun.address as u32)) {
+                if name.is_mangled {
+                    let mangled = Name::new(
+                        name.name.as_str(),
+                        NameMangling::Mangled,
+                        fun.name.language(),
+                    );
+                    return Self::demangle(&mut self.demangled, &mangled, fun.address as u32);
+                }
+                return name.name.clone();
             }
         }
+        Self::demangle(&mut self.demangled, &fun.name, fun.address as u32)
+    }
+
+    fn is_skipped(&self, rva: u32) -> bool {
+        self.superseded.contains(&rva) || self.skipped.contains(&rva)
     }
 
     pub fn collect_function<'a>(
@@ -93,7 +665,7 @@ impl Collector {
         source: &mut SourceFiles,
         inline_origins: &mut InlineOrigins<'a>,
     ) {
-        if fun.address == 0 {
+        if fun.address == 0 || self.is_skipped(fun.address as u32) {
             return;
         }
 
@@ -104,9 +676,64 @@ impl Collector {
             }
         }
 
+        let mut lines = self.get_function_lines(fun, source, inline_origins);
+        let name = self.get_function_name(fun);
+        if let Some(fragments) = self.fragments.get(&(fun.address as u32)) {
+            lines.finalize_fragments(fragments);
+            for fragment in fragments {
+                if self.syms.contains_key(&fragment.start)
+                    || self.syms.is_inside_symbol(fragment.start)
+                {
+                    continue;
+                }
+
+                // Don't overlap the next function
+                let end = self
+                    .syms
+                    .range(fragment.start..fragment.end)
+                    .next()
+                    .map_or(fragment.end, |(rva, _)| *rva);
+                let lines = lines.slice(&(fragment.start..end));
+                let len = end - fragment.start;
+
+                self.syms.insert(
+                    fragment.start,
+                    Symbol {
+                        name: name.clone(),
+                        is_public: false,
+                        is_multiple: false,
+                        is_synthetic: false,
+                        rva: fragment.start,
+                        len,
+                        parameter_size: 0,
+                        source: lines,
+                        labels: Vec::new(),
+                        language: None,
+                    },
+                );
+            }
+            return;
+        }
+
+        // compute line length
+        lines.finalize(fun.address as u32, fun.size as u32);
+
+        self.syms.insert(
+            fun.address as u32,
+            Self::function_symbol(fun, name, lines, self.options.languages),
+        );
+    }
+
+    /// The lines of a function, with its inlines if they're collected
+    fn get_function_lines<'a>(
+        &self,
+        fun: &Function<'a>,
+        source: &mut SourceFiles,
+        inline_origins: &mut InlineOrigins<'a>,
+    ) -> Lines {
         let mut lines = Lines::new();
 
-        if self.collect_inlines {
+        if self.options.inlines {
             Self::collect_function_with_inlines_recursive(
                 fun,
                 &mut lines,
@@ -118,22 +745,45 @@ impl Collector {
             Self::collect_function_without_inlines(fun, &mut lines, source);
         }
 
-        // compute line length
-        lines.finalize(fun.address as u32, fun.size as u32);
+        // A function without line rows (e.g. generated code) gets the line of its
+        // declaration, so it's at least linked to its source file
+        if let (true, Some(decl)) = (
+            lines.lines.is_empty(),
+            self.decl_lines.get(&(fun.address as u32)),
+        ) {
+            let file = FileInfo {
+                name: &decl.name,
+                dir: &decl.dir,
+            };
+            let file_id = source.get_id(&decl.comp_dir, &file);
+            lines.add_line(fun.address as u32, decl.line, source.get_true_id(file_id));
+        }
 
-        self.syms.insert(
-            fun.address as u32,
-            Symbol {
-                name: Self::demangle(&fun.name),
-                is_public: false,
-                is_multiple: false,
-                is_synthetic: false,
-                rva: fun.address as u32,
-                len: fun.size as u32,
-                parameter_size: 0,
-                source: lines,
-            },
-        );
+        if let Some(line_flags) = &self.line_flags {
+            let start = fun.address as u32;
+            line_flags.apply(&mut lines, &(start..start.saturating_add(fun.size as u32)));
+        }
+
+        lines
+    }
+
+    fn function_symbol(fun: &Function, name: String, source: Lines, languages: bool) -> Symbol {
+        let language = match fun.name.language() {
+            Language::Unknown => None,
+            language => languages.then(|| language.name().to_string()),
+        };
+        Symbol {
+            name,
+            is_public: false,
+            is_multiple: false,
+            is_synthetic: false,
+            rva: fun.address as u32,
+            len: fun.size as u32,
+            parameter_size: 0,
+            source,
+            labels: Vec::new(),
+            language,
+        }
     }
 
     fn collect_function_without_inlines<'a>(
@@ -345,29 +995,227 @@ impl Collector {
         source: &mut SourceFiles,
         inline_origins: &mut InlineOrigins<'a>,
     ) -> common::Result<()> {
-        for fun in ds.functions() {
-            match fun {
-                Ok(fun) => {
-                    self.collect_function(&fun, source, inline_origins);
+        // The split functions are inserted piece by piece in the symbols
+        if !self.syms.is_empty() || !self.fragments.is_empty() {
+            for fun in ds.functions() {
+                match fun {
+                    Ok(fun) => {
+                        progress::add_function();
+                        self.collect_function(&fun, source, inline_origins);
+                    }
+                    Err(e) => {
+                        error!("Function collection: {:?}", e);
+                    }
                 }
+            }
+            return Ok(());
+        }
+
+        // Else the functions are pushed in a vector and the map is built at once
+        // from it: it's faster than inserting them one by one in the big modules.
+        // The first function at an address is kept, like in collect_function.
+        // The parse limits are checked as the functions come, all the symbols
+        // are checked at the end.
+        let limits = self.options.limits;
+        let mut funcs: Vec<Symbol> = Vec::new();
+        let mut indices: HashMap<u32, usize> = HashMap::new();
+        let mut line_count = 0;
+        for fun in ds.functions() {
+            let fun = match fun {
+                Ok(fun) => fun,
                 Err(e) => {
                     error!("Function collection: {:?}", e);
+                    continue;
                 }
+            };
+            progress::add_function();
+            if fun.address == 0 || self.is_skipped(fun.address as u32) {
+                continue;
             }
+            if let Some(i) = indices.get(&(fun.address as u32)) {
+                funcs[*i].is_multiple = true;
+                continue;
+            }
+
+            let mut lines = self.get_function_lines(&fun, source, inline_origins);
+            lines.finalize(fun.address as u32, fun.size as u32);
+            let name = self.get_function_name(&fun);
+            line_count += lines.lines.len();
+            limits.check_symbols(funcs.len() + 1)?;
+            limits.check_lines(line_count)?;
+            limits.check_string(&name)?;
+            indices.insert(fun.address as u32, funcs.len());
+            funcs.push(Self::function_symbol(
+                &fun,
+                name,
+                lines,
+                self.options.languages,
+            ));
         }
+        self.syms = funcs.into_iter().map(|sym| (sym.rva, sym)).collect();
 
         Ok(())
     }
 
+    // This runs after collect_publics.
+    // The funclets keep their own records (and so their lines) since they aren't
+    // contiguous with their parent, but they get its name.
+    pub fn merge_funclets(&mut self) {
+        if !self.options.merge_funclets {
+            return;
+        }
+
+        let mut names = Vec::new();
+        for sym in self.syms.values() {
+            let parent = match get_funclet_parent(&sym.name) {
+                Some(parent) => parent,
+                None => continue,
+            };
+            let is_parent = |s: &Symbol| {
+                s.name
+                    .strip_prefix(parent)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('('))
+            };
+
+            // With overloads, the parent is the closest function before the funclet
+            let parent = self
+                .syms
+                .range(..sym.rva)
+                .rev()
+                .map(|(_, s)| s)
+                .find(|s| is_parent(s))
+                .or_else(|| self.syms.values().find(|s| is_parent(s)));
+            if let Some(parent) = parent {
+                names.push((sym.rva, parent.name.clone()));
+            }
+        }
+
+        for (rva, name) in names {
+            if let Some(sym) = self.syms.get_mut(&rva) {
+                sym.name = name;
+            }
+        }
+    }
+
+    // This runs after collect_publics.
+    // A part gets the name of its parent and when it's right after the FUNC of its
+    // parent, it's merged in it. The cold parts are usually far from their parent
+    // so they keep their own record.
+    pub fn merge_split_functions(&mut self) {
+        if !self.options.merge_split_functions {
+            return;
+        }
+
+        let mut parts = Vec::new();
+        for sym in self.syms.values() {
+            let parent = match get_split_parent(&sym.name) {
+                Some(parent) => parent,
+                None => continue,
+            };
+
+            // With overloads, the parent is the closest function before the part
+            let parent = self
+                .syms
+                .range(..sym.rva)
+                .rev()
+                .map(|(_, s)| s)
+                .find(|s| s.name == parent)
+                .or_else(|| self.syms.values().find(|s| s.name == parent));
+            if let Some(parent) = parent {
+                parts.push((sym.rva, parent.rva));
+            }
+        }
+
+        // The parts are in the order of their addresses: a part after a merged one
+        // can be merged too
+        for (rva, parent_rva) in parts {
+            let parent = &self.syms[&parent_rva];
+            let part = &self.syms[&rva];
+            if !parent.is_public && !part.is_public && parent.rva + parent.len == rva {
+                let part = self.syms.remove(&rva).unwrap();
+                let parent = self.syms.get_mut(&parent_rva).unwrap();
+                parent.len += part.len;
+                parent.source.append(part.source);
+                parent.labels.extend(part.labels);
+            } else {
+                let name = parent.name.clone();
+                self.syms.get_mut(&rva).unwrap().name = name;
+            }
+        }
+    }
+
+    // This runs after collect_publics.
+    pub fn rename_thunks(&mut self) {
+        let thunks = self.options.thunks;
+        if thunks == Thunks::Keep {
+            return;
+        }
+
+        for sym in self.syms.values_mut().filter(|sym| is_thunk(&sym.name)) {
+            sym.name = match thunks {
+                Thunks::Flag => format!("{} [thunk]", sym.name),
+                _ => "<compiler thunk>".to_string(),
+            };
+        }
+    }
+
+    // This runs before collect_publics.
+    #[cfg(feature = "pdb")]
+    pub fn collect_exports(&mut self, pe: &PeObject) {
+        let pe = match goblin::pe::PE::parse(pe.data()) {
+            Ok(pe) => pe,
+            Err(e) => {
+                warn!("Unable to get the exports: {}", e);
+                return;
+            }
+        };
+
+        for export in pe.exports.iter().filter(|e| e.reexport.is_none()) {
+            if let Some(name) = export.name {
+                self.exports
+                    .entry(export.rva as u32)
+                    .or_default()
+                    .push(name.to_string());
+            }
+        }
+    }
+
+    fn is_exported(&self, rva: u32, name: &str, parsed_win_name: &ParsedWinFuncName) -> bool {
+        self.exports
+            .get(&rva)
+            .is_some_and(|names| names.iter().any(|n| n == name || n == parsed_win_name.name))
+    }
+
+    /// When several publics have the same rva, the exported name is preferred
+    /// to a decorated one which is preferred to an alias. The exported names
+    /// aren't preferred when the exports come after the publics in the priority.
+    fn get_public_rank(&self, rva: u32, name: &str, parsed_win_name: &ParsedWinFuncName) -> u8 {
+        let priority = &self.options.priority;
+        if priority.prefers(SymbolSource::Exports, SymbolSource::Publics)
+            && self.is_exported(rva, name, parsed_win_name)
+        {
+            2
+        } else if name.starts_with('?') || parsed_win_name.param_size.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
     // This runs after collect_functions / collect_placeholder_functions.
     pub fn collect_publics(&mut self, o: &Object) {
+        let mut ranks = HashMap::new();
+        let keep_inner = self.options.inner_publics == InnerPublics::Keep;
         for sym in o.symbols() {
-            if self.syms.is_inside_symbol(sym.address as u32) {
+            if !keep_inner && self.syms.is_inside_symbol(sym.address as u32) {
                 continue;
             }
 
+            if self.skipped.contains(&(sym.address as u32)) {
+                continue;
+            }
             if let Some(name) = sym.name() {
-                if should_skip_symbol(name) {
+                if self.options.publics.should_skip(name) {
                     continue;
                 }
             }
@@ -378,33 +1226,101 @@ impl Collector {
                 None
             };
 
+            let rank = match (&parsed_win_name, sym.name()) {
+                (Some(parsed), Some(name)) => {
+                    self.get_public_rank(sym.address as u32, name, parsed)
+                }
+                _ => 0,
+            };
+            let raw_name = sym.name();
+            let public_source = match (&parsed_win_name, raw_name) {
+                (Some(parsed), Some(name))
+                    if self.is_exported(sym.address as u32, name, parsed) =>
+                {
+                    SymbolSource::Exports
+                }
+                _ => SymbolSource::Publics,
+            };
+
             match self.syms.entry(sym.address as u32) {
                 btree_map::Entry::Occupied(mut e) => {
                     let sym = e.get_mut();
                     if sym.is_public {
                         sym.is_multiple = true;
-                    } else if let Some(parsed_win_name) = parsed_win_name {
-                        // If we have both a symbol and a function at the same address, the function
-                        // may not have parameters but the symbol's mangled name might.
-                        if !sym.name.contains('(') {
-                            // Get the name from the symbol.
-                            sym.name = Self::demangle_str(&parsed_win_name.name);
+                        let prev_rank = ranks.entry(sym.rva).or_insert(0);
+                        if let (Some(parsed_win_name), true) = (parsed_win_name, rank > *prev_rank)
+                        {
+                            *prev_rank = rank;
+                            sym.name = Self::demangle_str(
+                                &mut self.demangled,
+                                parsed_win_name.name,
+                                sym.rva,
+                            );
+                            sym.parameter_size = parsed_win_name.param_size.unwrap_or_default();
                         }
-                        if let Some(size) = parsed_win_name.param_size {
-                            // Get the parameter size from the symbol.
-                            sym.parameter_size = size;
+                    } else {
+                        let source = if sym.is_synthetic {
+                            SymbolSource::ExceptionData
+                        } else if self.mapped.contains(&sym.rva) {
+                            SymbolSource::LinkerMap
+                        } else {
+                            SymbolSource::DebugInfo
+                        };
+                        if source == SymbolSource::ExceptionData {
+                            // A function from the exception data is named by the publics at its
+                            // address: like for a PUBLIC record, several ones are marked and the
+                            // best ranked one wins
+                            match ranks.entry(sym.rva) {
+                                hash_map::Entry::Occupied(mut prev_rank) => {
+                                    sym.is_multiple = true;
+                                    if rank <= *prev_rank.get() {
+                                        continue;
+                                    }
+                                    prev_rank.insert(rank);
+                                }
+                                hash_map::Entry::Vacant(e) => {
+                                    e.insert(rank);
+                                }
+                            }
+                        }
+                        let wins = self.options.priority.prefers(public_source, source);
+                        if let Some(parsed_win_name) = parsed_win_name {
+                            // If we have both a symbol and a function at the same address, the function
+                            // may not have parameters but the symbol's mangled name might.
+                            if wins
+                                || (source != SymbolSource::ExceptionData
+                                    && !sym.name.contains('('))
+                            {
+                                // Get the name from the symbol.
+                                sym.name = Self::demangle_str(
+                                    &mut self.demangled,
+                                    parsed_win_name.name,
+                                    sym.rva,
+                                );
+                            }
+                            if let Some(size) = parsed_win_name.param_size {
+                                // Get the parameter size from the symbol.
+                                sym.parameter_size = size;
+                            }
+                        } else if let (true, Some(name)) = (wins, raw_name) {
+                            sym.name = Self::demangle_str(&mut self.demangled, name, sym.rva);
                         }
                     }
                 }
                 btree_map::Entry::Vacant(e) => {
-                    let sym_name = match (&parsed_win_name, sym.name) {
-                        (Some(name), _) => Self::demangle_str(&name.name),
-                        (None, Some(name)) => Self::demangle_str(&name),
+                    let sym_name = match (&parsed_win_name, sym.name()) {
+                        (Some(name), _) => {
+                            Self::demangle_str(&mut self.demangled, name.name, sym.address as u32)
+                        }
+                        (None, Some(name)) => {
+                            Self::demangle_str(&mut self.demangled, name, sym.address as u32)
+                        }
                         _ => "<name omitted>".to_string(),
                     };
                     let parameter_size = parsed_win_name
                         .and_then(|n| n.param_size)
                         .unwrap_or_default();
+                    ranks.insert(sym.address as u32, rank);
                     e.insert(Symbol {
                         name: sym_name,
                         is_public: true,
@@ -414,15 +1330,381 @@ impl Collector {
                         len: sym.size as u32,
                         parameter_size,
                         source: Lines::default(),
+                        labels: Vec::new(),
+                        language: None,
                     });
                 }
             }
         }
     }
 
+    /// Add a `foo@plt` public for the PLT entries jumping to `foo`, the calls to
+    /// the functions of the shared libraries go through them.
+    // This runs after collect_publics, the symbols of the symbol table win.
+    #[cfg(feature = "elf")]
+    pub fn collect_plt_entries(&mut self, o: &Object) {
+        for entry in crate::linux::get_plt_entries(o) {
+            if self.syms.contains_key(&entry.rva) || self.syms.is_inside_symbol(entry.rva) {
+                continue;
+            }
+            let name = format!(
+                "{}@plt",
+                Self::demangle_str(&mut self.demangled, &entry.name, entry.rva)
+            );
+            if self.options.publics.should_skip(&name) {
+                continue;
+            }
+            self.syms.insert(
+                entry.rva,
+                Symbol {
+                    name,
+                    is_public: true,
+                    is_multiple: false,
+                    is_synthetic: false,
+                    rva: entry.rva,
+                    len: entry.len,
+                    parameter_size: 0,
+                    source: Lines::default(),
+                    labels: Vec::new(),
+                    language: None,
+                },
+            );
+        }
+    }
+
+    /// Set the size of the parameters pushed on the stack of the functions, by
+    /// rva, e.g. the ones computed from the frame data of the x86 PDBs.
+    // This runs after collect_functions, the sizes in the decorated names
+    // of the publics are preferred.
+    pub fn collect_parameter_sizes(&mut self, sizes: &HashMap<u32, u32>) {
+        for (rva, size) in sizes {
+            if let Some(sym) = self.syms.get_mut(rva) {
+                if !sym.is_public {
+                    sym.parameter_size = *size;
+                }
+            }
+        }
+    }
+
+    /// Attach the labels to the functions containing them, the ones at the
+    /// beginning of a function or outside of any function are useless.
+    pub fn collect_labels(&mut self, labels: Vec<Label>) {
+        for label in labels {
+            let sym = match self.syms.range_mut(..label.rva).next_back() {
+                Some((_, sym)) if !sym.is_public && label.rva < sym.rva + sym.len => sym,
+                _ => continue,
+            };
+            sym.labels.push(label);
+        }
+
+        for sym in self.syms.values_mut() {
+            if !sym.labels.is_empty() {
+                sym.labels.sort_by_key(|l| l.rva);
+                sym.labels
+                    .dedup_by(|a, b| a.rva == b.rva && a.name == b.name);
+            }
+        }
+    }
+
+    /// Decode the names of the functions which aren't UTF-8 in the debug info,
+    /// by rva: the lossy names (with U+FFFD) are replaced by the decoded ones
+    pub fn collect_decoded_names(&mut self, names: &HashMap<u32, (String, String)>) {
+        for (rva, (lossy, decoded)) in names {
+            if let Some(sym) = self.syms.get_mut(rva).filter(|sym| !sym.is_public) {
+                // The arguments may follow the name
+                if sym.name.contains(lossy.as_str()) {
+                    sym.name = sym.name.replacen(lossy.as_str(), decoded, 1);
+                }
+            }
+        }
+    }
+
+    /// Set the source languages of the functions, by rva
+    pub fn collect_languages(&mut self, languages: &HashMap<u32, &str>) {
+        for (rva, language) in languages {
+            if let Some(sym) = self.syms.get_mut(rva).filter(|sym| !sym.is_public) {
+                sym.language = Some(language.to_string());
+            }
+        }
+    }
+
+    /// Collect the global and static variables
+    pub fn collect_data_symbols(&mut self, object: &Object) {
+        let data: Vec<DataSymbol> = match object {
+            #[cfg(feature = "pdb")]
+            Object::Pdb(pdb) => {
+                match windows::symbols::get_data_symbols(pdb, self.options.encoding) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        warn!("Unable to get the data symbols: {}", e);
+                        return;
+                    }
+                }
+            }
+            #[cfg(feature = "elf")]
+            Object::Elf(_) => super::symbol::get_elf_data_symbols(object),
+            _ => Vec::new(),
+        };
+
+        for mut sym in data {
+            if let btree_map::Entry::Vacant(e) = self.data.entry((sym.rva, sym.is_tls)) {
+                sym.name = Self::demangle_str(&mut self.demangled, &sym.name, sym.rva);
+                e.insert(sym);
+            }
+        }
+    }
+
+    /// Use the symbols of a linker map to name the functions found in the exception data
+    /// and to collect the ones which aren't there.
+    // This runs between collect_placeholder_functions and collect_linker_map_symbols,
+    // so the functions from the map don't extend over the stubs.
+    #[cfg(feature = "pdb")]
+    pub fn collect_jump_stubs(&mut self, pe: &PeObject) {
+        let arch = pe.arch();
+        if arch != Arch::X86 && arch != Arch::Amd64 {
+            return;
+        }
+
+        let mut stubs = Vec::new();
+        for code in windows::stubs::get_code_sections(pe) {
+            if arch == Arch::X86 {
+                // The hot-patch padding is right before the function
+                for sym in self.syms.range(code.rva..code.end()).map(|(_, sym)| sym) {
+                    let rva = sym.rva.wrapping_sub(windows::stubs::HOTPATCH_LEN);
+                    if code.is_hotpatchable(sym.rva)
+                        && !self.syms.is_inside_symbol(rva)
+                        && self.syms.range(rva..sym.rva).next().is_none()
+                    {
+                        stubs.push((rva, windows::stubs::HOTPATCH_LEN, "hotpatch_stub", sym.rva));
+                    }
+                }
+            }
+
+            // The incremental linking thunks are a table of jumps to the functions,
+            // it's at the start of a gap between the symbols.
+            let mut gaps = Vec::new();
+            let mut start = code.rva;
+            for sym in self.syms.range(code.rva..code.end()).map(|(_, sym)| sym) {
+                if start < sym.rva {
+                    gaps.push(start..sym.rva);
+                }
+                start = start.max(sym.rva + sym.len);
+            }
+            if start < code.end() {
+                gaps.push(start..code.end());
+            }
+
+            for gap in gaps {
+                let mut rva = code.skip_padding(gap.start, gap.end);
+                while rva < gap.end {
+                    let target = match code.get_jump_target(rva) {
+                        Some(target) if code.rva <= target && target < code.end() => target,
+                        _ => break,
+                    };
+                    if self.syms.contains_key(&target) {
+                        stubs.push((rva, windows::stubs::JMP_LEN, "jump_stub", target));
+                    }
+                    rva += windows::stubs::JMP_LEN;
+                }
+            }
+        }
+
+        for (rva, len, kind, target) in stubs {
+            let name = format!("{} for {}", kind, self.syms[&target].name);
+            self.syms.entry(rva).or_insert(Symbol {
+                name,
+                is_public: false,
+                is_multiple: false,
+                is_synthetic: true,
+                rva,
+                len,
+                parameter_size: 0,
+                source: Lines::default(),
+                labels: Vec::new(),
+                language: None,
+            });
+        }
+    }
+
+    // This runs between collect_placeholder_functions and collect_publics.
+    #[cfg(feature = "pdb")]
+    pub fn collect_linker_map_symbols(&mut self, map: &LinkerMap, sections: &[SectionTable]) {
+        let section_end = |rva: u32| {
+            sections
+                .iter()
+                .map(|s| {
+                    (
+                        s.virtual_address,
+                        s.virtual_address.saturating_add(s.virtual_size),
+                    )
+                })
+                .find(|(start, end)| *start <= rva && rva < *end)
+                .map(|(_, end)| end)
+        };
+
+        let map_syms = map.symbols();
+        for (i, map_sym) in map_syms.iter().enumerate() {
+            let parsed_win_name = ParsedWinFuncName::parse_unknown(&map_sym.name);
+            let name = Self::demangle_str(&mut self.demangled, parsed_win_name.name, map_sym.rva);
+            let parameter_size = parsed_win_name.param_size.unwrap_or_default();
+
+            if let Some(sym) = self.syms.get_mut(&map_sym.rva) {
+                // The function is folded whatever the name it keeps
+                sym.is_multiple |= map_sym.is_multiple;
+                // A placeholder function from the exception data has the right size.
+                let source = if sym.is_synthetic {
+                    SymbolSource::ExceptionData
+                } else {
+                    SymbolSource::DebugInfo
+                };
+                if self
+                    .options
+                    .priority
+                    .prefers(SymbolSource::LinkerMap, source)
+                {
+                    sym.name = name;
+                    sym.is_synthetic = false;
+                    sym.parameter_size = parameter_size;
+                    self.mapped.insert(map_sym.rva);
+                }
+                continue;
+            }
+
+            if !map_sym.is_function || self.syms.is_inside_symbol(map_sym.rva) {
+                continue;
+            }
+
+            // Its extent isn't known, it'd only be guessed from the next symbol.
+            if map_sym.len.is_none() && self.options.gap_fill != GapFill::Extend {
+                self.syms.insert(
+                    map_sym.rva,
+                    Symbol {
+                        name,
+                        is_public: true,
+                        is_multiple: map_sym.is_multiple,
+                        is_synthetic: false,
+                        rva: map_sym.rva,
+                        len: 0,
+                        parameter_size,
+                        source: Lines::default(),
+                        labels: Vec::new(),
+                        language: None,
+                    },
+                );
+                continue;
+            }
+
+            // The function extends up to the next symbol we know about.
+            let next_sym = self.syms.range(map_sym.rva..).next().map(|(rva, _)| *rva);
+            let ends = [
+                map_sym.len.and_then(|len| map_sym.rva.checked_add(len)),
+                map_syms.get(i + 1).map(|s| s.rva),
+                next_sym,
+                section_end(map_sym.rva),
+            ];
+            let len = match ends.iter().flatten().min() {
+                Some(end) => end - map_sym.rva,
+                None => continue,
+            };
+
+            self.mapped.insert(map_sym.rva);
+            self.syms.insert(
+                map_sym.rva,
+                Symbol {
+                    name,
+                    is_public: false,
+                    is_multiple: map_sym.is_multiple,
+                    is_synthetic: false,
+                    rva: map_sym.rva,
+                    len,
+                    parameter_size,
+                    source: Lines::default(),
+                    labels: Vec::new(),
+                    language: None,
+                },
+            );
+        }
+    }
+
+    /// The functions of the STABS debug info, when there are no other ones
+    // This runs after collect_functions.
+    pub fn collect_stabs_functions(
+        &mut self,
+        functions: Vec<StabsFunction>,
+        source: &mut SourceFiles,
+    ) {
+        for fun in functions {
+            if self.syms.contains_key(&fun.rva) {
+                continue;
+            }
+
+            let mut lines = Lines::new();
+            let mut prev = None;
+            for line in fun.lines {
+                let line_info = (line.num, line.file);
+                if line_info.0 == 0 || prev.as_ref() == Some(&line_info) {
+                    continue;
+                }
+                let file_id = source.get_path_id(line_info.1.clone());
+                lines.add_line(line.rva, line_info.0, source.get_true_id(file_id));
+                prev = Some(line_info);
+            }
+            lines.finalize(fun.rva, fun.len);
+
+            progress::add_function();
+            let name = Self::demangle_str(&mut self.demangled, fun.name, fun.rva);
+            self.syms.insert(
+                fun.rva,
+                Symbol {
+                    name,
+                    is_public: false,
+                    is_multiple: false,
+                    is_synthetic: false,
+                    rva: fun.rva,
+                    len: fun.len,
+                    parameter_size: 0,
+                    source: lines,
+                    labels: Vec::new(),
+                    language: None,
+                },
+            );
+        }
+    }
+
+    /// The functions of the object files referenced by a /DEBUG:FASTLINK PDB, they
+    /// have no lines
+    // This runs after collect_functions.
+    #[cfg(feature = "pdb")]
+    pub fn collect_object_functions(&mut self, functions: Vec<ObjectFunction>) {
+        for fun in functions {
+            if self.syms.contains_key(&fun.rva) || self.syms.is_inside_symbol(fun.rva) {
+                continue;
+            }
+
+            progress::add_function();
+            let name = Self::demangle_str(&mut self.demangled, &fun.name, fun.rva);
+            self.syms.insert(
+                fun.rva,
+                Symbol {
+                    name,
+                    is_public: false,
+                    is_multiple: false,
+                    is_synthetic: false,
+                    rva: fun.rva,
+                    len: fun.len,
+                    parameter_size: 0,
+                    source: Lines::default(),
+                    labels: Vec::new(),
+                    language: None,
+                },
+            );
+        }
+    }
+
     /// Based on the exception data, collect a synthetic symbol for every function start
     /// address, if there is no other symbol at that address.
     // This runs between collect_functions and collect_publics.
+    #[cfg(feature = "pdb")]
     pub fn collect_placeholder_functions(
         &mut self,
         exception_data: &ExceptionData,
@@ -456,9 +1738,191 @@ impl Collector {
                         len: size,
                         parameter_size: 0,
                         source: Lines::default(),
+                        labels: Vec::new(),
+                        language: None,
                     });
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_rank() {
+        let mut collector = Collector {
+            platform: Platform::Win,
+            options: CollectOptions::default(),
+            syms: Symbols::default(),
+            data: DataSymbols::default(),
+            exports: HashMap::new(),
+            fragments: HashMap::new(),
+            line_flags: None,
+            dwarf_names: HashMap::new(),
+            decl_lines: HashMap::new(),
+            demangled: DemangleCache::default(),
+            mapped: HashSet::new(),
+            superseded: HashSet::new(),
+            skipped: HashSet::new(),
+        };
+        collector
+            .exports
+            .insert(0x1000, vec!["Foo".to_string(), "?bar@@YAXXZ".to_string()]);
+
+        let rank = |rva, name| {
+            collector.get_public_rank(rva, name, &ParsedWinFuncName::parse_unknown(name))
+        };
+
+        assert_eq!(rank(0x1000, "_Foo@4"), 2);
+        assert_eq!(rank(0x1000, "?bar@@YAXXZ"), 2);
+        assert_eq!(rank(0x1000, "?baz@@YAXXZ"), 1);
+        assert_eq!(rank(0x2000, "_Foo@4"), 1);
+        assert_eq!(rank(0x1000, "foo_alias"), 0);
+
+        // The exported names don't win anymore
+        collector.options.priority = "publics,exports".parse().unwrap();
+        let rank = |rva, name| {
+            collector.get_public_rank(rva, name, &ParsedWinFuncName::parse_unknown(name))
+        };
+        assert_eq!(rank(0x1000, "_Foo@4"), 1);
+        assert_eq!(rank(0x1000, "foo_alias"), 0);
+    }
+
+    #[test]
+    fn test_merge_split_functions() {
+        let func = |rva, len, name: &str| {
+            let mut source = Lines::new();
+            source.add_line(rva, 1, 0);
+            source.finalize(rva, len);
+            Symbol {
+                name: name.to_string(),
+                rva,
+                len,
+                source,
+                ..Default::default()
+            }
+        };
+        let mut collector = Collector {
+            platform: Platform::Linux,
+            options: CollectOptions {
+                merge_split_functions: true,
+                ..Default::default()
+            },
+            syms: Symbols::default(),
+            data: DataSymbols::default(),
+            exports: HashMap::new(),
+            fragments: HashMap::new(),
+            line_flags: None,
+            dwarf_names: HashMap::new(),
+            decl_lines: HashMap::new(),
+            demangled: DemangleCache::default(),
+            mapped: HashSet::new(),
+            superseded: HashSet::new(),
+            skipped: HashSet::new(),
+        };
+        for sym in [
+            func(0x100, 0x20, "foo"),
+            func(0x120, 0x10, "foo.part.0"),
+            func(0x130, 0x8, "foo.part.0.cold"),
+            func(0x200, 0x10, "bar"),
+            func(0x400, 0x10, "bar.cold"),
+            func(0x500, 0x10, "baz.cold"),
+        ] {
+            collector.syms.insert(sym.rva, sym);
+        }
+        collector.merge_split_functions();
+
+        let syms: Vec<_> = collector
+            .syms
+            .values()
+            .map(|s| (s.rva, s.len, s.name.as_str(), s.source.lines.len()))
+            .collect();
+        assert_eq!(
+            syms,
+            vec![
+                (0x100, 0x38, "foo", 3),
+                (0x200, 0x10, "bar", 1),
+                (0x400, 0x10, "bar", 1),
+                (0x500, 0x10, "baz.cold", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_priority() {
+        use SymbolSource::*;
+
+        let priority: SourcePriority = "exports, publics".parse().unwrap();
+        assert_eq!(
+            priority,
+            SourcePriority(vec![Exports, Publics, DebugInfo, LinkerMap, ExceptionData])
+        );
+        assert!(priority.prefers(Exports, DebugInfo));
+        assert!(!priority.prefers(LinkerMap, Publics));
+        assert!(SourcePriority::default().prefers(DebugInfo, Publics));
+
+        assert!("publics,foo".parse::<SourcePriority>().is_err());
+        assert!("publics,exports,publics".parse::<SourcePriority>().is_err());
+    }
+
+    #[test]
+    fn test_funclet_parent() {
+        assert_eq!(
+            get_funclet_parent("`UnDecorator::getDecoratedName'::`1'::dtor$0"),
+            Some("UnDecorator::getDecoratedName")
+        );
+        assert_eq!(get_funclet_parent("`foo'::`1'::catch$12"), Some("foo"));
+        assert_eq!(
+            get_funclet_parent(
+                "`UnDecorator::getDecoratedName'::`2'::TrackRecursion::TrackRecursion()"
+            ),
+            None
+        );
+        assert_eq!(
+            get_funclet_parent("`dllmain_dispatch'::`1'::filt$0"),
+            Some("dllmain_dispatch")
+        );
+        assert_eq!(
+            get_funclet_parent("_free_locale$fin$1"),
+            Some("_free_locale")
+        );
+        assert_eq!(
+            get_funclet_parent("__DestructExceptionObject$filt$0"),
+            Some("__DestructExceptionObject")
+        );
+        assert_eq!(get_funclet_parent("$fin$0"), None);
+        assert_eq!(get_funclet_parent("foo$bar$0"), None);
+        assert_eq!(get_funclet_parent("foo(int)"), None);
+    }
+
+    #[test]
+    fn test_split_parent() {
+        assert_eq!(get_split_parent("main.cold"), Some("main"));
+        assert_eq!(get_split_parent("foo.cold.1"), Some("foo"));
+        assert_eq!(get_split_parent("foo.part.0"), Some("foo"));
+        assert_eq!(get_split_parent("foo.part.0.cold"), Some("foo"));
+        assert_eq!(
+            get_split_parent("ns::foo(int) [clone .part.0] [clone .cold]"),
+            Some("ns::foo(int)")
+        );
+        assert_eq!(get_split_parent("foo(int) [clone .isra.0]"), None);
+        assert_eq!(get_split_parent("foo.isra.0"), None);
+        assert_eq!(get_split_parent("foo.1"), None);
+        assert_eq!(get_split_parent(".cold"), None);
+        assert_eq!(get_split_parent("cold"), None);
+    }
+
+    #[test]
+    fn test_is_thunk() {
+        assert!(is_thunk("__security_check_cookie(unsigned int)"));
+        assert!(is_thunk("@__security_check_cookie@4"));
+        assert!(is_thunk("_guard_check_icall_nop(unsigned int)"));
+        assert!(is_thunk("__guard_dispatch_icall_fptr"));
+        assert!(is_thunk("_alloca_probe_16"));
+        assert!(!is_thunk("foo(__security_check_cookie)"));
+        assert!(!is_thunk("DllMain(HINSTANCE__*, unsigned long, void*)"));
+    }
+}
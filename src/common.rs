@@ -3,15 +3,93 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use encoding_rs::Encoding;
+use hashbrown::HashMap;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use std::borrow::Cow;
 use std::env::consts::ARCH;
-use std::result;
-use symbolic::common::{Arch, Name};
-use symbolic::demangle::DemangleOptions;
+use std::str::FromStr;
+use std::{error, fmt, result};
+use symbolic::common::{Arch, Language, Name, NameMangling};
+use symbolic::demangle::{Demangle, DemangleOptions};
 
 pub type Result<T> = result::Result<T, anyhow::Error>;
 
+/// The classes of failures, each one has its own exit code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    /// An unexpected failure (I/O error, corrupted file, ...)
+    Internal,
+    /// Invalid command line arguments
+    Usage,
+    /// The file format isn't supported (or there's no object for the arch in a fat binary)
+    UnsupportedFormat,
+    /// The files to dump together don't have the same identity
+    IdentityMismatch,
+    /// Some files have been dumped in batch mode but not all of them
+    PartialSuccess,
+    /// A file can't be retrieved from a symbol server: it may be worth retrying
+    Network,
+    /// The file exceeds one of the parse limits (e.g. `--max-symbols`)
+    LimitExceeded,
+}
+
+impl FailureKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Internal => 1,
+            Self::Usage => 2,
+            Self::UnsupportedFormat => 3,
+            Self::IdentityMismatch => 4,
+            Self::PartialSuccess => 5,
+            Self::Network => 6,
+            Self::LimitExceeded => 7,
+        }
+    }
+
+    /// Get the class of an error: the innermost one of the chain, an internal error else.
+    pub fn of(e: &anyhow::Error) -> Self {
+        e.chain()
+            .filter_map(|e| {
+                if let Some(failure) = e.downcast_ref::<Failure>() {
+                    return Some(failure.kind);
+                }
+                #[cfg(feature = "http")]
+                if e.is::<reqwest::Error>() {
+                    return Some(Self::Network);
+                }
+                None
+            })
+            .last()
+            .unwrap_or(Self::Internal)
+    }
+}
+
+/// An error with its failure class
+#[derive(Debug)]
+pub struct Failure {
+    pub kind: FailureKind,
+    msg: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl error::Error for Failure {}
+
+/// Make an error with the given class
+pub fn failure(kind: FailureKind, msg: impl Into<String>) -> anyhow::Error {
+    Failure {
+        kind,
+        msg: msg.into(),
+    }
+    .into()
+}
+
 pub fn demangle_options() -> DemangleOptions {
     DemangleOptions::complete().return_type(false)
 }
@@ -33,9 +111,14 @@ pub fn get_compile_time_arch() -> &'static str {
     .name()
 }
 
-pub(crate) fn normalize_anonymous_namespace(text: &str) -> String {
-    let fixed = text.replace("`anonymous namespace'", "(anonymous namespace)");
-    String::from(&fixed)
+const MSVC_ANONYMOUS_NAMESPACE: &str = "`anonymous namespace'";
+
+pub(crate) fn normalize_anonymous_namespace(text: &str) -> Cow<'_, str> {
+    if text.contains(MSVC_ANONYMOUS_NAMESPACE) {
+        Cow::Owned(text.replace(MSVC_ANONYMOUS_NAMESPACE, "(anonymous namespace)"))
+    } else {
+        Cow::Borrowed(text)
+    }
 }
 
 pub(crate) fn fix_symbol_name<'a>(name: &'a Name<'a>) -> Name<'a> {
@@ -45,16 +128,118 @@ pub(crate) fn fix_symbol_name<'a>(name: &'a Name<'a>) -> Name<'a> {
         )
         .unwrap()
     });
-    let fixed = COMPILER_NNN.replace(name.as_str(), "");
-    let fixed = normalize_anonymous_namespace(&fixed);
+    // Most of the names don't need a fix so they're still borrowed
+    let fixed = match COMPILER_NNN.replace(name.as_str(), "") {
+        Cow::Borrowed(fixed) => normalize_anonymous_namespace(fixed),
+        Cow::Owned(fixed) => Cow::Owned(normalize_anonymous_namespace(&fixed).into_owned()),
+    };
 
     Name::new(fixed, name.mangling(), name.language())
 }
 
+/// The demangled names of a module: the same mangled names (template instances,
+/// thunks, ...) are many times in the symbol table and in the debug info.
+#[derive(Debug, Default)]
+pub struct DemangleCache {
+    names: HashMap<(Language, NameMangling), HashMap<String, result::Result<String, String>>>,
+}
+
+impl DemangleCache {
+    /// Demangle a name without its compiler suffixes (see fix_symbol_name),
+    /// the error is the fixed name when it can't be demangled.
+    pub fn demangle(&mut self, name: &Name) -> result::Result<String, String> {
+        let names = self
+            .names
+            .entry((name.language(), name.mangling()))
+            .or_default();
+        if let Some(demangled) = names.get(name.as_str()) {
+            return demangled.clone();
+        }
+
+        let fixed = fix_symbol_name(name);
+        let demangled = fixed
+            .demangle(demangle_options())
+            .ok_or_else(|| fixed.as_str().to_string());
+        names.insert(name.as_str().to_string(), demangled.clone());
+        demangled
+    }
+}
+
+/// The encoding of the strings of the debug files which aren't UTF-8, e.g. the
+/// paths in a PDB built on a Japanese Windows are in CP932. By default, they're
+/// decoded as Latin-1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InputEncoding(Option<&'static Encoding>);
+
+impl InputEncoding {
+    pub fn is_auto(self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Decode a string, it's borrowed when it's valid UTF-8
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        if let Ok(s) = std::str::from_utf8(bytes) {
+            return Cow::Borrowed(s);
+        }
+        match self.0 {
+            Some(encoding) => encoding.decode_without_bom_handling(bytes).0,
+            None => Cow::Owned(bytes.iter().map(|&c| c as char).collect()),
+        }
+    }
+}
+
+impl FromStr for InputEncoding {
+    type Err = anyhow::Error;
+
+    /// An encoding label (`shift_jis`, `gbk`, ...) or a Windows code page
+    /// (`932`, `cp936`, ...)
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "auto" {
+            return Ok(Self(None));
+        }
+        let code_page = s.strip_prefix("cp").unwrap_or(s);
+        let label: Cow<str> = match code_page {
+            "932" => "shift_jis".into(),
+            "936" => "gbk".into(),
+            "949" => "euc-kr".into(),
+            "950" => "big5".into(),
+            "874" | "1250" | "1251" | "1252" | "1253" | "1254" | "1255" | "1256" | "1257"
+            | "1258" => format!("windows-{}", code_page).into(),
+            _ => s.into(),
+        };
+        Encoding::for_label(label.as_bytes())
+            .map(|encoding| Self(Some(encoding)))
+            .ok_or_else(|| anyhow::anyhow!("Invalid input encoding: {}", s))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use symbolic::common::{Language, NameMangling};
+    use anyhow::Context;
+
+    #[test]
+    fn test_failure_kind() {
+        let e = anyhow::anyhow!("Oops");
+        assert_eq!(FailureKind::of(&e), FailureKind::Internal);
+
+        let e = failure(FailureKind::UnsupportedFormat, "Unknown file format");
+        assert_eq!(e.to_string(), "Unknown file format");
+        assert_eq!(FailureKind::of(&e), FailureKind::UnsupportedFormat);
+
+        let e = Err::<(), _>(failure(FailureKind::Network, "Impossible to get file"))
+            .context("Cannot dump")
+            .unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::Network);
+
+        let e = Err::<(), _>(failure(FailureKind::IdentityMismatch, "Not the same id"))
+            .context(Failure {
+                kind: FailureKind::PartialSuccess,
+                msg: "1 of 2 files failed".to_string(),
+            })
+            .unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::IdentityMismatch);
+    }
 
     #[test]
     fn test_fix_symbol_name() {
@@ -90,5 +275,54 @@ mod tests {
 
         let name = "`anonymous namespace'";
         assert_eq!("(anonymous namespace)", normalize_anonymous_namespace(name));
+
+        // Nothing is allocated for the names without one
+        assert!(matches!(
+            normalize_anonymous_namespace("foo::bar"),
+            Cow::Borrowed("foo::bar")
+        ));
+    }
+
+    #[test]
+    fn test_demangle_cache() {
+        let mut cache = DemangleCache::default();
+        let name = Name::new("_Z3fooi.cold", NameMangling::Mangled, Language::Cpp);
+        assert_eq!(cache.demangle(&name), Ok("foo(int)".to_string()));
+        assert_eq!(cache.demangle(&name), Ok("foo(int)".to_string()));
+        assert_eq!(cache.names.values().map(|n| n.len()).sum::<usize>(), 1);
+
+        let name = Name::new("_Zfoo", NameMangling::Mangled, Language::Cpp);
+        assert_eq!(cache.demangle(&name), Err("_Zfoo".to_string()));
+        // The same name in another language is another entry
+        let name = Name::new("_Z3fooi", NameMangling::Mangled, Language::Unknown);
+        assert_eq!(cache.demangle(&name), Ok("foo(int)".to_string()));
+        assert_eq!(cache.names.len(), 2);
+    }
+
+    #[test]
+    fn test_input_encoding() {
+        let auto = InputEncoding::default();
+        assert!(auto.is_auto());
+        assert_eq!("auto".parse::<InputEncoding>().unwrap(), auto);
+        assert!("klingon".parse::<InputEncoding>().is_err());
+
+        // 日本語 in CP932 and in GBK
+        let sjis = b"c:\\\x93\xfa\x96\x7b\x8c\xea\\a.cpp";
+        let gbk = b"c:\\\xc8\xd5\xb1\xbe\xd3\xef\\a.cpp";
+        for label in ["932", "cp932", "shift_jis", "Windows-31J"] {
+            let encoding = label.parse::<InputEncoding>().unwrap();
+            assert_eq!(encoding.decode(sjis), "c:\\日本語\\a.cpp");
+        }
+        let encoding = "cp936".parse::<InputEncoding>().unwrap();
+        assert_eq!(encoding.decode(gbk), "c:\\日本语\\a.cpp");
+        let encoding = "1252".parse::<InputEncoding>().unwrap();
+        assert_eq!(encoding.decode(b"caf\xe9 \x80"), "café €");
+
+        // The UTF-8 strings are kept, the other ones are Latin-1 by default
+        assert!(matches!(
+            encoding.decode("日本語".as_bytes()),
+            Cow::Borrowed("日本語")
+        ));
+        assert_eq!(auto.decode(b"caf\xe9"), "café");
     }
 }
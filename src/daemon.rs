@@ -0,0 +1,339 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::common::{self, FailureKind};
+use crate::dumper::{self, Config, Output};
+
+/// A request, it's a JSON object on one line, e.g.
+/// `{"command": "dump", "files": ["xul.dll", "xul.pdb"]}`
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum Request {
+    /// Dump local files, like the files of a batch
+    Dump { files: Vec<String> },
+    /// Dump the `size` bytes following the request line, `name` is the file name
+    DumpBuffer { name: String, size: u64 },
+    /// Get the counters of the daemon
+    Status,
+    /// Stop the daemon once the current requests are finished
+    Shutdown,
+}
+
+/// The counters of the daemon since its start
+struct Status {
+    start: Instant,
+    requests: AtomicUsize,
+    dumped: AtomicUsize,
+    failed: AtomicUsize,
+    stop: AtomicBool,
+}
+
+impl Status {
+    fn to_json(&self) -> Value {
+        json!({
+            "status": "ok",
+            "uptime": self.start.elapsed().as_secs(),
+            "requests": self.requests.load(Ordering::SeqCst),
+            "dumped": self.dumped.load(Ordering::SeqCst),
+            "failed": self.failed.load(Ordering::SeqCst),
+        })
+    }
+}
+
+fn dump(status: &Status, res: common::Result<()>) -> Value {
+    match res {
+        Ok(()) => {
+            status.dumped.fetch_add(1, Ordering::SeqCst);
+            json!({"status": "ok"})
+        }
+        Err(e) => {
+            status.failed.fetch_add(1, Ordering::SeqCst);
+            warn!("{:#}", e);
+            json!({
+                "status": "error",
+                "error": format!("{:#}", e),
+                "exit_code": FailureKind::of(&e).exit_code(),
+            })
+        }
+    }
+}
+
+fn respond<R: Read>(config: &Config, status: &Status, line: &str, reader: &mut R) -> Value {
+    let request = match serde_json::from_str::<Request>(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return json!({
+                "status": "error",
+                "error": format!("Invalid request: {}", e),
+                "exit_code": FailureKind::Usage.exit_code(),
+            })
+        }
+    };
+    status.requests.fetch_add(1, Ordering::SeqCst);
+
+    match request {
+        Request::Dump { files } => {
+            let files: Vec<_> = files.iter().map(String::as_str).collect();
+            let res = match files.as_slice() {
+                [] => Err(common::failure(FailureKind::Usage, "No file to dump")),
+                [file] => dumper::single_file(config, file),
+                files => dumper::several_files(config, files),
+            };
+            dump(status, res)
+        }
+        Request::DumpBuffer { name, size } => {
+            let mut data = Vec::new();
+            let res = match reader.take(size).read_to_end(&mut data) {
+                Ok(n) if n as u64 == size => dumper::dump_buffer(config, &name, data),
+                Ok(n) => Err(common::failure(
+                    FailureKind::Usage,
+                    format!("Only {} of the {} bytes of {} were received", n, size, name),
+                )),
+                Err(e) => Err(e.into()),
+            };
+            dump(status, res)
+        }
+        Request::Status => status.to_json(),
+        Request::Shutdown => {
+            status.stop.store(true, Ordering::SeqCst);
+            json!({"status": "ok"})
+        }
+    }
+}
+
+/// Answer to the requests of a connection, one response line per request line
+fn handle(config: &Config, status: &Status, stream: UnixStream) -> common::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? != 0 {
+        // No new request once the daemon is stopping
+        if status.stop.load(Ordering::SeqCst) {
+            break;
+        }
+        if !line.trim().is_empty() {
+            // A panic while dumping a file mustn't leave the client without response
+            let response = panic::catch_unwind(AssertUnwindSafe(|| {
+                respond(config, status, line.trim(), &mut reader)
+            }))
+            .unwrap_or_else(|_| {
+                status.failed.fetch_add(1, Ordering::SeqCst);
+                json!({
+                    "status": "error",
+                    "error": "The request panicked",
+                    "exit_code": FailureKind::Internal.exit_code(),
+                })
+            });
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+        }
+        line.clear();
+        if status.stop.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Dump the files requested on a unix socket, the process and its caches are
+/// kept between the requests. The symbols are written in the store of the
+/// configuration.
+pub fn serve(config: &Config, socket: &Path) -> common::Result<()> {
+    if !matches!(config.output, Output::Store(_)) {
+        return Err(common::failure(
+            FailureKind::Usage,
+            "The daemon writes the symbols in a symbol store (--store) only",
+        ));
+    }
+
+    // The socket of a daemon which didn't stop properly is replaced
+    if socket.exists() {
+        anyhow::ensure!(
+            UnixStream::connect(socket).is_err(),
+            "A daemon is already listening on {}",
+            socket.display()
+        );
+        fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    info!("Listen on {}", socket.display());
+
+    let status = Status {
+        start: Instant::now(),
+        requests: AtomicUsize::new(0),
+        dumped: AtomicUsize::new(0),
+        failed: AtomicUsize::new(0),
+        stop: AtomicBool::new(false),
+    };
+    // The open connections, to stop the ones waiting for a request
+    let connections = Mutex::new(HashMap::new());
+    thread::scope(|scope| {
+        for (id, stream) in listener.incoming().enumerate() {
+            if status.stop.load(Ordering::SeqCst) {
+                break;
+            }
+            let stream = match stream.and_then(|s| Ok((s.try_clone()?, s))) {
+                Ok((clone, stream)) => {
+                    connections.lock().unwrap().insert(id, clone);
+                    stream
+                }
+                Err(e) => {
+                    warn!("Connection failed: {}", e);
+                    continue;
+                }
+            };
+            let (status, connections) = (&status, &connections);
+            scope.spawn(move || {
+                if let Err(e) = handle(config, status, stream) {
+                    warn!("Request failed: {}", e);
+                }
+                connections.lock().unwrap().remove(&id);
+                // Wake up the listener so it sees the stop
+                if status.stop.load(Ordering::SeqCst) {
+                    let _ = UnixStream::connect(socket);
+                }
+            });
+        }
+
+        // The idle connections get the end of their stream, the current
+        // requests are finished
+        for stream in connections.lock().unwrap().values() {
+            let _ = stream.shutdown(Shutdown::Read);
+        }
+    });
+
+    fs::remove_file(socket)?;
+    info!("Stop listening on {}", socket.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    fn request(stream: &mut UnixStream, reader: &mut BufReader<UnixStream>, req: &str) -> Value {
+        writeln!(stream, "{}", req).unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command": "dump", "files": ["a.so"]}"#).unwrap(),
+            Request::Dump {
+                files: vec!["a.so".to_string()]
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command": "status"}"#).unwrap(),
+            Request::Status
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"command": "dump"}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"command": "exit"}"#).is_err());
+    }
+
+    #[test]
+    fn test_daemon() {
+        let tmp_dir = Builder::new().prefix("daemon").tempdir().unwrap();
+        let socket = tmp_dir.path().join("dump_syms.sock");
+        let store = tmp_dir.path().join("store");
+        let config = Config {
+            output: Output::Store(store.clone()),
+            ..Default::default()
+        };
+
+        thread::scope(|scope| {
+            let daemon = scope.spawn(|| serve(&config, &socket));
+            while UnixStream::connect(&socket).is_err() {
+                thread::sleep(std::time::Duration::from_millis(10));
+            }
+
+            let mut stream = UnixStream::connect(&socket).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            // Another client stays connected without sending anything
+            let idle = UnixStream::connect(&socket).unwrap();
+
+            let res = request(
+                &mut stream,
+                &mut reader,
+                r#"{"command": "dump", "files": ["./test_data/linux/basic.full"]}"#,
+            );
+            assert_eq!(res, json!({"status": "ok"}));
+            assert!(store
+                .join("basic.full/20AD60B0B4C68177552708AA192E77390/basic.full.sym")
+                .exists());
+
+            let data = fs::read("./test_data/windows/basic64.pdb").unwrap();
+            writeln!(
+                stream,
+                r#"{{"command": "dump_buffer", "name": "basic64.pdb", "size": {}}}"#,
+                data.len()
+            )
+            .unwrap();
+            stream.write_all(&data).unwrap();
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Value>(&line).unwrap()["status"],
+                "ok"
+            );
+            assert!(store
+                .join("basic64.pdb/D09EA7D6D2C24C1EBFFE78B0C866BB7F2/basic64.sym")
+                .exists());
+
+            let res = request(
+                &mut stream,
+                &mut reader,
+                r#"{"command": "dump", "files": ["./test_data/linux/basic.cpp"]}"#,
+            );
+            assert_eq!(res["status"], "error");
+            assert_eq!(res["exit_code"], FailureKind::UnsupportedFormat.exit_code());
+
+            let res = request(
+                &mut stream,
+                &mut reader,
+                r#"{"command": "dump", "files": ["./test_data/linux/missing.so"]}"#,
+            );
+            assert_eq!(res["status"], "error");
+            assert_eq!(res["exit_code"], FailureKind::Internal.exit_code());
+
+            let res = request(&mut stream, &mut reader, "dump basic.full");
+            assert_eq!(res["exit_code"], FailureKind::Usage.exit_code());
+
+            let res = request(&mut stream, &mut reader, r#"{"command": "status"}"#);
+            assert_eq!(res["requests"], 5);
+            assert_eq!(res["dumped"], 2);
+            assert_eq!(res["failed"], 2);
+
+            let res = request(&mut stream, &mut reader, r#"{"command": "shutdown"}"#);
+            assert_eq!(res, json!({"status": "ok"}));
+            daemon.join().unwrap().unwrap();
+
+            // The idle connection is closed
+            let mut line = String::new();
+            assert_eq!(BufReader::new(idle).read_line(&mut line).unwrap(), 0);
+        });
+
+        assert!(!socket.exists());
+    }
+}
@@ -0,0 +1,347 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use log::warn;
+use std::ops::Range;
+use symbolic::common::{Arch, CpuFamily};
+use symbolic::debuginfo::Object;
+
+use crate::line::Lines;
+use crate::symbol::{Symbol, Symbols};
+
+// call rel32
+const CALL: u8 = 0xe8;
+const CALL_LEN: usize = 5;
+
+// ret and ret imm16
+const RET: u8 = 0xc3;
+const RET_IMM: u8 = 0xc2;
+
+// The prologues of the x86 functions
+static X86_PROLOGUES: &[&[u8]] = &[
+    // endbr32
+    &[0xf3, 0x0f, 0x1e, 0xfb],
+    // push ebp; mov ebp, esp
+    &[0x55, 0x89, 0xe5],
+    &[0x55, 0x8b, 0xec],
+    // mov edi, edi; push ebp (hot-patchable)
+    &[0x8b, 0xff, 0x55],
+];
+
+// The prologues of the x86_64 functions
+static X86_64_PROLOGUES: &[&[u8]] = &[
+    // endbr64
+    &[0xf3, 0x0f, 0x1e, 0xfa],
+    // push rbp; mov rbp, rsp
+    &[0x55, 0x48, 0x89, 0xe5],
+    &[0x55, 0x48, 0x8b, 0xec],
+];
+
+/// The code of an executable section
+struct CodeRegion<'a> {
+    rva: u32,
+    data: &'a [u8],
+}
+
+impl CodeRegion<'_> {
+    fn range(&self) -> Range<u32> {
+        self.rva..self.rva + self.data.len() as u32
+    }
+
+    fn get(&self, rva: u32) -> &[u8] {
+        rva.checked_sub(self.rva)
+            .and_then(|start| self.data.get(start as usize..))
+            .unwrap_or_default()
+    }
+}
+
+/// Get the executable sections of an ELF or PE file which are in the file
+fn get_code_regions<'a>(object: &Object<'a>) -> Vec<CodeRegion<'a>> {
+    let data = object.data();
+    let load_address = object.load_address();
+    match goblin::Object::parse(data) {
+        Ok(goblin::Object::Elf(elf)) => elf
+            .section_headers
+            .iter()
+            .filter(|h| {
+                h.is_alloc()
+                    && h.is_executable()
+                    && h.sh_type != goblin::elf::section_header::SHT_NOBITS
+            })
+            .filter_map(|h| {
+                let start = h.sh_offset as usize;
+                Some(CodeRegion {
+                    rva: h.sh_addr.checked_sub(load_address)? as u32,
+                    data: data.get(start..start.checked_add(h.sh_size as usize)?)?,
+                })
+            })
+            .collect(),
+        Ok(goblin::Object::PE(pe)) => pe
+            .sections
+            .iter()
+            .filter(|s| s.characteristics & goblin::pe::section_table::IMAGE_SCN_MEM_EXECUTE != 0)
+            .filter_map(|s| {
+                let start = s.pointer_to_raw_data as usize;
+                let len = s.size_of_raw_data.min(s.virtual_size) as usize;
+                Some(CodeRegion {
+                    rva: s.virtual_address,
+                    data: data.get(start..start + len)?,
+                })
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_padding(b: u8) -> bool {
+    b == 0xcc || b == 0x90
+}
+
+/// The parts of the code which aren't covered by the given ranges, sorted
+fn get_gaps(regions: &[CodeRegion], covered: &[Range<u32>]) -> Vec<Range<u32>> {
+    let mut covered = covered.to_vec();
+    covered.sort_by_key(|r| r.start);
+
+    let mut gaps = Vec::new();
+    for region in regions.iter().map(CodeRegion::range) {
+        let mut pos = region.start;
+        for range in covered
+            .iter()
+            .filter(|r| r.end > region.start && r.start < region.end)
+        {
+            if range.start > pos {
+                gaps.push(pos..range.start);
+            }
+            pos = pos.max(range.end);
+        }
+        if pos < region.end {
+            gaps.push(pos..region.end);
+        }
+    }
+    gaps.sort_by_key(|r| r.start);
+
+    gaps
+}
+
+/// Find the function starts in the code not covered by the given ranges: the
+/// prologues following a padding byte or a `ret`, and the targets of the
+/// `call rel32` instructions of the code which are at such a boundary or start
+/// with a prologue. The functions extend up to the next start or to the end of
+/// their gap.
+fn discover(regions: &[CodeRegion], covered: &[Range<u32>], arch: Arch) -> Vec<Range<u32>> {
+    let prologues = match arch.cpu_family() {
+        CpuFamily::Intel32 => X86_PROLOGUES,
+        CpuFamily::Amd64 => X86_64_PROLOGUES,
+        _ => return Vec::new(),
+    };
+    let gaps = get_gaps(regions, covered);
+    let get_gap = |rva: u32| {
+        let i = gaps.partition_point(|g| g.end <= rva);
+        gaps.get(i).filter(|g| g.start <= rva)
+    };
+    let region_of = |rva: u32| regions.iter().find(|r| r.range().contains(&rva));
+    let is_prologue = |rva: u32| {
+        region_of(rva).is_some_and(|r| prologues.iter().any(|p| r.get(rva).starts_with(p)))
+    };
+    // The start of a function is after the padding at the beginning of a gap,
+    // or after a padding byte or a `ret`
+    let is_boundary = |rva: u32, gap: &Range<u32>| {
+        let code = match region_of(rva) {
+            Some(code) => code,
+            None => return false,
+        };
+        let first = (gap.start..gap.end)
+            .find(|rva| !code.get(*rva).first().copied().is_some_and(is_padding))
+            .unwrap_or(gap.end);
+        rva == first
+            || (rva > code.rva
+                && code
+                    .get(rva - 1)
+                    .first()
+                    .is_some_and(|b| is_padding(*b) || *b == RET))
+    };
+
+    let mut starts = Vec::new();
+    for gap in &gaps {
+        for rva in gap.clone() {
+            if is_prologue(rva) && is_boundary(rva, gap) {
+                starts.push(rva);
+            }
+        }
+    }
+    for region in regions {
+        for (i, window) in region.data.windows(CALL_LEN).enumerate() {
+            if window[0] != CALL {
+                continue;
+            }
+            let rel = i32::from_le_bytes([window[1], window[2], window[3], window[4]]);
+            let target = match (region.rva + (i + CALL_LEN) as u32).checked_add_signed(rel) {
+                Some(target) => target,
+                None => continue,
+            };
+            if let Some(gap) = get_gap(target) {
+                if is_prologue(target) || is_boundary(target, gap) {
+                    starts.push(target);
+                }
+            }
+        }
+    }
+    starts.sort_unstable();
+    starts.dedup();
+
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, start)| {
+            let gap_end = get_gap(*start)?.end;
+            let end = starts.get(i + 1).map_or(gap_end, |next| gap_end.min(*next));
+            Some(*start..end)
+        })
+        .collect()
+}
+
+/// Add a synthetic function `fn_<address>` for every function start found in the
+/// code which isn't covered by a symbol nor by the CFI (e.g. hand-written assembly
+/// or the stubs of a packer).
+// This runs before set_missing_lengths, the symbols without a length don't cover
+// the code after them.
+pub(crate) fn add_discovered_functions(
+    mut syms: Symbols,
+    object: &Object,
+    cfi: &[Range<u32>],
+) -> Symbols {
+    let regions = get_code_regions(object);
+    let mut covered: Vec<_> = syms
+        .values()
+        .map(|sym| sym.rva..sym.rva.saturating_add(sym.len.max(1)))
+        .collect();
+    covered.extend_from_slice(cfi);
+
+    for range in discover(&regions, &covered, object.arch()) {
+        syms.entry(range.start).or_insert(Symbol {
+            name: format!("fn_{:x}", range.start),
+            is_public: false,
+            is_multiple: false,
+            is_synthetic: true,
+            rva: range.start,
+            len: range.end - range.start,
+            parameter_size: 0,
+            source: Lines::new(),
+            labels: Vec::new(),
+            language: None,
+        });
+    }
+
+    syms
+}
+
+/// Get the number of bytes popped by the last instruction of a function when
+/// it's a `ret` (the padding after it is skipped), None when the function ends
+/// with something else (e.g. a tail call).
+fn get_popped_size(code: &[u8]) -> Option<u32> {
+    let end = code.iter().rposition(|b| !is_padding(*b))? + 1;
+    let code = &code[..end];
+    // The last bytes could be the displacement of a jmp rel8, a jmp rel32 or a call rel32
+    let at = |i: usize| end.checked_sub(i).map(|i| code[i]);
+    let is_branch = at(2) == Some(0xeb) || matches!(at(5), Some(CALL | 0xe9));
+    match code {
+        [.., RET_IMM, lo, hi] if !is_branch => {
+            let size = u16::from_le_bytes([*lo, *hi]) as u32;
+            // The parameters are pushed in 4 bytes slots
+            size.is_multiple_of(4).then_some(size)
+        }
+        [.., RET] if !is_branch => Some(0),
+        _ => None,
+    }
+}
+
+/// Check the parameter sizes of the x86 functions against the `ret n`
+/// instruction ending them and fix the wrong ones: they're computed from the
+/// calling conventions and the types of the parameters which aren't always
+/// right, and a wrong size breaks the unwinding of the callers.
+/// Only the functions with a size are checked, the thiscall methods pop their
+/// parameters too but they don't get one.
+// This runs after set_missing_lengths, the symbols without a length are skipped.
+pub(crate) fn check_parameter_sizes(syms: &mut Symbols, object: &Object) {
+    if object.arch().cpu_family() != CpuFamily::Intel32 {
+        return;
+    }
+
+    let regions = get_code_regions(object);
+    for sym in syms
+        .values_mut()
+        .filter(|sym| sym.len != 0 && sym.parameter_size != 0)
+    {
+        let code = match regions.iter().find(|r| r.range().contains(&sym.rva)) {
+            Some(region) => region.get(sym.rva),
+            None => continue,
+        };
+        let code = code.get(..sym.len as usize).unwrap_or(code);
+        if let Some(size) = get_popped_size(code) {
+            if size != sym.parameter_size {
+                warn!(
+                    "The parameter size of {} is {} but it pops {} bytes",
+                    sym.name, sym.parameter_size, size
+                );
+                sym.parameter_size = size;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover() {
+        let data = [
+            // A known function: call 0x1010; call 0x1020; ret
+            0xe8, 0x0b, 0x00, 0x00, 0x00, 0xe8, 0x16, 0x00, 0x00, 0x00, 0xc3, //
+            // Padding
+            0xcc, 0xcc, 0xcc, 0xcc, 0xcc, //
+            // 0x1010: a function without prologue: xor eax, eax; ret
+            0x31, 0xc0, 0xc3, //
+            // Padding
+            0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, //
+            // 0x1020: push rbp; mov rbp, rsp; pop rbp; ret
+            0x55, 0x48, 0x89, 0xe5, 0x5d, 0xc3, //
+            // 0x1026: endbr64; ret, it's found from its prologue
+            0xf3, 0x0f, 0x1e, 0xfa, 0xc3, //
+            // 0x102b: some bytes looking like a prologue in the middle of a function
+            0x01, 0x55, 0x48, 0x89, 0xe5, 0xc3,
+        ];
+        let regions = [CodeRegion {
+            rva: 0x1000,
+            data: &data,
+        }];
+
+        let known = 0x1000..0x100b;
+        assert_eq!(
+            discover(&regions, std::slice::from_ref(&known), Arch::Amd64),
+            vec![0x1010..0x1020, 0x1020..0x1026, 0x1026..0x1031]
+        );
+        assert!(discover(&regions, std::slice::from_ref(&known), Arch::Arm64).is_empty());
+        // Everything is covered
+        let known = 0x1000..0x1031;
+        assert!(discover(&regions, std::slice::from_ref(&known), Arch::Amd64).is_empty());
+    }
+
+    #[test]
+    fn test_get_popped_size() {
+        // ret 8 and its padding
+        assert_eq!(
+            get_popped_size(&[0x5d, 0xc2, 0x08, 0x00, 0xcc, 0xcc]),
+            Some(8)
+        );
+        assert_eq!(get_popped_size(&[0x33, 0xc0, 0xc3]), Some(0));
+        // A tail call: jmp rel32
+        assert_eq!(get_popped_size(&[0xe9, 0x10, 0x00, 0x00, 0x00]), None);
+        assert_eq!(get_popped_size(&[0xe9, 0x10, 0xc2, 0x04, 0x00]), None);
+        // Not a multiple of the stack slots
+        assert_eq!(get_popped_size(&[0xc2, 0x03, 0x00]), None);
+        assert_eq!(get_popped_size(&[0xcc, 0xcc]), None);
+    }
+}
@@ -0,0 +1,343 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+#[cfg(feature = "pdb")]
+use symbolic::common::{Arch, DebugId};
+use symbolic::debuginfo::{Archive, FileFormat};
+
+use crate::common;
+#[cfg(feature = "pdb")]
+use crate::sym_cache;
+use crate::sym_cache::SymCache;
+use crate::utils;
+#[cfg(feature = "pdb")]
+use crate::windows;
+
+/// What is checked by the doctor
+#[derive(Debug, Default)]
+pub struct DoctorConfig<'a> {
+    /// The symbol servers as in `--symbol-server`, `~/.dump_syms/config` when none
+    pub symbol_server: Option<&'a str>,
+    /// The symbols cache as in `--sym-cache`, the default one when empty
+    pub sym_cache: Option<&'a str>,
+    pub store: Option<&'a str>,
+    pub upload_url: Option<&'a str>,
+    pub auth_token: Option<&'a str>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// The results of the checks, the failed ones come with a remediation
+#[derive(Debug, Default)]
+pub struct Report {
+    checks: Vec<(Status, String, Option<String>)>,
+}
+
+impl Report {
+    fn ok(&mut self, msg: String) {
+        self.checks.push((Status::Ok, msg, None));
+    }
+
+    fn warning(&mut self, msg: String, fix: String) {
+        self.checks.push((Status::Warning, msg, Some(fix)));
+    }
+
+    fn error(&mut self, msg: String, fix: String) {
+        self.checks.push((Status::Error, msg, Some(fix)));
+    }
+
+    /// The number of failed checks
+    pub fn errors(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|(status, _, _)| *status == Status::Error)
+            .count()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (status, msg, fix) in &self.checks {
+            let status = match status {
+                Status::Ok => "ok",
+                Status::Warning => "warning",
+                Status::Error => "error",
+            };
+            writeln!(f, "{}: {}", status, msg)?;
+            if let Some(fix) = fix {
+                writeln!(f, "  fix: {}", fix)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that a file can be created in the directory or in its first existing
+/// ancestor when it doesn't exist yet, nothing is left behind.
+fn check_writable(dir: &Path) -> io::Result<()> {
+    let dir = dir
+        .ancestors()
+        .find(|d| d.is_dir())
+        .unwrap_or_else(|| Path::new("."));
+    let probe = dir.join(format!(".dump_syms_doctor.{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+fn check_dir(report: &mut Report, what: &str, dir: &Path, fix: &str) {
+    match check_writable(dir) {
+        Ok(()) if dir.is_dir() => report.ok(format!("{} {} is writable", what, dir.display())),
+        Ok(()) => report.ok(format!("{} {} can be created", what, dir.display())),
+        Err(e) => report.error(
+            format!("{} {} isn't writable: {}", what, dir.display(), e),
+            fix.to_string(),
+        ),
+    }
+}
+
+#[cfg(feature = "http")]
+fn check_server(report: &mut Report, what: &str, url: &str, fix: &str) {
+    match crate::cache::check_server(url) {
+        Ok(status) => report.ok(format!("{} {} is reachable ({})", what, url, status)),
+        Err(e) => report.error(
+            format!("{} {} isn't reachable: {}", what, url, e.root_cause()),
+            fix.to_string(),
+        ),
+    }
+}
+
+#[cfg(feature = "http")]
+fn check_servers(report: &mut Report, config: &DoctorConfig) {
+    let servers = crate::cache::get_sym_servers(config.symbol_server).unwrap_or_default();
+    for server in &servers {
+        check_server(
+            report,
+            "the symbol server",
+            server.url(),
+            "check the URL in --symbol-server or in ~/.dump_syms/config and the proxy settings (HTTPS_PROXY)",
+        );
+    }
+    for dir in crate::cache::get_cache_dirs(&servers) {
+        check_dir(
+            report,
+            "the symbol server cache",
+            &dir,
+            "fix its permissions or use another cache in the SRV*CACHE*URL entry",
+        );
+    }
+
+    if let Some(url) = config.upload_url {
+        check_server(
+            report,
+            "the upload server",
+            url,
+            "check the URL in --url and the proxy settings (HTTPS_PROXY)",
+        );
+        if config.auth_token.is_none() {
+            report.warning(
+                "there is no token for the uploads".to_string(),
+                "set --auth-token or DUMP_SYMS_AUTH_TOKEN if the server requires one".to_string(),
+            );
+        }
+    }
+}
+
+/// Check that the companion files required to dump a file are there
+#[cfg_attr(not(feature = "pdb"), allow(unused_variables))]
+fn check_input(report: &mut Report, config: &DoctorConfig, file: &str) {
+    let path = Path::new(file);
+    let buf = match utils::read(path) {
+        Ok(buf) => buf,
+        Err(e) => {
+            report.error(
+                format!("{} can't be read: {:#}", file, e),
+                "check the path and the permissions of the file".to_string(),
+            );
+            return;
+        }
+    };
+
+    let format = Archive::peek(&buf);
+    let supported = (format == FileFormat::Elf && cfg!(feature = "elf"))
+        || (matches!(format, FileFormat::Pdb | FileFormat::Pe) && cfg!(feature = "pdb"))
+        || (format == FileFormat::MachO && cfg!(feature = "macho"));
+    if !supported {
+        report.error(
+            format!("{} has an unsupported file format: {}", file, format),
+            "dump the dll, exe, pdb, ELF or Mach-O files (see `dump_syms --help` for the enabled formats)".to_string(),
+        );
+        return;
+    }
+
+    match format {
+        #[cfg(feature = "pdb")]
+        FileFormat::Pe => match sym_cache::peek_pdb_name(&buf, Arch::Unknown) {
+            Some(pdb_name) => match windows::utils::get_pdb_path(path, &pdb_name).0 {
+                Some(pdb) => report.ok(format!("{}: its pdb is {}", file, pdb.display())),
+                None if config.symbol_server.is_some() => report.warning(
+                    format!("{}: the pdb {} isn't next to it", file, pdb_name),
+                    "it's searched on the symbol servers, check that they're reachable".to_string(),
+                ),
+                None => report.error(
+                    format!("{}: the pdb {} isn't found", file, pdb_name),
+                    format!("copy {} next to {} or set --symbol-server", pdb_name, file),
+                ),
+            },
+            None => report.warning(
+                format!("{} has no pdb", file),
+                "only its exports are dumped, link it with /DEBUG to get a pdb".to_string(),
+            ),
+        },
+        #[cfg(feature = "pdb")]
+        FileFormat::Pdb => {
+            let pe = sym_cache::peek_debug_id(&buf, Arch::Unknown)
+                .and_then(|id| DebugId::from_breakpad(&id).ok())
+                .and_then(|id| windows::utils::find_pe_for_pdb(path, &id));
+            match pe {
+                Some((pe_name, _)) => report.ok(format!("{}: its PE file is {}", file, pe_name)),
+                None => report.warning(
+                    format!("{}: there is no dll or exe next to it", file),
+                    "copy the dll or the exe next to the pdb to get the unwind info and the exports"
+                        .to_string(),
+                ),
+            }
+        }
+        _ => {
+            let has_debug_info = Archive::parse(&buf)
+                .ok()
+                .and_then(|archive| archive.objects().next()?.ok().map(|o| o.has_debug_info()))
+                .unwrap_or_default();
+            if has_debug_info {
+                report.ok(format!("{} has debug info", file));
+            } else if format == FileFormat::MachO {
+                report.warning(
+                    format!("{} has no debug info", file),
+                    "dump the dSYM bundle made by dsymutil to get the functions and the lines"
+                        .to_string(),
+                );
+            } else {
+                report.warning(
+                    format!("{} has no debug info", file),
+                    "dump its debug file (e.g. the one from its .gnu_debuglink) to get the functions and the lines".to_string(),
+                );
+            }
+        }
+    }
+}
+
+/// Check the environment used to dump and to upload the symbols: the symbol
+/// servers are reachable, the caches and the store are writable and the sample
+/// inputs have the companion files required to dump them.
+pub fn doctor(config: &DoctorConfig, files: &[&str]) -> common::Result<Report> {
+    let mut report = Report::default();
+
+    #[cfg(feature = "http")]
+    check_servers(&mut report, config);
+
+    if let Some(dir) = config.sym_cache {
+        let dir = (!dir.is_empty()).then(|| Path::new(dir));
+        check_dir(
+            &mut report,
+            "the symbols cache",
+            SymCache::new(dir)?.dir(),
+            "fix its permissions or set --sym-cache to another directory",
+        );
+    }
+    if let Some(store) = config.store {
+        check_dir(
+            &mut report,
+            "the store",
+            Path::new(store),
+            "fix its permissions or set --store to another directory",
+        );
+    }
+
+    for file in files {
+        check_input(&mut report, config, file);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_doctor() {
+        let tmp_dir = Builder::new().prefix("doctor").tempdir().unwrap();
+        let store = tmp_dir.path().join("store");
+        let store = store.to_str().unwrap();
+        let config = DoctorConfig {
+            sym_cache: Some(store),
+            store: Some(store),
+            ..Default::default()
+        };
+
+        let report = doctor(
+            &config,
+            &[
+                "./test_data/linux/basic.full",
+                "./test_data/linux/basic.stripped",
+            ],
+        )
+        .unwrap();
+        assert_eq!(report.errors(), 0);
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "ok: the symbols cache {store} can be created
+ok: the store {store} can be created
+ok: ./test_data/linux/basic.full has debug info
+warning: ./test_data/linux/basic.stripped has no debug info
+  fix: dump its debug file (e.g. the one from its .gnu_debuglink) to get the functions and the lines
+"
+            )
+        );
+        // Nothing has been created
+        assert!(!tmp_dir.path().join("store").exists());
+
+        let report = doctor(&config, &["./test_data/linux/basic.cpp"]).unwrap();
+        assert_eq!(report.errors(), 1);
+    }
+
+    #[cfg(feature = "pdb")]
+    #[test]
+    fn test_doctor_pdb() {
+        let tmp_dir = Builder::new().prefix("doctor").tempdir().unwrap();
+        let dll = tmp_dir.path().join("basic64.dll");
+        fs::copy("./test_data/windows/basic64.dll", &dll).unwrap();
+        let dll = dll.to_str().unwrap();
+
+        let report = doctor(&DoctorConfig::default(), &[dll]).unwrap();
+        assert_eq!(report.errors(), 1);
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "error: {dll}: the pdb basic64.pdb isn't found
+  fix: copy basic64.pdb next to {dll} or set --symbol-server
+"
+            )
+        );
+
+        fs::copy(
+            "./test_data/windows/basic64.pdb",
+            tmp_dir.path().join("basic64.pdb"),
+        )
+        .unwrap();
+        let report = doctor(&DoctorConfig::default(), &[dll]).unwrap();
+        assert_eq!(report.errors(), 0);
+    }
+}
@@ -5,25 +5,50 @@
 
 use crossbeam::channel::{bounded, Receiver, Sender};
 use hashbrown::HashMap;
-use log::{error, info};
+use log::{error, info, warn};
+use std::cell::RefCell;
 use std::fmt;
 use std::fs;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use symbolic::common::Arch;
+use std::time::Instant;
+use symbolic::common::{Arch, ByteView, DebugId};
+#[cfg(feature = "pdb")]
 use symbolic::debuginfo::pdb::PdbObject;
+#[cfg(feature = "pdb")]
 use symbolic::debuginfo::pe::PeObject;
 use symbolic::debuginfo::{peek, FileFormat};
 
-use crate::common;
-use crate::mapping::PathMappings;
+use crate::archive::{self, ArchiveKind};
+use crate::cfi::CfiValidation;
+use crate::collector::{
+    CollectOptions, CompilandFilter, GapFill, InnerPublics, ParseLimits, PublicFilter, Sentinel,
+    SourcePriority, Thunks,
+};
+use crate::common::{self, FailureKind, InputEncoding};
+#[cfg(feature = "http")]
+use crate::jobs;
+use crate::jobs::{Job, JobReport};
+use crate::mapping::{PathGlobs, PathMappings, SourceUrlTemplate, SourceUrls};
+use crate::metrics::{self, MetricsSink, ModuleMetrics, ParseStats};
 use crate::object_info::ObjectInfo;
+use crate::outlined::Outlined;
+#[cfg(feature = "elf")]
 use crate::platform::Platform;
+use crate::progress::{self, Phase, Progress, ProgressFormat};
+use crate::sym_cache::{self, CacheLimits, SymCache};
 use crate::utils;
+use crate::vcs::GitCheckouts;
+#[cfg(feature = "pdb")]
 use crate::windows;
+#[cfg(feature = "pdb")]
+use crate::windows::map::LinkerMap;
+#[cfg(feature = "pdb")]
+use crate::windows::utils::pair_pe_pdb;
 
 /// Different locations for file output
 #[derive(Clone)]
@@ -74,90 +99,343 @@ impl From<PathBuf> for Output {
     }
 }
 
+/// The format used to write the symbols
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The Breakpad text format (.sym)
+    #[default]
+    Breakpad,
+    /// An address-sorted list of `rva size name` entries which can be imported
+    /// in reverse-engineering tools such as IDA or Ghidra
+    Map,
+    /// The records of the Breakpad format as a JSON document
+    Json,
+}
+
+impl OutputFormat {
+    /// The extension of the files in the symbol store, so a store doesn't
+    /// serve a map or a JSON document as Breakpad symbols
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Breakpad => "sym",
+            Self::Map => "map",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "breakpad" | "sym" => Ok(Self::Breakpad),
+            "map" => Ok(Self::Map),
+            "json" => Ok(Self::Json),
+            _ => anyhow::bail!("Invalid output format: {}", s),
+        }
+    }
+}
+
+/// The layout of the symbol store
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StoreLayout {
+    /// FILENAME.pdb/DEBUG_ID/FILENAME.sym as the Breakpad symbol servers
+    #[default]
+    Breakpad,
+    /// The same paths in lowercase, as the keys of the symbol stores using the
+    /// Simple Symbol Query Protocol (SSQP)
+    Ssqp,
+}
+
+impl FromStr for StoreLayout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "breakpad" => Ok(Self::Breakpad),
+            "ssqp" => Ok(Self::Ssqp),
+            _ => anyhow::bail!("Invalid store layout: {}", s),
+        }
+    }
+}
+
 pub struct Config<'a> {
     pub output: Output,
+    pub format: OutputFormat,
+    /// The layout of the paths in the symbol store
+    pub store_layout: StoreLayout,
+    pub legacy_order: bool,
     pub symbol_server: Option<&'a str>,
     pub debug_id: Option<&'a str>,
     pub code_id: Option<&'a str>,
+    /// The OS on the MODULE line instead of the detected one (`windows`, `mac`, `Linux`, ...)
+    pub module_os: Option<&'a str>,
+    /// The CPU architecture on the MODULE line instead of the one of the file
+    pub module_arch: Option<&'a str>,
+    /// The name of the module on the MODULE line instead of the one of the file
+    pub module_name: Option<&'a str>,
+    /// The debug id on the MODULE line instead of the one of the file (in the Breakpad format)
+    pub module_debug_id: Option<&'a str>,
     pub arch: &'a str,
     pub num_jobs: usize,
     pub check_cfi: bool,
+    /// Skip the dump when the outputs already contain the symbols for the same debug id
+    pub incremental: bool,
+    /// Symbol servers where to look for already published symbols before dumping
+    pub published_servers: Option<Vec<&'a str>>,
+    /// The directory of the symbols cache, an empty string means the default one
+    pub sym_cache: Option<&'a str>,
+    /// The limits applied to the caches after a dump
+    pub cache_limits: CacheLimits,
     pub emit_inlines: bool,
+    /// Emit the labels as LABEL records in the functions (extension)
+    pub labels: bool,
+    /// Emit the source languages of the functions as LANGUAGE records (extension)
+    pub languages: bool,
+    /// Emit the global and static variables as DATA records (extension)
+    pub data_symbols: bool,
+    /// Select the public symbols to emit
+    pub public_filter: PublicFilter,
+    /// Select the modules of the PDB files to dump
+    pub compiland_filter: CompilandFilter,
+    /// What to do with the compiler thunks
+    pub thunks: Thunks,
+    /// What to do with the functions of the LLVM machine outliner
+    pub outlined: Outlined,
+    /// Give the name of their parent function to the funclets
+    pub merge_funclets: bool,
+    /// Give the name of their parent function to the parts of the split functions
+    pub merge_split_functions: bool,
+    /// Emit a STACK CFI record for the code without unwind info (leaf functions)
+    pub leaf_cfi: bool,
+    /// Emit a STACK SIGNAL record after the STACK CFI INIT one of the signal frames (extension)
+    pub signal_frames: bool,
+    /// Merge the adjacent STACK CFI INIT records with the same rules
+    pub merge_cfi: bool,
+    /// Check the consistency of the STACK CFI records
+    pub validate_cfi: CfiValidation,
+    /// Look for the functions in the code without symbols nor unwind info (disasm feature)
+    pub discover_functions: bool,
+    /// Fix the x86 parameter sizes from the `ret n` ending the functions (disasm feature)
+    pub check_param_sizes: bool,
+    /// Get the functions of a /DEBUG:FASTLINK PDB from the object files it references
+    pub fastlink_objects: bool,
+    /// The hard limits for the untrusted files
+    pub limits: ParseLimits,
+    /// The encoding of the strings which aren't UTF-8 in the debug files
+    pub encoding: InputEncoding,
+    /// What covers the code between the functions
+    pub gap_fill: GapFill,
+    /// Warn about the code of the executable sections not covered by a FUNC or a PUBLIC record
+    pub report_gaps: bool,
+    /// Where the public symbol after the last one of a PE file is added
+    pub sentinel: Sentinel,
+    /// The name of that symbol instead of `<unknown in module>`
+    pub sentinel_name: Option<&'a str>,
+    /// What to do with the public symbols inside a function
+    pub inner_publics: InnerPublics,
+    /// Which source names a symbol when several of them have one for its address
+    pub priority: SourcePriority,
+    /// How the progress is reported on stderr
+    pub progress: ProgressFormat,
+    /// Describe the inputs and the outputs without dumping nor writing anything
+    pub dry_run: bool,
+    /// Receive the counters of the dumped modules
+    pub metrics: Option<Arc<dyn MetricsSink>>,
+    /// Write the counters of the dumped modules in a JSON file next to their symbols
+    pub metrics_sidecar: bool,
     pub mapping_var: Option<Vec<&'a str>>,
     pub mapping_src: Option<Vec<&'a str>>,
     pub mapping_dest: Option<Vec<&'a str>>,
     pub mapping_file: Option<&'a str>,
+    /// Drop the line records in the source files matching these globs
+    pub prune_lines: PathGlobs,
+    /// Rewrite the FILE records into URLs with this template
+    pub source_url_template: Option<SourceUrlTemplate>,
+    /// Emit the git revision, remote and path of the source files in a checkout
+    /// as FILE_GIT records (extension)
+    pub git_info: bool,
+}
+
+impl Default for Config<'_> {
+    fn default() -> Self {
+        Self {
+            output: Output::File(FileOutput::Stdout),
+            format: OutputFormat::default(),
+            store_layout: StoreLayout::default(),
+            legacy_order: false,
+            symbol_server: None,
+            debug_id: None,
+            code_id: None,
+            module_os: None,
+            module_arch: None,
+            module_name: None,
+            module_debug_id: None,
+            arch: common::get_compile_time_arch(),
+            num_jobs: 1,
+            check_cfi: false,
+            incremental: false,
+            published_servers: None,
+            sym_cache: None,
+            cache_limits: CacheLimits::default(),
+            emit_inlines: false,
+            labels: false,
+            languages: false,
+            data_symbols: false,
+            public_filter: PublicFilter::default(),
+            compiland_filter: CompilandFilter::default(),
+            thunks: Thunks::default(),
+            outlined: Outlined::default(),
+            merge_funclets: false,
+            merge_split_functions: false,
+            leaf_cfi: false,
+            signal_frames: false,
+            merge_cfi: false,
+            validate_cfi: CfiValidation::default(),
+            discover_functions: false,
+            check_param_sizes: false,
+            fastlink_objects: false,
+            limits: ParseLimits::default(),
+            encoding: InputEncoding::default(),
+            gap_fill: GapFill::default(),
+            report_gaps: false,
+            sentinel: Sentinel::default(),
+            sentinel_name: None,
+            inner_publics: InnerPublics::default(),
+            priority: SourcePriority::default(),
+            progress: ProgressFormat::default(),
+            dry_run: false,
+            metrics: None,
+            metrics_sidecar: false,
+            mapping_var: None,
+            mapping_src: None,
+            mapping_dest: None,
+            mapping_file: None,
+            prune_lines: PathGlobs::default(),
+            source_url_template: None,
+            git_info: false,
+        }
+    }
+}
+
+impl Config<'_> {
+    pub fn collect_options(&self) -> CollectOptions {
+        CollectOptions {
+            inlines: self.emit_inlines,
+            labels: self.labels,
+            languages: self.languages,
+            data: self.data_symbols,
+            fastlink_objects: self.fastlink_objects,
+            publics: self.public_filter.clone(),
+            compilands: self.compiland_filter.clone(),
+            thunks: self.thunks,
+            outlined: self.outlined,
+            merge_funclets: self.merge_funclets,
+            merge_split_functions: self.merge_split_functions,
+            leaf_cfi: self.leaf_cfi,
+            signal_frames: self.signal_frames,
+            merge_cfi: self.merge_cfi,
+            validate_cfi: self.validate_cfi,
+            discover_functions: self.discover_functions,
+            check_param_sizes: self.check_param_sizes,
+            gap_fill: self.gap_fill,
+            report_gaps: self.report_gaps,
+            sentinel: self.sentinel,
+            sentinel_name: self.sentinel_name.map(ToOwned::to_owned),
+            inner_publics: self.inner_publics,
+            priority: self.priority.clone(),
+            limits: self.limits,
+            encoding: self.encoding,
+        }
+    }
 }
 
+#[cfg(feature = "pdb")]
 fn get_pdb_object_info(
     buf: &[u8],
     path: &Path,
     filename: &str,
+    pe: Option<&Path>,
     mapping: Option<Arc<PathMappings>>,
-    collect_inlines: bool,
+    options: CollectOptions,
 ) -> common::Result<ObjectInfo> {
     let pdb = PdbObject::parse(buf)?;
 
-    let (pe_name, pe_buf) = match windows::utils::find_pe_for_pdb(path, &pdb.debug_id()) {
+    // The PE file paired with the pdb in a batch, else the one next to it
+    let pe = match pe {
+        Some(pe) => Some((utils::get_filename(pe), utils::read_file(pe)?)),
+        None => windows::utils::find_pe_for_pdb(path, &pdb.debug_id()),
+    };
+    let (pe_name, pe_buf) = match pe {
         Some((pe_name, pe_buf)) => (Some(pe_name), Some(pe_buf)),
         None => (None, None),
     };
-    let pe = pe_buf.as_deref().map(|buf| PeObject::parse(buf).unwrap());
+    // The pdb is dumped alone when the PE file next to it is corrupted
+    let pe = pe_buf
+        .as_deref()
+        .and_then(|buf| match PeObject::parse(buf) {
+            Ok(pe) => Some(pe),
+            Err(e) => {
+                warn!(
+                    "Unable to parse the PE file {}: {}",
+                    pe_name.as_deref().unwrap_or_default(),
+                    e
+                );
+                None
+            }
+        });
 
-    ObjectInfo::from_pdb(
-        pdb,
-        filename,
-        pe_name.as_deref(),
-        pe,
-        mapping,
-        collect_inlines,
-    )
+    ObjectInfo::from_pdb(pdb, filename, pe_name.as_deref(), pe, mapping, options)
 }
 
-#[cfg(feature = "http")]
+#[cfg(feature = "pdb")]
+#[cfg_attr(not(feature = "http"), allow(unused_variables))]
 fn get_pe_pdb_object_info(
     buf: &[u8],
     path: &Path,
     filename: &str,
     mapping: Option<Arc<PathMappings>>,
     symbol_server: Option<&str>,
-    emit_inlines: bool,
+    options: CollectOptions,
 ) -> common::Result<ObjectInfo> {
-    let symbol_server = crate::cache::get_sym_servers(symbol_server);
-    let res = windows::utils::get_pe_pdb_buf(path, buf, symbol_server.as_ref());
+    #[cfg(feature = "http")]
+    let res = {
+        let symbol_server = crate::cache::get_sym_servers(symbol_server);
+        windows::utils::get_pe_pdb_buf(path, buf, symbol_server.as_ref())
+    };
+    #[cfg(not(feature = "http"))]
+    let res = windows::utils::get_pe_pdb_buf(path, buf);
 
     if let Some((pe, pdb_buf, pdb_name)) = res {
         let pdb = PdbObject::parse(&pdb_buf)?;
-        let pdb = ObjectInfo::from_pdb(
-            pdb,
-            &pdb_name,
-            Some(filename),
-            Some(pe),
-            mapping,
-            emit_inlines,
-        )?;
+        let pdb = ObjectInfo::from_pdb(pdb, &pdb_name, Some(filename), Some(pe), mapping, options)?;
         Ok(pdb)
     } else {
         anyhow::bail!("No pdb file found")
     }
 }
 
-#[cfg(not(feature = "http"))]
-fn get_pe_pdb_object_info<'a>(
+#[cfg(feature = "pdb")]
+fn get_pe_object_info(
     buf: &[u8],
     path: &Path,
     filename: &str,
-    mapping: Option<Arc<PathMappings>>,
-    symbol_server: Option<&str>,
-    emit_inlines: bool,
+    options: CollectOptions,
 ) -> common::Result<ObjectInfo> {
-    anyhow::bail!("HTTP symbol retrieval not enabled")
-}
-
-fn get_pe_object_info(buf: &[u8], path: &Path, filename: &str) -> common::Result<ObjectInfo> {
     let pe = PeObject::parse(buf)
-        .unwrap_or_else(|_| panic!("Unable to parse the PE file {}", path.to_str().unwrap()));
-    let pe = ObjectInfo::from_pe(filename, pe)?;
+        .map_err(|e| anyhow::anyhow!("Unable to parse the PE file {}: {}", path.display(), e))?;
+
+    // Without a pdb, a linker map could help to get the function names.
+    let map_path = path.with_extension("map");
+    let linker_map = if map_path.is_file() {
+        info!("Use the linker map {}", map_path.display());
+        Some(LinkerMap::from_file(&map_path, pe.load_address())?)
+    } else {
+        None
+    };
+
+    let pe = ObjectInfo::from_pe(filename, pe, linker_map.as_ref(), options)?;
     Ok(pe)
 }
 
@@ -173,71 +451,444 @@ pub fn get_writer_for_sym(fo: &FileOutput) -> std::io::BufWriter<Box<dyn std::io
         }
     };
 
-    std::io::BufWriter::new(output)
+    std::io::BufWriter::new(Box::new(progress::CountBytes(output)))
 }
 
-fn store(output: &Output, check_cfi: bool, object_info: ObjectInfo) -> common::Result<()> {
-    anyhow::ensure!(!check_cfi || object_info.has_stack(), "No CFI data");
+/// What is needed to write the symbols once they've been collected
+#[derive(Clone)]
+struct StoreConfig {
+    output: Output,
+    format: OutputFormat,
+    store_layout: StoreLayout,
+    legacy_order: bool,
+    check_cfi: bool,
+    module_os: Option<String>,
+    module_arch: Option<String>,
+    module_name: Option<String>,
+    module_debug_id: Option<String>,
+    prune_lines: PathGlobs,
+    source_urls: Option<SourceUrls>,
+    git_checkouts: Option<GitCheckouts>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    metrics_sidecar: bool,
+}
 
-    let sym_store_path = |dir: &Path| -> Option<PathBuf> {
-        if dir.to_str()?.is_empty() {
-            return None;
+impl StoreConfig {
+    fn new(config: &Config) -> Self {
+        Self {
+            output: config.output.clone(),
+            format: config.format,
+            store_layout: config.store_layout,
+            legacy_order: config.legacy_order,
+            check_cfi: config.check_cfi,
+            module_os: config.module_os.map(ToOwned::to_owned),
+            module_arch: config.module_arch.map(ToOwned::to_owned),
+            module_name: config.module_name.map(ToOwned::to_owned),
+            module_debug_id: config.module_debug_id.map(|id| {
+                DebugId::from_breakpad(id)
+                    .map(|id| id.breakpad().to_string())
+                    .unwrap_or_else(|_| id.to_string())
+            }),
+            prune_lines: config.prune_lines.clone(),
+            source_urls: config
+                .source_url_template
+                .clone()
+                .map(|template| SourceUrls::new(template, &config.mapping_var)),
+            git_checkouts: config.git_info.then(GitCheckouts::default),
+            metrics: config.metrics.clone(),
+            metrics_sidecar: config.metrics_sidecar,
         }
+    }
 
-        let mut pb = PathBuf::new();
-        pb.push(dir);
-        pb.push(utils::get_path_for_sym(
-            object_info.get_name(),
+    /// Get the output file and the path in the symbol store if any
+    fn destinations(&self, name: &str, debug_id: &str) -> (Option<&FileOutput>, Option<PathBuf>) {
+        let sym_store_path = |dir: &Path| -> Option<PathBuf> {
+            if dir.to_str()?.is_empty() {
+                return None;
+            }
+
+            let mut pb = PathBuf::new();
+            pb.push(dir);
+            match self.store_layout {
+                StoreLayout::Breakpad => pb.push(utils::get_path_for_sym(name, debug_id)),
+                StoreLayout::Ssqp => pb.push(utils::get_path_for_sym(
+                    &name.to_lowercase(),
+                    &debug_id.to_lowercase(),
+                )),
+            }
+            pb.set_extension(self.format.extension());
+            Some(pb)
+        };
+
+        match &self.output {
+            Output::File(fo) => (Some(fo), None),
+            Output::Store(store) => (None, sym_store_path(store)),
+            Output::FileAndStore {
+                file,
+                store_directory,
+            } => (Some(file), sym_store_path(store_directory)),
+        }
+    }
+
+    /// Check if all the outputs already exist and contain the symbols for the
+    /// given debug id, stdout and stderr are never up to date.
+    fn is_up_to_date(&self, name: &str, debug_id: &str) -> bool {
+        let (foutput, store) = self.destinations(name, debug_id);
+        let file = match foutput {
+            Some(FileOutput::Path(path)) => Some(path.as_path()),
+            Some(_) => return false,
+            None => None,
+        };
+
+        let mut paths = file.into_iter().chain(store.as_deref()).peekable();
+        paths.peek().is_some()
+            && paths.all(|path| sym_cache::read_module_debug_id(path).as_deref() == Some(debug_id))
+    }
+
+    fn write_symbols<W: std::io::Write>(
+        &self,
+        object_info: &ObjectInfo,
+        writer: W,
+    ) -> common::Result<()> {
+        match self.format {
+            OutputFormat::Breakpad if self.legacy_order => object_info.dump_legacy_order(writer),
+            OutputFormat::Breakpad => object_info.dump(writer),
+            OutputFormat::Map => object_info.dump_map(writer),
+            OutputFormat::Json => object_info.dump_json(writer),
+        }
+    }
+}
+
+/// Prune the lines of the source files, annotate the source files with their git
+/// checkout and rewrite them into URLs, the globs and the checkouts are matched
+/// with the paths before the rewriting.
+fn rewrite_files(config: &StoreConfig, object_info: &mut ObjectInfo) {
+    if !config.prune_lines.is_empty() {
+        object_info.prune_lines(&config.prune_lines);
+    }
+    if let Some(checkouts) = config.git_checkouts.as_ref() {
+        object_info.collect_git_files(checkouts);
+    }
+    if let Some(source_urls) = config.source_urls.as_ref() {
+        object_info.apply_source_urls(source_urls);
+    }
+}
+
+fn store(config: &StoreConfig, mut object_info: ObjectInfo) -> common::Result<()> {
+    let start = Instant::now();
+    let warnings = metrics::warnings();
+    anyhow::ensure!(!config.check_cfi || object_info.has_stack(), "No CFI data");
+
+    if let Some(name) = config.module_name.as_deref() {
+        object_info.set_name(name);
+    }
+    if let Some(os) = config.module_os.as_deref() {
+        object_info.set_os(os);
+    }
+    if let Some(cpu) = config.module_arch.as_deref() {
+        object_info.set_cpu(cpu);
+    }
+    if let Some(debug_id) = config.module_debug_id.as_deref() {
+        warn!(
+            "The debug id {} of {} is replaced by {}",
             object_info.get_debug_id(),
-        ));
-        Some(pb)
-    };
+            object_info.get_name(),
+            debug_id
+        );
+        object_info.set_debug_id(debug_id);
+    }
+    rewrite_files(config, &mut object_info);
 
-    let (foutput, store) = match output {
-        Output::File(fo) => (Some(fo), None),
-        Output::Store(store) => (None, sym_store_path(store)),
-        Output::FileAndStore {
-            file,
-            store_directory,
-        } => (Some(file), sym_store_path(store_directory)),
-    };
+    let size = store_with(
+        config,
+        object_info.get_name(),
+        object_info.get_debug_id(),
+        |writer| config.write_symbols(&object_info, writer),
+    )?;
+
+    report_metrics(config, start, warnings, || ModuleMetrics {
+        size,
+        ..object_info.metrics()
+    })
+}
+
+/// Report the counters of a stored module to the sink and write them in the
+/// sidecar files, `start` and `warnings` are the time and the number of warnings
+/// when its store began.
+fn report_metrics(
+    config: &StoreConfig,
+    start: Instant,
+    warnings: usize,
+    module_metrics: impl FnOnce() -> ModuleMetrics,
+) -> common::Result<()> {
+    if config.metrics.is_none() && !config.metrics_sidecar {
+        return Ok(());
+    }
+
+    let mut module_metrics = module_metrics();
+    module_metrics.warnings += metrics::warnings() - warnings;
+    module_metrics.store_duration = start.elapsed();
+    if let Some(sink) = config.metrics.as_ref() {
+        sink.module_dumped(&module_metrics);
+    }
+    if config.metrics_sidecar {
+        let (foutput, store) = config.destinations(&module_metrics.name, &module_metrics.debug_id);
+        let file = match foutput {
+            Some(FileOutput::Path(path)) => Some(path.clone()),
+            _ => None,
+        };
+        for path in file.into_iter().chain(store) {
+            let path = path.with_extension("metrics.json");
+            fs::write(&path, format!("{}\n", module_metrics.to_json()))?;
+            info!("Write metrics at {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Store the symbols from the cache or from a symbol server
+fn store_cached(config: &StoreConfig, data: &[u8]) -> common::Result<()> {
+    let start = Instant::now();
+    let warnings = metrics::warnings();
+    let (name, debug_id) = store_data(config, data)?;
+    report_metrics(config, start, warnings, || ModuleMetrics {
+        name,
+        debug_id,
+        ..ModuleMetrics::from_sym(data)
+    })
+}
+
+/// Store already generated symbols, the module name and the debug id are read
+/// from the MODULE line, they're returned with the overridden values.
+fn store_data(config: &StoreConfig, data: &[u8]) -> common::Result<(String, String)> {
+    let first_line = data.split(|c| *c == b'\n').next().unwrap_or_default();
+    let first_line = String::from_utf8_lossy(first_line);
+    let toks: Vec<_> = first_line.trim_end().splitn(5, ' ').collect();
+    anyhow::ensure!(
+        toks.len() == 5 && toks[0] == "MODULE",
+        "Invalid MODULE line: {}",
+        first_line
+    );
+    anyhow::ensure!(
+        !config.check_cfi || data.windows(7).any(|w| w == b"\nSTACK "),
+        "No CFI data"
+    );
+
+    let os = config.module_os.as_deref().unwrap_or(toks[1]);
+    let cpu = config.module_arch.as_deref().unwrap_or(toks[2]);
+    let name = config.module_name.as_deref().unwrap_or(toks[4]);
+    let debug_id = config.module_debug_id.as_deref().unwrap_or(toks[3]);
+    if [os, cpu, debug_id, name] == toks[1..] {
+        store_bytes(config, name, debug_id, data)?;
+        return Ok((name.to_string(), debug_id.to_string()));
+    }
+    if debug_id != toks[3] {
+        warn!(
+            "The debug id {} of {} is replaced by {}",
+            toks[3], toks[4], debug_id
+        );
+    }
+
+    // The MODULE line is rewritten with the overridden values
+    let rest = data
+        .iter()
+        .position(|c| *c == b'\n')
+        .map_or(&[][..], |pos| &data[pos + 1..]);
+    store_with(config, name, debug_id, |writer| {
+        writeln!(writer, "MODULE {} {} {} {}", os, cpu, debug_id, name)?;
+        writer.write_all(rest)?;
+        Ok(())
+    })?;
+    Ok((name.to_string(), debug_id.to_string()))
+}
+
+/// Store the symbols written by `write`, the size of the symbols is returned
+fn store_with<F>(
+    config: &StoreConfig,
+    name: &str,
+    debug_id: &str,
+    write: F,
+) -> common::Result<usize>
+where
+    F: FnOnce(&mut dyn std::io::Write) -> common::Result<()>,
+{
+    // The symbols are generated once for all the destinations
+    with_scratch(|data| {
+        write(data)?;
+        store_bytes(config, name, debug_id, data)?;
+        Ok(data.len())
+    })
+}
+
+fn store_bytes(
+    config: &StoreConfig,
+    name: &str,
+    debug_id: &str,
+    data: &[u8],
+) -> common::Result<()> {
+    let (foutput, store) = config.destinations(name, debug_id);
 
     if let Some(store) = store {
         fs::create_dir_all(store.parent().unwrap())?;
 
         let fo = FileOutput::Path(store);
-        let output = get_writer_for_sym(&fo);
-        object_info.dump(output)?;
+        let mut output = get_writer_for_sym(&fo);
+        output.write_all(data)?;
+        output.flush()?;
 
         info!("Store symbols at {}", fo);
     }
 
     if let Some(file) = foutput {
-        let writer = get_writer_for_sym(file);
-        object_info.dump(writer)?;
+        let mut writer = get_writer_for_sym(file);
+        writer.write_all(data)?;
+        writer.flush()?;
 
         info!("Write symbols at {}", file);
     }
     Ok(())
 }
 
+/// The scratch buffers bigger than that aren't kept for the next file
+const MAX_SCRATCH_CAPACITY: usize = 64 * 1024 * 1024;
+
+thread_local! {
+    /// The buffer where the symbols of a module are written before being stored,
+    /// it's kept between the files of a batch to avoid growing a new one for each
+    static SCRATCH: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Call `f` with the empty scratch buffer of the current thread, a nested call
+/// gets a new buffer.
+fn with_scratch<R>(f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    let mut buf = SCRATCH.with(|scratch| std::mem::take(&mut *scratch.borrow_mut()));
+    buf.clear();
+    let res = f(&mut buf);
+    if buf.capacity() <= MAX_SCRATCH_CAPACITY {
+        SCRATCH.with(|scratch| {
+            let mut scratch = scratch.borrow_mut();
+            if buf.capacity() > scratch.capacity() {
+                *scratch = buf;
+            }
+        });
+    }
+    res
+}
+
+/// Get the cache and the debug id of the given file when the cache is enabled
+fn get_sym_cache(
+    config: &Config,
+    buf: &[u8],
+    arch: Arch,
+) -> common::Result<Option<(SymCache, String)>> {
+    let dir = match config.sym_cache {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+    let debug_id = match sym_cache::peek_debug_id(buf, arch) {
+        Some(debug_id) => debug_id,
+        None => return Ok(None),
+    };
+    let cache = SymCache::new(if dir.is_empty() {
+        None
+    } else {
+        Some(Path::new(dir))
+    })?;
+
+    Ok(Some((cache, debug_id)))
+}
+
+/// Get the key of the parsed objects in the cache: everything which could change
+/// the collected data must be in the key.
+fn get_object_key(config: &Config, path: &Path) -> common::Result<String> {
+    let mut options = vec![
+        config.arch.to_string(),
+        format!("{:?}", config.collect_options()),
+    ];
+    let map_path = path.with_extension("map");
+    if map_path.is_file() {
+        options.push(String::from_utf8_lossy(&utils::read(map_path)?).into_owned());
+    }
+
+    Ok(SymCache::get_key(options))
+}
+
+/// Get the key of the symbol files in the cache: the object key and everything
+/// which could change the generated file.
+fn get_sym_key(config: &Config, object_key: &str) -> common::Result<String> {
+    let mut options = vec![
+        object_key.to_string(),
+        format!("legacy_order={}", config.legacy_order),
+        format!("git_info={}", config.git_info),
+        format!("prune_lines={:?}", config.prune_lines),
+        config
+            .source_url_template
+            .as_ref()
+            .map_or("", |t| t.as_str())
+            .to_string(),
+    ];
+    for values in [
+        &config.mapping_var,
+        &config.mapping_src,
+        &config.mapping_dest,
+    ] {
+        options.push(values.as_ref().map(|v| v.join("\0")).unwrap_or_default());
+    }
+    if let Some(mapping_file) = config.mapping_file {
+        options.push(String::from_utf8_lossy(&utils::read(mapping_file)?).into_owned());
+    }
+
+    Ok(SymCache::get_key(options))
+}
+
+/// Get the name of the module the symbols are stored with: the name of the pdb
+/// file for a PE file, unless it's overridden
+fn get_module_name(config: &Config, buf: &[u8], arch: Arch, filename: &str) -> String {
+    config
+        .module_name
+        .map(ToOwned::to_owned)
+        .or_else(|| sym_cache::peek_pdb_name(buf, arch))
+        .unwrap_or_else(|| filename.to_string())
+}
+
 #[cfg(feature = "http")]
 fn get_from_id(
     config: &Config,
     path: &Path,
     filename: String,
-) -> common::Result<(Vec<u8>, String)> {
+) -> common::Result<(ByteView<'static>, String)> {
     if let Some(id) = config.debug_id.or(config.code_id) {
         let symbol_server = crate::cache::get_sym_servers(config.symbol_server);
         let (buf, filename) = crate::cache::search_file(filename, id, symbol_server.as_ref());
         return if let Some(buf) = buf {
             Ok((buf, filename))
         } else {
-            anyhow::bail!("Impossible to get file {} with id {}", filename, id)
+            Err(common::failure(
+                FailureKind::Network,
+                format!("Impossible to get file {} with id {}", filename, id),
+            ))
         };
     }
 
-    Ok((utils::read_file(path), filename))
+    Ok((utils::read_file(path)?, filename))
+}
+
+/// Get the symbols already published on a symbol server if any
+#[cfg(feature = "http")]
+fn get_published(config: &Config, buf: &[u8], arch: Arch, filename: &str) -> Option<Vec<u8>> {
+    let servers = config.published_servers.as_ref()?;
+    if config.format != OutputFormat::Breakpad {
+        return None;
+    }
+
+    let debug_id = sym_cache::peek_debug_id(buf, arch)?;
+    let name = sym_cache::peek_pdb_name(buf, arch).unwrap_or_else(|| filename.to_string());
+    crate::cache::fetch_sym_file(servers, &name, &debug_id)
+}
+
+#[cfg(not(feature = "http"))]
+fn get_published(_config: &Config, _buf: &[u8], _arch: Arch, _filename: &str) -> Option<Vec<u8>> {
+    None
 }
 
 #[cfg(not(feature = "http"))]
@@ -245,16 +896,178 @@ fn get_from_id(
     _config: &Config,
     path: &Path,
     filename: String,
-) -> common::Result<(Vec<u8>, String)> {
-    Ok((utils::read_file(path), filename))
+) -> common::Result<(ByteView<'static>, String)> {
+    Ok((utils::read_file(path)?, filename))
 }
 
-pub fn single_file(config: &Config, filename: &str) -> common::Result<()> {
+/// Download a file from the symbol servers with its debug id or its code id.
+/// It's written in the output file, or in the current directory with its name
+/// when there's no output file.
+#[cfg(feature = "http")]
+pub fn fetch(config: &Config, filename: &str) -> common::Result<()> {
+    anyhow::ensure!(
+        config.debug_id.is_some() || config.code_id.is_some(),
+        "A debug id or a code id is required to fetch {}",
+        filename
+    );
+
     let path = Path::new(filename);
+    let (buf, filename) = get_from_id(config, path, utils::get_filename(path))?;
+    let out = match &config.output {
+        Output::File(FileOutput::Path(out)) => out.clone(),
+        _ => PathBuf::from(filename),
+    };
+    fs::write(&out, buf)?;
+    info!("Write {}", out.display());
+
+    Ok(())
+}
+
+/// Describe what would be done for a file without parsing its debug info nor
+/// writing anything: its format and debug id, the file paired with it, the
+/// outputs and whether it would be skipped.
+pub fn dry_run(config: &Config, file: &str) -> common::Result<String> {
+    use std::fmt::Write as _;
+
+    let mut out = String::new();
+    writeln!(out, "{}:", file)?;
+
+    if let Some(id) = config.debug_id.or(config.code_id) {
+        writeln!(out, "  input: from the symbol servers with id {}", id)?;
+        return Ok(out);
+    }
+
+    let path = Path::new(file);
+    let buf = utils::read(path)?;
+    let arch = Arch::from_str(config.arch)?;
+    let format = peek(&buf, true /* check for fat binary */);
+    writeln!(out, "  format: {}", format)?;
+    let supported = (format == FileFormat::Elf && cfg!(feature = "elf"))
+        || (matches!(format, FileFormat::Pdb | FileFormat::Pe) && cfg!(feature = "pdb"))
+        || (format == FileFormat::MachO && cfg!(feature = "macho"));
+    if !supported {
+        writeln!(out, "  action: fail, unsupported file format")?;
+        return Ok(out);
+    }
+
+    let debug_id = sym_cache::peek_debug_id(&buf, arch);
+    #[allow(unused_mut)]
+    let mut name = utils::get_filename(path);
+    #[cfg(feature = "pdb")]
+    match format {
+        FileFormat::Pe => match sym_cache::peek_pdb_name(&buf, arch) {
+            Some(pdb_name) => {
+                match windows::utils::get_pdb_path(path, &pdb_name).0 {
+                    Some(pdb) => writeln!(out, "  pdb: {}", pdb.display())?,
+                    None if config.symbol_server.is_some() => {
+                        writeln!(out, "  pdb: {} from the symbol servers", pdb_name)?
+                    }
+                    None => writeln!(
+                        out,
+                        "  pdb: {} not found, the PE file is dumped alone",
+                        pdb_name
+                    )?,
+                }
+                name = pdb_name;
+            }
+            None => writeln!(out, "  pdb: none")?,
+        },
+        FileFormat::Pdb => {
+            let pe = debug_id
+                .as_deref()
+                .and_then(|id| DebugId::from_breakpad(id).ok())
+                .and_then(|id| windows::utils::find_pe_for_pdb(path, &id));
+            match pe {
+                Some((pe_name, _)) => writeln!(out, "  pe: {}", pe_name)?,
+                None => writeln!(out, "  pe: none")?,
+            }
+        }
+        _ => {}
+    }
+
+    let store_config = StoreConfig::new(config);
+    let name = config.module_name.map_or(name, ToOwned::to_owned);
+    let debug_id = store_config.module_debug_id.clone().or(debug_id);
+    writeln!(out, "  module: {}", name)?;
+    writeln!(out, "  debug id: {}", debug_id.as_deref().unwrap_or("none"))?;
+
+    let (file_output, store) = store_config.destinations(&name, debug_id.as_deref().unwrap_or(""));
+    if let Some(file_output) = file_output {
+        writeln!(out, "  output: {}", file_output)?;
+    }
+    if let Some(store) = store {
+        writeln!(out, "  store: {}", store.display())?;
+    }
+
+    let breakpad = config.format == OutputFormat::Breakpad;
+    let action = match debug_id {
+        Some(id) if breakpad && config.incremental && store_config.is_up_to_date(&name, &id) => {
+            "skip, the symbols are up to date"
+        }
+        _ if breakpad && config.published_servers.is_some() => {
+            "get the published symbols if any, else dump"
+        }
+        _ => "dump",
+    };
+    writeln!(out, "  action: {}", action)?;
+
+    Ok(out)
+}
+
+pub fn single_file(config: &Config, filename: &str) -> common::Result<()> {
+    // The objects of an archive are dumped like the files of a batch
+    if ArchiveKind::of(Path::new(filename)).is_some() {
+        return several_files(config, &[filename]);
+    }
+
+    let progress = Progress::new(config.progress, 1);
+    progress.started(filename);
+    let res = dump_file(config, filename, &progress);
+    progress.finished(filename, &res);
+    res
+}
+
+fn dump_file(config: &Config, file: &str, progress: &Progress) -> common::Result<()> {
+    let path = Path::new(file);
     let filename = utils::get_filename(path);
 
-    let (buf, filename) = get_from_id(config, path, filename)?;
+    progress.phase(file, Phase::Read);
+    let (buf, filename) = match archive::split_path(file) {
+        Some((archive, entry)) => (
+            archive::read_entry(Path::new(archive), entry)?,
+            utils::get_filename(Path::new(entry)),
+        ),
+        None => get_from_id(config, path, filename)?,
+    };
+    dump_buf(config, file, buf, filename, progress)
+}
+
+/// Dump the content of a file received by another way than the file system
+/// (e.g. by the daemon), `name` is its file name.
+pub fn dump_buffer(config: &Config, name: &str, data: Vec<u8>) -> common::Result<()> {
+    let progress = Progress::new(config.progress, 1);
+    progress.started(name);
+    let res = dump_buf(
+        config,
+        name,
+        ByteView::from_vec(data),
+        name.to_string(),
+        &progress,
+    );
+    progress.finished(name, &res);
+    res
+}
 
+/// Dump the content of a file, `file` is its path or its name when it comes
+/// from a symbol server.
+fn dump_buf(
+    config: &Config,
+    file: &str,
+    buf: ByteView<'static>,
+    filename: String,
+    progress: &Progress,
+) -> common::Result<()> {
+    let path = Path::new(file);
     let path_mappings = PathMappings::new(
         &config.mapping_var,
         &config.mapping_src,
@@ -263,33 +1076,224 @@ pub fn single_file(config: &Config, filename: &str) -> common::Result<()> {
     )?
     .map(Arc::new);
     let arch = Arch::from_str(config.arch)?;
-    let object_info = get_object_info(
-        buf,
-        path,
-        &filename,
-        path_mappings,
-        arch,
-        config.symbol_server,
-        config.emit_inlines,
-    )?;
-    store(&config.output, config.check_cfi, object_info)
+    let store_config = StoreConfig::new(config);
+
+    if config.incremental && config.format == OutputFormat::Breakpad {
+        if let Some(debug_id) = sym_cache::peek_debug_id(&buf, arch) {
+            let name = get_module_name(config, &buf, arch, &filename);
+            let debug_id = store_config.module_debug_id.as_deref().unwrap_or(&debug_id);
+            if store_config.is_up_to_date(&name, debug_id) {
+                info!("Symbols for {} are up to date", filename);
+                return Ok(());
+            }
+        }
+    }
+
+    if let Some(data) = get_published(config, &buf, arch, &filename) {
+        progress.phase(file, Phase::Store);
+        return store_cached(&store_config, &data);
+    }
+
+    let (cache, debug_id) = match get_sym_cache(config, &buf, arch)? {
+        Some(sym_cache) => sym_cache,
+        None => {
+            progress.phase(file, Phase::Parse);
+            let object_info = get_object_info(
+                buf,
+                path,
+                &filename,
+                None,
+                path_mappings,
+                arch,
+                config.symbol_server,
+                config.collect_options(),
+            )?;
+            progress.phase(file, Phase::Store);
+            return store(&store_config, object_info);
+        }
+    };
+
+    let object_key = get_object_key(config, path)?;
+    let sym_key = get_sym_key(config, &object_key)?;
+    if config.format == OutputFormat::Breakpad {
+        if let Some(data) = cache.get(&debug_id, &filename, &sym_key) {
+            progress.phase(file, Phase::Store);
+            return store_cached(&store_config, &data);
+        }
+    }
+
+    // The parsed object is cached without the path mapping, so it can be reused
+    // with other output options.
+    let mut object_info = match cache.get_object(&debug_id, &filename, &object_key) {
+        Some(object_info) => object_info,
+        None => {
+            progress.phase(file, Phase::Parse);
+            let object_info = get_object_info(
+                buf,
+                path,
+                &filename,
+                None,
+                None,
+                arch,
+                config.symbol_server,
+                config.collect_options(),
+            )?;
+            // The pdb could be available the next time
+            if !object_info.is_pe_only() {
+                if let Err(e) = cache.put_object(&debug_id, &filename, &object_key, &object_info) {
+                    warn!("Unable to cache the object: {}", e);
+                }
+            }
+            object_info
+        }
+    };
+    if let Some(path_mappings) = path_mappings.as_ref() {
+        object_info.apply_mapping(path_mappings);
+    }
+
+    progress.phase(file, Phase::Store);
+    if config.format != OutputFormat::Breakpad {
+        return store(&store_config, object_info);
+    }
+
+    let start = Instant::now();
+    let warnings = metrics::warnings();
+    rewrite_files(&store_config, &mut object_info);
+    let (name, debug_id, size) = with_scratch(|data| {
+        store_config.write_symbols(&object_info, &mut *data)?;
+        // The pdb could be available the next time
+        if !object_info.is_pe_only() {
+            if let Err(e) = cache.put(&debug_id, &filename, &sym_key, data) {
+                warn!("Unable to cache the symbols: {}", e);
+            }
+        }
+        let (name, debug_id) = store_data(&store_config, data)?;
+        common::Result::Ok((name, debug_id, data.len()))
+    })?;
+    report_metrics(&store_config, start, warnings, || ModuleMetrics {
+        name,
+        debug_id,
+        size,
+        ..object_info.metrics()
+    })
+}
+
+/// Get a module of a job file: the local files are used when they exist, else
+/// the debug file is searched on the symbol servers with its debug id, then the
+/// code file with its code id. The content is only returned for the downloaded
+/// files.
+fn get_job_file(config: &Config, job: &Job) -> common::Result<(String, Option<ByteView<'static>>)> {
+    for file in job.debug_file.iter().chain(&job.code_file) {
+        if Path::new(file).is_file() {
+            return Ok((file.clone(), None));
+        }
+    }
+
+    let candidates = [
+        (&job.debug_file, &job.debug_id),
+        (&job.code_file, &job.code_id),
+    ];
+    anyhow::ensure!(
+        candidates.iter().any(|(f, id)| f.is_some() && id.is_some()),
+        "A file with its id is required"
+    );
+
+    for (file, id) in candidates.iter() {
+        if let (Some(file), Some(id)) = (file, id) {
+            if let Some((buf, name)) = search_job_file(config, file, id) {
+                return Ok((name, Some(buf)));
+            }
+        }
+    }
+
+    Err(common::failure(
+        FailureKind::Network,
+        "Impossible to get the file from the symbol servers",
+    ))
+}
+
+/// Search a file of a job file on the symbol servers, by its name only
+#[cfg(feature = "http")]
+fn search_job_file(config: &Config, file: &str, id: &str) -> Option<(ByteView<'static>, String)> {
+    let symbol_server = crate::cache::get_sym_servers(config.symbol_server);
+    let name = jobs::get_basename(file).to_string();
+    match crate::cache::search_file(name, id, symbol_server.as_ref()) {
+        (Some(buf), name) => Some((buf, name)),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn search_job_file(
+    _config: &Config,
+    _file: &str,
+    _id: &str,
+) -> Option<(ByteView<'static>, String)> {
+    None
+}
+
+/// Dump the modules of a job file in the symbol store, the failures are in the
+/// report.
+pub fn dump_jobs(config: &Config, jobs: &[Job]) -> common::Result<JobReport> {
+    if !matches!(config.output, Output::Store(_)) {
+        return Err(common::failure(
+            FailureKind::Usage,
+            "A job file is dumped in a symbol store (--store) only",
+        ));
+    }
+
+    let progress = Progress::new(config.progress, jobs.len());
+    let mut report = JobReport::default();
+    for job in jobs {
+        let (file, res) = match get_job_file(config, job) {
+            Ok((file, buf)) => {
+                progress.started(&file);
+                let res = match buf {
+                    Some(buf) => dump_buf(config, &file, buf, file.clone(), &progress),
+                    None => dump_file(config, &file, &progress),
+                };
+                (file, res)
+            }
+            Err(e) => {
+                let file = job.name();
+                progress.started(&file);
+                (file, Err(e))
+            }
+        };
+        progress.finished(&file, &res);
+        report.push(job.name(), res);
+    }
+
+    Ok(report)
 }
 
-/// Detects the object format based on the bytes in the file.
+/// Detects the object format based on the bytes in the file. `pe` is the PE
+/// file paired with a pdb file.
+#[cfg_attr(
+    not(all(feature = "pdb", feature = "elf", feature = "macho")),
+    allow(unused_variables, unreachable_code)
+)]
+#[allow(clippy::too_many_arguments)]
 fn get_object_info(
-    buf: Vec<u8>,
+    buf: ByteView<'static>,
     path: &Path,
     filename: &str,
+    pe: Option<&Path>,
     file_mapping: Option<Arc<PathMappings>>,
     arch: Arch,
     symbol_server: Option<&str>,
-    emit_inlines: bool,
+    options: CollectOptions,
 ) -> common::Result<ObjectInfo> {
-    let object_info = match peek(&buf, true /* check for fat binary */) {
+    let start = Instant::now();
+    let warnings = metrics::warnings();
+    let mut object_info: ObjectInfo = match peek(&buf, true /* check for fat binary */) {
+        #[cfg(feature = "elf")]
         FileFormat::Elf => {
-            ObjectInfo::from_elf(&buf, filename, Platform::Linux, file_mapping, emit_inlines)?
+            ObjectInfo::from_elf(&buf, path, filename, Platform::Linux, file_mapping, options)?
         }
-        FileFormat::Pdb => get_pdb_object_info(&buf, path, filename, file_mapping, emit_inlines)?,
+        #[cfg(feature = "pdb")]
+        FileFormat::Pdb => get_pdb_object_info(&buf, path, filename, pe, file_mapping, options)?,
+        #[cfg(feature = "pdb")]
         FileFormat::Pe => {
             if let Ok(pdb_info) = get_pe_pdb_object_info(
                 &buf,
@@ -297,24 +1301,92 @@ fn get_object_info(
                 filename,
                 file_mapping,
                 symbol_server,
-                emit_inlines,
+                options.clone(),
             ) {
                 pdb_info
             } else {
-                get_pe_object_info(&buf, path, filename)?
+                get_pe_object_info(&buf, path, filename, options)?
             }
         }
-        FileFormat::MachO => {
-            ObjectInfo::from_macho(&buf, filename, arch, file_mapping, emit_inlines)?
+        #[cfg(feature = "macho")]
+        FileFormat::MachO => ObjectInfo::from_macho(&buf, filename, arch, file_mapping, options)?,
+        FileFormat::Unknown => {
+            return Err(common::failure(
+                FailureKind::UnsupportedFormat,
+                "Unknown file format",
+            ))
+        }
+        format => {
+            return Err(common::failure(
+                FailureKind::UnsupportedFormat,
+                format!("Unsupported file format: {}", format),
+            ))
         }
-        _ => anyhow::bail!("Unknown file format"),
     };
+    object_info.add_parse_stats(ParseStats {
+        duration: start.elapsed(),
+        warnings: metrics::warnings() - warnings,
+    });
     Ok(object_info)
 }
 
+/// The size of the beginning of an archive entry which is enough to get its format
+const PEEK_SIZE: u64 = 64 * 1024;
+
+/// Call `f` with the path, the file name and the content of the objects of an
+/// input: a file, an entry of an archive (`archive.zip!bin/app.exe`) or all the
+/// objects of an archive. The entries are read one by one, the ones which aren't
+/// objects are skipped.
+fn for_each_object<F>(file: &str, mut f: F) -> common::Result<()>
+where
+    F: FnMut(&Path, String, ByteView<'static>) -> common::Result<()>,
+{
+    if let Some((archive, entry)) = archive::split_path(file) {
+        let buf = archive::read_entry(Path::new(archive), entry)?;
+        let filename = utils::get_filename(Path::new(entry));
+        return f(Path::new(file), filename, buf);
+    }
+
+    let path = Path::new(file);
+    if ArchiveKind::of(path).is_none() {
+        return f(path, utils::get_filename(path), utils::read_file(path)?);
+    }
+
+    let mut found = false;
+    archive::for_each_entry(path, |entry, reader| {
+        let mut buf = Vec::new();
+        reader.take(PEEK_SIZE).read_to_end(&mut buf)?;
+        if !matches!(
+            peek(&buf, true),
+            FileFormat::Elf | FileFormat::MachO | FileFormat::Pdb | FileFormat::Pe
+        ) {
+            return Ok(true);
+        }
+        let buf = archive::map_entry(&buf, reader)?;
+        found = true;
+
+        let path = format!("{}!{}", file, entry);
+        let filename = utils::get_filename(Path::new(entry));
+        f(Path::new(&path), filename, buf)?;
+        Ok(true)
+    })?;
+
+    if found {
+        Ok(())
+    } else {
+        Err(common::failure(
+            FailureKind::UnsupportedFormat,
+            format!("No object to dump in {}", file),
+        ))
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 enum JobType {
-    Get,
+    /// Read a file, with the PE file paired with it when it's a pdb file
+    Get {
+        pe: Option<String>,
+    },
     Dump(ObjectInfo),
 }
 
@@ -322,28 +1394,45 @@ struct JobItem {
     file: String,
     typ: JobType,
     mapping: Option<Arc<PathMappings>>,
-    collect_inlines: bool,
+    options: CollectOptions,
 }
 
 fn send_store_jobs(
     sender: &Sender<Option<JobItem>>,
     results: &mut HashMap<String, ObjectInfo>,
     num_threads: usize,
-    output: Output,
-    check_cfi: bool,
-    collect_inlines: bool,
+    store_config: &StoreConfig,
+    progress: &Progress,
+    options: CollectOptions,
 ) -> common::Result<()> {
-    if results.len() == 1 {
-        let (_, d) = results.drain().take(1).next().unwrap();
-        self::store(&output, check_cfi, d)?;
+    // The debug file of an ELF binary without build id has a nil debug id: it goes
+    // with the binary when it's the only other file
+    let nil = DebugId::nil().breakpad().to_string();
+    if results.len() == 2 {
+        if let Some(info) = results.remove(&nil) {
+            let (id, other) = results.drain().next().unwrap();
+            let info = ObjectInfo::merge(other, info).inspect_err(|_| {
+                poison_queue(sender, num_threads);
+            })?;
+            results.insert(id, info);
+        }
+    }
+
+    if results.len() == 1 || num_threads == 1 {
+        // The only thread can't send the jobs to itself (e.g. for the objects of
+        // an archive): the queue is bounded
+        for (_, d) in results.drain() {
+            progress.phase(d.get_name(), Phase::Store);
+            self::store(store_config, d)?;
+        }
     } else {
         for (_, d) in results.drain() {
             sender
                 .send(Some(JobItem {
-                    file: "".to_string(),
+                    file: d.get_name().to_string(),
                     typ: JobType::Dump(d),
                     mapping: None,
-                    collect_inlines,
+                    options: options.clone(),
                 }))
                 .unwrap();
         }
@@ -367,44 +1456,80 @@ fn consumer(
     receiver: Receiver<Option<JobItem>>,
     results: Arc<Mutex<HashMap<String, ObjectInfo>>>,
     counter: Arc<AtomicUsize>,
+    inputs: Arc<AtomicUsize>,
     num_threads: usize,
-    output: Output,
-    check_cfi: bool,
-) -> common::Result<()> {
+    store_config: StoreConfig,
+    progress: Progress,
+) -> common::Result<Vec<anyhow::Error>> {
+    // The files which can't be read: the other ones are still dumped
+    let mut failures = Vec::new();
     while let Ok(job) = receiver.recv() {
         if job.is_none() {
-            return Ok(());
+            return Ok(failures);
         }
 
         let JobItem {
             file,
             typ,
             mapping,
-            collect_inlines,
+            options,
         } = job.unwrap();
 
         match typ {
-            JobType::Get => {
-                let path = PathBuf::from(file);
-                let filename = utils::get_filename(&path);
-                let buf = utils::read_file(&path);
-
-                let info =
-                    get_object_info(buf, &path, &filename, mapping, arch, None, collect_inlines)?;
-
-                let mut results = results.lock().unwrap();
-                let info = if let Some(prev) = results.remove(info.get_debug_id()) {
-                    ObjectInfo::merge(info, prev).map_err(|e| {
-                        poison_queue(&sender, num_threads);
-                        e
-                    })?
-                } else {
-                    info
+            JobType::Get { pe } => {
+                progress.started(&file);
+                progress.phase(&file, Phase::Read);
+                // A failure to merge two objects stops everything
+                let mut fatal = None;
+                let failed = failures.len();
+                let res = for_each_object(&file, |path, filename, buf| {
+                    inputs.fetch_add(1, Ordering::SeqCst);
+                    progress.phase(&file, Phase::Parse);
+                    let res = get_object_info(
+                        buf,
+                        path,
+                        &filename,
+                        pe.as_deref().map(Path::new),
+                        mapping.clone(),
+                        arch,
+                        None,
+                        options.clone(),
+                    );
+                    let info = match res {
+                        Ok(info) => info,
+                        Err(e) => {
+                            failures.push(e.context(format!("Cannot dump {}", path.display())));
+                            return Ok(());
+                        }
+                    };
+                    let mut results = results.lock().unwrap();
+                    let info = match results.remove(info.get_debug_id()) {
+                        Some(prev) => ObjectInfo::merge(info, prev).map_err(|e| {
+                            poison_queue(&sender, num_threads);
+                            fatal = Some(e);
+                            anyhow::anyhow!("Cannot merge {}", path.display())
+                        })?,
+                        None => info,
+                    };
+                    results.insert(info.get_debug_id().to_string(), info);
+                    Ok(())
+                });
+                if let Some(e) = fatal {
+                    return Err(e);
+                }
+                if let Err(e) = res {
+                    inputs.fetch_add(1, Ordering::SeqCst);
+                    failures.push(e.context(format!("Cannot dump {}", file)));
+                }
+                let res = match failures[failed..].last() {
+                    Some(e) => Err(anyhow::anyhow!("{:#}", e)),
+                    None => Ok(()),
                 };
-                results.insert(info.get_debug_id().to_string(), info);
+                progress.finished(&file, &res);
             }
             JobType::Dump(d) => {
-                self::store(&output, check_cfi, d)?;
+                progress.phase(&file, Phase::Store);
+                self::store(&store_config, d)?;
                 continue;
             }
         }
@@ -417,15 +1542,53 @@ fn consumer(
                 &sender,
                 &mut results,
                 num_threads,
-                output.clone(),
-                check_cfi,
-                collect_inlines,
+                &store_config,
+                &progress,
+                options,
             )?;
         } else {
             counter.fetch_sub(1, Ordering::SeqCst);
         }
     }
 
+    Ok(failures)
+}
+
+#[cfg(not(feature = "pdb"))]
+fn pair_pe_pdb<'a>(_files: &[&'a str]) -> HashMap<&'a str, &'a str> {
+    HashMap::default()
+}
+
+#[cfg(feature = "http")]
+fn get_symbol_server_caches(config: &Config) -> Vec<PathBuf> {
+    crate::cache::get_sym_servers(config.symbol_server)
+        .map(|servers| crate::cache::get_cache_dirs(&servers))
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "http"))]
+fn get_symbol_server_caches(_config: &Config) -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// Evict the files from the symbols cache and from the caches of the symbol
+/// servers according to the cache limits. Without limits, only the symbols cache
+/// is emptied: the caches of the symbol servers can be shared with other tools.
+pub fn clean_caches(config: &Config) -> common::Result<()> {
+    let mut dirs = if config.cache_limits.is_empty() {
+        Vec::new()
+    } else {
+        get_symbol_server_caches(config)
+    };
+    let sym_cache = config
+        .sym_cache
+        .filter(|dir| !dir.is_empty())
+        .map(Path::new);
+    dirs.push(SymCache::new(sym_cache)?.dir().to_path_buf());
+
+    for dir in dirs {
+        sym_cache::clean_dir(&dir, &config.cache_limits)?;
+    }
     Ok(())
 }
 
@@ -438,9 +1601,19 @@ pub fn several_files(config: &Config, filenames: &[&str]) -> common::Result<()>
     )?
     .map(Arc::new);
     let arch = Arch::from_str(config.arch)?;
+    // The PE files paired with a pdb file are read with it
+    let pairs = pair_pe_pdb(filenames);
+    let filenames: Vec<_> = filenames
+        .iter()
+        .copied()
+        .filter(|f| !pairs.values().any(|pe| pe == f))
+        .collect();
     let results = Arc::new(Mutex::new(HashMap::default()));
     let num_jobs = config.num_jobs.min(filenames.len());
     let counter = Arc::new(AtomicUsize::new(filenames.len()));
+    // The objects read from the files: an archive can contain several ones
+    let inputs = Arc::new(AtomicUsize::new(0));
+    let progress = Progress::new(config.progress, filenames.len());
 
     let (sender, receiver) = bounded(num_jobs + 1);
 
@@ -450,15 +1623,23 @@ pub fn several_files(config: &Config, filenames: &[&str]) -> common::Result<()>
         let receiver = receiver.clone();
         let results = Arc::clone(&results);
         let counter = Arc::clone(&counter);
-        let output = config.output.clone();
-
-        let check_cfi = config.check_cfi;
+        let inputs = Arc::clone(&inputs);
+        let store_config = StoreConfig::new(config);
+        let progress = progress.clone();
 
         let t = thread::Builder::new()
             .name(format!("dump-syms {}", i))
             .spawn(move || {
                 consumer(
-                    arch, sender, receiver, results, counter, num_jobs, output, check_cfi,
+                    arch,
+                    sender,
+                    receiver,
+                    results,
+                    counter,
+                    inputs,
+                    num_jobs,
+                    store_config,
+                    progress,
                 )
             })
             .unwrap();
@@ -470,18 +1651,43 @@ pub fn several_files(config: &Config, filenames: &[&str]) -> common::Result<()>
         sender
             .send(Some(JobItem {
                 file: f.to_string(),
-                typ: JobType::Get,
+                typ: JobType::Get {
+                    pe: pairs.get(f).map(ToString::to_string),
+                },
                 mapping: file_mapping.as_ref().map(Arc::clone),
-                collect_inlines: config.emit_inlines,
+                options: config.collect_options(),
             }))
             .unwrap();
     }
 
+    let mut failures = Vec::new();
+    let mut error = None;
     for receiver in receivers {
-        if let Err(e) = receiver.join().unwrap() {
-            error!("{}", e);
+        match receiver.join().unwrap() {
+            Ok(f) => failures.extend(f),
+            Err(e) => {
+                error.get_or_insert(e);
+            }
         }
     }
 
+    if let Some(e) = error {
+        return Err(e);
+    }
+    let inputs = inputs.load(Ordering::SeqCst);
+    if failures.len() == inputs {
+        // Nothing has been dumped: the first failure is the relevant one
+        return Err(failures.remove(0));
+    }
+    if !failures.is_empty() {
+        for e in &failures {
+            error!("{:#}", e);
+        }
+        return Err(common::failure(
+            FailureKind::PartialSuccess,
+            format!("{} of {} files failed", failures.len(), inputs),
+        ));
+    }
+
     Ok(())
 }
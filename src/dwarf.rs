@@ -0,0 +1,824 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use log::warn;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use symbolic::debuginfo::dwarf::gimli::{self, EndianSlice};
+use symbolic::debuginfo::dwarf::Dwarf;
+use symbolic::debuginfo::Object;
+
+use crate::common;
+use crate::line::Lines;
+
+/// The flags of the rows of the DWARF line programs which aren't exposed by symbolic
+#[derive(Debug, Default)]
+pub struct LineFlags {
+    /// Whether the row at an address is a recommended breakpoint location (is_stmt)
+    stmts: HashMap<u32, bool>,
+    /// The addresses where the function prologues end (prologue_end)
+    prologue_ends: BTreeSet<u32>,
+}
+
+impl LineFlags {
+    fn is_stmt(&self, rva: u32) -> bool {
+        self.stmts.get(&rva).copied().unwrap_or(true)
+    }
+
+    fn get_prologue_end(&self, range: &Range<u32>) -> Option<u32> {
+        self.prologue_ends
+            .range(range.start + 1..range.end)
+            .next()
+            .copied()
+    }
+
+    /// Drop the lines which don't begin a statement, the code there belongs to
+    /// the previous statement, and give the line of the function body, where the
+    /// prologue ends, to the start of the function like the debuggers do.
+    pub(crate) fn apply(&self, lines: &mut Lines, range: &Range<u32>) {
+        if lines.lines.is_empty() {
+            return;
+        }
+
+        lines.ensure_order();
+        let first = lines.lines[0].rva;
+        lines
+            .lines
+            .retain(|line| line.rva == first || self.is_stmt(line.rva));
+
+        if let Some(prologue_end) = self.get_prologue_end(range) {
+            let body = lines.lines.iter().rposition(|l| l.rva <= prologue_end);
+            if let Some(body) = body.filter(|body| *body != 0) {
+                let body = lines.lines.drain(1..=body).next_back().unwrap();
+                let start = &mut lines.lines[0];
+                start.num = body.num;
+                start.file_id = body.file_id;
+            }
+        }
+
+        lines
+            .lines
+            .dedup_by(|next, prev| (next.num, next.file_id) == (prev.num, prev.file_id));
+    }
+}
+
+/// A function name from the DWARF debug info
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DwarfName {
+    pub name: String,
+    /// Whether it's a linkage name
+    pub is_mangled: bool,
+}
+
+/// Where a function is declared, for the functions without line rows: the file is
+/// split like in the line programs so it's joined like the other source files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeclLine {
+    pub comp_dir: Vec<u8>,
+    pub dir: Vec<u8>,
+    pub name: Vec<u8>,
+    pub line: u32,
+}
+
+/// The code id of an ELF file without build id, like the Breakpad tools and
+/// crash reporters compute it: the first page of .text is XORed in 16 bytes.
+/// The debug id is computed from the same hash by symbolic.
+pub fn get_text_hash_code_id(object: &Object) -> Option<String> {
+    let elf = match object {
+        Object::Elf(elf) if elf.code_id().is_none() => elf,
+        _ => return None,
+    };
+    // In a file made with objcopy --only-keep-debug, .text has no data (NOBITS):
+    // the id must come from the binary
+    let section = elf.raw_section("text")?;
+    let mut hash = [0u8; 16];
+    for (i, b) in section.data.iter().take(4096).enumerate() {
+        hash[i % 16] ^= b;
+    }
+    Some(hash.iter().map(|b| format!("{:02X}", b)).collect())
+}
+
+/// Get the path and the build id of the supplementary file of a file processed
+/// with dwz (.gnu_debugaltlink): the debug info shared by several files is moved there.
+fn get_debugaltlink(object: &Object) -> Option<(PathBuf, Vec<u8>)> {
+    let section = match object {
+        Object::Elf(elf) => elf.section("gnu_debugaltlink")?,
+        _ => return None,
+    };
+    // A null-terminated path followed by the build id
+    let data = &section.data;
+    let end = data.iter().position(|b| *b == 0)?;
+    let path = String::from_utf8_lossy(&data[..end]).into_owned();
+    let build_id = data[end + 1..].to_vec();
+    if path.is_empty() || build_id.is_empty() {
+        return None;
+    }
+    Some((PathBuf::from(path), build_id))
+}
+
+/// Find and read the supplementary file of the ELF file at `path`.
+///
+/// The path in .gnu_debugaltlink is absolute in the distro packages
+/// (/usr/lib/debug/.dwz/...) or relative to the file, else the file is looked
+/// for next to it and in the build-id directory. A candidate is only used when
+/// its build id is the expected one.
+pub fn find_supplementary_file(object: &Object, path: &Path) -> Option<Vec<u8>> {
+    let (alt_path, build_id) = get_debugaltlink(object)?;
+    let build_id: String = build_id.iter().map(|b| format!("{:02x}", b)).collect();
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut candidates = vec![dir.join(&alt_path)];
+    if let Some(file_name) = alt_path.file_name() {
+        candidates.push(dir.join(file_name));
+    }
+    if build_id.len() > 2 {
+        candidates.push(
+            Path::new("/usr/lib/debug/.build-id")
+                .join(&build_id[..2])
+                .join(format!("{}.debug", &build_id[2..])),
+        );
+    }
+
+    candidates.into_iter().find_map(|candidate| {
+        let buf = std::fs::read(&candidate).ok()?;
+        let matches = Object::parse(&buf)
+            .ok()?
+            .code_id()
+            .is_some_and(|id| id.as_str() == build_id);
+        if matches {
+            Some(buf)
+        } else {
+            warn!(
+                "The supplementary file {} doesn't have the expected build id",
+                candidate.display()
+            );
+            None
+        }
+    })
+}
+
+/// Call `f` with the DWARF sections of an ELF or Mach-O file, the ones of the
+/// supplementary file are attached to them.
+fn with_dwarf<T, F>(object: &Object, sup: Option<&Object>, f: F) -> common::Result<Option<T>>
+where
+    F: FnOnce(&gimli::Dwarf<Slice>, u64) -> common::Result<T>,
+{
+    fn load_section<'d, D: Dwarf<'d>>(
+        object: &D,
+        id: gimli::SectionId,
+    ) -> Result<Cow<'d, [u8]>, gimli::Error> {
+        let name = id.name().trim_start_matches('.');
+        Ok(object
+            .section(name)
+            .map_or(Cow::Borrowed(&[][..]), |s| s.data))
+    }
+
+    let (mut sections, endian) = match object {
+        Object::Elf(elf) => (
+            gimli::Dwarf::load(|id| load_section(elf, id))?,
+            elf.endianity(),
+        ),
+        Object::MachO(macho) => (
+            gimli::Dwarf::load(|id| load_section(macho, id))?,
+            macho.endianity(),
+        ),
+        _ => return Ok(None),
+    };
+    if let Some(Object::Elf(sup)) = sup {
+        sections.load_sup(|id| load_section(sup, id))?;
+    }
+    let dwarf = sections.borrow(|section| EndianSlice::new(section, endian));
+
+    f(&dwarf, object.load_address()).map(Some)
+}
+
+/// Get the flags of the line program rows of an ELF or Mach-O file
+pub fn get_line_flags(object: &Object) -> common::Result<LineFlags> {
+    let flags = with_dwarf(object, None, |dwarf, load_address| {
+        let mut flags = LineFlags::default();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let program = match unit.line_program {
+                Some(program) => program,
+                None => continue,
+            };
+
+            let mut rows = program.rows();
+            while let Some((_, row)) = rows.next_row()? {
+                // Like in symbolic, the rows at address 0 are skipped
+                if row.end_sequence() || row.address() == 0 || row.address() < load_address {
+                    continue;
+                }
+                let rva = (row.address() - load_address) as u32;
+                // gcc emits several rows at the same address (with location views) and
+                // only the first one is a statement
+                *flags.stmts.entry(rva).or_default() |= row.is_stmt();
+                if row.prologue_end() {
+                    flags.prologue_ends.insert(rva);
+                }
+            }
+        }
+        Ok(flags)
+    })?;
+
+    Ok(flags.unwrap_or_default())
+}
+
+/// What the DWARF debug info gives about the functions, by rva
+#[derive(Debug, Default)]
+pub struct DwarfFunctions {
+    pub names: HashMap<u32, DwarfName>,
+    pub decls: HashMap<u32, DeclLine>,
+}
+
+/// Get the names and the declarations of the functions from the DWARF debug info.
+///
+/// The name of an out-of-line definition or of a concrete instance is often only
+/// in the declaration or in the abstract instance it refers to (DW_AT_specification
+/// and DW_AT_abstract_origin), maybe in another compilation unit: the references
+/// are followed and a linkage name is preferred wherever it is in the chain.
+/// The DW_AT_decl_file and DW_AT_decl_line attributes are found the same way.
+///
+/// With dwz, the references and the strings can be in the supplementary file
+/// (DW_FORM_GNU_ref_alt and DW_FORM_GNU_strp_alt).
+pub fn get_functions(object: &Object, sup: Option<&Object>) -> common::Result<DwarfFunctions> {
+    let functions = with_dwarf(object, sup, |dwarf, load_address| {
+        let resolver = NameResolver::new(dwarf)?;
+        let mut functions = DwarfFunctions::default();
+        for (i, unit) in resolver.units[..resolver.sup_start].iter().enumerate() {
+            let mut entries = unit.entries();
+            while let Some((_, entry)) = entries.next_dfs()? {
+                if entry.tag() != gimli::DW_TAG_subprogram {
+                    continue;
+                }
+
+                let mut starts = Vec::new();
+                let mut ranges = dwarf.die_ranges(unit, entry)?;
+                while let Some(range) = ranges.next()? {
+                    if range.begin != 0 && range.begin < range.end && range.begin >= load_address {
+                        starts.push((range.begin - load_address) as u32);
+                    }
+                }
+                if starts.is_empty() {
+                    continue;
+                }
+
+                if let Some(name) = resolver.resolve(i, entry.offset(), 0)? {
+                    for start in starts.iter() {
+                        functions
+                            .names
+                            .entry(*start)
+                            .or_insert_with(|| name.clone());
+                    }
+                }
+                if let Some(decl) = resolver.get_decl(i, entry.offset())? {
+                    for start in starts {
+                        functions.decls.entry(start).or_insert_with(|| decl.clone());
+                    }
+                }
+            }
+        }
+        Ok(functions)
+    })?;
+
+    Ok(functions.unwrap_or_default())
+}
+
+type Slice<'d> = EndianSlice<'d, gimli::RunTimeEndian>;
+
+struct NameResolver<'a, 'd> {
+    dwarf: &'a gimli::Dwarf<Slice<'d>>,
+    /// The units sorted by offset in .debug_info, followed by the ones of the
+    /// supplementary file
+    units: Vec<gimli::Unit<Slice<'d>>>,
+    /// The index of the first unit of the supplementary file
+    sup_start: usize,
+    /// For each unit, the parent of the entries in a namespace or in a type
+    scopes: Vec<HashMap<gimli::UnitOffset, gimli::UnitOffset>>,
+    /// The types defined in the type units, by signature
+    types: HashMap<gimli::DebugTypeSignature, (usize, gimli::UnitOffset)>,
+}
+
+impl<'a, 'd> NameResolver<'a, 'd> {
+    // Avoid to loop forever on a reference cycle
+    const MAX_DEPTH: usize = 16;
+
+    fn new(dwarf: &'a gimli::Dwarf<Slice<'d>>) -> common::Result<Self> {
+        let mut units = Vec::new();
+        let mut headers = dwarf.units();
+        while let Some(header) = headers.next()? {
+            units.push(dwarf.unit(header)?);
+        }
+        // The DWARF 4 type units are in .debug_types, they're put after the others
+        let mut headers = dwarf.type_units();
+        while let Some(header) = headers.next()? {
+            units.push(dwarf.unit(header)?);
+        }
+        units.sort_by_key(|u| match u.header.offset().as_debug_info_offset() {
+            Some(offset) => (false, offset.0),
+            None => (true, 0),
+        });
+
+        let sup_start = units.len();
+        if let Some(sup) = dwarf.sup() {
+            let mut headers = sup.units();
+            while let Some(header) = headers.next()? {
+                units.push(sup.unit(header)?);
+            }
+        }
+
+        let scopes = units
+            .iter()
+            .map(Self::get_scopes)
+            .collect::<common::Result<_>>()?;
+
+        let mut types = HashMap::new();
+        for (i, unit) in units[..sup_start].iter().enumerate() {
+            match unit.header.type_() {
+                gimli::UnitType::Type {
+                    type_signature,
+                    type_offset,
+                }
+                | gimli::UnitType::SplitType {
+                    type_signature,
+                    type_offset,
+                } => {
+                    types.insert(type_signature, (i, type_offset));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            dwarf,
+            units,
+            sup_start,
+            scopes,
+            types,
+        })
+    }
+
+    /// Get the sections of a unit (the ones of the main or of the supplementary file)
+    fn dwarf_of(&self, unit: usize) -> &'a gimli::Dwarf<Slice<'d>> {
+        match self.dwarf.sup() {
+            Some(sup) if unit >= self.sup_start => sup,
+            _ => self.dwarf,
+        }
+    }
+
+    /// Get a string attribute, the string is missing when it's in a supplementary
+    /// file which isn't there.
+    fn get_string(&self, unit: usize, value: gimli::AttributeValue<Slice<'d>>) -> Option<String> {
+        self.dwarf_of(unit)
+            .attr_string(&self.units[unit], value)
+            .ok()
+            .map(|s| s.to_string_lossy().into_owned())
+    }
+
+    fn is_scope(tag: gimli::DwTag) -> bool {
+        matches!(
+            tag,
+            gimli::DW_TAG_namespace
+                | gimli::DW_TAG_class_type
+                | gimli::DW_TAG_structure_type
+                | gimli::DW_TAG_union_type
+                | gimli::DW_TAG_interface_type
+        )
+    }
+
+    fn get_scopes(
+        unit: &gimli::Unit<Slice<'d>>,
+    ) -> common::Result<HashMap<gimli::UnitOffset, gimli::UnitOffset>> {
+        let mut scopes = HashMap::new();
+        // The ancestors of the current entry, with their tag
+        let mut stack: Vec<(gimli::UnitOffset, gimli::DwTag)> = Vec::new();
+        let mut entries = unit.entries();
+        while let Some((delta, entry)) = entries.next_dfs()? {
+            // delta is the depth of this entry relative to the previous one
+            let depth = stack.len() as isize + delta - 1;
+            stack.truncate(depth.max(0) as usize);
+            if let Some((parent, tag)) = stack.last() {
+                if Self::is_scope(*tag) {
+                    scopes.insert(entry.offset(), *parent);
+                }
+            }
+            stack.push((entry.offset(), entry.tag()));
+        }
+        Ok(scopes)
+    }
+
+    /// Follow the DW_AT_abstract_origin, DW_AT_specification and DW_AT_signature
+    /// references of an entry without a name, they can be in another unit
+    /// (DW_FORM_ref_addr) or in a type unit (DW_FORM_ref_sig8).
+    fn get_origin(
+        &self,
+        mut unit: usize,
+        mut offset: gimli::UnitOffset,
+    ) -> common::Result<(usize, gimli::UnitOffset)> {
+        for _ in 0..Self::MAX_DEPTH {
+            let entry = self.units[unit].entry(offset)?;
+            if entry.attr(gimli::DW_AT_name)?.is_some() {
+                break;
+            }
+            // A type declaration can refer to its definition in a type unit
+            let mut reference = None;
+            for attr in [
+                gimli::DW_AT_abstract_origin,
+                gimli::DW_AT_specification,
+                gimli::DW_AT_signature,
+            ] {
+                reference = reference.or(entry.attr_value(attr)?);
+            }
+            match reference.and_then(|value| self.get_reference(unit, value)) {
+                Some(origin) => (unit, offset) = origin,
+                None => break,
+            }
+        }
+        Ok((unit, offset))
+    }
+
+    /// Get the qualification (`ns::Class`) of an entry from its parents
+    fn get_scope_name(
+        &self,
+        unit: usize,
+        offset: gimli::UnitOffset,
+        depth: usize,
+    ) -> common::Result<Option<String>> {
+        if depth > Self::MAX_DEPTH {
+            return Ok(None);
+        }
+        let parent = match self.scopes[unit].get(&offset) {
+            Some(parent) => *parent,
+            None => {
+                // A definition outside of its scope (e.g. a class in a type unit)
+                // is qualified like its declaration
+                let entry = self.units[unit].entry(offset)?;
+                let declaration = entry
+                    .attr_value(gimli::DW_AT_specification)?
+                    .and_then(|value| self.get_reference(unit, value));
+                return match declaration {
+                    Some((unit, offset)) => self.get_scope_name(unit, offset, depth + 1),
+                    None => Ok(None),
+                };
+            }
+        };
+
+        // With LTO, the namespaces of the final units refer to the ones of the
+        // early debug info
+        let (unit, parent) = self.get_origin(unit, parent)?;
+        let dw_unit = &self.units[unit];
+        let entry = dw_unit.entry(parent)?;
+        let name = match entry.attr_value(gimli::DW_AT_name)? {
+            Some(name) => match self.get_string(unit, name) {
+                Some(name) => name,
+                None => return Ok(None),
+            },
+            None if entry.tag() == gimli::DW_TAG_namespace => "(anonymous namespace)".to_string(),
+            None => return Ok(None),
+        };
+
+        Ok(Some(
+            match self.get_scope_name(unit, parent, depth + 1)? {
+                Some(scope) => format!("{}::{}", scope, name),
+                None => name,
+            },
+        ))
+    }
+
+    /// Find the unit containing an entry referenced with DW_FORM_ref_addr,
+    /// in the main or in the supplementary file
+    fn find_unit(
+        &self,
+        offset: gimli::DebugInfoOffset,
+        sup: bool,
+    ) -> Option<(usize, gimli::UnitOffset)> {
+        let (base, units) = if sup {
+            (self.sup_start, &self.units[self.sup_start..])
+        } else {
+            (0, &self.units[..self.sup_start])
+        };
+        let i = base
+            + units
+                .partition_point(|u| {
+                    u.header
+                        .offset()
+                        .as_debug_info_offset()
+                        .is_some_and(|o| o <= offset)
+                })
+                .checked_sub(1)?;
+        let offset = offset.to_unit_offset(&self.units[i].header)?;
+        Some((i, offset))
+    }
+
+    fn get_reference(
+        &self,
+        unit: usize,
+        value: gimli::AttributeValue<Slice<'d>>,
+    ) -> Option<(usize, gimli::UnitOffset)> {
+        match value {
+            gimli::AttributeValue::UnitRef(offset) => Some((unit, offset)),
+            gimli::AttributeValue::DebugInfoRef(offset) => {
+                self.find_unit(offset, unit >= self.sup_start)
+            }
+            // DW_FORM_GNU_ref_alt
+            gimli::AttributeValue::DebugInfoRefSup(offset) => self.find_unit(offset, true),
+            // DW_FORM_ref_sig8
+            gimli::AttributeValue::DebugTypesRef(signature) => self.types.get(&signature).copied(),
+            _ => None,
+        }
+    }
+
+    /// Get the path of a file of the line program of a unit
+    fn get_file(&self, unit: usize, index: u64) -> Option<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        let dwarf = self.dwarf_of(unit);
+        let dw_unit = &self.units[unit];
+        let header = dw_unit.line_program.as_ref()?.header();
+        let file = header.file(index)?;
+        let name = dwarf.attr_string(dw_unit, file.path_name()).ok()?;
+        let dir = file
+            .directory(header)
+            .and_then(|dir| dwarf.attr_string(dw_unit, dir).ok());
+        Some((
+            dw_unit.comp_dir.map_or_else(Vec::new, |dir| dir.to_vec()),
+            dir.map_or_else(Vec::new, |dir| dir.to_vec()),
+            name.to_vec(),
+        ))
+    }
+
+    /// Get the declaration of an entry, a definition can refer to its declaration
+    /// or to its abstract instance for it (DW_AT_specification or DW_AT_abstract_origin)
+    fn get_decl(
+        &self,
+        mut unit: usize,
+        mut offset: gimli::UnitOffset,
+    ) -> common::Result<Option<DeclLine>> {
+        for _ in 0..Self::MAX_DEPTH {
+            let entry = self.units[unit].entry(offset)?;
+            let file = match entry.attr_value(gimli::DW_AT_decl_file)? {
+                Some(gimli::AttributeValue::FileIndex(index)) => Some(index),
+                Some(value) => value.udata_value(),
+                None => None,
+            };
+            let line = entry
+                .attr_value(gimli::DW_AT_decl_line)?
+                .and_then(|value| value.udata_value())
+                .filter(|line| *line != 0);
+            if let (Some(file), Some(line)) = (file, line) {
+                return Ok(self
+                    .get_file(unit, file)
+                    .map(|(comp_dir, dir, name)| DeclLine {
+                        comp_dir,
+                        dir,
+                        name,
+                        line: line as u32,
+                    }));
+            }
+
+            let mut reference = None;
+            for attr in [gimli::DW_AT_specification, gimli::DW_AT_abstract_origin] {
+                reference = reference.or(entry.attr_value(attr)?);
+            }
+            match reference.and_then(|value| self.get_reference(unit, value)) {
+                Some(origin) => (unit, offset) = origin,
+                None => break,
+            }
+        }
+        Ok(None)
+    }
+
+    fn resolve(
+        &self,
+        unit: usize,
+        offset: gimli::UnitOffset,
+        depth: usize,
+    ) -> common::Result<Option<DwarfName>> {
+        if depth > Self::MAX_DEPTH {
+            return Ok(None);
+        }
+
+        let dw_unit = &self.units[unit];
+        let entry = dw_unit.entry(offset)?;
+        let mut name = None;
+        let mut reference = None;
+        let mut attrs = entry.attrs();
+        while let Some(attr) = attrs.next()? {
+            match attr.name() {
+                gimli::DW_AT_linkage_name | gimli::DW_AT_MIPS_linkage_name => {
+                    if let Some(name) = self.get_string(unit, attr.value()) {
+                        return Ok(Some(DwarfName {
+                            name,
+                            is_mangled: true,
+                        }));
+                    }
+                }
+                gimli::DW_AT_name => {
+                    name = self.get_string(unit, attr.value());
+                }
+                gimli::DW_AT_specification | gimli::DW_AT_abstract_origin => {
+                    reference = self.get_reference(unit, attr.value());
+                }
+                _ => {}
+            }
+        }
+
+        let referenced = match reference {
+            Some((unit, offset)) => self.resolve(unit, offset, depth + 1)?,
+            None => None,
+        };
+
+        // Without a linkage name, the name is qualified with the namespaces and
+        // the types containing the entry, which is the declaration for a definition
+        // outside of its class.
+        let scope = match name {
+            Some(_) => self.get_scope_name(unit, offset, 0)?,
+            None => None,
+        };
+
+        Ok(match (referenced, name, scope) {
+            (Some(referenced), _, _) if referenced.is_mangled => Some(referenced),
+            (_, Some(name), Some(scope)) => Some(DwarfName {
+                name: format!("{}::{}", scope, name),
+                is_mangled: false,
+            }),
+            (Some(referenced), _, _) => Some(referenced),
+            (None, name, _) => name.map(|name| DwarfName {
+                name,
+                is_mangled: false,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_names() {
+        let data = std::fs::read("./test_data/linux/basic.full").unwrap();
+        let object = Object::parse(&data).unwrap();
+        let names = get_functions(&object, None).unwrap().names;
+
+        assert_eq!(
+            names.get(&0x11e4),
+            Some(&DwarfName {
+                name: "_Z3fooi".to_string(),
+                is_mangled: true
+            })
+        );
+        assert_eq!(
+            names.get(&0x12bd),
+            Some(&DwarfName {
+                name: "main".to_string(),
+                is_mangled: false
+            })
+        );
+    }
+
+    #[test]
+    fn test_decl_lines() {
+        let data = std::fs::read("./test_data/linux/decl.full").unwrap();
+        let object = Object::parse(&data).unwrap();
+        let decls = get_functions(&object, None).unwrap().decls;
+
+        let nolines = &decls[&0x1139];
+        assert_eq!(nolines.name, b"decl.c");
+        assert_eq!(nolines.line, 3);
+        assert_eq!(decls[&0x113e].line, 7);
+    }
+
+    #[test]
+    fn test_scoped_function_names() {
+        let data = std::fs::read("./test_data/linux/names.full").unwrap();
+        let object = Object::parse(&data).unwrap();
+        let mut names: Vec<_> = get_functions(&object, None)
+            .unwrap()
+            .names
+            .into_values()
+            .map(|n| n.name)
+            .collect();
+        names.sort();
+
+        assert_eq!(
+            names,
+            vec![
+                "main",
+                "outer::(anonymous namespace)::Local::method",
+                "outer::inner::c_function"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lto_function_names() {
+        let data = std::fs::read("./test_data/linux/lto.full").unwrap();
+        let object = Object::parse(&data).unwrap();
+        let names = get_functions(&object, None).unwrap().names;
+
+        // The concrete instances refer to the early debug info with DW_FORM_ref_addr
+        assert_eq!(
+            names.get(&0x1150).map(|n| n.name.as_str()),
+            Some("_ZNK2ns1B5twiceEv")
+        );
+        assert_eq!(
+            names.get(&0x1170).map(|n| n.name.as_str()),
+            Some("ns::c_callme")
+        );
+    }
+
+    #[test]
+    fn test_type_units() {
+        for file in &["types4.full", "types5.full"] {
+            let data = std::fs::read(format!("./test_data/linux/{}", file)).unwrap();
+            let object = Object::parse(&data).unwrap();
+
+            // The declaration of the class in the compilation unit refers to its
+            // definition in a type unit
+            let (name, scope) = with_dwarf(&object, None, |dwarf, _| {
+                let resolver = NameResolver::new(dwarf)?;
+                for (i, unit) in resolver.units.iter().enumerate() {
+                    let mut entries = unit.entries();
+                    while let Some((_, entry)) = entries.next_dfs()? {
+                        let signature = match entry.attr_value(gimli::DW_AT_signature)? {
+                            Some(signature) => signature,
+                            None => continue,
+                        };
+                        let (unit, offset) = resolver.get_reference(i, signature).unwrap();
+                        let entry = resolver.units[unit].entry(offset)?;
+                        let name = entry.attr_value(gimli::DW_AT_name)?.unwrap();
+                        let name = dwarf.attr_string(&resolver.units[unit], name)?;
+                        let scope = resolver.get_scope_name(unit, offset, 0)?;
+                        return Ok((name.to_string_lossy().into_owned(), scope));
+                    }
+                }
+                panic!("No type unit reference in {}", file);
+            })
+            .unwrap()
+            .unwrap();
+
+            assert_eq!(name, "Point");
+            assert_eq!(scope.as_deref(), Some("geometry"));
+        }
+    }
+
+    #[test]
+    fn test_supplementary_file() {
+        let path = Path::new("./test_data/linux/dwz.full");
+        let data = std::fs::read(path).unwrap();
+        let object = Object::parse(&data).unwrap();
+        let sup_data = find_supplementary_file(&object, path).unwrap();
+        let sup = Object::parse(&sup_data).unwrap();
+
+        let names = get_functions(&object, Some(&sup)).unwrap().names;
+        assert_eq!(
+            names.get(&0x1000),
+            Some(&DwarfName {
+                name: "dwz::function".to_string(),
+                is_mangled: false
+            })
+        );
+
+        // The reference can't be followed without the supplementary file
+        let names = get_functions(&object, None).unwrap().names;
+        assert_eq!(names.get(&0x1000), None);
+    }
+
+    #[test]
+    fn test_supplementary_file_build_id() {
+        // The supplementary file of basic.dwz isn't there and basic.dwz itself
+        // doesn't have the expected build id
+        let path = Path::new("./test_data/linux/basic.dwz");
+        let data = std::fs::read(path).unwrap();
+        let object = Object::parse(&data).unwrap();
+
+        let (alt_path, build_id) = get_debugaltlink(&object).unwrap();
+        assert_eq!(alt_path, PathBuf::from("basic.dwz"));
+        assert_eq!(build_id.len(), 20);
+        assert!(find_supplementary_file(&object, path).is_none());
+    }
+
+    #[test]
+    fn test_line_flags() {
+        let mut flags = LineFlags::default();
+        flags.stmts.insert(0x1008, false);
+        flags.prologue_ends.insert(0x1004);
+
+        let mut lines = Lines::new();
+        // The prologue
+        lines.add_line(0x1000, 10, 0);
+        // The body
+        lines.add_line(0x1004, 11, 0);
+        lines.add_line(0x1008, 12, 0);
+        lines.add_line(0x100c, 13, 0);
+
+        flags.apply(&mut lines, &(0x1000..0x1010));
+        let lines: Vec<_> = lines.lines.iter().map(|l| (l.rva, l.num)).collect();
+        assert_eq!(lines, vec![(0x1000, 11), (0x100c, 13)]);
+    }
+}
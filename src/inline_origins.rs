@@ -48,7 +48,7 @@ impl<'a> InlineOrigins<'a> {
             Some(demangled) => demangled,
             None => {
                 let aname = name.as_str();
-                warn!("Didn't manage to demangle {:?}", name);
+                warn!(category = "demangle"; "Didn't manage to demangle {:?}", name);
                 aname.to_string()
             }
         }
@@ -75,7 +75,7 @@ pub fn merge_inline_origins(left: &mut Vec<String>, right: Vec<String>) -> Vec<u
     // Just append the two vecs to each other. We don't bother with deduplication.
     let count = right.len() as u32;
     let offset = left.len() as u32;
-    left.extend(right.into_iter());
+    left.extend(right);
     (offset..(offset + count)).collect()
 }
 
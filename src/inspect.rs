@@ -0,0 +1,86 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt::Write;
+use symbolic::debuginfo::{Archive, FileFormat};
+
+use crate::common::{self, FailureKind};
+use crate::ssqp;
+
+fn yes_no(b: bool) -> &'static str {
+    if b {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Describe the objects of a file: their format, arch, identifiers and the
+/// kinds of debug data they have.
+pub fn inspect(buf: &[u8], file_name: &str) -> common::Result<String> {
+    if Archive::peek(buf) == FileFormat::Unknown {
+        return Err(common::failure(
+            FailureKind::UnsupportedFormat,
+            format!("Unknown file format: {}", file_name),
+        ));
+    }
+
+    let archive = Archive::parse(buf)?;
+    let mut out = String::new();
+    for (i, object) in archive.objects().enumerate() {
+        let object = object?;
+        if archive.object_count() == 1 {
+            writeln!(out, "{}:", file_name)?;
+        } else {
+            writeln!(out, "{} (object {}):", file_name, i)?;
+        }
+        writeln!(out, "  format: {}", object.file_format())?;
+        writeln!(out, "  kind: {}", object.kind())?;
+        writeln!(out, "  arch: {}", object.arch().name())?;
+        writeln!(out, "  debug id: {}", object.debug_id().breakpad())?;
+        if let Some(code_id) = object.code_id() {
+            writeln!(out, "  code id: {}", code_id.as_str().to_uppercase())?;
+        }
+        for key in ssqp::get_keys(&object, file_name) {
+            writeln!(out, "  ssqp key: {}", key)?;
+        }
+        writeln!(out, "  symbols: {}", yes_no(object.has_symbols()))?;
+        writeln!(out, "  debug info: {}", yes_no(object.has_debug_info()))?;
+        writeln!(out, "  unwind info: {}", yes_no(object.has_unwind_info()))?;
+        writeln!(out, "  sources: {}", yes_no(object.has_sources()))?;
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect() {
+        let buf = std::fs::read("./test_data/linux/basic.full").unwrap();
+        let out = inspect(&buf, "basic.full").unwrap();
+        assert_eq!(
+            out,
+            "basic.full:
+  format: elf
+  kind: lib
+  arch: x86_64
+  debug id: 20AD60B0B4C68177552708AA192E77390
+  code id: B060AD20C6B47781552708AA192E7739FAC7C84A
+  ssqp key: basic.full/elf-buildid-b060ad20c6b47781552708aa192e7739fac7c84a/basic.full
+  ssqp key: _.debug/elf-buildid-sym-b060ad20c6b47781552708aa192e7739fac7c84a/_.debug
+  symbols: yes
+  debug info: yes
+  unwind info: yes
+  sources: no
+"
+        );
+
+        let e = inspect(b"MODULE Linux", "basic.sym").unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::UnsupportedFormat);
+    }
+}
@@ -0,0 +1,195 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+use crate::common::{self, FailureKind};
+use crate::utils;
+
+/// A module to dump, as recorded by the crash reporters: the files can be
+/// paths on the machine where the crash occurred, only their names are used
+/// to get them from the symbol servers.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Job {
+    #[serde(default)]
+    pub code_file: Option<String>,
+    #[serde(default)]
+    pub code_id: Option<String>,
+    #[serde(default)]
+    pub debug_file: Option<String>,
+    #[serde(default)]
+    pub debug_id: Option<String>,
+}
+
+impl Job {
+    /// The name of the entry in the report
+    pub fn name(&self) -> String {
+        match (
+            &self.debug_file,
+            &self.debug_id,
+            &self.code_file,
+            &self.code_id,
+        ) {
+            (Some(file), Some(id), _, _) | (_, _, Some(file), Some(id)) => {
+                format!("{} {}", file, id)
+            }
+            (Some(file), _, _, _) | (_, _, Some(file), _) => file.clone(),
+            _ => "<no file>".to_string(),
+        }
+    }
+}
+
+/// The file name without the directories, they can be Windows ones
+pub fn get_basename(file: &str) -> &str {
+    file.rsplit(['/', '\\']).next().unwrap_or(file)
+}
+
+/// Read a job file: a JSON array of objects or a CSV file whose first line
+/// contains the names of the columns (code_file, code_id, debug_file, debug_id).
+pub fn read_jobs<P: AsRef<Path>>(path: P) -> common::Result<Vec<Job>> {
+    let path = path.as_ref();
+    let data = String::from_utf8(utils::read(path)?)?;
+    let jobs = if data.trim_start().starts_with('[') {
+        serde_json::from_str(&data).map_err(anyhow::Error::from)
+    } else {
+        parse_csv(&data)
+    };
+    jobs.map_err(|e| {
+        common::failure(
+            FailureKind::Usage,
+            format!("Invalid job file {}: {}", path.display(), e),
+        )
+    })
+}
+
+fn parse_csv(data: &str) -> common::Result<Vec<Job>> {
+    let mut lines = data.lines().filter(|l| !l.trim().is_empty());
+    let header: Vec<_> = match lines.next() {
+        Some(header) => header.split(',').map(str::trim).collect(),
+        None => return Ok(Vec::new()),
+    };
+    for column in &header {
+        anyhow::ensure!(
+            ["code_file", "code_id", "debug_file", "debug_id"].contains(column),
+            "unknown column {:?}",
+            column
+        );
+    }
+
+    let mut jobs = Vec::new();
+    for line in lines {
+        let values: Vec<_> = line.split(',').map(str::trim).collect();
+        anyhow::ensure!(
+            values.len() == header.len(),
+            "expected {} values: {}",
+            header.len(),
+            line
+        );
+        let mut job = Job::default();
+        for (column, value) in header.iter().zip(values) {
+            let value = Some(value.to_string()).filter(|v| !v.is_empty());
+            match *column {
+                "code_file" => job.code_file = value,
+                "code_id" => job.code_id = value,
+                "debug_file" => job.debug_file = value,
+                _ => job.debug_id = value,
+            }
+        }
+        jobs.push(job);
+    }
+    Ok(jobs)
+}
+
+/// The status of each entry of a job file
+#[derive(Debug, Default)]
+pub struct JobReport {
+    entries: Vec<(String, Option<anyhow::Error>)>,
+}
+
+impl JobReport {
+    pub(crate) fn push(&mut self, name: String, res: common::Result<()>) {
+        self.entries.push((name, res.err()));
+    }
+
+    /// The number of entries which can't be dumped
+    pub fn failures(&self) -> usize {
+        self.entries.iter().filter(|(_, e)| e.is_some()).count()
+    }
+
+    /// An error when some entries can't be dumped, like a batch of files
+    pub fn result(&self) -> common::Result<()> {
+        let failures = self.failures();
+        if failures == 0 {
+            return Ok(());
+        }
+        let kind = if failures < self.entries.len() {
+            FailureKind::PartialSuccess
+        } else {
+            // Nothing has been dumped: the first failure is the relevant one
+            self.entries
+                .iter()
+                .find_map(|(_, e)| e.as_ref().map(FailureKind::of))
+                .unwrap()
+        };
+        Err(common::failure(
+            kind,
+            format!("{} of {} entries failed", failures, self.entries.len()),
+        ))
+    }
+}
+
+impl fmt::Display for JobReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, error) in &self.entries {
+            match error {
+                Some(e) => writeln!(f, "error: {}: {:#}", name, e)?,
+                None => writeln!(f, "ok: {}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let jobs = parse_csv(
+            "debug_file,debug_id,code_file,code_id
+xul.pdb,C4BAFAE5E2E7B6B24C4C44205044422E1,xul.dll,5F3A1B2C8D34000
+
+C:\\Windows\\ntdll.pdb, 1EB9FACB04EA273BB24BA52C8B8D336A1,,
+",
+        )
+        .unwrap();
+        assert_eq!(
+            jobs,
+            vec![
+                Job {
+                    code_file: Some("xul.dll".to_string()),
+                    code_id: Some("5F3A1B2C8D34000".to_string()),
+                    debug_file: Some("xul.pdb".to_string()),
+                    debug_id: Some("C4BAFAE5E2E7B6B24C4C44205044422E1".to_string()),
+                },
+                Job {
+                    debug_file: Some("C:\\Windows\\ntdll.pdb".to_string()),
+                    debug_id: Some("1EB9FACB04EA273BB24BA52C8B8D336A1".to_string()),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(
+            get_basename(jobs[1].debug_file.as_ref().unwrap()),
+            "ntdll.pdb"
+        );
+
+        assert!(parse_csv("debug_file,module\nxul.pdb,xul").is_err());
+        assert!(parse_csv("debug_file,debug_id\nxul.pdb").is_err());
+    }
+}
@@ -0,0 +1,368 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use serde::Serialize;
+
+use crate::line::{InlineAddressRange, InlineSite, Line, Lines};
+use crate::symbol::{DataSymbol, Label, Symbol};
+use crate::vcs::GitFile;
+
+/// The version of the layout of the document, it's bumped on breaking changes
+pub(crate) const JSON_VERSION: u32 = 1;
+
+/// The JSON output format: the records of the Breakpad format as a document, so
+/// the tools consuming the symbols don't have to parse the text format.
+/// The addresses are relative to the module base and the numbers are decimal.
+#[derive(Serialize)]
+pub(crate) struct Module<'a> {
+    pub version: u32,
+    pub os: &'a str,
+    pub cpu: &'a str,
+    pub debug_id: &'a str,
+    pub name: &'a str,
+    pub code_id: Option<&'a str>,
+    pub pe_name: Option<&'a str>,
+    pub generator: Generator<'a>,
+    pub provenance: Option<Provenance>,
+    pub files: Vec<File<'a>>,
+    pub inline_origins: Vec<&'a str>,
+    pub functions: Vec<Function<'a>>,
+    pub publics: Vec<Public<'a>>,
+    pub data: Vec<Data<'a>>,
+    pub stack_cfi: Vec<StackCfi<'a>>,
+    pub stack_win: Vec<StackWin<'a>>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Generator<'a> {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub features: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Provenance {
+    pub source: &'static str,
+    pub level: &'static str,
+}
+
+#[derive(Serialize)]
+pub(crate) struct File<'a> {
+    pub id: u32,
+    pub name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<Git<'a>>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Git<'a> {
+    pub rev: &'a str,
+    pub remote: Option<&'a str>,
+    pub path: &'a str,
+}
+
+impl<'a> From<&'a GitFile> for Git<'a> {
+    fn from(git: &'a GitFile) -> Self {
+        Self {
+            rev: &git.rev,
+            remote: git.remote.as_deref(),
+            path: &git.path,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct Function<'a> {
+    pub address: u32,
+    pub size: u32,
+    pub parameter_size: u32,
+    pub name: &'a str,
+    pub multiple: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<&'a str>,
+    pub lines: Vec<LineRecord>,
+    pub inlines: Vec<Inline>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<LabelRecord<'a>>,
+}
+
+impl<'a> From<&'a Symbol> for Function<'a> {
+    fn from(sym: &'a Symbol) -> Self {
+        Self {
+            address: sym.rva,
+            size: sym.len,
+            parameter_size: sym.parameter_size,
+            name: &sym.name,
+            multiple: sym.is_multiple,
+            language: sym.language.as_deref(),
+            lines: sym.source.lines.iter().map(LineRecord::from).collect(),
+            inlines: get_inlines(&sym.source),
+            labels: sym.labels.iter().map(LabelRecord::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct LineRecord {
+    pub address: u32,
+    pub size: u32,
+    pub line: u32,
+    pub file: u32,
+}
+
+impl From<&Line> for LineRecord {
+    fn from(line: &Line) -> Self {
+        Self {
+            address: line.rva,
+            size: line.len,
+            line: line.num,
+            file: line.file_id,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct Inline {
+    pub depth: u32,
+    pub call_line: u32,
+    pub call_file: u32,
+    pub origin: u32,
+    pub ranges: Vec<Range>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Range {
+    pub address: u32,
+    pub size: u32,
+}
+
+impl From<&InlineAddressRange> for Range {
+    fn from(range: &InlineAddressRange) -> Self {
+        Self {
+            address: range.rva,
+            size: range.len,
+        }
+    }
+}
+
+/// Get the inline records in the order of the INLINE ones: by the address of
+/// their first range and by depth
+fn get_inlines(lines: &Lines) -> Vec<Inline> {
+    let mut inlines: Vec<(&InlineSite, &Vec<InlineAddressRange>)> = lines.inlines.iter().collect();
+    inlines.sort_by_key(|(site, ranges)| (ranges.first().map_or(0, |r| r.rva), site.call_depth));
+    inlines
+        .into_iter()
+        .map(|(site, ranges)| Inline {
+            depth: site.call_depth,
+            call_line: site.call_line_number,
+            call_file: site.call_file_id,
+            origin: site.inline_origin_id,
+            ranges: ranges.iter().map(Range::from).collect(),
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+pub(crate) struct LabelRecord<'a> {
+    pub address: u32,
+    pub name: &'a str,
+}
+
+impl<'a> From<&'a Label> for LabelRecord<'a> {
+    fn from(label: &'a Label) -> Self {
+        Self {
+            address: label.rva,
+            name: &label.name,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct Public<'a> {
+    pub address: u32,
+    pub parameter_size: u32,
+    pub name: &'a str,
+    pub multiple: bool,
+}
+
+impl<'a> From<&'a Symbol> for Public<'a> {
+    fn from(sym: &'a Symbol) -> Self {
+        Self {
+            address: sym.rva,
+            parameter_size: sym.parameter_size,
+            name: &sym.name,
+            multiple: sym.is_multiple,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct Data<'a> {
+    pub address: u32,
+    pub size: u32,
+    pub name: &'a str,
+    pub tls: bool,
+}
+
+impl<'a> From<&'a DataSymbol> for Data<'a> {
+    fn from(data: &'a DataSymbol) -> Self {
+        Self {
+            address: data.rva,
+            size: data.len,
+            name: &data.name,
+            tls: data.is_tls,
+        }
+    }
+}
+
+/// A STACK CFI INIT record with the STACK CFI ones following it
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct StackCfi<'a> {
+    pub address: u32,
+    pub size: u32,
+    pub rules: &'a str,
+    pub deltas: Vec<CfiDelta<'a>>,
+    /// The range is a signal frame (STACK SIGNAL record)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub signal: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct CfiDelta<'a> {
+    pub address: u32,
+    pub rules: &'a str,
+}
+
+/// A STACK WIN record, the program string and allocates_base_pointer are exclusive
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct StackWin<'a> {
+    #[serde(rename = "type")]
+    pub ty: u32,
+    pub address: u32,
+    pub size: u32,
+    pub prolog_size: u32,
+    pub epilog_size: u32,
+    pub parameter_size: u32,
+    pub saved_register_size: u32,
+    pub local_size: u32,
+    pub max_stack_size: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program_string: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocates_base_pointer: Option<bool>,
+}
+
+fn parse_hex(field: Option<&str>) -> Option<u32> {
+    u32::from_str_radix(field?, 16).ok()
+}
+
+fn parse_stack_win(record: &str) -> Option<StackWin<'_>> {
+    let mut fields = record.splitn(11, ' ');
+    let mut numbers = [0; 9];
+    for n in numbers.iter_mut() {
+        *n = parse_hex(fields.next())?;
+    }
+    let has_program_string = fields.next()? == "1";
+    let last = fields.next().unwrap_or_default();
+    let [ty, address, size, prolog_size, epilog_size, parameter_size, saved_register_size, local_size, max_stack_size] =
+        numbers;
+    Some(StackWin {
+        ty,
+        address,
+        size,
+        prolog_size,
+        epilog_size,
+        parameter_size,
+        saved_register_size,
+        local_size,
+        max_stack_size,
+        program_string: has_program_string.then_some(last),
+        allocates_base_pointer: (!has_program_string).then(|| last == "1"),
+    })
+}
+
+/// Split the STACK records of a module into the CFI and the WIN ones, the
+/// malformed records are skipped.
+pub(crate) fn parse_stack(stack: &str) -> (Vec<StackCfi<'_>>, Vec<StackWin<'_>>) {
+    let mut cfi: Vec<StackCfi> = Vec::new();
+    let mut win = Vec::new();
+    for line in stack.lines() {
+        if let Some(init) = line.strip_prefix("STACK CFI INIT ") {
+            let mut fields = init.splitn(3, ' ');
+            if let (Some(address), Some(size)) =
+                (parse_hex(fields.next()), parse_hex(fields.next()))
+            {
+                cfi.push(StackCfi {
+                    address,
+                    size,
+                    rules: fields.next().unwrap_or_default(),
+                    deltas: Vec::new(),
+                    signal: false,
+                });
+            }
+        } else if let Some(delta) = line.strip_prefix("STACK CFI ") {
+            let mut fields = delta.splitn(2, ' ');
+            if let (Some(address), Some(init)) = (parse_hex(fields.next()), cfi.last_mut()) {
+                init.deltas.push(CfiDelta {
+                    address,
+                    rules: fields.next().unwrap_or_default(),
+                });
+            }
+        } else if line.starts_with("STACK SIGNAL ") {
+            if let Some(init) = cfi.last_mut() {
+                init.signal = true;
+            }
+        } else if let Some(record) = line.strip_prefix("STACK WIN ").and_then(parse_stack_win) {
+            win.push(record);
+        }
+    }
+    (cfi, win)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stack() {
+        let stack = "STACK CFI INIT 1000 20 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+                     STACK CFI 1001 .cfa: $rsp 16 +\n\
+                     STACK CFI INIT 1020 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^\n\
+                     STACK SIGNAL 1020 10\n\
+                     STACK WIN 4 2000 30 5 0 8 4 0 0 1 $T0 $ebp 4 + = $eip $T0 ^ =\n\
+                     STACK WIN 0 2030 10 0 0 0 0 0 0 0 1\n";
+        let (cfi, win) = parse_stack(stack);
+        assert_eq!(
+            cfi,
+            vec![
+                StackCfi {
+                    address: 0x1000,
+                    size: 0x20,
+                    rules: ".cfa: $rsp 8 + .ra: .cfa -8 + ^",
+                    deltas: vec![CfiDelta {
+                        address: 0x1001,
+                        rules: ".cfa: $rsp 16 +",
+                    }],
+                    signal: false,
+                },
+                StackCfi {
+                    address: 0x1020,
+                    size: 0x10,
+                    rules: ".cfa: $rsp 8 + .ra: .cfa -8 + ^",
+                    deltas: Vec::new(),
+                    signal: true,
+                },
+            ]
+        );
+        assert_eq!(win.len(), 2);
+        assert_eq!(win[0].ty, 4);
+        assert_eq!(win[0].prolog_size, 5);
+        assert_eq!(win[0].parameter_size, 8);
+        assert_eq!(win[0].program_string, Some("$T0 $ebp 4 + = $eip $T0 ^ ="));
+        assert_eq!(win[0].allocates_base_pointer, None);
+        assert_eq!(win[1].program_string, None);
+        assert_eq!(win[1].allocates_base_pointer, Some(true));
+    }
+}
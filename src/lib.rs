@@ -3,19 +3,44 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+pub mod archive;
 #[cfg(feature = "http")]
 pub mod cache;
+pub mod cfi;
 pub mod collector;
 pub mod common;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(feature = "disasm")]
+mod disasm;
+pub mod doctor;
 pub mod dumper;
+pub mod dwarf;
 pub mod inline_origins;
+pub mod inspect;
+pub mod jobs;
+mod json;
 mod line;
+#[cfg(feature = "elf")]
 pub mod linux;
+pub mod list;
+pub mod logging;
+#[cfg(feature = "macho")]
 pub mod mac;
 pub mod mapping;
+pub mod metrics;
 pub mod object_info;
+pub mod outlined;
 pub mod platform;
+pub mod progress;
+#[cfg(feature = "http")]
+pub mod server;
 mod source;
+pub mod ssqp;
+mod stabs;
+pub mod sym_cache;
+pub mod sym_diff;
 mod symbol;
 pub mod utils;
+mod vcs;
 pub mod windows;
@@ -3,12 +3,14 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
     fmt::{self, Debug, Display, Formatter},
+    ops::Range,
 };
 
-#[derive(Clone, Default, PartialEq, Eq)]
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) struct Line {
     // rva stands for relative virtual address
     pub(crate) rva: u32,
@@ -32,7 +34,7 @@ impl Debug for Line {
 }
 
 /// Represents an inlined function call.
-#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub(crate) struct InlineSite {
     /// The identifier of the function name, as an index into InlineOrigins.
     pub(crate) inline_origin_id: u32,
@@ -57,7 +59,7 @@ impl Debug for InlineSite {
 
 /// Represents a contiguous slice of instructions (i.e. an rva range)
 /// for an inlined function call.
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub(crate) struct InlineAddressRange {
     /// rva stands for relative virtual address
     pub(crate) rva: u32,
@@ -82,7 +84,7 @@ impl Debug for InlineAddressRange {
 /// file and line number, and to the inline stack at each address (with file + line
 /// at each level of inlining).
 // TODO: Consider renaming this struct to FunctionDebugInfo
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Lines {
     /// The line records.
     ///
@@ -134,7 +136,8 @@ impl Display for Lines {
 
         // Sort the inlines by the first range's rva and by call depth.
         let mut inlines: Vec<(&_, &_)> = self.inlines.iter().collect();
-        inlines.sort_by_key(|(site, ranges)| (ranges.first().unwrap().rva, site.call_depth));
+        inlines
+            .sort_by_key(|(site, ranges)| (ranges.first().map_or(0, |r| r.rva), site.call_depth));
         for (site, ranges) in inlines {
             write_inline_record(site, ranges, f)?;
         }
@@ -178,11 +181,84 @@ impl Lines {
             .push(address_range);
     }
 
+    /// Get the lines and the inline ranges which are in the given address range.
+    ///
+    /// The lines must have their length (see `finalize` and `finalize_fragments`) and
+    /// the ones straddling a boundary of the range are cut at it, so the slice of a
+    /// function starting in the middle of a line still begins with this line.
+    pub(crate) fn slice(&self, range: &Range<u32>) -> Self {
+        let mut lines = Self::new();
+        for line in &self.lines {
+            let start = line.rva.max(range.start);
+            let end = line.rva.saturating_add(line.len).min(range.end);
+            if start < end || (line.len == 0 && range.contains(&line.rva)) {
+                lines.lines.push(Line {
+                    rva: start,
+                    len: end.saturating_sub(start),
+                    num: line.num,
+                    file_id: line.file_id,
+                });
+            }
+        }
+        lines.last_line_rva = lines.lines.last().map_or(0, |l| l.rva);
+
+        for (site, ranges) in &self.inlines {
+            for r in ranges {
+                let start = r.rva.max(range.start);
+                let end = r.rva.saturating_add(r.len).min(range.end);
+                if start < end {
+                    lines.add_inline(
+                        site.clone(),
+                        InlineAddressRange {
+                            rva: start,
+                            len: end - start,
+                        },
+                    );
+                }
+            }
+        }
+
+        lines
+    }
+
+    /// Append the lines and the inline ranges of the code following these ones,
+    /// they're already finalized.
+    pub(crate) fn append(&mut self, other: Lines) {
+        self.are_lines_sorted &= other.are_lines_sorted
+            && other
+                .lines
+                .first()
+                .is_none_or(|l| self.last_line_rva <= l.rva);
+        if !other.lines.is_empty() {
+            self.last_line_rva = other.last_line_rva;
+        }
+        self.lines.extend(other.lines);
+        for (site, ranges) in other.inlines {
+            self.inlines.entry(site).or_default().extend(ranges);
+        }
+    }
+
     pub fn finalize(&mut self, sym_rva: u32, sym_len: u32) {
         self.ensure_order();
         self.compute_len(sym_rva, sym_len);
     }
 
+    /// Same as `finalize` for a function made of several pieces: a line can't
+    /// run over the end of the piece which contains it.
+    pub(crate) fn finalize_fragments(&mut self, fragments: &[Range<u32>]) {
+        self.ensure_order();
+        for i in 0..self.lines.len() {
+            let rva = self.lines[i].rva;
+            let next = self.lines.get(i + 1).map(|l| l.rva);
+            let end = fragments
+                .iter()
+                .find(|f| f.contains(&rva))
+                .map(|f| next.map_or(f.end, |next| next.min(f.end)))
+                .or(next);
+            self.lines[i].len = end.map_or(0, |end| end - rva);
+        }
+    }
+
     fn compute_len(&mut self, sym_rva: u32, sym_len: u32) {
         // The length (in the binary) of the line is not in the pdb but we can infer it
         // based on the rva of the next line. For the last line, we can infer it because
@@ -225,7 +301,7 @@ impl Lines {
     ///
     /// Must be called before invoking the `Display` implementation and
     /// before calling `compute_len`.
-    fn ensure_order(&mut self) {
+    pub(crate) fn ensure_order(&mut self) {
         if !self.are_lines_sorted {
             // Sort the lines.
             self.lines.sort_by_key(|x| x.rva);
@@ -292,4 +368,69 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn test_slice() {
+        let site = InlineSite {
+            inline_origin_id: 1,
+            call_depth: 0,
+            call_line_number: 10,
+            call_file_id: 0,
+        };
+        let mut lines = Lines::new();
+        lines.add_line(0x1000, 1, 0);
+        lines.add_line(0x1004, 2, 0);
+        lines.add_line(0x2000, 3, 0);
+        lines.add_inline(
+            site.clone(),
+            InlineAddressRange {
+                rva: 0x1002,
+                len: 0x10,
+            },
+        );
+        // The function is in two pieces: 0x1000..0x1010 and 0x2000..0x2008
+        lines.finalize_fragments(&[0x1000..0x1010, 0x2000..0x2008]);
+        assert_eq!(lines.lines[1].len, 0xc);
+        assert_eq!(lines.lines[2].len, 0x8);
+
+        // The piece moved at 0x2000
+        let fragment = lines.slice(&(0x2000..0x2008));
+        assert_eq!(
+            fragment.lines,
+            vec![Line {
+                rva: 0x2000,
+                len: 0x8,
+                num: 3,
+                file_id: 0
+            }]
+        );
+        assert!(fragment.inlines.is_empty());
+
+        // Both boundaries are in the middle of a line
+        let mut fragment = lines.slice(&(0x1002..0x1008));
+        assert_eq!(
+            fragment.lines,
+            vec![
+                Line {
+                    rva: 0x1002,
+                    len: 0x2,
+                    num: 1,
+                    file_id: 0
+                },
+                Line {
+                    rva: 0x1004,
+                    len: 0x4,
+                    num: 2,
+                    file_id: 0
+                }
+            ]
+        );
+        let ranges = &fragment.inlines[&site];
+        assert_eq!((ranges[0].rva, ranges[0].len), (0x1002, 0x6));
+
+        // Finalizing a slice doesn't change it
+        let expected = fragment.lines.clone();
+        fragment.finalize(0x1002, 0x6);
+        assert_eq!(fragment.lines, expected);
+    }
 }
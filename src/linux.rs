@@ -3,10 +3,17 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use goblin::elf::Elf;
+use hashbrown::HashMap;
+use std::convert::TryInto;
+use std::path::Path;
 use std::sync::Arc;
+use symbolic::common::Arch;
 use symbolic::debuginfo::Object;
 
+use crate::collector::CollectOptions;
 use crate::common;
+use crate::dwarf;
 use crate::mapping::PathMappings;
 use crate::object_info::ObjectInfo;
 use crate::platform::Platform;
@@ -14,20 +21,203 @@ use crate::platform::Platform;
 impl ObjectInfo {
     pub fn from_elf(
         buf: &[u8],
+        path: &Path,
         file_name: &str,
         platform: Platform,
         mapping: Option<Arc<PathMappings>>,
-        collect_inlines: bool,
+        options: CollectOptions,
     ) -> common::Result<Self> {
         let o = Object::parse(buf)?;
+        let sup_buf = dwarf::find_supplementary_file(&o, path);
+        let sup = sup_buf.as_deref().map(Object::parse).transpose()?;
         Self::from_object(
             &o,
             file_name,
             None,
             None,
+            sup.as_ref(),
             platform,
             mapping,
-            collect_inlines,
+            options,
+            None,
         )
     }
 }
+
+/// The sections of the stubs jumping through the GOT: the lazy ones, the ones used
+/// with IBT or BTI (the lazy ones are then only pushing the relocation index) and
+/// the non-lazy ones.
+const PLT_SECTIONS: [&str; 4] = [".plt", ".plt.sec", ".plt.got", ".plt.bnd"];
+
+/// An entry of a PLT, with the name of the function whose GOT slot it jumps through
+#[derive(Debug, PartialEq, Eq)]
+pub struct PltEntry {
+    pub rva: u32,
+    pub len: u32,
+    pub name: String,
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Get the address of the GOT slot read by the x86 PLT entry at `addr`:
+/// `jmp *disp(%rip)` on x86-64 and `jmp *abs` or `jmp *disp(%ebx)` on x86, maybe
+/// after an `endbr` and with a `bnd` prefix.
+fn get_x86_slot(entry: &[u8], addr: u64, got: u64, is_64: bool) -> Option<u64> {
+    for at in [0, 1, 4, 5] {
+        let disp = read_u32(entry, at + 2);
+        let next = addr + at as u64 + 6;
+        match (entry.get(at..at + 2)?, disp) {
+            ([0xff, 0x25], Some(disp)) if is_64 => {
+                return next.checked_add_signed(disp as i32 as i64);
+            }
+            ([0xff, 0x25], Some(disp)) => return Some(disp as u64),
+            ([0xff, 0xa3], Some(disp)) if !is_64 => {
+                return got.checked_add_signed(disp as i32 as i64);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Get the address of the GOT slot read by the ARM64 PLT entry at `addr`:
+/// `adrp x16, page; ldr x17, [x16, offset]`, maybe after a `bti c`.
+fn get_arm64_slot(entry: &[u8], addr: u64) -> Option<u64> {
+    for at in [0, 4] {
+        let (adrp, ldr) = (read_u32(entry, at)?, read_u32(entry, at + 4)?);
+        if adrp & 0x9f00_0000 != 0x9000_0000 || ldr & 0xffc0_0000 != 0xf940_0000 {
+            continue;
+        }
+        let imm = ((adrp >> 5) & 0x7ffff) << 2 | (adrp >> 29) & 0x3;
+        // Sign-extend the 21 bits immediate, it's a number of pages
+        let pages = ((imm << 11) as i32 >> 11) as i64;
+        let page = ((addr + at as u64) & !0xfff).checked_add_signed(pages << 12)?;
+        return Some(page + (((ldr >> 10) & 0xfff) as u64) * 8);
+    }
+    None
+}
+
+/// Get the names of the functions by the address of their GOT slot, from the
+/// relocations of the dynamic symbols.
+fn get_got_slots<'a>(elf: &Elf<'a>) -> HashMap<u64, &'a str> {
+    elf.pltrelocs
+        .iter()
+        .chain(elf.dynrelas.iter())
+        .chain(elf.dynrels.iter())
+        .filter_map(|reloc| {
+            let sym = elf.dynsyms.get(reloc.r_sym).filter(|_| reloc.r_sym != 0)?;
+            let name = elf.dynstrtab.get_at(sym.st_name)?;
+            (!name.is_empty()).then_some((reloc.r_offset, name))
+        })
+        .collect()
+}
+
+/// Get the entries of the PLTs of an ELF file jumping to a named function, for
+/// the x86, x86-64 and ARM64 code. The entries are decoded to find the GOT slot
+/// they jump through, so the ones of the lazy PLT without it are skipped.
+pub fn get_plt_entries(object: &Object) -> Vec<PltEntry> {
+    let arch = object.arch();
+    if !matches!(arch, Arch::X86 | Arch::Amd64 | Arch::Arm64) {
+        return Vec::new();
+    }
+    let data = object.data();
+    let elf = match Elf::parse(data) {
+        Ok(elf) => elf,
+        Err(_) => return Vec::new(),
+    };
+
+    let slots = get_got_slots(&elf);
+    if slots.is_empty() {
+        return Vec::new();
+    }
+    // The base of the `jmp *disp(%ebx)` of the x86 PIC code
+    let got = elf
+        .section_headers
+        .iter()
+        .find(|h| matches!(elf.shdr_strtab.get_at(h.sh_name), Some(".got.plt")))
+        .map_or(0, |h| h.sh_addr);
+    let load_address = object.load_address();
+
+    let mut entries = Vec::new();
+    for header in elf.section_headers.iter().filter(|h| {
+        h.is_executable()
+            && h.sh_type != goblin::elf::section_header::SHT_NOBITS
+            && elf
+                .shdr_strtab
+                .get_at(h.sh_name)
+                .is_some_and(|name| PLT_SECTIONS.contains(&name))
+    }) {
+        let start = header.sh_offset as usize;
+        let code = match data.get(start..start.saturating_add(header.sh_size as usize)) {
+            Some(code) => code,
+            None => continue,
+        };
+        // The x86 .plt.got entries are 8 bytes long without IBT
+        let len = match header.sh_entsize {
+            8 | 16 => header.sh_entsize,
+            _ => 16,
+        };
+        for (i, entry) in code.chunks(len as usize).enumerate() {
+            let addr = header.sh_addr + i as u64 * len;
+            let slot = match arch {
+                Arch::Arm64 => get_arm64_slot(entry, addr),
+                _ => get_x86_slot(entry, addr, got, arch == Arch::Amd64),
+            };
+            let name = match slot.and_then(|slot| slots.get(&slot)) {
+                Some(name) => name,
+                None => continue,
+            };
+            if let Some(rva) = addr.checked_sub(load_address) {
+                entries.push(PltEntry {
+                    rva: rva as u32,
+                    len: entry.len() as u32,
+                    name: name.to_string(),
+                });
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_x86_slot() {
+        // jmp *0x2fe2(%rip)
+        let entry = [0xff, 0x25, 0xe2, 0x2f, 0x00, 0x00, 0x68, 0x00];
+        assert_eq!(get_x86_slot(&entry, 0x1030, 0, true), Some(0x4018));
+        // endbr64; bnd jmp *0x2fa5(%rip)
+        let entry = [
+            0xf3, 0x0f, 0x1e, 0xfa, 0xf2, 0xff, 0x25, 0xa5, 0x2f, 0x00, 0x00,
+        ];
+        assert_eq!(get_x86_slot(&entry, 0x1060, 0, true), Some(0x4010));
+        // jmp *0xc(%ebx)
+        let entry = [0xff, 0xa3, 0x0c, 0x00, 0x00, 0x00];
+        assert_eq!(get_x86_slot(&entry, 0x1030, 0x4000, false), Some(0x400c));
+        // jmp *0x804a00c
+        let entry = [0xff, 0x25, 0x0c, 0xa0, 0x04, 0x08];
+        assert_eq!(get_x86_slot(&entry, 0x1030, 0, false), Some(0x804a00c));
+        // pushq 0x2fe2(%rip)
+        let entry = [0xff, 0x35, 0xe2, 0x2f, 0x00, 0x00];
+        assert_eq!(get_x86_slot(&entry, 0x1020, 0, true), None);
+    }
+
+    #[test]
+    fn test_get_arm64_slot() {
+        // adrp x16, 0x11000; ldr x17, [x16, #0xf80]
+        let entry = [0x10, 0x00, 0x00, 0xb0, 0x11, 0xc2, 0x47, 0xf9];
+        assert_eq!(get_arm64_slot(&entry, 0x10600), Some(0x11f80));
+        // bti c; adrp x16, 0x11000; ldr x17, [x16, #0xf80]
+        let entry = [
+            0x5f, 0x24, 0x03, 0xd5, 0x10, 0x00, 0x00, 0xb0, 0x11, 0xc2, 0x47, 0xf9,
+        ];
+        assert_eq!(get_arm64_slot(&entry, 0x105fc), Some(0x11f80));
+        // nop; nop
+        let entry = [0x1f, 0x20, 0x03, 0xd5, 0x1f, 0x20, 0x03, 0xd5];
+        assert_eq!(get_arm64_slot(&entry, 0x10600), None);
+    }
+}
@@ -0,0 +1,161 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt::Write;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use symbolic::debuginfo::{Archive, FileFormat};
+
+use crate::common::{self, FailureKind};
+use crate::utils;
+
+/// The magic of the zip files (and so of the apk, aar and jar files)
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Write a line per object of the buffer, nothing if it isn't a dumpable format
+fn list_objects(out: &mut String, buf: &[u8], name: &str) -> common::Result<bool> {
+    if matches!(
+        Archive::peek(buf),
+        FileFormat::Unknown | FileFormat::Breakpad | FileFormat::SourceBundle
+    ) {
+        return Ok(false);
+    }
+
+    let archive = Archive::parse(buf)?;
+    for (i, object) in archive.objects().enumerate() {
+        let object = object?;
+        if archive.object_count() == 1 {
+            write!(out, "{}", name)?;
+        } else {
+            write!(out, "{} (object {})", name, i)?;
+        }
+        writeln!(
+            out,
+            ": {} {} {} {}",
+            object.file_format(),
+            object.arch().name(),
+            object.debug_id().breakpad(),
+            object.kind()
+        )?;
+    }
+    Ok(true)
+}
+
+/// Write the objects of the members of a static library or of a zip file
+fn list_members(out: &mut String, buf: &[u8], name: &str) -> common::Result<bool> {
+    if buf.starts_with(ZIP_MAGIC) {
+        let mut zip = zip::ZipArchive::new(Cursor::new(buf))?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i)?;
+            if !entry.is_file() {
+                continue;
+            }
+            let mut member = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut member)?;
+            let member_name = format!("{}!{}", name, entry.name());
+            if !list_members(out, &member, &member_name)? {
+                list_objects(out, &member, &member_name)?;
+            }
+        }
+        return Ok(true);
+    }
+
+    if let Ok(ar) = goblin::archive::Archive::parse(buf) {
+        for member in ar.members() {
+            if let Ok(member_buf) = ar.extract(member, buf) {
+                list_objects(out, member_buf, &format!("{}({})", name, member))?;
+            }
+        }
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+fn list_dir(out: &mut String, path: &Path) -> common::Result<()> {
+    let mut entries = fs::read_dir(path)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            list_dir(out, &entry)?;
+        } else {
+            let buf = fs::read(&entry)?;
+            let name = entry.display().to_string();
+            let buf = utils::read_cabinet(buf, entry).unwrap_or_default();
+            if !list_members(out, &buf, &name)? {
+                list_objects(out, &buf, &name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// List the dumpable objects of a file with their format, arch, debug id and
+/// kind: the ones of a fat binary, the members of a static library or of a zip
+/// file such as an apk, or the files of a directory.
+pub fn list(path: &Path) -> common::Result<String> {
+    let mut out = String::new();
+    let name = path.display().to_string();
+    if path.is_dir() && !path.join("Contents/Resources/DWARF").is_dir() {
+        list_dir(&mut out, path)?;
+        return Ok(out);
+    }
+
+    let buf = utils::read_file(path)?;
+    if !list_members(&mut out, &buf, &name)? && !list_objects(&mut out, &buf, &name)? {
+        return Err(common::failure(
+            FailureKind::UnsupportedFormat,
+            format!("Unknown file format: {}", name),
+        ));
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_list() {
+        let tmp_dir = Builder::new().prefix("list").tempdir().unwrap();
+        let basic = fs::read("./test_data/linux/basic.full").unwrap();
+        fs::create_dir(tmp_dir.path().join("lib")).unwrap();
+        fs::write(tmp_dir.path().join("lib/basic.full"), &basic).unwrap();
+        fs::copy(
+            "./test_data/linux/basic.full.sym",
+            tmp_dir.path().join("basic.sym"),
+        )
+        .unwrap();
+
+        let apk = tmp_dir.path().join("basic.apk");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&apk).unwrap());
+        zip.start_file("lib/x86_64/libbasic.so", Default::default())
+            .unwrap();
+        zip.write_all(&basic).unwrap();
+        zip.start_file("AndroidManifest.xml", Default::default())
+            .unwrap();
+        zip.write_all(b"<manifest/>").unwrap();
+        zip.finish().unwrap();
+
+        let root = tmp_dir.path().display();
+        assert_eq!(
+            list(tmp_dir.path()).unwrap(),
+            format!(
+                "{root}/basic.apk!lib/x86_64/libbasic.so: elf x86_64 20AD60B0B4C68177552708AA192E77390 lib
+{root}/lib/basic.full: elf x86_64 20AD60B0B4C68177552708AA192E77390 lib
+"
+            )
+        );
+
+        let e = list(&tmp_dir.path().join("basic.sym")).unwrap_err();
+        assert_eq!(FailureKind::of(&e), FailureKind::UnsupportedFormat);
+    }
+}
@@ -0,0 +1,186 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::Map;
+use std::cell::RefCell;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::common;
+use crate::progress;
+
+/// How the log records are written on stderr
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per record with its level, category, module and rva
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> common::Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => anyhow::bail!("Invalid log format: {}", s),
+        }
+    }
+}
+
+thread_local! {
+    /// The name of the module dumped by the current thread
+    static MODULE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Set the module dumped by the current thread, it's in the records logged by it
+pub fn set_module(file: Option<&str>) {
+    let name = file.map(|file| {
+        Path::new(file).file_name().map_or_else(
+            || file.to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    });
+    MODULE.with(|module| *module.borrow_mut() = name);
+}
+
+/// The records of our modules are in the category named after the module
+/// (e.g. `cfi` or `windows::pdb`) unless they've an explicit `category` key.
+fn get_category(target: &str) -> &str {
+    target.strip_prefix("dump_syms::").unwrap_or(target)
+}
+
+/// Copy the key-values of a record in the JSON object, the `rva` is in hexadecimal
+struct Fields<'a>(&'a mut Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = match (key.as_str(), value.to_u64()) {
+            ("rva", Some(rva)) => format!("{:x}", rva).into(),
+            (_, Some(n)) => n.into(),
+            _ => value.to_string().into(),
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
+
+fn to_json(record: &Record) -> serde_json::Value {
+    let event = match record.level() {
+        Level::Warn => "warning".to_string(),
+        level => level.as_str().to_lowercase(),
+    };
+    let mut fields = Map::new();
+    fields.insert("event".to_string(), event.into());
+    fields.insert(
+        "level".to_string(),
+        record.level().as_str().to_lowercase().into(),
+    );
+    fields.insert("category".to_string(), get_category(record.target()).into());
+    if let Some(module) = MODULE.with(|module| module.borrow().clone()) {
+        fields.insert("module".to_string(), module.into());
+    }
+    let _ = record.key_values().visit(&mut Fields(&mut fields));
+    fields.insert("message".to_string(), record.args().to_string().into());
+    fields.into()
+}
+
+/// Report a message as a record of the given level
+pub fn log_event(level: Level, message: &str) {
+    progress::emit(to_json(
+        &Record::builder()
+            .level(level)
+            .target("dump_syms")
+            .args(format_args!("{}", message))
+            .build(),
+    ));
+}
+
+/// A logger which writes the records as JSON objects, one per line, they're
+/// events in the stream of the JSON progress too.
+pub struct JsonLogger {
+    level: LevelFilter,
+}
+
+impl JsonLogger {
+    pub fn init(level: LevelFilter) -> common::Result<()> {
+        log::set_boxed_logger(Box::new(Self { level }))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            progress::emit(to_json(record));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_record() {
+        assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("xml".parse::<LogFormat>().is_err());
+
+        let kvs = [
+            ("rva", Value::from(0x1a2bu32)),
+            ("count", Value::from(3u32)),
+        ];
+        let record = Record::builder()
+            .level(Level::Warn)
+            .target("dump_syms::windows::pdb")
+            .key_values(&kvs)
+            .args(format_args!("Oops"))
+            .build();
+
+        set_module(Some("/tmp/basic.pdb"));
+        assert_eq!(
+            to_json(&record),
+            json!({
+                "event": "warning",
+                "level": "warn",
+                "category": "windows::pdb",
+                "module": "basic.pdb",
+                "rva": "1a2b",
+                "count": 3,
+                "message": "Oops",
+            })
+        );
+
+        set_module(None);
+        let kvs = [("category", Value::from("demangle"))];
+        let record = Record::builder()
+            .level(Level::Info)
+            .target("dump_syms::collector")
+            .key_values(&kvs)
+            .args(format_args!("Oops"))
+            .build();
+        assert_eq!(
+            to_json(&record),
+            json!({
+                "event": "info",
+                "level": "info",
+                "category": "demangle",
+                "message": "Oops",
+            })
+        );
+    }
+}
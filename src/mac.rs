@@ -7,7 +7,8 @@ use std::sync::Arc;
 use symbolic::common::Arch;
 use symbolic::debuginfo::Archive;
 
-use crate::common;
+use crate::collector::CollectOptions;
+use crate::common::{self, FailureKind};
 use crate::mapping::PathMappings;
 use crate::object_info::ObjectInfo;
 use crate::platform::Platform;
@@ -18,7 +19,7 @@ impl ObjectInfo {
         file_name: &str,
         arch: Arch,
         mapping: Option<Arc<PathMappings>>,
-        collect_inlines: bool,
+        options: CollectOptions,
     ) -> common::Result<Self> {
         // Fat files may contain several objects for different architectures
         // So if there is only one object, then we don't care about the arch (as argument)
@@ -39,16 +40,21 @@ impl ObjectInfo {
                 file_name,
                 None,
                 None,
+                None,
                 Platform::Mac,
                 mapping,
-                collect_inlines,
+                options,
+                None,
             )
         } else {
-            anyhow::bail!(
-                "Cannot find a valid object for architecture {} in file {}",
-                arch.name(),
-                file_name
-            );
+            Err(common::failure(
+                FailureKind::UnsupportedFormat,
+                format!(
+                    "Cannot find a valid object for architecture {} in file {}",
+                    arch.name(),
+                    file_name
+                ),
+            ))
         }
     }
 }
@@ -3,130 +3,689 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use clap::builder::BoolishValueParser;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
 use clap::ArgAction;
+#[cfg(feature = "http")]
+use clap::ArgGroup;
 use clap::{crate_authors, crate_version, Arg, Command};
-use log::error;
+use log::{error, Level};
 use simplelog::{ColorChoice, ConfigBuilder, LevelFilter, TermLogger, TerminalMode};
+use std::io::{self, IsTerminal};
 use std::ops::Deref;
 use std::panic;
+use symbolic::common::DebugId;
 
 mod action;
 
 use action::Action;
-use dump_syms::common;
+use dump_syms::collector::{CompilandFilter, ParseLimits, PublicFilter};
+use dump_syms::common::{self, FailureKind};
+use dump_syms::doctor::DoctorConfig;
 use dump_syms::dumper;
+use dump_syms::logging::{self, JsonLogger, LogFormat};
+use dump_syms::mapping::PathGlobs;
+use dump_syms::progress::ProgressFormat;
+use dump_syms::sym_cache::{self, CacheLimits};
 
 fn cli() -> Command {
     Command::new("dump_syms")
     .version(crate_version!())
     .author(crate_authors!("\n"))
     .about("Dump debug symbols to breakpad symbols")
+    .after_help("Exit codes:\n  0  success\n  1  internal error\n  2  invalid arguments\n  3  unsupported file format\n  4  identity mismatch (the files don't have the same debug id)\n  5  partial success: some files can't be dumped in batch mode\n  6  network failure: a file can't be retrieved from a symbol server, it may be worth retrying")
+    .subcommand_negates_reqs(true)
+    // The flat invocation is an alias of the dump subcommand
+    .args(dump_args())
+    .subcommand(
+        Command::new("dump")
+            .about("Dump debug symbols to breakpad symbols (the default command)")
+            .args(dump_args())
+    )
+    .subcommands(http_commands())
+    .subcommand(inspect_command())
+    .subcommand(diff_command())
+    .subcommand(doctor_command())
+    .subcommands(daemon_commands())
+    .subcommand(
+        Command::new("cache")
+            .about("Manage the caches")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("clean")
+                    .about("Evict the files from the caches according to --cache-max-size and --cache-max-age\nor empty the symbols cache when there are no limits (the caches of the symbol servers\nare kept, they can be shared with other tools)")
+            )
+    )
     .arg(
-        Arg::new("filenames")
-            .help("Files to dump (.dll, .exe, .pdb, .pd_, .so, .dbg)")
-            .required(true)
-            .num_args(1..)
+        Arg::new("symbol_server")
+            .help("Symbol Server configuration\n(e.g. \"SRV*c:\\symcache\\*https://symbols.mozilla.org/\")\nIt can be in file $HOME/.dump_syms/config too.")
+            .long("symbol-server")
+            .env("DUMP_SYMS_SYMBOL_SERVER")
+            .hide(!cfg!(feature = "http"))
+            .global(true)
+    )
+    .arg(
+        Arg::new("sym_cache")
+            .help("Cache the generated symbols in the given directory (~/.cache/dump_syms by default)\nand reuse them when the same file is dumped again with the same options")
+            .long("sym-cache")
+            .env("DUMP_SYMS_SYM_CACHE")
+            .num_args(0..=1)
+            .default_missing_value("")
+            .global(true)
+    )
+    .arg(
+        Arg::new("cache_max_size")
+            .help("The maximum size of each cache after a dump (e.g. 500M or 10G), the least recently used files are evicted first")
+            .long("cache-max-size")
+            .env("DUMP_SYMS_CACHE_MAX_SIZE")
+            .global(true)
     )
     .arg(
+        Arg::new("cache_max_age")
+            .help("The maximum age of the files in the caches after a dump (e.g. 12h or 30d)")
+            .long("cache-max-age")
+            .env("DUMP_SYMS_CACHE_MAX_AGE")
+            .global(true)
+    )
+    .arg(
+        Arg::new("verbose")
+            .help("Set the level of verbosity (off, error (default), warn, info, debug, trace)")
+            .long("verbose")
+            .env("DUMP_SYMS_VERBOSE")
+            .default_value("error")
+            .global(true)
+    )
+    .arg(
+        Arg::new("log_format")
+            .help("Set the format of the log records on stderr: text (default) or json (one object per\nrecord with its level, category, module, rva when it's known and message)")
+            .long("log-format")
+            .env("DUMP_SYMS_LOG_FORMAT")
+            .default_value("text")
+            .global(true)
+    )
+}
+
+/// The options of the dump, they're the ones of the command without subcommand too
+fn dump_args() -> Vec<Arg> {
+    vec![
+        Arg::new("filenames")
+            .help("Files to dump (.dll, .exe, .pdb, .pd_, .so, .dbg)")
+            .required_unless_present("jobs_file")
+            .num_args(1..),
+        Arg::new("jobs_file")
+            .help("Dump the modules listed in a JSON file (an array of objects) or a CSV file (with a header line)\nwith their code_file, code_id, debug_file and debug_id, as recorded by the crash reporters.\nThe local files are used when they exist, else they're fetched from the symbol servers.\nThe symbols are written in the store and the status of each entry is printed")
+            .long("jobs-file")
+            .env("DUMP_SYMS_JOBS_FILE")
+            .value_name("FILE")
+            .requires("store")
+            .conflicts_with_all(["filenames", "output", "debug_id", "code_id", "dry_run", "list", "list_arch"]),
         Arg::new("output")
             .help("Output file or - for stdout")
             .short('o')
             .long("output")
-    )
-    .arg(
+            .env("DUMP_SYMS_OUTPUT"),
+        Arg::new("format")
+            .help("Output format: breakpad, map (rva, size and name of each symbol) or json (the records of\nthe breakpad format as a JSON document)\nIn a symbol store, the files get the extension of their format (.sym, .map or .json)")
+            .long("format")
+            .env("DUMP_SYMS_FORMAT")
+            .value_parser(["breakpad", "map", "json"])
+            .default_value("breakpad"),
+        Arg::new("legacy_order")
+            .help("Write all the FUNC records before the PUBLIC ones, like the Breakpad dump_syms tools")
+            .long("legacy-order")
+            .env("DUMP_SYMS_LEGACY_ORDER")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
         Arg::new("store")
             .help("Store output file as FILENAME.pdb/DEBUG_ID/FILENAME.sym in the given directory")
             .short('s')
             .long("store")
-    )
-    .arg(
+            .env("DUMP_SYMS_STORE"),
+        Arg::new("store_layout")
+            .help("The layout of the symbol store: breakpad (default) or ssqp (the same paths in lowercase,\nas the keys of the symbol stores using the Simple Symbol Query Protocol)")
+            .long("store-layout")
+            .env("DUMP_SYMS_STORE_LAYOUT")
+            .value_parser(["breakpad", "ssqp"])
+            .default_value("breakpad"),
+        Arg::new("module_os")
+            .help("The OS to write on the MODULE line instead of the detected one (windows, mac, Linux, ...)")
+            .long("module-os")
+            .env("DUMP_SYMS_MODULE_OS"),
+        Arg::new("module_arch")
+            .help("The CPU architecture to write on the MODULE line instead of the one of the file\n(e.g. x86_64, arm64), for the cross-dumps or the ambiguous architectures")
+            .long("module-arch")
+            .env("DUMP_SYMS_MODULE_ARCH"),
+        Arg::new("module_name")
+            .help("The module name to write on the MODULE line and to use in the path of the symbol store\n(e.g. when the file has been renamed)")
+            .long("module-name")
+            .env("DUMP_SYMS_MODULE_NAME"),
+        Arg::new("module_debug_id")
+            .help("The debug id to write on the MODULE line instead of the one of the file (in the Breakpad format),\ne.g. to re-create the symbols of a binary whose PDB is lost: the symbols may not match the module")
+            .long("module-debug-id")
+            .env("DUMP_SYMS_MODULE_DEBUG_ID"),
         Arg::new("debug_id")
             .help("Get the pdb file passed as argument from the cache or from symbol server using the debug id")
             .long("debug-id")
-    )
-    .arg(
+            .env("DUMP_SYMS_DEBUG_ID")
+            .conflicts_with("code_id"),
         Arg::new("code_id")
             .help("Get the dll/exe file passed as argument from the cache or from symbol server using the code id")
             .long("code-id")
-    )
-    .arg(
-        Arg::new("symbol_server")
-            .help("Symbol Server configuration\n(e.g. \"SRV*c:\\symcache\\*https://symbols.mozilla.org/\")\nIt can be in file $HOME/.dump_syms/config too.")
-            .long("symbol-server")
-    )
-    .arg(
+            .env("DUMP_SYMS_CODE_ID"),
         Arg::new("check_cfi")
             .help("Fail if there are no CFI data")
             .long("check-cfi")
+            .env("DUMP_SYMS_CHECK_CFI")
             .action(ArgAction::SetTrue)
-    )
-    .arg(
-        Arg::new("verbose")
-            .help("Set the level of verbosity (off, error (default), warn, info, debug, trace)")
-            .long("verbose")
-            .default_value("error")
-    )
-    .arg(
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("incremental")
+            .help("Don't dump the file when the output files already contain the symbols for the same debug id")
+            .long("incremental")
+            .env("DUMP_SYMS_INCREMENTAL")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("published")
+            .help("Download the symbols from the given symbol servers (https://symbols.mozilla.org by default)\nwhen they have already been published instead of dumping them")
+            .long("published")
+            .env("DUMP_SYMS_PUBLISHED")
+            .hide(!cfg!(feature = "http"))
+            .num_args(0..)
+            .default_missing_value("https://symbols.mozilla.org")
+            .action(ArgAction::Append),
+        Arg::new("progress")
+            .help("Report the progress on stderr: auto (default: bar when stderr is a terminal, else none),\nbar (the phase, the functions collected and the bytes written), none or json (newline-delimited\nevents when a file is started, for each phase and when it's finished, the log records are events too)")
+            .long("progress")
+            .env("DUMP_SYMS_PROGRESS")
+            .default_value("auto"),
         Arg::new("arch")
             .help("Set the architecture to select in fat binaries")
             .short('a')
             .long("arch")
-            .default_value(common::get_compile_time_arch())
-    )
-    .arg(
+            .env("DUMP_SYMS_ARCH")
+            .default_value(common::get_compile_time_arch()),
         Arg::new("type")
             .help("Ignored, listed for compatibility only")
             .short('t')
             .long("type")
-            .default_value("")
-    ).arg(
+            .default_value(""),
+        Arg::new("dry_run")
+            .help("Print the format, the debug id, the paired file and the outputs of the inputs and whether they\nwould be skipped, without dumping nor writing anything")
+            .long("dry-run")
+            .env("DUMP_SYMS_DRY_RUN")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
         Arg::new("list_arch")
             .help("List the architectures present in the fat binaries")
             .long("list-arch")
+            .env("DUMP_SYMS_LIST_ARCH")
             .action(ArgAction::SetTrue)
-    )
-    .arg(
+            .value_parser(BoolishValueParser::new())
+            .conflicts_with_all(["output", "store"]),
+        Arg::new("list")
+            .help("List the dumpable objects of the inputs with their format, arch, debug id and kind:\nthe ones of the fat binaries, the members of the static libraries and of the zip files\n(e.g. apk) and the files of the directories")
+            .long("list")
+            .env("DUMP_SYMS_LIST")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new())
+            .conflicts_with_all(["output", "store", "list_arch"]),
         Arg::new("num_jobs")
             .help("Number of jobs")
             .short('j')
+            .env("DUMP_SYMS_NUM_JOBS")
             .value_name("NUMBER")
-            .default_value("")
-    )
-    .arg(
+            .default_value(""),
         Arg::new("mapping_var")
             .help("A pair var=value such as rev=123abcd")
             .long("mapping-var")
-            .action(ArgAction::Append)
-    )
-    .arg(
+            .env("DUMP_SYMS_MAPPING_VAR")
+            .action(ArgAction::Append),
         Arg::new("mapping_src")
             .help("Regex to match a path with capturing groups")
             .long("mapping-src")
-            .action(ArgAction::Append)
-    )
-    .arg(
+            .env("DUMP_SYMS_MAPPING_SRC")
+            .action(ArgAction::Append),
         Arg::new("mapping_dest")
             .help(r#"A replacement string using groups, variables (set with --mapping-var), special variable like DIGEST or digest.
 For example with --mapping-var="rev=123abc" --mapping-src="/foo/bar/(.*)" --mapping-dest="https://my.source.org/{rev}/{digest}/{1}" a path like "/foo/bar/myfile.cpp" will be transformed into "https://my.source.org/123abc/sha512_of_myfile.cpp/myfile.cpp"
 "#)
             .long("mapping-dest")
-            .action(ArgAction::Append)
-    )
-    .arg(
+            .env("DUMP_SYMS_MAPPING_DEST")
+            .action(ArgAction::Append),
         Arg::new("mapping_file")
             .help("A json file containing mapping")
             .long("mapping-file")
-    )
-    .arg(
+            .env("DUMP_SYMS_MAPPING_FILE"),
+        Arg::new("prune_lines")
+            .help("Drop the line records in the source files matching the glob (e.g. \"**/include/c++/**\" or \"**/msvc/**\"),\nthe functions are kept. The glob is matched on the mapped path, without case and with / for \\")
+            .long("prune-lines")
+            .env("DUMP_SYMS_PRUNE_LINES")
+            .value_name("GLOB")
+            .action(ArgAction::Append),
+        Arg::new("source_url_template")
+            .help("Rewrite the FILE records into URLs with a template such as\n\"https://raw.githubusercontent.com/org/repo/{rev}/{path}\", the variables are the ones of --mapping-var,\nthe fields of the srcsrv stream of the PDB files ({var1} is the path, {var2}, {var3}... the next ones)\nand the revision of the git checkout containing the file ({rev}) with the path in it ({path}).\nThe files with an unknown variable are kept")
+            .long("source-url-template")
+            .env("DUMP_SYMS_SOURCE_URL_TEMPLATE")
+            .value_name("TEMPLATE"),
+        Arg::new("metrics_sidecar")
+            .help("Write the identity of each module, its counts of records, the size of its symbols and the time\nspent to dump it in a JSON file next to its symbols (FILENAME.metrics.json)")
+            .long("metrics-sidecar")
+            .env("DUMP_SYMS_METRICS_SIDECAR")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("git_info")
+            .help("Emit the revision of HEAD, the URL of the origin remote and the path in the checkout of the source\nfiles found in a local git checkout as a FILE_GIT record after the FILE ones (extension to the Breakpad format)")
+            .long("git-info")
+            .env("DUMP_SYMS_GIT_INFO")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
         Arg::new("inlines")
-            .help("Whether to emit INLINE and INLINE_ORIGIN directives")
+            .help("Emit the INLINE and INLINE_ORIGIN records of the inlined calls: from the inline sites\n(S_INLINESITE) and the inlinee lines of the PDB files or from the DWARF")
             .long("inlines")
+            .env("DUMP_SYMS_INLINES")
             .action(ArgAction::SetTrue)
-    )
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("labels")
+            .help("Emit the code labels (S_LABEL32) of PDB files as LABEL records in the FUNC ones\n(extension to the Breakpad format)")
+            .long("labels")
+            .env("DUMP_SYMS_LABELS")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("languages")
+            .help("Emit the source language of the functions (from the compilands of PDB files or the DWARF units)\nas a LANGUAGE record in the FUNC ones (extension to the Breakpad format)")
+            .long("languages")
+            .env("DUMP_SYMS_LANGUAGES")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("data")
+            .help("Emit the global and static variables as DATA records with their address, size and name\n(extension to the Breakpad format)")
+            .long("data")
+            .env("DUMP_SYMS_DATA")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("public_include")
+            .help("Emit the public symbols matching the regex, even if they're skipped by the other filters\n(the regex is matched on the mangled name)")
+            .long("public-include")
+            .env("DUMP_SYMS_PUBLIC_INCLUDE")
+            .value_name("REGEX")
+            .action(ArgAction::Append),
+        Arg::new("public_exclude")
+            .help("Skip the public symbols matching the regex (the regex is matched on the mangled name)")
+            .long("public-exclude")
+            .env("DUMP_SYMS_PUBLIC_EXCLUDE")
+            .value_name("REGEX")
+            .action(ArgAction::Append),
+        Arg::new("no_default_public_filter")
+            .help("Don't skip the constants (strings, floats, ...) in the public symbols")
+            .long("no-default-public-filter")
+            .env("DUMP_SYMS_NO_DEFAULT_PUBLIC_FILTER")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("include_compiland")
+            .help("Dump only the modules of the PDB files whose object or library matches the glob (e.g. \"**/src/**\"),\nthe globs are matched on the paths and on the file names")
+            .long("include-compiland")
+            .env("DUMP_SYMS_INCLUDE_COMPILAND")
+            .value_name("GLOB")
+            .action(ArgAction::Append),
+        Arg::new("exclude_compiland")
+            .help("Skip the modules of the PDB files whose object or library matches the glob (e.g. \"vendored*.lib\")")
+            .long("exclude-compiland")
+            .env("DUMP_SYMS_EXCLUDE_COMPILAND")
+            .value_name("GLOB")
+            .action(ArgAction::Append),
+        Arg::new("thunks")
+            .help("What to do with the compiler thunks (Control Flow Guard and stack cookie checks, stack probes, ...):\nkeep them, flag them with a [thunk] suffix or collapse them into a <compiler thunk> symbol")
+            .long("thunks")
+            .env("DUMP_SYMS_THUNKS")
+            .value_parser(["keep", "flag", "collapse"])
+            .default_value("keep"),
+        Arg::new("outlined")
+            .help("What to do with the functions of the LLVM machine outliner (OUTLINED_FUNCTION_<N>): keep them,\nflag them with an [outlined] suffix or add their callers found in the ARM64 code to their names")
+            .long("outlined")
+            .env("DUMP_SYMS_OUTLINED")
+            .value_parser(["keep", "flag", "callers"])
+            .default_value("keep"),
+        Arg::new("inner_publics")
+            .help("What to do with the PUBLIC records inside a function: skip the ones inside the symbol before\nthem (nearest), drop all of them or keep all of them")
+            .long("inner-publics")
+            .env("DUMP_SYMS_INNER_PUBLICS")
+            .value_parser(["nearest", "drop", "keep"])
+            .default_value("nearest"),
+        Arg::new("priority")
+            .help("The sources naming a symbol when they disagree about an address, the first one wins:\na comma-separated list of debug-info, linker-map, exports, publics and exception-data\n(the missing ones come after the others in this default order)")
+            .long("priority")
+            .env("DUMP_SYMS_PRIORITY")
+            .value_name("SOURCES"),
+        Arg::new("merge_funclets")
+            .help("Give the name of their parent function to the exception handling funclets\n(catch blocks, unwind actions, __except filters and __finally blocks)")
+            .long("merge-funclets")
+            .env("DUMP_SYMS_MERGE_FUNCLETS")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("merge_split_functions")
+            .help("Give the name of their parent function to the parts of the functions split by GCC and Clang\n(foo.cold and foo.part.0), a part right after its parent is merged in its FUNC record")
+            .long("merge-split-functions")
+            .env("DUMP_SYMS_MERGE_SPLIT_FUNCTIONS")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("leaf_cfi")
+            .help("Emit a STACK CFI record with the rule of a function entry for the code without unwind info\n(mainly the leaf functions), the ELF files only")
+            .long("leaf-cfi")
+            .env("DUMP_SYMS_LEAF_CFI")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("discover_functions")
+            .help("Look for the functions in the x86 code without symbols nor unwind info (e.g. hand-written\nassembly) from their prologues and the call targets, they're named fn_<address>")
+            .long("discover-functions")
+            .env("DUMP_SYMS_DISCOVER_FUNCTIONS")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new())
+            .hide(!cfg!(feature = "disasm")),
+        Arg::new("check_param_sizes")
+            .help("Check the parameter sizes of the x86 functions against the `ret n` instruction ending them\nand fix the wrong ones")
+            .long("check-param-sizes")
+            .env("DUMP_SYMS_CHECK_PARAM_SIZES")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new())
+            .hide(!cfg!(feature = "disasm")),
+        Arg::new("fastlink_objects")
+            .help("Get the functions of a PDB linked with /DEBUG:FASTLINK from the object files it references,\nwhen they're still on the disk (without lines): such a PDB only has the public symbols")
+            .long("fastlink-objects")
+            .env("DUMP_SYMS_FASTLINK_OBJECTS")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("input_encoding")
+            .help("The encoding of the symbol names and the source paths which aren't UTF-8, e.g. a PDB built on a\nJapanese or Chinese Windows: auto (Latin-1), a Windows code page (932, 936, 1252, ...) or an\nencoding label (shift_jis, gbk, big5, euc-kr, ...)")
+            .long("input-encoding")
+            .env("DUMP_SYMS_INPUT_ENCODING")
+            .default_value("auto"),
+        Arg::new("max_symbols")
+            .help("Fail when a file has more symbols (FUNC and PUBLIC records), to dump the untrusted files")
+            .long("max-symbols")
+            .env("DUMP_SYMS_MAX_SYMBOLS"),
+        Arg::new("max_lines")
+            .help("Fail when a file has more line records")
+            .long("max-lines")
+            .env("DUMP_SYMS_MAX_LINES"),
+        Arg::new("max_string_length")
+            .help("Fail when a symbol name or a source path is longer (in bytes)")
+            .long("max-string-length")
+            .env("DUMP_SYMS_MAX_STRING_LENGTH"),
+        Arg::new("max_expansion")
+            .help("Fail when the symbols are more than this number of times the size of the file")
+            .long("max-expansion")
+            .env("DUMP_SYMS_MAX_EXPANSION"),
+        Arg::new("gap_fill")
+            .help("What covers the code between the functions (padding, stripped code, ...): extend the functions\nof the linker map without a size up to the next symbol, keep the exact ranges (off) or start an\n<unknown> symbol at the end of the functions followed by a gap (unknown)")
+            .long("gap-fill")
+            .env("DUMP_SYMS_GAP_FILL")
+            .value_parser(["extend", "off", "unknown"])
+            .default_value("extend"),
+        Arg::new("no_gap_fill")
+            .help("Keep the exact ranges of the functions, the same as --gap-fill off")
+            .long("no-gap-fill")
+            .env("DUMP_SYMS_NO_GAP_FILL")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new())
+            .conflicts_with("gap_fill"),
+        Arg::new("report_gaps")
+            .help("Warn about the code of the executable sections which isn't covered by a FUNC or a PUBLIC\nrecord, with its size and the symbols around it: the gaps are the ones of the exact ranges, the\n<unknown> symbols and the section placeholders don't cover anything (use with --verbose warn)")
+            .long("report-gaps")
+            .env("DUMP_SYMS_REPORT_GAPS")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("sentinel")
+            .help("Where the PUBLIC record ending the last function of a PE file is added: just after the last\nsymbol, at the end of its section or nowhere (off)")
+            .long("sentinel")
+            .env("DUMP_SYMS_SENTINEL")
+            .value_parser(["after-last", "section-end", "off"])
+            .default_value("after-last"),
+        Arg::new("sentinel_name")
+            .help("The name of the PUBLIC record ending the last function instead of <unknown in module>")
+            .long("sentinel-name")
+            .env("DUMP_SYMS_SENTINEL_NAME")
+            .value_name("NAME"),
+        Arg::new("signal_frames")
+            .help("Emit a STACK SIGNAL record with the range of the signal frames after their STACK CFI INIT one,\nthe ELF files only (extension to the Breakpad format)")
+            .long("signal-frames")
+            .env("DUMP_SYMS_SIGNAL_FRAMES")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("merge_cfi")
+            .help("Merge a STACK CFI INIT record into the previous function when it's adjacent and has the same rules\n(smaller output for the large C++ binaries)")
+            .long("merge-cfi")
+            .env("DUMP_SYMS_MERGE_CFI")
+            .action(ArgAction::SetTrue)
+            .value_parser(BoolishValueParser::new()),
+        Arg::new("validate_cfi")
+            .help("Check the STACK CFI records (ranges in the module and not overlapping, .cfa and .ra rules,\nwell-formed expressions): warn about the invalid ones or fail with --validate-cfi=strict")
+            .long("validate-cfi")
+            .env("DUMP_SYMS_VALIDATE_CFI")
+            .value_parser(["off", "warn", "strict"])
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_value("off")
+            .default_missing_value("warn"),
+    ]
+}
+
+/// The subcommands using the network, there are none in the offline builds
+#[cfg(feature = "http")]
+fn http_commands() -> Vec<Command> {
+    vec![fetch_command(), upload_command(), serve_command()]
+}
+
+#[cfg(not(feature = "http"))]
+fn http_commands() -> Vec<Command> {
+    Vec::new()
+}
+
+#[cfg(feature = "http")]
+fn fetch_command() -> Command {
+    Command::new("fetch")
+        .about("Get the files from the cache or from the symbol servers without dumping them")
+        .arg(
+            Arg::new("filenames")
+                .help("Files to get (.dll, .exe, .pdb, .pd_, .so, .dbg)")
+                .required(true)
+                .num_args(1..),
+        )
+        .arg(
+            Arg::new("debug_id")
+                .help("The debug id of the pdb files")
+                .long("debug-id")
+                .env("DUMP_SYMS_DEBUG_ID"),
+        )
+        .arg(
+            Arg::new("code_id")
+                .help("The code id of the dll/exe files")
+                .long("code-id")
+                .env("DUMP_SYMS_CODE_ID"),
+        )
+        .group(
+            ArgGroup::new("id")
+                .args(["debug_id", "code_id"])
+                .required(true),
+        )
+        .arg(
+            Arg::new("output")
+                .help("Output file, the file is written in the current directory by default")
+                .short('o')
+                .long("output")
+                .env("DUMP_SYMS_OUTPUT"),
+        )
+}
+
+#[cfg(feature = "http")]
+fn upload_command() -> Command {
+    Command::new("upload")
+        .about("Upload symbol files to a symbol server, the directories are searched for .sym files")
+        .arg(
+            Arg::new("filenames")
+                .help("Symbol files or directories (e.g. a symbol store)")
+                .required(true)
+                .num_args(1..),
+        )
+        .arg(
+            Arg::new("url")
+                .help("The URL of the symbol server, the files are uploaded with PUT requests\nat URL/FILENAME.pdb/DEBUG_ID/FILENAME.sym")
+                .long("url")
+                .env("DUMP_SYMS_UPLOAD_URL")
+                .required(true),
+        )
+        .arg(
+            Arg::new("auth_token")
+                .help("The token sent in the Auth-Token header")
+                .long("auth-token")
+                .env("DUMP_SYMS_AUTH_TOKEN")
+                .hide_env_values(true),
+        )
+        .arg(
+            Arg::new("dry_run")
+                .help("Print the files and the URLs where they would be uploaded, without uploading them")
+                .long("dry-run")
+                .env("DUMP_SYMS_DRY_RUN")
+                .action(ArgAction::SetTrue)
+                .value_parser(BoolishValueParser::new()),
+        )
+}
+
+fn inspect_command() -> Command {
+    Command::new("inspect")
+        .about("Print the format, the arch, the identifiers and the kinds of debug data of files")
+        .arg(
+            Arg::new("filenames")
+                .help("Files to inspect")
+                .required(true)
+                .num_args(1..),
+        )
+}
+
+fn diff_command() -> Command {
+    Command::new("diff")
+        .about("Print the records which differ between two symbol files, regardless of their order")
+        .arg(
+            Arg::new("filenames")
+                .help("The two symbol files")
+                .required(true)
+                .num_args(2)
+                .value_names(["LEFT", "RIGHT"]),
+        )
+}
+
+/// The daemon listens on a unix socket, there's none on Windows
+#[cfg(unix)]
+fn daemon_commands() -> Vec<Command> {
+    let mut command = Command::new("daemon")
+        .about("Dump the files requested on a unix socket in the store, one JSON request per line:\n{\"command\": \"dump\", \"files\": [...]}, {\"command\": \"dump_buffer\", \"name\": ..., \"size\": ...}\nfollowed by the bytes of the file, {\"command\": \"status\"} or {\"command\": \"shutdown\"}")
+        .arg(
+            Arg::new("filenames")
+                .help("The path of the socket")
+                .value_name("SOCKET")
+                .required(true),
+        )
+        // The files to dump are in the requests
+        .args(dump_args().into_iter().skip(1))
+        .mut_arg("store", |arg| arg.required(true));
+    for id in [
+        "jobs_file",
+        "output",
+        "dry_run",
+        "list",
+        "list_arch",
+        "progress",
+    ] {
+        command = command.mut_arg(id, |arg| arg.hide(true));
+    }
+    vec![command]
+}
+
+#[cfg(not(unix))]
+fn daemon_commands() -> Vec<Command> {
+    Vec::new()
+}
+
+fn doctor_command() -> Command {
+    Command::new("doctor")
+        .about("Check that the symbol servers are reachable, that the caches and the store are writable\nand that the given files have the companion files required to dump them")
+        .arg(
+            Arg::new("filenames")
+                .help("Sample files to dump")
+                .num_args(0..),
+        )
+        .arg(
+            Arg::new("store")
+                .help("The store to check")
+                .short('s')
+                .long("store")
+                .env("DUMP_SYMS_STORE"),
+        )
+        .args(doctor_http_args())
+}
+
+#[cfg(feature = "http")]
+fn doctor_http_args() -> Vec<Arg> {
+    vec![
+        Arg::new("url")
+            .help("The URL of the symbol server where the files are uploaded")
+            .long("url")
+            .env("DUMP_SYMS_UPLOAD_URL"),
+        Arg::new("auth_token")
+            .help("The token sent in the Auth-Token header of the uploads")
+            .long("auth-token")
+            .env("DUMP_SYMS_AUTH_TOKEN")
+            .hide_env_values(true),
+    ]
+}
+
+#[cfg(not(feature = "http"))]
+fn doctor_http_args() -> Vec<Arg> {
+    Vec::new()
+}
+
+#[cfg(feature = "http")]
+fn serve_command() -> Command {
+    Command::new("serve")
+        .about("Serve a symbol store over HTTP")
+        .arg(
+            Arg::new("filenames")
+                .help("The directory of the symbol store")
+                .required(true)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("address")
+                .help("The address to listen on")
+                .long("address")
+                .env("DUMP_SYMS_ADDRESS")
+                .default_value("127.0.0.1:8080"),
+        )
 }
 
 fn main() {
-    let matches = cli().get_matches();
+    let root = cli().get_matches();
+    // The options of the flat invocation can't be used with a subcommand
+    if let Some((command, _)) = root.subcommand() {
+        for arg in dump_args() {
+            if root.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+                cli()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!(
+                            "The argument '--{}' can't be used before the subcommand '{}'",
+                            arg.get_long().unwrap_or_default(),
+                            command
+                        ),
+                    )
+                    .exit();
+            }
+        }
+    }
+    // The invocation without subcommand is the dump one
+    let (command, matches) = match root.subcommand() {
+        Some((command, matches)) => (command, matches),
+        None => ("dump", &root),
+    };
 
     let verbosity = match matches.get_one::<String>("verbose").unwrap().as_str() {
         "off" => LevelFilter::Off,
@@ -137,17 +696,54 @@ fn main() {
         _ => LevelFilter::Error,
     };
 
-    // Init the logger
-    let mut config = ConfigBuilder::new();
-    // Note that this will fail if we have more than 1 thread running, but this
-    // should be fine here at startup
-    let _res = config.set_time_offset_to_local();
-    let _ = TermLogger::init(
-        verbosity,
-        config.build(),
-        TerminalMode::Stderr,
-        ColorChoice::Auto,
-    );
+    let log_format: LogFormat = match matches.get_one::<String>("log_format").unwrap().parse() {
+        Ok(log_format) => log_format,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(FailureKind::Usage.exit_code());
+        }
+    };
+
+    let progress = match command {
+        "dump" => matches.get_one::<String>("progress").unwrap().as_str(),
+        _ => "none",
+    };
+    let progress = match progress {
+        "auto" if io::stderr().is_terminal() && log_format == LogFormat::Text => {
+            Ok(ProgressFormat::Bar)
+        }
+        "auto" => Ok(ProgressFormat::None),
+        progress => progress.parse(),
+    };
+    let progress = match progress {
+        Ok(progress) => progress,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(FailureKind::Usage.exit_code());
+        }
+    };
+
+    // Init the logger, the JSON progress events and the log records are in the same stream
+    let json_log = log_format == LogFormat::Json || progress == ProgressFormat::Json;
+    if json_log {
+        // The warnings are records too unless the log is off
+        let level = match verbosity {
+            LevelFilter::Off => LevelFilter::Off,
+            _ => verbosity.max(LevelFilter::Warn),
+        };
+        let _ = JsonLogger::init(level);
+    } else {
+        let mut config = ConfigBuilder::new();
+        // Note that this will fail if we have more than 1 thread running, but this
+        // should be fine here at startup
+        let _res = config.set_time_offset_to_local();
+        let _ = TermLogger::init(
+            verbosity,
+            config.build(),
+            TerminalMode::Stderr,
+            ColorChoice::Auto,
+        );
+    }
 
     // Set a panic hook to redirect to the logger
     panic::set_hook(Box::new(|panic_info| {
@@ -169,69 +765,429 @@ fn main() {
         error!("A panic occurred at {}:{}: {}", filename, line, cause);
     }));
 
-    let output = matches.get_one::<String>("output").map(String::as_str);
-    let filenames = to_vec(matches.get_many::<String>("filenames").unwrap());
+    // The cache command has no file
+    let filenames = matches
+        .try_get_many::<String>("filenames")
+        .ok()
+        .flatten()
+        .map(to_vec)
+        .unwrap_or_default();
     let symbol_server = matches
         .get_one::<String>("symbol_server")
         .map(String::as_str);
-    let store = matches.get_one::<String>("store").map(String::as_str);
-    let debug_id = matches.get_one::<String>("debug_id").map(String::as_str);
-    let code_id = matches.get_one::<String>("code_id").map(String::as_str);
-    let arch = matches.get_one::<String>("arch").unwrap().as_str();
-    let check_cfi = matches.get_flag("check_cfi");
-    let emit_inlines = matches.get_flag("inlines");
-    let mapping_var = matches.get_many("mapping_var").map(to_vec);
-    let mapping_src = matches.get_many("mapping_src").map(to_vec);
-    let mapping_dest = matches.get_many("mapping_dest").map(to_vec);
-    let mapping_file = matches
-        .get_one::<String>("mapping_file")
-        .map(String::as_str);
-    let num_jobs = if let Ok(num_jobs) = matches
-        .get_one::<String>("num_jobs")
-        .unwrap()
-        .parse::<usize>()
-    {
-        num_jobs
-    } else {
-        num_cpus::get()
-    };
+    let sym_cache = matches.get_one::<String>("sym_cache").map(String::as_str);
 
-    let action = if matches.get_flag("list_arch") {
-        Action::ListArch
-    } else {
-        let output = match (output, store) {
-            (Some(out), Some(store)) => dumper::Output::FileAndStore {
-                file: out.into(),
-                store_directory: store.into(),
-            },
-            (Some(out), None) => dumper::Output::File(out.into()),
-            (None, Some(store)) => dumper::Output::Store(store.into()),
-            (None, None) => dumper::Output::File(dumper::FileOutput::Stdout),
-        };
+    let cache_limits = match get_cache_limits(matches) {
+        Ok(cache_limits) => cache_limits,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(FailureKind::Usage.exit_code());
+        }
+    };
 
-        Action::Dump(dumper::Config {
-            output,
+    let action = match command {
+        "cache" => Action::CleanCache(dumper::Config {
+            symbol_server,
+            sym_cache,
+            cache_limits,
+            ..Default::default()
+        }),
+        #[cfg(feature = "http")]
+        "fetch" => Action::Fetch(dumper::Config {
+            output: dumper::Output::File(
+                matches
+                    .get_one::<String>("output")
+                    .map_or(dumper::FileOutput::Stdout, |out| out.as_str().into()),
+            ),
             symbol_server,
-            debug_id,
-            code_id,
-            arch,
-            num_jobs,
-            check_cfi,
-            emit_inlines,
-            mapping_var,
-            mapping_src,
-            mapping_dest,
-            mapping_file,
-        })
+            debug_id: matches.get_one::<String>("debug_id").map(String::as_str),
+            code_id: matches.get_one::<String>("code_id").map(String::as_str),
+            sym_cache,
+            cache_limits,
+            ..Default::default()
+        }),
+        #[cfg(feature = "http")]
+        "upload" => Action::Upload {
+            url: matches.get_one::<String>("url").unwrap(),
+            auth_token: matches.get_one::<String>("auth_token").map(String::as_str),
+            dry_run: matches.get_flag("dry_run"),
+        },
+        "inspect" => Action::Inspect,
+        "diff" => Action::Diff,
+        "doctor" => Action::Doctor(DoctorConfig {
+            symbol_server,
+            sym_cache,
+            store: matches.get_one::<String>("store").map(String::as_str),
+            upload_url: matches
+                .try_get_one::<String>("url")
+                .ok()
+                .flatten()
+                .map(String::as_str),
+            auth_token: matches
+                .try_get_one::<String>("auth_token")
+                .ok()
+                .flatten()
+                .map(String::as_str),
+        }),
+        #[cfg(feature = "http")]
+        "serve" => Action::Serve {
+            address: matches.get_one::<String>("address").unwrap(),
+        },
+        _ if matches.get_flag("list_arch") => Action::ListArch,
+        _ if matches.get_flag("list") => Action::List,
+        _ => {
+            let output = matches.get_one::<String>("output").map(String::as_str);
+            let store = matches.get_one::<String>("store").map(String::as_str);
+            let format = matches.get_one::<String>("format").unwrap();
+            let store_layout = matches.get_one::<String>("store_layout").unwrap();
+            let thunks = matches.get_one::<String>("thunks").unwrap();
+            let outlined = matches.get_one::<String>("outlined").unwrap();
+            let validate_cfi = matches.get_one::<String>("validate_cfi").unwrap();
+            let debug_id = matches.get_one::<String>("debug_id").map(String::as_str);
+            let code_id = matches.get_one::<String>("code_id").map(String::as_str);
+            let module_os = matches.get_one::<String>("module_os").map(String::as_str);
+            let module_arch = matches.get_one::<String>("module_arch").map(String::as_str);
+            let module_name = matches.get_one::<String>("module_name").map(String::as_str);
+            let module_debug_id = matches
+                .get_one::<String>("module_debug_id")
+                .map(String::as_str);
+            let arch = matches.get_one::<String>("arch").unwrap().as_str();
+            let check_cfi = matches.get_flag("check_cfi");
+            let incremental = matches.get_flag("incremental");
+            let published_servers = matches.get_many("published").map(to_vec);
+            let legacy_order = matches.get_flag("legacy_order");
+            let emit_inlines = matches.get_flag("inlines");
+            let labels = matches.get_flag("labels");
+            let languages = matches.get_flag("languages");
+            let data_symbols = matches.get_flag("data");
+            let git_info = matches.get_flag("git_info");
+            let metrics_sidecar = matches.get_flag("metrics_sidecar");
+            let merge_funclets = matches.get_flag("merge_funclets");
+            let merge_split_functions = matches.get_flag("merge_split_functions");
+            let leaf_cfi = matches.get_flag("leaf_cfi");
+            let discover_functions = matches.get_flag("discover_functions");
+            if discover_functions && !cfg!(feature = "disasm") {
+                eprintln!("--discover-functions requires the disasm feature");
+                std::process::exit(FailureKind::Usage.exit_code());
+            }
+            let check_param_sizes = matches.get_flag("check_param_sizes");
+            if check_param_sizes && !cfg!(feature = "disasm") {
+                eprintln!("--check-param-sizes requires the disasm feature");
+                std::process::exit(FailureKind::Usage.exit_code());
+            }
+            let fastlink_objects = matches.get_flag("fastlink_objects");
+            let limits = match get_parse_limits(matches) {
+                Ok(limits) => limits,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+            let encoding = match matches.get_one::<String>("input_encoding").unwrap().parse() {
+                Ok(encoding) => encoding,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+            let gap_fill = match matches.get_flag("no_gap_fill") {
+                true => "off",
+                false => matches.get_one::<String>("gap_fill").unwrap(),
+            };
+            let gap_fill = match gap_fill.parse() {
+                Ok(gap_fill) => gap_fill,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+            let report_gaps = matches.get_flag("report_gaps");
+            let sentinel = match matches.get_one::<String>("sentinel").unwrap().parse() {
+                Ok(sentinel) => sentinel,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+            let inner_publics = match matches.get_one::<String>("inner_publics").unwrap().parse() {
+                Ok(inner_publics) => inner_publics,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+            let priority = match matches
+                .get_one::<String>("priority")
+                .map_or(Ok(Default::default()), |p| p.parse())
+            {
+                Ok(priority) => priority,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+            let sentinel_name = matches
+                .get_one::<String>("sentinel_name")
+                .map(String::as_str);
+            if let Some(name) = sentinel_name.filter(|n| n.is_empty() || n.contains('\n')) {
+                eprintln!("Invalid name for the sentinel symbol: {:?}", name);
+                std::process::exit(FailureKind::Usage.exit_code());
+            }
+            let signal_frames = matches.get_flag("signal_frames");
+            let merge_cfi = matches.get_flag("merge_cfi");
+            let dry_run = matches.get_flag("dry_run");
+            let mapping_var = matches.get_many("mapping_var").map(to_vec);
+            let mapping_src = matches.get_many("mapping_src").map(to_vec);
+            let mapping_dest = matches.get_many("mapping_dest").map(to_vec);
+            let mapping_file = matches
+                .get_one::<String>("mapping_file")
+                .map(String::as_str);
+            let prune_lines = match PathGlobs::new(
+                &matches
+                    .get_many("prune_lines")
+                    .map(to_vec)
+                    .unwrap_or_default(),
+            ) {
+                Ok(prune_lines) => prune_lines,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+            let source_url_template = match matches
+                .get_one::<String>("source_url_template")
+                .map(|t| t.parse())
+                .transpose()
+            {
+                Ok(source_url_template) => source_url_template,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+            let num_jobs = if let Ok(num_jobs) = matches
+                .get_one::<String>("num_jobs")
+                .unwrap()
+                .parse::<usize>()
+            {
+                num_jobs
+            } else {
+                num_cpus::get()
+            };
+
+            let public_filter = match get_public_filter(matches) {
+                Ok(public_filter) => public_filter,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+
+            let compiland_filter = match get_compiland_filter(matches) {
+                Ok(compiland_filter) => compiland_filter,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+
+            let output = match (output, store) {
+                (Some(out), Some(store)) => dumper::Output::FileAndStore {
+                    file: out.into(),
+                    store_directory: store.into(),
+                },
+                (Some(out), None) => dumper::Output::File(out.into()),
+                (None, Some(store)) => dumper::Output::Store(store.into()),
+                (None, None) => dumper::Output::File(dumper::FileOutput::Stdout),
+            };
+
+            let format = match format.parse() {
+                Ok(format) => format,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+
+            let store_layout = match store_layout.parse() {
+                Ok(store_layout) => store_layout,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+
+            let thunks = match thunks.parse() {
+                Ok(thunks) => thunks,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+
+            let outlined = match outlined.parse() {
+                Ok(outlined) => outlined,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+
+            let validate_cfi = match validate_cfi.parse() {
+                Ok(validate_cfi) => validate_cfi,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            };
+
+            // The fields of the MODULE line are separated by spaces
+            for value in [module_os, module_arch].iter().flatten() {
+                if value.is_empty() || value.contains(char::is_whitespace) {
+                    eprintln!("Invalid value for the MODULE line: {:?}", value);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+            }
+
+            if let Some(id) = module_debug_id {
+                if DebugId::from_breakpad(id).is_err() {
+                    eprintln!("Invalid debug id: {}", id);
+                    std::process::exit(FailureKind::Usage.exit_code());
+                }
+                let msg = format!(
+                "the debug id {} is forced on the MODULE line, nothing checks that the symbols match the module",
+                id
+            );
+                if json_log {
+                    logging::log_event(Level::Warn, &msg);
+                } else {
+                    eprintln!("Warning: {}", msg);
+                }
+            }
+
+            let config = dumper::Config {
+                output,
+                format,
+                store_layout,
+                legacy_order,
+                symbol_server,
+                debug_id,
+                code_id,
+                module_os,
+                module_arch,
+                module_name,
+                module_debug_id,
+                arch,
+                num_jobs,
+                check_cfi,
+                incremental,
+                published_servers,
+                sym_cache,
+                cache_limits,
+                emit_inlines,
+                labels,
+                languages,
+                data_symbols,
+                public_filter,
+                compiland_filter,
+                thunks,
+                outlined,
+                merge_funclets,
+                merge_split_functions,
+                leaf_cfi,
+                signal_frames,
+                merge_cfi,
+                validate_cfi,
+                discover_functions,
+                check_param_sizes,
+                fastlink_objects,
+                limits,
+                encoding,
+                gap_fill,
+                report_gaps,
+                sentinel,
+                sentinel_name,
+                inner_publics,
+                priority,
+                progress,
+                dry_run,
+                metrics: None,
+                metrics_sidecar,
+                mapping_var,
+                mapping_src,
+                mapping_dest,
+                mapping_file,
+                prune_lines,
+                source_url_template,
+                git_info,
+            };
+            match matches.get_one::<String>("jobs_file") {
+                #[cfg(unix)]
+                _ if command == "daemon" => Action::Daemon(config),
+                Some(jobs_file) => Action::DumpJobs { config, jobs_file },
+                None => Action::Dump(config),
+            }
+        }
     };
 
     if let Err(e) = action.action(&filenames) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+        if json_log {
+            logging::log_event(Level::Error, &e.to_string());
+        } else {
+            eprintln!("{}", e);
+        }
+        std::process::exit(FailureKind::of(&e).exit_code());
     }
 }
 
-fn to_vec(values: clap::parser::ValuesRef<String>) -> Vec<&str> {
+fn get_cache_limits(matches: &clap::ArgMatches) -> common::Result<CacheLimits> {
+    let max_size = matches.get_one::<String>("cache_max_size");
+    let max_age = matches.get_one::<String>("cache_max_age");
+
+    Ok(CacheLimits {
+        max_size: max_size.map(|s| sym_cache::parse_size(s)).transpose()?,
+        max_age: max_age.map(|s| sym_cache::parse_age(s)).transpose()?,
+    })
+}
+
+fn get_parse_limits(matches: &clap::ArgMatches) -> common::Result<ParseLimits> {
+    let get = |name: &str| -> common::Result<Option<u64>> {
+        matches
+            .get_one::<String>(name)
+            .map(|s| {
+                s.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid --{}: {}", name.replace('_', "-"), s))
+            })
+            .transpose()
+    };
+
+    Ok(ParseLimits {
+        max_symbols: get("max_symbols")?.map(|n| n as usize),
+        max_lines: get("max_lines")?.map(|n| n as usize),
+        max_string_len: get("max_string_length")?.map(|n| n as usize),
+        max_expansion: get("max_expansion")?,
+    })
+}
+
+fn get_public_filter(matches: &clap::ArgMatches) -> common::Result<PublicFilter> {
+    let include = matches.get_many("public_include").map(to_vec);
+    let exclude = matches.get_many("public_exclude").map(to_vec);
+
+    PublicFilter::new(
+        &include.unwrap_or_default(),
+        &exclude.unwrap_or_default(),
+        matches.get_flag("no_default_public_filter"),
+    )
+}
+
+fn get_compiland_filter(matches: &clap::ArgMatches) -> common::Result<CompilandFilter> {
+    let include = matches.get_many("include_compiland").map(to_vec);
+    let exclude = matches.get_many("exclude_compiland").map(to_vec);
+
+    CompilandFilter::new(&include.unwrap_or_default(), &exclude.unwrap_or_default())
+}
+
+fn to_vec(values: clap::parser::ValuesRef<'_, String>) -> Vec<&str> {
     values.map(String::as_str).collect()
 }
 
@@ -239,3 +1195,135 @@ fn to_vec(values: clap::parser::ValuesRef<String>) -> Vec<&str> {
 fn verify_cli() {
     cli().debug_assert();
 }
+
+#[test]
+fn test_env_options() {
+    std::env::set_var("DUMP_SYMS_MERGE_CFI", "1");
+    std::env::set_var("DUMP_SYMS_THUNKS", "collapse");
+    std::env::set_var("DUMP_SYMS_NUM_JOBS", "3");
+
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "-j", "2", "foo.pdb"])
+        .unwrap();
+    assert!(matches.get_flag("merge_cfi"));
+    assert!(!matches.get_flag("leaf_cfi"));
+    assert_eq!(matches.get_one::<String>("thunks").unwrap(), "collapse");
+    // The command line wins
+    assert_eq!(matches.get_one::<String>("num_jobs").unwrap(), "2");
+
+    std::env::set_var("DUMP_SYMS_MERGE_CFI", "no");
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "foo.pdb"])
+        .unwrap();
+    assert!(!matches.get_flag("merge_cfi"));
+    assert_eq!(matches.get_one::<String>("num_jobs").unwrap(), "3");
+
+    std::env::set_var("DUMP_SYMS_NO_GAP_FILL", "true");
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "foo.pdb"])
+        .unwrap();
+    std::env::remove_var("DUMP_SYMS_NO_GAP_FILL");
+    assert!(matches.get_flag("no_gap_fill"));
+}
+
+#[test]
+fn test_subcommands() {
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "--inlines", "foo.pdb"])
+        .unwrap();
+    assert!(matches.subcommand().is_none());
+    assert!(matches.get_flag("inlines"));
+
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "dump", "--inlines", "foo.pdb"])
+        .unwrap();
+    let (command, sub) = matches.subcommand().unwrap();
+    assert_eq!(command, "dump");
+    assert!(sub.get_flag("inlines"));
+
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "--verbose", "info", "inspect", "foo.pdb"])
+        .unwrap();
+    let (command, sub) = matches.subcommand().unwrap();
+    assert_eq!(command, "inspect");
+    assert_eq!(sub.get_one::<String>("verbose").unwrap(), "info");
+
+    // The files are optional for the doctor
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "doctor", "--store", "store"])
+        .unwrap();
+    let (command, sub) = matches.subcommand().unwrap();
+    assert_eq!(command, "doctor");
+    assert!(sub.get_many::<String>("filenames").is_none());
+
+    // The files come from the job file
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "--jobs-file", "jobs.csv", "-s", "store"])
+        .unwrap();
+    assert_eq!(matches.get_one::<String>("jobs_file").unwrap(), "jobs.csv");
+    assert!(matches.get_many::<String>("filenames").is_none());
+
+    // The exclusive options are checked per subcommand
+    for args in [
+        &["dump_syms", "--jobs-file", "jobs.csv"][..],
+        &[
+            "dump_syms",
+            "--jobs-file",
+            "jobs.csv",
+            "-s",
+            "store",
+            "foo.pdb",
+        ],
+        &["dump_syms", "diff", "a.sym"],
+        &["dump_syms", "inspect", "--inlines", "foo.pdb"],
+        &["dump_syms", "--list-arch", "-o", "out", "foo"],
+    ] {
+        assert!(cli().try_get_matches_from(args).is_err(), "{:?}", args);
+    }
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_http_subcommands() {
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "fetch", "--debug-id", "ID", "foo.pdb"])
+        .unwrap();
+    let (command, sub) = matches.subcommand().unwrap();
+    assert_eq!(command, "fetch");
+    assert_eq!(sub.get_one::<String>("debug_id").unwrap(), "ID");
+
+    for args in [
+        &["dump_syms", "fetch", "foo.pdb"][..],
+        &[
+            "dump_syms",
+            "fetch",
+            "--debug-id",
+            "A",
+            "--code-id",
+            "B",
+            "foo",
+        ],
+        &["dump_syms", "upload", "store"],
+    ] {
+        assert!(cli().try_get_matches_from(args).is_err(), "{:?}", args);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_subcommand() {
+    let matches = cli()
+        .try_get_matches_from(["dump_syms", "daemon", "--inlines", "-s", "store", "d.sock"])
+        .unwrap();
+    let (command, sub) = matches.subcommand().unwrap();
+    assert_eq!(command, "daemon");
+    assert!(sub.get_flag("inlines"));
+    assert_eq!(sub.get_one::<String>("filenames").unwrap(), "d.sock");
+
+    for args in [
+        &["dump_syms", "daemon", "d.sock"][..],
+        &["dump_syms", "daemon", "-s", "store", "a.sock", "b.sock"],
+    ] {
+        assert!(cli().try_get_matches_from(args).is_err(), "{:?}", args);
+    }
+}
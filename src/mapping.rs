@@ -4,15 +4,17 @@
 // copied, modified, or distributed except according to those terms.
 
 use hashbrown::{HashMap, HashSet};
-use log::warn;
+use log::{debug, warn};
 use regex::Regex;
 use serde::Deserialize;
 use sha2::{Digest, Sha512};
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::Mutex;
 
 use crate::common;
 use crate::utils;
+use crate::vcs::GitCheckouts;
 
 #[derive(Debug, Default, Deserialize)]
 struct Mapping {
@@ -149,14 +151,9 @@ impl PathMappingGenerator {
                     match action.kind {
                         ActionKind::Group(group) => {
                             if let Some(group) = caps.get(group) {
-                                let group = group.as_str();
-
-                                if cfg!(windows) {
-                                    let group = group.replace('\\', "/");
-                                    buf.extend_from_slice(group.as_bytes());
-                                } else {
-                                    buf.extend_from_slice(group.as_bytes());
-                                }
+                                // The Windows paths can be dumped on any host
+                                let group = group.as_str().replace('\\', "/");
+                                buf.extend_from_slice(group.as_bytes());
                             } else {
                                 return Ok(None);
                             }
@@ -336,10 +333,199 @@ impl PathMappings {
     }
 }
 
+/// The variables of the source files section of the srcsrv stream of a PDB by
+/// lowercase path: `var1` is the path itself and the next ones are the fields
+/// after it (e.g. the revision and the path in the repository).
+pub type SrcSrvFiles = HashMap<String, Vec<String>>;
+
+/// Get the source files of the srcsrv stream of a PDB
+#[cfg_attr(not(feature = "pdb"), allow(dead_code))]
+pub(crate) fn parse_srcsrv(data: &str) -> SrcSrvFiles {
+    let mut files = SrcSrvFiles::default();
+    let mut in_files = false;
+    for line in data.lines() {
+        let line = line.trim_end();
+        if let Some(section) = line.strip_prefix("SRCSRV:") {
+            in_files = section.trim_start().starts_with("source files");
+            continue;
+        }
+        if !in_files || line.is_empty() {
+            continue;
+        }
+        let vars: Vec<_> = line.split('*').map(String::from).collect();
+        files.insert(vars[0].to_lowercase(), vars);
+    }
+    files
+}
+
+/// A URL with `{var}` placeholders to rewrite the FILE records into fetchable
+/// URLs when they're written, e.g. `https://raw.githubusercontent.com/org/repo/{rev}/{path}`
+#[derive(Clone, Debug)]
+pub struct SourceUrlTemplate {
+    template: String,
+    /// The chunks of the template and the name of the variable after them
+    chunks: Vec<(String, Option<String>)>,
+}
+
+impl FromStr for SourceUrlTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chunks = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => anyhow::bail!("Unclosed variable in the source URL template: {}", s),
+            };
+            let var = &rest[start + 1..end];
+            anyhow::ensure!(
+                !var.is_empty() && !var.contains('{'),
+                "Invalid variable {{{}}} in the source URL template: {}",
+                var,
+                s
+            );
+            chunks.push((rest[..start].to_string(), Some(var.to_string())));
+            rest = &rest[end + 1..];
+        }
+        chunks.push((rest.to_string(), None));
+
+        Ok(Self {
+            template: s.to_string(),
+            chunks,
+        })
+    }
+}
+
+impl SourceUrlTemplate {
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+}
+
+/// Rewrite the source paths with a template, the variables come from the
+/// command line (`--mapping-var`), from the srcsrv stream of the PDB files and
+/// from the git checkout containing the file on the local machine (`rev` for the
+/// revision of HEAD and `path` for the path in the checkout).
+#[derive(Clone, Debug)]
+pub(crate) struct SourceUrls {
+    template: SourceUrlTemplate,
+    variables: HashMap<String, String>,
+    checkouts: GitCheckouts,
+}
+
+impl SourceUrls {
+    pub(crate) fn new(template: SourceUrlTemplate, variables: &Option<Vec<&str>>) -> Self {
+        // The invalid pairs are reported with the path mappings
+        let variables = variables
+            .iter()
+            .flatten()
+            .filter_map(|var| var.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Self {
+            template,
+            variables,
+            checkouts: GitCheckouts::default(),
+        }
+    }
+
+    /// The URL of a source file, none when a variable of the template is unknown
+    pub(crate) fn apply(&self, file: &str, srcsrv: Option<&SrcSrvFiles>) -> Option<String> {
+        let vars = srcsrv.and_then(|files| files.get(&file.to_lowercase()));
+        let mut git = None;
+        let mut url = String::with_capacity(file.len() * 2);
+        for (chunk, var) in &self.template.chunks {
+            url.push_str(chunk);
+            let var = match var {
+                Some(var) => var,
+                None => continue,
+            };
+            let value = if let Some(value) = self.variables.get(var) {
+                value.clone()
+            } else if let Some(value) = var
+                .strip_prefix("var")
+                .and_then(|n| n.parse::<usize>().ok())
+                .and_then(|n| vars?.get(n.checked_sub(1)?))
+            {
+                value.clone()
+            } else if var == "rev" || var == "path" {
+                let git = git
+                    .get_or_insert_with(|| self.checkouts.get_file(file))
+                    .as_ref()?;
+                if var == "rev" {
+                    git.rev.clone()
+                } else {
+                    git.path.clone()
+                }
+            } else {
+                debug!("No value for {{{}}} in the URL of {}", var, file);
+                return None;
+            };
+            url.push_str(&value);
+        }
+        Some(url)
+    }
+}
+
+/// Glob patterns matched on the source paths, e.g. `**/include/c++/**`: `**`
+/// matches any number of directories, `*` and `?` match in a path component.
+/// The backslashes of the Windows paths are matched as slashes and the case is
+/// ignored.
+#[derive(Clone, Debug, Default)]
+pub struct PathGlobs {
+    globs: Vec<Regex>,
+}
+
+impl PathGlobs {
+    pub fn new(globs: &[&str]) -> common::Result<Self> {
+        let globs = globs
+            .iter()
+            .map(|glob| glob_to_regex(glob))
+            .collect::<common::Result<_>>()?;
+        Ok(Self { globs })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        let path = path.replace('\\', "/");
+        self.globs.iter().any(|re| re.is_match(&path))
+    }
+}
+
+fn glob_to_regex(glob: &str) -> common::Result<Regex> {
+    anyhow::ensure!(!glob.is_empty(), "Empty glob pattern");
+    let glob = glob.replace('\\', "/");
+    let mut re = String::from("(?i)^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    Ok(Regex::new(&re)?)
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use std::fs;
 
     #[test]
     fn test_path_mapping_generator() {
@@ -378,4 +564,79 @@ mod tests {
             assert_eq!(mappings.map(path).unwrap().unwrap(), expected.to_string())
         }
     }
+
+    #[test]
+    fn test_source_url_template() {
+        let template: SourceUrlTemplate = "https://src/{rev}/{path}?repo={var2}".parse().unwrap();
+        assert!("https://src/{rev".parse::<SourceUrlTemplate>().is_err());
+        assert!("https://src/{}".parse::<SourceUrlTemplate>().is_err());
+
+        let srcsrv = parse_srcsrv(
+            "SRCSRV: ini ------------------------------------------------
+VERSION=2
+SRCSRV: variables ------------------------------------------
+SRCSRVTRG=%var2%
+SRCSRV: source files ---------------------------------------
+C:\\build\\src\\foo.cpp*mozilla-central*abcdef*src/foo.cpp
+SRCSRV: end ------------------------------------------------
+",
+        );
+        assert_eq!(srcsrv.len(), 1);
+
+        // The explicit variables win
+        let urls = SourceUrls::new(template, &Some(vec!["rev=123", "path=a.cpp"]));
+        assert_eq!(
+            urls.apply("c:\\build\\src\\FOO.cpp", Some(&srcsrv))
+                .as_deref(),
+            Some("https://src/123/a.cpp?repo=mozilla-central")
+        );
+        // The FILE record is kept when a variable is unknown
+        assert_eq!(urls.apply("/src/bar.cpp", Some(&srcsrv)), None);
+
+        let urls = SourceUrls::new("https://src/{var3}/{var4}".parse().unwrap(), &None);
+        assert_eq!(
+            urls.apply("C:\\build\\src\\foo.cpp", Some(&srcsrv))
+                .as_deref(),
+            Some("https://src/abcdef/src/foo.cpp")
+        );
+    }
+
+    #[test]
+    fn test_source_url_git() {
+        let tmp_dir = tempfile::Builder::new().prefix("git").tempdir().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join(".git/refs/heads")).unwrap();
+        fs::create_dir_all(root.join("src/dir")).unwrap();
+        fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(
+            root.join(".git/packed-refs"),
+            "# pack-refs\nabcdef refs/heads/main\n",
+        )
+        .unwrap();
+
+        let urls = SourceUrls::new("https://src/{rev}/{path}".parse().unwrap(), &None);
+        let file = root.join("src/dir/foo.cpp");
+        assert_eq!(
+            urls.apply(file.to_str().unwrap(), None).as_deref(),
+            Some("https://src/abcdef/src/dir/foo.cpp")
+        );
+
+        // Outside of a checkout
+        assert_eq!(urls.apply("/nonexistent/foo.cpp", None), None);
+    }
+
+    #[test]
+    fn test_path_globs() {
+        let globs = PathGlobs::new(&["**/include/c++/**", "**/msvc/**", "/src/*.h"]).unwrap();
+        assert!(globs.is_match("/usr/include/c++/9/bits/stl_vector.h"));
+        assert!(globs.is_match("include/c++/9/vector"));
+        assert!(globs.is_match("C:\\Program Files\\VC\\Tools\\MSVC\\14.16.27023\\include\\xstring"));
+        assert!(globs.is_match("/src/foo.h"));
+        assert!(!globs.is_match("/src/dir/foo.h"));
+        assert!(!globs.is_match("/usr/include/stdio.h"));
+        assert!(!globs.is_match("/home/me/msvc.cpp"));
+
+        assert!(PathGlobs::new(&[""]).is_err());
+        assert!(PathGlobs::default().is_empty());
+    }
 }
@@ -0,0 +1,261 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{json, Value};
+use std::cell::Cell;
+use std::ops::AddAssign;
+use std::time::Duration;
+
+use crate::common;
+
+/// The counters of a dumped module, they're reported to the `MetricsSink` of
+/// the configuration once its symbols are stored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleMetrics {
+    pub name: String,
+    pub debug_id: String,
+    pub code_id: Option<String>,
+    pub os: String,
+    pub cpu: String,
+    /// The FUNC records
+    pub functions: usize,
+    /// The PUBLIC records
+    pub publics: usize,
+    /// The FILE records
+    pub files: usize,
+    /// The line records of the functions
+    pub lines: usize,
+    /// The INLINE records
+    pub inlines: usize,
+    /// The STACK records
+    pub cfi: usize,
+    /// The size of the symbols in bytes
+    pub size: usize,
+    /// The warnings logged while parsing and storing the module, they're only
+    /// counted when the logger is wrapped in `CountWarnings`
+    pub warnings: usize,
+    /// The time spent to parse the files of the module, it's zero when the
+    /// module is from the symbols cache
+    pub parse_duration: Duration,
+    pub store_duration: Duration,
+}
+
+impl ModuleMetrics {
+    /// Count the records of symbols already generated, e.g. the ones from the
+    /// symbols cache
+    pub fn from_sym(data: &[u8]) -> Self {
+        let mut metrics = Self {
+            size: data.len(),
+            ..Default::default()
+        };
+        let mut in_func = false;
+        for line in String::from_utf8_lossy(data).lines() {
+            let mut toks = line.split(' ');
+            let first = toks.next().unwrap_or_default();
+            match first {
+                "MODULE" => {
+                    let toks: Vec<_> = line.splitn(5, ' ').collect();
+                    if toks.len() == 5 {
+                        metrics.os = toks[1].to_string();
+                        metrics.cpu = toks[2].to_string();
+                        metrics.debug_id = toks[3].to_string();
+                        metrics.name = toks[4].to_string();
+                    }
+                }
+                "INFO" if toks.next() == Some("CODE_ID") => {
+                    metrics.code_id = toks.next().map(ToString::to_string);
+                }
+                "FILE" => metrics.files += 1,
+                "FUNC" => {
+                    metrics.functions += 1;
+                    in_func = true;
+                    continue;
+                }
+                "PUBLIC" => metrics.publics += 1,
+                "INLINE" => metrics.inlines += 1,
+                "STACK" => metrics.cfi += 1,
+                _ if in_func
+                    && !first.is_empty()
+                    && first.chars().all(|c| c.is_ascii_hexdigit()) =>
+                {
+                    metrics.lines += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            // The INLINE records are in the functions too
+            in_func &= first == "INLINE";
+        }
+        metrics
+    }
+
+    /// The counters as written in the JSON sidecar files, the durations are
+    /// in milliseconds
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "debug_id": self.debug_id,
+            "code_id": self.code_id,
+            "os": self.os,
+            "cpu": self.cpu,
+            "functions": self.functions,
+            "publics": self.publics,
+            "files": self.files,
+            "lines": self.lines,
+            "inlines": self.inlines,
+            "cfi": self.cfi,
+            "size": self.size,
+            "warnings": self.warnings,
+            "parse_ms": self.parse_duration.as_millis() as u64,
+            "store_ms": self.store_duration.as_millis() as u64,
+        })
+    }
+}
+
+/// Receive the counters of the dumped modules, e.g. to export them to a
+/// monitoring system in a service embedding the library.
+pub trait MetricsSink: Send + Sync {
+    fn module_dumped(&self, metrics: &ModuleMetrics);
+}
+
+/// What it cost to parse the files of a module
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ParseStats {
+    pub duration: Duration,
+    pub warnings: usize,
+}
+
+impl AddAssign for ParseStats {
+    fn add_assign(&mut self, other: Self) {
+        self.duration += other.duration;
+        self.warnings += other.warnings;
+    }
+}
+
+thread_local! {
+    /// The number of warnings logged by the current thread
+    static WARNINGS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The number of warnings logged by the current thread so far
+pub fn warnings() -> usize {
+    WARNINGS.with(Cell::get)
+}
+
+/// A logger which counts the warnings of the current thread before passing
+/// the records to another one
+pub struct CountWarnings<L> {
+    inner: L,
+}
+
+impl<L: Log + 'static> CountWarnings<L> {
+    pub fn new(inner: L) -> Self {
+        Self { inner }
+    }
+
+    /// Set the logger, the warnings are counted even when `level` is lower
+    pub fn init(inner: L, level: LevelFilter) -> common::Result<()> {
+        log::set_boxed_logger(Box::new(Self::new(inner)))?;
+        log::set_max_level(level.max(LevelFilter::Warn));
+        Ok(())
+    }
+}
+
+impl<L: Log> Log for CountWarnings<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() == Level::Warn || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() == Level::Warn {
+            WARNINGS.with(|warnings| warnings.set(warnings.get() + 1));
+        }
+        if self.inner.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<String>>);
+
+    impl Log for Collect {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Error
+        }
+
+        fn log(&self, record: &Record) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_from_sym() {
+        let sym = b"MODULE windows x86_64 0123456789ABCDEF0123456789ABCDEF1 foo.pdb
+INFO CODE_ID 5F3A1B2C8D34000 foo.dll
+INFO GENERATOR mozilla/dump_syms 2.3.4
+FILE 0 foo.cpp
+FILE 1 bar.h
+INLINE_ORIGIN 0 bar()
+FUNC 1000 20 0 foo()
+INLINE 0 12 1 0 1008 4
+1000 8 10 0
+1008 4 3 1
+100c 14 12 0
+PUBLIC 1020 0 baz
+STACK CFI INIT 1000 20 .cfa: $rsp 8 +
+STACK CFI 1004 .cfa: $rsp 16 +
+";
+        let metrics = ModuleMetrics::from_sym(sym);
+        assert_eq!(
+            metrics,
+            ModuleMetrics {
+                name: "foo.pdb".to_string(),
+                debug_id: "0123456789ABCDEF0123456789ABCDEF1".to_string(),
+                code_id: Some("5F3A1B2C8D34000".to_string()),
+                os: "windows".to_string(),
+                cpu: "x86_64".to_string(),
+                functions: 1,
+                publics: 1,
+                files: 2,
+                lines: 3,
+                inlines: 1,
+                cfi: 2,
+                size: sym.len(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(metrics.to_json()["parse_ms"], 0);
+    }
+
+    #[test]
+    fn test_count_warnings() {
+        let logger = CountWarnings::new(Collect::default());
+        let before = warnings();
+        for level in [Level::Warn, Level::Error, Level::Info, Level::Warn] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .args(format_args!("{}", level))
+                    .build(),
+            );
+        }
+
+        assert_eq!(warnings() - before, 2);
+        assert_eq!(*logger.inner.0.lock().unwrap(), vec!["ERROR"]);
+    }
+}
@@ -3,49 +3,95 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use log::error;
+use hashbrown::{HashMap, HashSet};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
 use std::collections::btree_map;
 use std::fmt::{Display, Formatter};
 use std::io::Write;
 use std::sync::Arc;
 use symbolic::cfi::AsciiCfiWriter;
+use symbolic::common::Arch;
+use symbolic::common::DebugId;
 use symbolic::debuginfo::Object;
 
 use super::source::{SourceFiles, SourceMap};
-use super::symbol::{ContainsSymbol, Symbols};
-use crate::collector::Collector;
-use crate::common;
+use super::symbol::{ContainsSymbol, DataSymbols, Symbols};
+use crate::cfi::{self, CfiValidation};
+use crate::collector::{CollectOptions, Collector, GapFill, InnerPublics, ParseLimits};
+use crate::common::{self, DemangleCache, FailureKind};
+use crate::dwarf;
 use crate::inline_origins::{merge_inline_origins, InlineOrigins};
-use crate::mapping::PathMappings;
+use crate::json;
+use crate::mapping::{PathGlobs, PathMappings, SourceUrls, SrcSrvFiles};
+use crate::metrics::{ModuleMetrics, ParseStats};
 use crate::platform::Platform;
+use crate::stabs;
+use crate::vcs::{GitCheckouts, GitFile};
+#[cfg(feature = "pdb")]
+use crate::windows;
+use crate::windows::map::LinkerMap;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Type {
     Stripped,
     DebugInfo,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ObjectInfo {
     symbols: Symbols,
+    data: DataSymbols,
     files: SourceMap,
     inline_origins: Vec<String>,
     file_name: String,
-    cpu: &'static str,
+    cpu: String,
     debug_id: String,
     code_id: Option<String>,
     pe_name: Option<String>,
     stack: String,
     bin_type: Type,
     platform: Platform,
+    /// The options used to collect the symbols, see `CollectOptions::features`
+    features: Vec<String>,
+    /// The OS written on the MODULE line instead of the platform
+    os: Option<String>,
+    /// The source files of the srcsrv stream of a PDB, for the source URLs
+    srcsrv: Option<SrcSrvFiles>,
+    /// The git checkouts of the source files by file number, they're found when
+    /// the symbols are written
+    #[serde(skip)]
+    git_files: HashMap<u32, GitFile>,
+    /// The time and the warnings of the parsing, for the metrics
+    #[serde(skip)]
+    parse_stats: ParseStats,
 }
 
 impl Display for ObjectInfo {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.write_sym(f, false)
+    }
+}
+
+/// Display the symbols in the order used by the Breakpad dump_syms tools:
+/// all the FUNC records sorted by address and then all the PUBLIC ones.
+struct LegacyOrder<'a>(&'a ObjectInfo);
+
+impl Display for LegacyOrder<'_> {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.0.write_sym(f, true)
+    }
+}
+
+impl ObjectInfo {
+    fn write_sym(&self, f: &mut Formatter, legacy_order: bool) -> std::fmt::Result {
         writeln!(
             f,
             "MODULE {} {} {} {}",
-            self.platform, self.cpu, self.debug_id, self.file_name
+            self.os.as_deref().unwrap_or(&self.platform.to_string()),
+            self.cpu,
+            self.debug_id,
+            self.file_name
         )?;
 
         if let Some(code_id) = self.code_id.as_ref() {
@@ -54,14 +100,34 @@ impl Display for ObjectInfo {
             writeln!(f, "{}", line.trim())?;
         }
 
-        writeln!(
+        // The enabled features are appended to the version: `+inlines +leaf-cfi`
+        write!(
             f,
             "INFO GENERATOR mozilla/dump_syms {}",
             env!("CARGO_PKG_VERSION")
         )?;
+        let legacy = legacy_order.then_some("legacy-order");
+        for feature in self.features.iter().map(String::as_str).chain(legacy) {
+            write!(f, " +{}", feature)?;
+        }
+        writeln!(f)?;
+
+        if let Some((source, level)) = self.get_provenance() {
+            writeln!(f, "INFO PROVENANCE {} {}", source, level)?;
+        }
 
         for (n, file_name) in self.files.get_mapping().iter().enumerate() {
             writeln!(f, "FILE {} {}", n, file_name)?;
+            if let Some(git) = self.git_files.get(&(n as u32)) {
+                writeln!(
+                    f,
+                    "FILE_GIT {} {} {} {}",
+                    n,
+                    git.rev,
+                    git.remote.as_deref().unwrap_or("-"),
+                    git.path
+                )?;
+            }
         }
 
         for (n, function_name) in self.inline_origins.iter().enumerate() {
@@ -73,23 +139,62 @@ impl Display for ObjectInfo {
             writeln!(f, "INLINE_ORIGIN {} {}", n, function_name)?;
         }
 
-        for (_, sym) in self.symbols.iter() {
-            write!(f, "{}", sym)?;
+        if legacy_order {
+            for sym in self.symbols.values().filter(|sym| !sym.is_public) {
+                write!(f, "{}", sym)?;
+            }
+            for sym in self.symbols.values().filter(|sym| sym.is_public) {
+                write!(f, "{}", sym)?;
+            }
+        } else {
+            for (_, sym) in self.symbols.iter() {
+                write!(f, "{}", sym)?;
+            }
+        }
+
+        for (_, data) in self.data.iter() {
+            write!(f, "{}", data)?;
         }
 
         write!(f, "{}", self.stack)?;
 
         Ok(())
     }
+
+    /// Where the symbols come from and what they contain when there's no debug
+    /// info: a PE without its pdb or a stripped ELF or Mach-O file only has the
+    /// public symbols and the functions from the CFI (or the exception data)
+    fn get_provenance(&self) -> Option<(&'static str, &'static str)> {
+        if self.bin_type != Type::Stripped {
+            return None;
+        }
+        let source = match self.platform {
+            Platform::Win => "pe",
+            Platform::Linux => "elf",
+            Platform::Mac => "macho",
+        };
+        let level = match self.symbols.values().any(|sym| !sym.is_public) {
+            true => "functions",
+            false => "publics",
+        };
+        Some((source, level))
+    }
 }
 
-fn get_stack_info(pdb: Option<&Object>, pe: Option<&Object>) -> String {
+fn get_stack_info(pdb: Option<&Object>, pe: Option<&Object>, signal_frames: bool) -> String {
     let mut buf = Vec::new();
-    let mut cfi_writer = AsciiCfiWriter::new(&mut buf);
 
+    // The DWARF CFI of the ELF files is converted here, symbolic is used for the others
     let result = match (pdb, pe) {
-        (_, Some(pe)) if pe.has_unwind_info() => cfi_writer.process(pe),
-        (Some(pdb), _) if pdb.has_unwind_info() => cfi_writer.process(pdb),
+        (_, Some(pe)) if pe.has_unwind_info() => AsciiCfiWriter::new(&mut buf)
+            .process(pe)
+            .map_err(Into::into),
+        (Some(Object::Elf(elf)), _) if elf.has_unwind_info() => {
+            cfi::write_elf_cfi(elf, signal_frames, &mut buf)
+        }
+        (Some(pdb), _) if pdb.has_unwind_info() => AsciiCfiWriter::new(&mut buf)
+            .process(pdb)
+            .map_err(Into::into),
         _ => Ok(()),
     };
 
@@ -101,38 +206,128 @@ fn get_stack_info(pdb: Option<&Object>, pe: Option<&Object>) -> String {
 }
 
 impl ObjectInfo {
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(not(feature = "pdb"), allow(unused_variables))]
     pub fn from_object(
         main_object: &Object,
         main_file_name: &str,
         pe_object: Option<&Object>,
         pe_file_name: Option<&str>,
+        dwarf_sup_object: Option<&Object>,
         platform: Platform,
         mapping: Option<Arc<PathMappings>>,
-        collect_inlines: bool,
+        options: CollectOptions,
+        linker_map: Option<&LinkerMap>,
     ) -> common::Result<Self> {
         let mut collector = Collector {
             platform,
-            collect_inlines,
+            options: options.clone(),
             syms: Symbols::default(),
+            data: DataSymbols::default(),
+            exports: HashMap::new(),
+            fragments: HashMap::new(),
+            line_flags: None,
+            dwarf_names: HashMap::new(),
+            decl_lines: HashMap::new(),
+            demangled: DemangleCache::default(),
+            mapped: HashSet::new(),
+            superseded: HashSet::new(),
+            skipped: HashSet::new(),
         };
 
         let ds = main_object.debug_session()?;
-        let mut source = SourceFiles::new(mapping, platform);
+        let mut source = SourceFiles::new(mapping, platform, options.encoding);
         let mut inline_origins = InlineOrigins::default();
         let debug_id = format!("{}", main_object.debug_id().breakpad());
         let code_id = pe_object
             .and_then(|o| o.code_id())
             .or_else(|| main_object.code_id())
-            .map(|c| c.as_str().to_string().to_uppercase());
-        let cpu = main_object.arch().name();
+            .map(|c| c.as_str().to_string().to_uppercase())
+            .or_else(|| dwarf::get_text_hash_code_id(main_object));
+        if main_object.debug_id().is_nil() {
+            warn!(
+                "{} has no build id and no code to hash: the debug id is nil, dump it with its binary",
+                main_file_name
+            );
+        }
+        let cpu = main_object.arch().name().to_string();
         let bin_type = if main_object.has_debug_info() {
             Type::DebugInfo
         } else {
             Type::Stripped
         };
 
+        #[cfg(feature = "pdb")]
+        if let Object::Pdb(pdb) = main_object {
+            match windows::omap::get_function_fragments(pdb) {
+                Ok(fragments) => collector.fragments = fragments,
+                Err(e) => warn!("Unable to get the function fragments: {}", e),
+            }
+            match windows::enc::get_superseded_functions(pdb) {
+                Ok(superseded) => collector.superseded = superseded,
+                Err(e) => warn!("Unable to get the superseded functions: {}", e),
+            }
+            if !options.compilands.is_empty() {
+                match windows::compilands::get_skipped_functions(pdb, &options.compilands) {
+                    Ok(skipped) => collector.skipped = skipped,
+                    Err(e) => warn!("Unable to get the functions of the compilands: {}", e),
+                }
+            }
+        }
+
+        if let Object::Elf(_) | Object::MachO(_) = main_object {
+            match dwarf::get_line_flags(main_object) {
+                Ok(flags) => collector.line_flags = Some(flags),
+                Err(e) => warn!("Unable to get the line flags: {}", e),
+            }
+            match dwarf::get_functions(main_object, dwarf_sup_object) {
+                Ok(functions) => {
+                    collector.dwarf_names = functions.names;
+                    collector.decl_lines = functions.decls;
+                }
+                Err(e) => warn!("Unable to get the function names: {}", e),
+            }
+        }
+
         collector.collect_functions(&ds, &mut source, &mut inline_origins)?;
 
+        // The STABS of the old toolchains are only read when there's nothing else
+        if collector.syms.is_empty() {
+            if let Object::Elf(_) | Object::MachO(_) = main_object {
+                let functions = stabs::get_functions(main_object);
+                if !functions.is_empty() {
+                    collector.collect_stabs_functions(functions, &mut source);
+                }
+            }
+        }
+
+        #[cfg(feature = "pdb")]
+        if let Object::Pdb(pdb) = main_object {
+            if windows::fastlink::is_fastlink(pdb) {
+                if options.fastlink_objects {
+                    match windows::fastlink::get_object_functions(pdb) {
+                        Ok(functions) => collector.collect_object_functions(functions),
+                        Err(e) => warn!("Unable to get the functions of the object files: {}", e),
+                    }
+                } else {
+                    warn!(
+                        "{} was linked with /DEBUG:FASTLINK: the functions and their lines are in the object files, \
+                         only the public symbols are dumped (relink with /DEBUG:FULL or use --fastlink-objects)",
+                        main_file_name
+                    );
+                }
+            }
+        }
+
+        #[cfg(feature = "pdb")]
+        if let (Arch::X86, Object::Pdb(pdb)) = (main_object.arch(), main_object) {
+            match windows::params::get_parameter_sizes(pdb) {
+                Ok(sizes) => collector.collect_parameter_sizes(&sizes),
+                Err(e) => warn!("Unable to get the parameter sizes: {}", e),
+            }
+        }
+
+        #[cfg(feature = "pdb")]
         if let Object::Pe(pe) = &main_object {
             if let Some(exception_data) = pe.exception_data() {
                 collector.collect_placeholder_functions(
@@ -140,18 +335,70 @@ impl ObjectInfo {
                     pe_file_name.unwrap_or(main_file_name),
                 );
             }
+            collector.collect_jump_stubs(pe);
+            if let Some(linker_map) = linker_map {
+                collector.collect_linker_map_symbols(linker_map, pe.sections());
+            }
+        } else if let Some(Object::Pe(pe)) = pe_object {
+            collector.collect_jump_stubs(pe);
         }
 
+        #[cfg(feature = "pdb")]
+        match (main_object, pe_object) {
+            (Object::Pe(pe), _) | (_, Some(Object::Pe(pe))) => collector.collect_exports(pe),
+            _ => {}
+        }
         collector.collect_publics(main_object);
+        #[cfg(feature = "elf")]
+        if let Object::Elf(_) = main_object {
+            collector.collect_plt_entries(main_object);
+        }
+
+        if options.data {
+            collector.collect_data_symbols(main_object);
+        }
 
+        #[cfg(feature = "pdb")]
+        if let (false, Object::Pdb(pdb)) = (options.encoding.is_auto(), main_object) {
+            match windows::symbols::get_undecoded_procedure_names(pdb, options.encoding) {
+                Ok(names) => collector.collect_decoded_names(&names),
+                Err(e) => warn!("Unable to decode the function names: {}", e),
+            }
+        }
+
+        #[cfg(feature = "pdb")]
+        if let (true, Object::Pdb(pdb)) = (options.languages, main_object) {
+            match windows::symbols::get_languages(pdb) {
+                Ok(languages) => collector.collect_languages(&languages),
+                Err(e) => warn!("Unable to get the languages: {}", e),
+            }
+        }
+
+        #[cfg(feature = "pdb")]
+        if let (true, Object::Pdb(pdb)) = (options.labels, main_object) {
+            match windows::symbols::get_labels(pdb, options.encoding) {
+                Ok(labels) => collector.collect_labels(labels),
+                Err(e) => warn!("Unable to get the labels: {}", e),
+            }
+        }
+
+        #[cfg(feature = "elf")]
         if let Some(buf) = super::symbol::get_compressed_minidebuginfo(main_object) {
             if let Ok(o) = Object::parse(&buf) {
                 collector.collect_publics(&o);
             }
         }
 
-        let stack = get_stack_info(Some(main_object), pe_object);
-        let symbols = match platform {
+        collector.rename_thunks();
+        collector.merge_funclets();
+        collector.merge_split_functions();
+
+        let all_sections = match pe_object {
+            Some(pe) => super::symbol::get_sections(pe),
+            None => super::symbol::get_sections(main_object),
+        };
+        let sections = super::symbol::get_section_ranges(&all_sections);
+        let mut symbols = match platform {
             Platform::Linux | Platform::Mac => super::symbol::add_executable_section_symbols(
                 collector.syms,
                 main_file_name,
@@ -159,18 +406,122 @@ impl ObjectInfo {
             ),
             Platform::Win => super::symbol::append_dummy_symbol(
                 collector.syms,
-                pe_file_name.unwrap_or(main_file_name),
+                options.sentinel_name.clone().unwrap_or_else(|| {
+                    super::symbol::get_unknown_name(pe_file_name.unwrap_or(main_file_name))
+                }),
+                options.sentinel,
+                &sections,
             ),
         };
 
+        // The FDEs give the extents of the functions of a stripped file
+        let mut stack = get_stack_info(Some(main_object), pe_object, options.signal_frames);
+        if let (Object::Elf(_), Type::Stripped) = (main_object, &bin_type) {
+            symbols =
+                super::symbol::add_cfi_function_symbols(symbols, &cfi::get_init_ranges(&stack));
+        }
+
+        #[cfg(feature = "disasm")]
+        if options.discover_functions {
+            let cfi = cfi::get_init_ranges(&stack);
+            symbols = match pe_object {
+                Some(pe) => crate::disasm::add_discovered_functions(symbols, pe, &cfi),
+                None => crate::disasm::add_discovered_functions(symbols, main_object, &cfi),
+            };
+        }
+
+        if options.inner_publics == InnerPublics::Drop {
+            super::symbol::drop_inner_publics(&mut symbols);
+        }
+        super::symbol::set_missing_lengths(&mut symbols, &sections);
+        super::symbol::clamp_to_sections(&mut symbols, &sections);
+        crate::outlined::rename_outlined_functions(&mut symbols, main_object, options.outlined);
+        if options.report_gaps {
+            let code = super::symbol::get_executable_ranges(&all_sections);
+            super::symbol::report_gaps(&symbols, &code);
+        }
+        if options.gap_fill == GapFill::Unknown {
+            symbols = super::symbol::add_unknown_symbols(
+                symbols,
+                &sections,
+                pe_file_name.unwrap_or(main_file_name),
+            );
+        }
+
+        #[cfg(feature = "disasm")]
+        if options.check_param_sizes {
+            match pe_object {
+                Some(pe) => crate::disasm::check_parameter_sizes(&mut symbols, pe),
+                None => crate::disasm::check_parameter_sizes(&mut symbols, main_object),
+            }
+        }
+
+        // Some PDBs (e.g. the ones written by lld-link) have no frame data for
+        // the x86 code, the frame pointer prologues are found in the PE file
+        if let (Arch::X86, true) = (main_object.arch(), stack.is_empty()) {
+            let code = match pe_object {
+                Some(pe) => super::symbol::get_pe_code(pe),
+                None => super::symbol::get_pe_code(main_object),
+            };
+            // The S_FRAMEPROC records tell the FPO functions from the other ones
+            let frames = match main_object {
+                #[cfg(feature = "pdb")]
+                Object::Pdb(pdb) => windows::params::get_frames(pdb).unwrap_or_else(|e| {
+                    warn!("Unable to get the frames of the procedures: {}", e);
+                    HashMap::new()
+                }),
+                _ => HashMap::new(),
+            };
+            stack = cfi::get_frame_pointer_win(&code, &symbols, &frames);
+            if !stack.is_empty() {
+                warn!("No frame data: the STACK WIN records are derived from the prologues and the S_FRAMEPROC records");
+            }
+        }
+
+        // A file without CFI (e.g. a separate debug file) doesn't get any
+        if let (Object::Elf(_), true) = (main_object, options.leaf_cfi && !stack.is_empty()) {
+            // There's no PE file with an ELF one, the sections are the ELF ones
+            let code = super::symbol::get_executable_ranges(&all_sections);
+            let starts: Vec<_> = symbols.keys().copied().collect();
+            stack = cfi::add_leaf_cfi(&stack, main_object.arch(), &code, &starts);
+        }
+
+        if options.merge_cfi {
+            stack = cfi::merge_cfi(&stack);
+        }
+
+        if options.validate_cfi != CfiValidation::Off {
+            let problems = cfi::validate_cfi(&stack, &sections);
+            for problem in &problems {
+                warn!("Invalid CFI: {}", problem);
+            }
+            anyhow::ensure!(
+                options.validate_cfi != CfiValidation::Strict || problems.is_empty(),
+                "The CFI validation failed: {} invalid records",
+                problems.len()
+            );
+        }
+
+        #[cfg(feature = "pdb")]
+        let srcsrv = match main_object {
+            Object::Pdb(pdb) => windows::utils::get_srcsrv(pdb).unwrap_or_else(|e| {
+                warn!("Unable to get the srcsrv stream: {}", e);
+                None
+            }),
+            _ => None,
+        };
+        #[cfg(not(feature = "pdb"))]
+        let srcsrv = None;
+
         let file_name = match (&main_object, &pe_file_name) {
             (Object::Elf(elf), _) => elf.name().unwrap_or(main_file_name),
             (Object::MachO(macho), _) => macho.name().unwrap_or(main_file_name),
             _ => main_file_name,
         };
 
-        Ok(Self {
+        let object_info = Self {
             symbols,
+            data: collector.data,
             files: source.get_mapping(),
             inline_origins: inline_origins.get_list(),
             file_name: Self::file_name_only(file_name).to_string(),
@@ -181,7 +532,39 @@ impl ObjectInfo {
             stack,
             bin_type,
             platform,
-        })
+            features: options.features().into_iter().map(String::from).collect(),
+            os: None,
+            srcsrv,
+            git_files: HashMap::new(),
+            parse_stats: ParseStats::default(),
+        };
+        object_info.check_limits(&options.limits, main_object.data().len() as u64)?;
+
+        Ok(object_info)
+    }
+
+    /// Check all the symbols and the source files against the parse limits, only
+    /// the functions are checked while they're collected
+    fn check_limits(&self, limits: &ParseLimits, file_size: u64) -> common::Result<()> {
+        if *limits == ParseLimits::default() {
+            return Ok(());
+        }
+
+        limits.check_symbols(self.symbols.len())?;
+        let mut lines = 0;
+        // The size of the records, roughly
+        let mut size = self.stack.len() as u64;
+        for sym in self.symbols.values() {
+            limits.check_string(&sym.name)?;
+            lines += sym.source.lines.len();
+            size += 32 + sym.name.len() as u64 + 16 * sym.source.lines.len() as u64;
+        }
+        limits.check_lines(lines)?;
+        for file in self.files.get_mapping() {
+            limits.check_string(file)?;
+            size += 8 + file.len() as u64;
+        }
+        limits.check_expansion(size, file_size)
     }
 
     fn file_name_only(file_name: &str) -> &str {
@@ -189,12 +572,18 @@ impl ObjectInfo {
     }
 
     pub fn merge(left: ObjectInfo, right: ObjectInfo) -> common::Result<ObjectInfo> {
-        anyhow::ensure!(
-            left.debug_id == right.debug_id,
-            "The files don't have the same debug id: {} and {}",
-            left.debug_id,
-            right.debug_id
-        );
+        // The debug file of an ELF binary without build id has a nil debug id:
+        // the hash of .text is only in the binary
+        let nil = DebugId::nil().breakpad().to_string();
+        if left.debug_id != right.debug_id && left.debug_id != nil && right.debug_id != nil {
+            return Err(common::failure(
+                FailureKind::IdentityMismatch,
+                format!(
+                    "The files don't have the same debug id: {} and {}",
+                    left.debug_id, right.debug_id
+                ),
+            ));
+        }
 
         // Just to avoid to iterate on the bigger
         let (mut left, mut right) = if left.symbols.len() > right.symbols.len() {
@@ -203,6 +592,19 @@ impl ObjectInfo {
             (right, left)
         };
 
+        // The functions synthesized from the CFI of a stripped file are superseded
+        // by the ones of the debug info
+        if left.bin_type != right.bin_type {
+            let stripped = if left.bin_type == Type::Stripped {
+                &mut left
+            } else {
+                &mut right
+            };
+            stripped
+                .symbols
+                .retain(|_, sym| sym.is_public || !sym.is_synthetic);
+        }
+
         // merge the CFIs
         if left.stack.is_empty() {
             std::mem::swap(&mut left.stack, &mut right.stack);
@@ -219,14 +621,16 @@ impl ObjectInfo {
         let inline_origin_remapping =
             merge_inline_origins(&mut left.inline_origins, right.inline_origins);
 
-        for (addr, sym) in right.symbols.iter_mut() {
+        // The symbols are moved: a FUNC owns its line table which can be the biggest
+        // part of the module
+        for (addr, mut sym) in std::mem::take(&mut right.symbols) {
             if sym.is_public {
                 // No line info so just put the sym in the map
-                if left.symbols.is_inside_symbol(*addr) {
+                if left.symbols.is_inside_symbol(addr) {
                     continue;
                 }
 
-                match left.symbols.entry(*addr) {
+                match left.symbols.entry(addr) {
                     btree_map::Entry::Occupied(mut e) => {
                         if sym.is_synthetic {
                             // Do not replace an existing symbol with a synthetic one
@@ -235,21 +639,21 @@ impl ObjectInfo {
 
                         if e.get().is_synthetic {
                             // Always replace a synthetic symbol
-                            e.insert(sym.clone());
+                            e.insert(sym);
                         } else if e.get().name != sym.name {
                             // We already have one so just discard this one
                             e.get_mut().is_multiple = true;
                         }
                     }
                     btree_map::Entry::Vacant(e) => {
-                        e.insert(sym.clone());
+                        e.insert(sym);
                     }
                 }
                 continue;
             }
 
             // Deal with a FUNC
-            match left.symbols.entry(*addr) {
+            match left.symbols.entry(addr) {
                 btree_map::Entry::Occupied(mut e) => {
                     let a_sym = e.get_mut();
                     if a_sym.is_public {
@@ -257,26 +661,42 @@ impl ObjectInfo {
                         // so just keep the FUNC
                         sym.remap_lines(file_remapping.as_deref());
                         sym.remap_inlines(file_remapping.as_deref(), &inline_origin_remapping);
-                        std::mem::swap(a_sym, sym);
+                        *a_sym = sym;
                     }
                     a_sym.is_multiple = true;
                 }
                 btree_map::Entry::Vacant(e) => {
                     sym.remap_lines(file_remapping.as_deref());
                     sym.remap_inlines(file_remapping.as_deref(), &inline_origin_remapping);
-                    e.insert(sym.clone());
+                    e.insert(sym);
                 }
             }
         }
 
+        for (key, data) in right.data {
+            left.data.entry(key).or_insert(data);
+        }
+
+        if left.srcsrv.is_none() {
+            left.srcsrv = right.srcsrv;
+        }
+
+        if left.debug_id == nil {
+            left.debug_id = right.debug_id;
+        }
+
         if left.code_id.is_none() && right.code_id.is_some() {
             left.code_id = right.code_id;
         }
 
         if right.bin_type == Type::Stripped {
             left.file_name = right.file_name;
+        } else {
+            left.bin_type = Type::DebugInfo;
         }
 
+        left.parse_stats += right.parse_stats;
+
         Ok(left)
     }
 
@@ -285,6 +705,137 @@ impl ObjectInfo {
         Ok(())
     }
 
+    /// Same as `dump` but with the FUNC records first and then the PUBLIC ones,
+    /// like the Breakpad dump_syms tools.
+    pub fn dump_legacy_order<W: Write>(&self, mut writer: W) -> common::Result<()> {
+        write!(writer, "{}", LegacyOrder(self))?;
+        Ok(())
+    }
+
+    /// Writes an address-sorted list of `rva size name` entries.
+    ///
+    /// Symbols without a size are considered to extend up to the next symbol.
+    pub fn dump_map<W: Write>(&self, mut writer: W) -> common::Result<()> {
+        let mut syms = self.symbols.values().peekable();
+        while let Some(sym) = syms.next() {
+            let len = match (sym.len, syms.peek()) {
+                (0, Some(next)) => next.rva - sym.rva,
+                (len, _) => len,
+            };
+            writeln!(writer, "{:08x} {:08x} {}", sym.rva, len, sym.name)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the symbols as a JSON document with the same data as the Breakpad
+    /// format, see `json::Module`.
+    pub fn dump_json<W: Write>(&self, mut writer: W) -> common::Result<()> {
+        let platform = self.platform.to_string();
+        let (stack_cfi, stack_win) = json::parse_stack(&self.stack);
+        let module = json::Module {
+            version: json::JSON_VERSION,
+            os: self.os.as_deref().unwrap_or(&platform),
+            cpu: &self.cpu,
+            debug_id: &self.debug_id,
+            name: &self.file_name,
+            code_id: self.code_id.as_deref(),
+            pe_name: self.pe_name.as_deref(),
+            generator: json::Generator {
+                name: "mozilla/dump_syms",
+                version: env!("CARGO_PKG_VERSION"),
+                features: self.features.iter().map(String::as_str).collect(),
+            },
+            provenance: self
+                .get_provenance()
+                .map(|(source, level)| json::Provenance { source, level }),
+            files: self
+                .files
+                .get_mapping()
+                .iter()
+                .enumerate()
+                .map(|(n, name)| json::File {
+                    id: n as u32,
+                    name,
+                    git: self.git_files.get(&(n as u32)).map(json::Git::from),
+                })
+                .collect(),
+            inline_origins: self.inline_origins.iter().map(String::as_str).collect(),
+            functions: self
+                .symbols
+                .values()
+                .filter(|sym| !sym.is_public)
+                .map(json::Function::from)
+                .collect(),
+            publics: self
+                .symbols
+                .values()
+                .filter(|sym| sym.is_public)
+                .map(json::Public::from)
+                .collect(),
+            data: self.data.values().map(json::Data::from).collect(),
+            stack_cfi,
+            stack_win,
+        };
+        serde_json::to_writer(&mut writer, &module)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    /// Apply the path mapping on the source files, it's done when the object
+    /// hasn't been collected with the mapping (e.g. when it's from the cache).
+    pub fn apply_mapping(&mut self, mapping: &PathMappings) {
+        let file_remapping = match self.files.apply_mapping(mapping) {
+            Some(file_remapping) => file_remapping,
+            None => return,
+        };
+        let inline_origin_remapping: Vec<u32> = (0..self.inline_origins.len() as u32).collect();
+        for sym in self.symbols.values_mut() {
+            sym.remap_lines(Some(&file_remapping));
+            sym.remap_inlines(Some(&file_remapping), &inline_origin_remapping);
+        }
+    }
+
+    /// Find the git checkouts of the source files to emit the FILE_GIT records
+    pub(crate) fn collect_git_files(&mut self, checkouts: &GitCheckouts) {
+        self.git_files = self
+            .files
+            .get_mapping()
+            .iter()
+            .enumerate()
+            .filter_map(|(n, path)| Some((n as u32, checkouts.get_file(path)?)))
+            .collect();
+        if !self.features.iter().any(|f| f == "git-info") {
+            self.features.push("git-info".to_string());
+        }
+    }
+
+    /// Remove the line records in the source files matching the globs (e.g. the
+    /// system headers), the functions are kept
+    pub(crate) fn prune_lines(&mut self, globs: &PathGlobs) {
+        let pruned: Vec<bool> = self
+            .files
+            .get_mapping()
+            .iter()
+            .map(|path| globs.is_match(path))
+            .collect();
+        if !pruned.contains(&true) {
+            return;
+        }
+        for sym in self.symbols.values_mut() {
+            sym.source
+                .lines
+                .retain(|line| !pruned.get(line.file_id as usize).copied().unwrap_or(false));
+        }
+        if !self.features.iter().any(|f| f == "prune-lines") {
+            self.features.push("prune-lines".to_string());
+        }
+    }
+
+    /// Rewrite the source files into URLs with a template
+    pub(crate) fn apply_source_urls(&mut self, urls: &SourceUrls) {
+        self.files.apply_source_urls(urls, self.srcsrv.as_ref());
+    }
+
     pub fn get_debug_id(&self) -> &str {
         &self.debug_id
     }
@@ -293,7 +844,59 @@ impl ObjectInfo {
         &self.file_name
     }
 
+    pub fn set_name(&mut self, name: &str) {
+        self.file_name = name.to_string();
+    }
+
+    pub fn set_os(&mut self, os: &str) {
+        self.os = Some(os.to_string());
+    }
+
+    pub fn set_cpu(&mut self, cpu: &str) {
+        self.cpu = cpu.to_string();
+    }
+
+    pub fn set_debug_id(&mut self, debug_id: &str) {
+        self.debug_id = debug_id.to_string();
+    }
+
     pub fn has_stack(&self) -> bool {
         !self.stack.is_empty()
     }
+
+    /// The symbols of a PE file dumped without its pdb (not found locally nor on
+    /// the symbol servers): they're better once the pdb is there
+    pub fn is_pe_only(&self) -> bool {
+        self.platform == Platform::Win && self.bin_type == Type::Stripped
+    }
+
+    pub(crate) fn add_parse_stats(&mut self, stats: ParseStats) {
+        self.parse_stats += stats;
+    }
+
+    /// The counters of the module, without the store duration
+    pub fn metrics(&self) -> ModuleMetrics {
+        let (publics, functions): (Vec<_>, Vec<_>) =
+            self.symbols.values().partition(|sym| sym.is_public);
+        ModuleMetrics {
+            name: self.file_name.clone(),
+            debug_id: self.debug_id.clone(),
+            code_id: self.code_id.clone(),
+            os: self.os.clone().unwrap_or_else(|| self.platform.to_string()),
+            cpu: self.cpu.clone(),
+            functions: functions.len(),
+            publics: publics.len(),
+            files: self.files.get_mapping().len(),
+            lines: functions.iter().map(|sym| sym.source.lines.len()).sum(),
+            inlines: functions.iter().map(|sym| sym.source.inlines.len()).sum(),
+            cfi: self
+                .stack
+                .lines()
+                .filter(|l| l.starts_with("STACK "))
+                .count(),
+            warnings: self.parse_stats.warnings,
+            parse_duration: self.parse_stats.duration,
+            ..Default::default()
+        }
+    }
 }
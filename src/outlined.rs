@@ -0,0 +1,225 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+use symbolic::common::CpuFamily;
+use symbolic::debuginfo::Object;
+
+use crate::symbol::Symbols;
+
+/// The prefix of the functions made by the LLVM machine outliner from the
+/// instruction sequences shared by several functions
+const OUTLINED_PREFIX: &str = "OUTLINED_FUNCTION_";
+
+/// The callers named in the name of an outlined function, the other ones are counted
+const MAX_CALLERS: usize = 3;
+
+// The branches with a 26 bits immediate: bl and b
+const BL: u32 = 0b100101;
+const B: u32 = 0b000101;
+
+/// What to do with the outlined functions: they're shared by unrelated
+/// functions, so a crash in one of them says little without its caller
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Outlined {
+    /// Keep their names
+    #[default]
+    Keep,
+    /// Add a ` [outlined]` suffix to their names
+    Flag,
+    /// Add the functions calling them to their names (ARM64 code only), e.g.
+    /// `OUTLINED_FUNCTION_12 [outlined from foo(), bar()]`
+    Callers,
+}
+
+impl FromStr for Outlined {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "flag" => Ok(Self::Flag),
+            "callers" => Ok(Self::Callers),
+            _ => anyhow::bail!("Invalid outlined functions mode: {}", s),
+        }
+    }
+}
+
+fn is_outlined(name: &str) -> bool {
+    name.strip_prefix(OUTLINED_PREFIX)
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Get the executable sections of an ELF or a Mach-O file which are in the file
+fn get_code<'a>(object: &Object<'a>) -> Vec<(u32, &'a [u8])> {
+    let data = object.data();
+    let load_address = object.load_address();
+    match goblin::Object::parse(data) {
+        Ok(goblin::Object::Elf(elf)) => elf
+            .section_headers
+            .iter()
+            .filter(|h| {
+                h.is_alloc()
+                    && h.is_executable()
+                    && h.sh_type != goblin::elf::section_header::SHT_NOBITS
+            })
+            .filter_map(|h| {
+                let start = h.sh_offset as usize;
+                Some((
+                    h.sh_addr.checked_sub(load_address)? as u32,
+                    data.get(start..start.checked_add(h.sh_size as usize)?)?,
+                ))
+            })
+            .collect(),
+        Ok(goblin::Object::Mach(goblin::mach::Mach::Binary(macho))) => macho
+            .segments
+            .iter()
+            .filter_map(|segment| segment.sections().ok())
+            .flatten()
+            .filter(|(section, data)| {
+                section.flags & goblin::mach::constants::S_ATTR_PURE_INSTRUCTIONS != 0
+                    && !data.is_empty()
+            })
+            .filter_map(|(section, data)| {
+                Some((section.addr.checked_sub(load_address)? as u32, data))
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Get the rvas of the bl and b instructions branching to the targets, by target
+fn get_branches(code: &[(u32, &[u8])], targets: &HashSet<u32>) -> HashMap<u32, Vec<u32>> {
+    let mut branches: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (rva, data) in code {
+        for (i, insn) in data.chunks_exact(4).enumerate() {
+            let insn = u32::from_le_bytes(insn.try_into().unwrap());
+            if !matches!(insn >> 26, BL | B) {
+                continue;
+            }
+            // The offset is a signed number of instructions
+            let offset = (((insn << 6) as i32) >> 4) as i64;
+            let at = rva + 4 * i as u32;
+            let target = at as i64 + offset;
+            if let Ok(target) = u32::try_from(target) {
+                if targets.contains(&target) {
+                    branches.entry(target).or_default().push(at);
+                }
+            }
+        }
+    }
+    branches
+}
+
+fn get_caller(syms: &Symbols, rva: u32) -> Option<&str> {
+    let (_, sym) = syms.range(..=rva).next_back()?;
+    (sym.len == 0 || rva < sym.rva + sym.len).then_some(sym.name.as_str())
+}
+
+fn format_callers(callers: &[&str]) -> String {
+    let named = callers[..callers.len().min(MAX_CALLERS)].join(", ");
+    match callers.len().saturating_sub(MAX_CALLERS) {
+        0 => named,
+        1 => format!("{} and 1 other", named),
+        others => format!("{} and {} others", named, others),
+    }
+}
+
+/// Rename the outlined functions so they can be recognized (e.g. to be skipped
+/// by the signature generation) or so they give their callers. The callers are
+/// found with the branches in the ARM64 code: the ones of a function without a
+/// caller in the code (e.g. in a debug file) are flagged only.
+// This runs after set_missing_lengths, the callers are found with the lengths.
+pub(crate) fn rename_outlined_functions(syms: &mut Symbols, object: &Object, mode: Outlined) {
+    if mode == Outlined::Keep {
+        return;
+    }
+
+    let outlined: HashSet<u32> = syms
+        .values()
+        .filter(|sym| is_outlined(&sym.name))
+        .map(|sym| sym.rva)
+        .collect();
+    if outlined.is_empty() {
+        return;
+    }
+
+    let branches = if mode == Outlined::Callers && object.arch().cpu_family() == CpuFamily::Arm64 {
+        get_branches(&get_code(object), &outlined)
+    } else {
+        HashMap::new()
+    };
+
+    let mut names = Vec::with_capacity(outlined.len());
+    for rva in outlined {
+        let mut callers = Vec::new();
+        for at in branches.get(&rva).into_iter().flatten() {
+            if let Some(caller) = get_caller(syms, *at) {
+                // An outlined function can be called by another one
+                if !is_outlined(caller) && !callers.contains(&caller) {
+                    callers.push(caller);
+                }
+            }
+        }
+        let name = &syms[&rva].name;
+        let name = if callers.is_empty() {
+            format!("{} [outlined]", name)
+        } else {
+            format!("{} [outlined from {}]", name, format_callers(&callers))
+        };
+        names.push((rva, name));
+    }
+
+    for (rva, name) in names {
+        syms.get_mut(&rva).unwrap().name = name;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_outlined() {
+        assert!(is_outlined("OUTLINED_FUNCTION_0"));
+        assert!(is_outlined("OUTLINED_FUNCTION_1234"));
+        assert!(!is_outlined("OUTLINED_FUNCTION_"));
+        assert!(!is_outlined("OUTLINED_FUNCTION_1.cold"));
+        assert!(!is_outlined("foo"));
+    }
+
+    #[test]
+    fn test_get_branches() {
+        let code = [
+            // bl +8
+            0x94000002u32,
+            // nop
+            0xd503201f,
+            // b -8
+            0x17fffffe,
+            // bl +0xf4
+            0x9400003d,
+        ];
+        let data: Vec<u8> = code.iter().flat_map(|i| i.to_le_bytes()).collect();
+        let targets = [0x1008, 0x1000, 0x1100].iter().copied().collect();
+        let branches = get_branches(&[(0x1000, &data)], &targets);
+        assert_eq!(branches[&0x1008], vec![0x1000]);
+        assert_eq!(branches[&0x1000], vec![0x1008]);
+        assert_eq!(branches[&0x1100], vec![0x100c]);
+    }
+
+    #[test]
+    fn test_format_callers() {
+        assert_eq!(format_callers(&["a"]), "a");
+        assert_eq!(format_callers(&["a", "b", "c"]), "a, b, c");
+        assert_eq!(format_callers(&["a", "b", "c", "d"]), "a, b, c and 1 other");
+        assert_eq!(
+            format_callers(&["a", "b", "c", "d", "e"]),
+            "a, b, c and 2 others"
+        );
+    }
+}
@@ -3,9 +3,10 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Platform {
     Linux,
     Mac,
@@ -47,7 +48,7 @@ impl Platform {
                 // That's because Platform::Win is used for all PDB files, even for
                 // PDB files for build that were (cross-)compiled on a Linux machine;
                 // those contain Linux paths.
-                let left = left.trim_end_matches(&['/', '\\']);
+                let left = left.trim_end_matches(['/', '\\']);
                 let right = right.trim_start_matches(['/', '\\']);
 
                 // If `left` happens to be an absolute Linux-style path, use `/` as
@@ -61,6 +62,63 @@ impl Platform {
             }
         }
     }
+
+    /// Remove the `.` and `..` components of a path without touching the file
+    /// system. A Windows path gets a single kind of separator: the PDBs built with
+    /// clang-cl on Linux or macOS have POSIX paths, maybe mixed with Windows ones
+    /// (e.g. `/winsdk/include\um\windows.h`), so `/` is used when the path starts
+    /// as a POSIX one, else `\`.
+    pub fn normalize_path(&self, path: &str) -> String {
+        let is_sep = |c: char| c == '/' || (*self == Platform::Win && c == '\\');
+        let is_posix = match (self, path.find(is_sep)) {
+            (Platform::Linux | Platform::Mac, _) => true,
+            (Platform::Win, Some(0)) => path.starts_with('/') && !path[1..].starts_with(is_sep),
+            (Platform::Win, Some(i)) => !path[..i].ends_with(':') && path[i..].starts_with('/'),
+            (Platform::Win, None) => false,
+        };
+        let sep = if is_posix { "/" } else { "\\" };
+
+        // The root: `/`, `\\` (UNC), `\`, `C:\` or `C:`
+        let (root, rest) = if path.starts_with(is_sep) {
+            let rest = path.trim_start_matches(is_sep);
+            let unc = !is_posix && path.len() - rest.len() >= 2;
+            (
+                if unc {
+                    "\\\\".to_string()
+                } else {
+                    sep.to_string()
+                },
+                rest,
+            )
+        } else {
+            match path.split_once(':') {
+                Some((drive, rest)) if !is_posix && drive.len() == 1 => {
+                    let rooted = rest.starts_with(is_sep);
+                    let root = format!("{}:{}", drive, if rooted { sep } else { "" });
+                    (root, rest.trim_start_matches(is_sep))
+                }
+                _ => (String::new(), path),
+            }
+        };
+
+        let mut components: Vec<&str> = Vec::new();
+        for component in rest.split(is_sep) {
+            match component {
+                "" | "." => {}
+                ".." => match components.last() {
+                    Some(&last) if last != ".." => {
+                        components.pop();
+                    }
+                    // A relative path can start with some `..`
+                    _ if root.is_empty() => components.push(".."),
+                    _ => {}
+                },
+                _ => components.push(component),
+            }
+        }
+
+        root + &components.join(sep)
+    }
 }
 
 impl Display for Platform {
@@ -162,4 +220,29 @@ mod test {
             "/home/test/test2/filename"
         );
     }
+
+    #[test]
+    fn test_normalize_path() {
+        let win = |path| Platform::Win.normalize_path(path);
+        assert_eq!(win(r"C:\src\.\foo\..\bar.cpp"), r"C:\src\bar.cpp");
+        assert_eq!(win("C:/src/foo/bar.cpp"), r"C:\src\foo\bar.cpp");
+        assert_eq!(win(r"\\server\share\foo.cpp"), r"\\server\share\foo.cpp");
+        assert_eq!(win(r"..\..\src/foo.cpp"), r"..\..\src\foo.cpp");
+
+        // The paths of the PDBs cross-compiled with clang-cl on Linux
+        assert_eq!(
+            win(r"/winsdk/include\um\..\shared/windows.h"),
+            "/winsdk/include/shared/windows.h"
+        );
+        assert_eq!(
+            win("/home/build/obj/../src//foo.cpp"),
+            "/home/build/src/foo.cpp"
+        );
+        assert_eq!(win("../../src/foo.cpp"), "../../src/foo.cpp");
+
+        assert_eq!(
+            Platform::Linux.normalize_path("/src/./a/../foo.cpp"),
+            "/src/foo.cpp"
+        );
+    }
 }
@@ -0,0 +1,283 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use serde_json::{json, Value};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::common;
+use crate::logging;
+
+/// How the progress of the dump is reported on stderr
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProgressFormat {
+    #[default]
+    None,
+    /// Newline-delimited JSON events
+    Json,
+    /// A status line redrawn on the terminal
+    Bar,
+}
+
+/// The number of functions collected by all the threads
+static FUNCTIONS: AtomicUsize = AtomicUsize::new(0);
+/// The number of bytes of symbols written by all the threads
+static BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Count a collected function for the progress bar
+pub fn add_function() {
+    FUNCTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+impl FromStr for ProgressFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> common::Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "json" => Ok(Self::Json),
+            "bar" => Ok(Self::Bar),
+            _ => anyhow::bail!("Invalid progress format: {}", s),
+        }
+    }
+}
+
+/// The phases of the dump of a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Read the file or download it from a symbol server
+    Read,
+    /// Parse the debug info and collect the symbols
+    Parse,
+    /// Write the symbols
+    Store,
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Parse => "parse",
+            Self::Store => "store",
+        }
+    }
+}
+
+/// Report the progress of the dump of a set of files, the percent is the
+/// proportion of the files which are finished.
+#[derive(Clone, Debug, Default)]
+pub struct Progress {
+    format: ProgressFormat,
+    total: usize,
+    finished: Arc<AtomicUsize>,
+    bar: Option<Arc<Bar>>,
+}
+
+impl Progress {
+    pub fn new(format: ProgressFormat, total: usize) -> Self {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let bar = (format == ProgressFormat::Bar).then(|| Bar::start(total, Arc::clone(&finished)));
+        Self {
+            format,
+            total,
+            finished,
+            bar,
+        }
+    }
+
+    fn percent(&self, finished: usize) -> usize {
+        (finished * 100).checked_div(self.total).unwrap_or(100)
+    }
+
+    pub fn started(&self, file: &str) {
+        logging::set_module(Some(file));
+        if let Some(bar) = self.bar.as_ref() {
+            bar.set(file, "started");
+        }
+        if self.format == ProgressFormat::Json {
+            let percent = self.percent(self.finished.load(Ordering::SeqCst));
+            emit(json!({"event": "started", "file": file, "percent": percent}));
+        }
+    }
+
+    pub fn phase(&self, file: &str, phase: Phase) {
+        logging::set_module(Some(file));
+        if let Some(bar) = self.bar.as_ref() {
+            bar.set(file, phase.name());
+        }
+        if self.format == ProgressFormat::Json {
+            let percent = self.percent(self.finished.load(Ordering::SeqCst));
+            emit(
+                json!({"event": "phase", "file": file, "phase": phase.name(), "percent": percent}),
+            );
+        }
+    }
+
+    pub fn finished<T>(&self, file: &str, res: &common::Result<T>) {
+        logging::set_module(None);
+        let finished = self.finished.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.format == ProgressFormat::Json {
+            let mut event = json!({
+                "event": "finished",
+                "file": file,
+                "status": if res.is_ok() { "ok" } else { "error" },
+                "percent": self.percent(finished),
+            });
+            if let Err(e) = res {
+                event["error"] = format!("{:#}", e).into();
+            }
+            emit(event);
+        }
+    }
+}
+
+/// The status line: the finished files, the file and the phase of the last event and
+/// the counters of the current phase. It's redrawn periodically by a thread which
+/// stops when the progress is dropped.
+#[derive(Debug)]
+struct Bar {
+    total: usize,
+    finished: Arc<AtomicUsize>,
+    current: Mutex<(String, &'static str)>,
+    start: Instant,
+}
+
+impl Bar {
+    fn start(total: usize, finished: Arc<AtomicUsize>) -> Arc<Self> {
+        let bar = Arc::new(Self {
+            total,
+            finished,
+            current: Mutex::new((String::new(), "")),
+            start: Instant::now(),
+        });
+        let weak = Arc::downgrade(&bar);
+        let _ = thread::Builder::new()
+            .name("dump-syms progress".to_string())
+            .spawn(move || Self::draw_loop(weak));
+        bar
+    }
+
+    fn set(&self, file: &str, phase: &'static str) {
+        let mut current = self.current.lock().unwrap();
+        current.0.clear();
+        current.0.push_str(file);
+        current.1 = phase;
+    }
+
+    fn draw_loop(bar: Weak<Self>) {
+        loop {
+            thread::sleep(Duration::from_millis(200));
+            match bar.upgrade() {
+                Some(bar) => {
+                    let line = bar.line();
+                    let _ = write!(io::stderr().lock(), "\r\x1b[K{}", line);
+                }
+                None => return,
+            }
+        }
+    }
+
+    fn line(&self) -> String {
+        let (file, phase) = &*self.current.lock().unwrap();
+        let counters = match *phase {
+            "parse" => format!(", {} functions", FUNCTIONS.load(Ordering::Relaxed)),
+            "store" => format!(", {} written", format_bytes(BYTES.load(Ordering::Relaxed))),
+            _ => String::new(),
+        };
+        let elapsed = self.start.elapsed().as_secs();
+        format!(
+            "[{}/{}] {}: {}{} ({}:{:02})",
+            self.finished.load(Ordering::SeqCst),
+            self.total,
+            file,
+            phase,
+            counters,
+            elapsed / 60,
+            elapsed % 60
+        )
+    }
+}
+
+impl Drop for Bar {
+    fn drop(&mut self) {
+        let _ = write!(io::stderr().lock(), "\r\x1b[K");
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.),
+    }
+}
+
+/// A writer which counts the written bytes for the progress bar
+pub struct CountBytes<W: Write>(pub W);
+
+impl<W: Write> Write for CountBytes<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.0.write(buf)?;
+        BYTES.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Write an event on one line of stderr
+pub(crate) fn emit(event: Value) {
+    let mut stderr = io::stderr().lock();
+    let _ = writeln!(stderr, "{}", event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(
+            "json".parse::<ProgressFormat>().unwrap(),
+            ProgressFormat::Json
+        );
+        assert_eq!(
+            "none".parse::<ProgressFormat>().unwrap(),
+            ProgressFormat::None
+        );
+        assert_eq!(
+            "bar".parse::<ProgressFormat>().unwrap(),
+            ProgressFormat::Bar
+        );
+        assert!("auto".parse::<ProgressFormat>().is_err());
+
+        let progress = Progress::new(ProgressFormat::None, 3);
+        assert_eq!(progress.percent(0), 0);
+        progress.finished("a", &Ok(()));
+        progress
+            .clone()
+            .finished("b", &Err::<(), _>(anyhow::anyhow!("Oops")));
+        assert_eq!(
+            progress.percent(progress.finished.load(Ordering::SeqCst)),
+            66
+        );
+        assert_eq!(Progress::new(ProgressFormat::None, 0).percent(0), 100);
+
+        let mut out = CountBytes(Vec::new());
+        let before = BYTES.load(Ordering::Relaxed);
+        write!(out, "MODULE").unwrap();
+        assert!(BYTES.load(Ordering::Relaxed) >= before + 6);
+
+        assert_eq!(format_bytes(12), "12 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 << 20), "3.0 MiB");
+    }
+}
@@ -0,0 +1,181 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use log::{info, warn};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use crate::common;
+
+/// A response: the status and the body
+#[derive(Debug, PartialEq, Eq)]
+struct Response {
+    status: u16,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: u16) -> Self {
+        Self {
+            status,
+            body: Vec::new(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// Get the file of the store for the path of a request, the paths going out
+/// of the store are rejected.
+fn get_file(root: &Path, path: &str) -> Option<PathBuf> {
+    let path = path.split(['?', '#']).next()?;
+    let mut file = root.to_path_buf();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(name) => file.push(name),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(file)
+}
+
+/// Answer to the request line (`GET /xul.pdb/ID/xul.sym HTTP/1.1`)
+fn respond(root: &Path, request: &str) -> (Response, bool) {
+    let mut parts = request.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method, path),
+        _ => return (Response::error(400), false),
+    };
+    let head = match method {
+        "GET" => false,
+        "HEAD" => true,
+        _ => return (Response::error(405), false),
+    };
+
+    let response = match get_file(root, path) {
+        Some(file) if file.is_file() => match fs::read(&file) {
+            Ok(body) => Response { status: 200, body },
+            Err(_) => Response::error(500),
+        },
+        Some(_) => Response::error(404),
+        None => Response::error(400),
+    };
+    (response, head)
+}
+
+fn handle(root: &Path, stream: TcpStream) -> common::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Skip the headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let (response, head) = respond(root, &request);
+    info!("{} {}", request.trim_end(), response.status);
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.body.len()
+    )?;
+    if !head {
+        stream.write_all(&response.body)?;
+    }
+    stream.flush()?;
+
+    Ok(())
+}
+
+/// Serve the files of a symbol store over HTTP at the given address (e.g.
+/// `127.0.0.1:8080`), so it can be used as a symbol server.
+pub fn serve(root: &Path, addr: &str) -> common::Result<()> {
+    anyhow::ensure!(root.is_dir(), "{} isn't a directory", root.display());
+    let listener = TcpListener::bind(addr)?;
+    info!(
+        "Serve {} at http://{}",
+        root.display(),
+        listener.local_addr()?
+    );
+
+    let root = Arc::new(root.to_path_buf());
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Connection failed: {}", e);
+                continue;
+            }
+        };
+        let root = Arc::clone(&root);
+        thread::spawn(move || {
+            if let Err(e) = handle(&root, stream) {
+                warn!("Request failed: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_respond() {
+        let tmp_dir = Builder::new().prefix("serve").tempdir().unwrap();
+        let dir = tmp_dir.path().join("basic.pdb/ID");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("basic.sym"), "MODULE").unwrap();
+        let root = tmp_dir.path();
+
+        let ok = Response {
+            status: 200,
+            body: b"MODULE".to_vec(),
+        };
+        assert_eq!(
+            respond(root, "GET /basic.pdb/ID/basic.sym HTTP/1.1\r\n"),
+            (ok, false)
+        );
+        assert!(respond(root, "HEAD /basic.pdb/ID/basic.sym?x=1 HTTP/1.1\r\n").1);
+        assert_eq!(
+            respond(root, "GET /basic.pdb/ID/other.sym HTTP/1.1\r\n").0,
+            Response::error(404)
+        );
+        assert_eq!(
+            respond(root, "GET /basic.pdb/ID HTTP/1.1\r\n").0,
+            Response::error(404)
+        );
+        assert_eq!(
+            respond(root, "GET /../etc/passwd HTTP/1.1\r\n").0,
+            Response::error(400)
+        );
+        assert_eq!(
+            respond(root, "PUT /basic.pdb/ID/basic.sym HTTP/1.1\r\n").0,
+            Response::error(405)
+        );
+        assert_eq!(respond(root, "\r\n").0, Response::error(400));
+    }
+}
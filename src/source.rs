@@ -5,12 +5,14 @@
 
 use hashbrown::{hash_map, HashMap};
 use log::error;
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
 use symbolic::debuginfo::FileInfo;
 
-use crate::mapping::PathMappings;
+use crate::common::InputEncoding;
+use crate::mapping::{PathMappings, SourceUrls, SrcSrvFiles};
 use crate::platform::Platform;
 use crate::utils;
 
@@ -19,25 +21,34 @@ type SliceRef = (*const u8, usize);
 #[derive(Debug)]
 pub struct SourceFiles {
     platform: Platform,
+    encoding: InputEncoding,
     ref_to_id: HashMap<String, u32>,
+    /// The ids of the mapped paths, several paths can be mapped to the same one
+    mapped_to_id: HashMap<String, u32>,
     fake_id_to_ref: Vec<(Option<u32>, String)>,
     id_to_ref: Vec<String>,
     cache: HashMap<(SliceRef, SliceRef, SliceRef), u32>,
     mapping: Option<Arc<PathMappings>>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct SourceMap {
     ref_to_id: HashMap<String, u32>,
     id_to_ref: Vec<String>,
 }
 
 impl SourceFiles {
-    pub(super) fn new(mapping: Option<Arc<PathMappings>>, platform: Platform) -> Self {
+    pub(super) fn new(
+        mapping: Option<Arc<PathMappings>>,
+        platform: Platform,
+        encoding: InputEncoding,
+    ) -> Self {
         SourceFiles {
             mapping,
             platform,
+            encoding,
             ref_to_id: Default::default(),
+            mapped_to_id: Default::default(),
             fake_id_to_ref: Default::default(),
             id_to_ref: Default::default(),
             cache: Default::default(),
@@ -50,26 +61,32 @@ impl SourceFiles {
     }
 
     #[inline(always)]
-    fn path_to_string(name: &[u8]) -> String {
-        // Strings in DWARF are encoded either in latin-1 or in utf-8 (likely)
-        if let Ok(s) = String::from_utf8(name.to_vec()) {
-            s
-        } else {
-            name.iter().map(|&c| c as char).collect()
-        }
+    fn path_to_string(encoding: InputEncoding, name: &[u8]) -> String {
+        // Strings in DWARF are encoded either in latin-1 or in utf-8 (likely),
+        // the ones in a PDB are in the code page of the machine where it's built
+        encoding.decode(name).into_owned()
     }
 
-    fn get_path(platform: Platform, compilation_dir: &[u8], file: &FileInfo) -> String {
-        let mut dir = Self::path_to_string(file.dir);
-        let name = Self::path_to_string(file.name);
+    fn get_path(
+        platform: Platform,
+        encoding: InputEncoding,
+        compilation_dir: &[u8],
+        file: &FileInfo,
+    ) -> String {
+        let mut dir = Self::path_to_string(encoding, file.dir);
+        let name = Self::path_to_string(encoding, file.name);
 
         if !platform.is_absolute_path(&dir) && !compilation_dir.is_empty() {
-            let comp_dir = Self::path_to_string(compilation_dir);
+            let comp_dir = Self::path_to_string(encoding, compilation_dir);
             dir = platform.join_paths(&comp_dir, &dir);
         };
         let path = platform.join_paths(&dir, &name);
 
-        if platform.is_target() {
+        // The POSIX paths of the PDBs cross-compiled on Linux or macOS can't be
+        // resolved on Windows
+        if platform == Platform::Win && (!platform.is_target() || path.starts_with('/')) {
+            platform.normalize_path(&path)
+        } else if platform.is_target() {
             // Try to get the real path and in case we're on the machine where the files have been compiled
             // else fallback on the basic way to normalize a path
             let path = PathBuf::from(path);
@@ -91,31 +108,39 @@ impl SourceFiles {
             Self::cast_ptr(file.name),
         );
 
-        match self.cache.entry(cache_key) {
+        if let Some(id) = self.cache.get(&cache_key) {
+            return *id;
+        }
+        let path = Self::get_path(self.platform, self.encoding, compilation_dir, file);
+        let id = self.get_path_id(path);
+        self.cache.insert(cache_key, id);
+        id
+    }
+
+    /// The index of a full path in self.ref_to_id, e.g. for the STABS which
+    /// don't split it in a directory and a name
+    pub(super) fn get_path_id(&mut self, path: String) -> u32 {
+        match self.ref_to_id.entry(path) {
             hash_map::Entry::Occupied(e) => *e.get(),
             hash_map::Entry::Vacant(e) => {
-                let path = Self::get_path(self.platform, compilation_dir, file);
-                let id = match self.ref_to_id.entry(path.clone()) {
-                    hash_map::Entry::Occupied(e) => *e.get(),
-                    hash_map::Entry::Vacant(e) => {
-                        let id = self.fake_id_to_ref.len() as u32;
-                        let new_path = if let Some(mapping) = self.mapping.as_ref() {
-                            match mapping.map(&path) {
-                                Ok(p) => p,
-                                Err(e) => {
-                                    error!("Mapping error: {}", e);
-                                    None
-                                }
-                            }
-                        } else {
+                let id = self.fake_id_to_ref.len() as u32;
+                let new_path = if let Some(mapping) = self.mapping.as_ref() {
+                    match mapping.map(e.key()) {
+                        Ok(p) => p,
+                        Err(e) => {
+                            error!("Mapping error: {}", e);
                             None
-                        };
-                        let path = new_path.unwrap_or(path);
-                        e.insert(id);
-                        self.fake_id_to_ref.push((None, path));
-                        id
+                        }
                     }
+                } else {
+                    None
                 };
+                let path = new_path.unwrap_or_else(|| e.key().clone());
+                let fake_id_to_ref = &mut self.fake_id_to_ref;
+                let id = *self.mapped_to_id.entry(path).or_insert_with_key(|path| {
+                    fake_id_to_ref.push((None, path.clone()));
+                    id
+                });
                 e.insert(id);
                 id
             }
@@ -149,6 +174,43 @@ impl SourceMap {
         &self.id_to_ref
     }
 
+    /// Map the paths of the files, the ones mapped to the same path are merged:
+    /// the array to remap the ids is returned when it's the case
+    pub(super) fn apply_mapping(&mut self, mapping: &PathMappings) -> Option<Vec<u32>> {
+        let paths = std::mem::take(&mut self.id_to_ref);
+        self.ref_to_id.clear();
+        let mut remapping = Vec::with_capacity(paths.len());
+        for path in paths {
+            let path = match mapping.map(&path) {
+                Ok(Some(new_path)) => new_path,
+                Ok(None) => path,
+                Err(e) => {
+                    error!("Mapping error: {}", e);
+                    path
+                }
+            };
+            let id = match self.ref_to_id.entry(path) {
+                hash_map::Entry::Occupied(e) => *e.get(),
+                hash_map::Entry::Vacant(e) => {
+                    let id = self.id_to_ref.len() as u32;
+                    self.id_to_ref.push(e.key().clone());
+                    *e.insert(id)
+                }
+            };
+            remapping.push(id);
+        }
+
+        (self.id_to_ref.len() != remapping.len()).then_some(remapping)
+    }
+
+    pub(super) fn apply_source_urls(&mut self, urls: &SourceUrls, srcsrv: Option<&SrcSrvFiles>) {
+        for path in self.id_to_ref.iter_mut() {
+            if let Some(url) = urls.apply(path, srcsrv) {
+                *path = url;
+            }
+        }
+    }
+
     pub(super) fn merge(&mut self, other: &mut SourceMap) -> Option<Vec<u32>> {
         // No FUNC so nothing to do
         if other.id_to_ref.is_empty() {
@@ -0,0 +1,86 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use symbolic::debuginfo::{Object, ObjectKind};
+
+/// A key of the Simple Symbol Query Protocol: `file/index/file` in lowercase
+fn key(file_name: &str, index: &str) -> String {
+    format!("{}/{}/{}", file_name, index, file_name).to_lowercase()
+}
+
+/// Get the SSQP keys of an object, the ones used by the .NET symbol stores
+/// (e.g. `dotnet-symbol` or the Azure DevOps symbol server):
+///  - PE files: the timestamp and the size of the image (the code id),
+///  - PDB files: the signature and the age (the debug id),
+///  - Portable PDB files: the id followed by `FFFFFFFF`,
+///  - ELF and Mach-O files: the build id or the uuid, the debug info has its own key.
+pub fn get_keys(object: &Object, file_name: &str) -> Vec<String> {
+    let file_name = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+    let code_id = object.code_id().map(|id| id.as_str().to_lowercase());
+    let is_debug = object.kind() == ObjectKind::Debug;
+    let has_debug_info = object.has_debug_info();
+
+    let mut keys = Vec::new();
+    match object {
+        Object::Pe(_) => keys.extend(code_id.map(|id| key(file_name, &id))),
+        Object::Pdb(_) => keys.push(key(file_name, &object.debug_id().breakpad().to_string())),
+        Object::PortablePdb(_) => keys.push(key(
+            file_name,
+            &format!("{}ffffffff", object.debug_id().uuid().simple()),
+        )),
+        Object::Elf(_) => {
+            if let Some(id) = code_id {
+                if !is_debug {
+                    keys.push(key(file_name, &format!("elf-buildid-{}", id)));
+                }
+                if has_debug_info {
+                    keys.push(key("_.debug", &format!("elf-buildid-sym-{}", id)));
+                }
+            }
+        }
+        Object::MachO(_) => {
+            if let Some(id) = code_id {
+                if !is_debug {
+                    keys.push(key(file_name, &format!("mach-uuid-{}", id)));
+                }
+                if has_debug_info {
+                    keys.push(key("_.dwarf", &format!("mach-uuid-sym-{}", id)));
+                }
+            }
+        }
+        _ => {}
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssqp_keys() {
+        let keys = |path: &str| {
+            let buf = std::fs::read(path).unwrap();
+            let object = Object::parse(&buf).unwrap();
+            get_keys(&object, path)
+        };
+
+        assert_eq!(
+            keys("./test_data/windows/basic64.dll"),
+            vec!["basic64.dll/5ddc1e998c000/basic64.dll"]
+        );
+        assert_eq!(
+            keys("./test_data/windows/basic64.pdb"),
+            vec!["basic64.pdb/d09ea7d6d2c24c1ebffe78b0c866bb7f2/basic64.pdb"]
+        );
+        assert_eq!(
+            keys("./test_data/linux/basic.full"),
+            vec![
+                "basic.full/elf-buildid-b060ad20c6b47781552708aa192e7739fac7c84a/basic.full",
+                "_.debug/elf-buildid-sym-b060ad20c6b47781552708aa192e7739fac7c84a/_.debug",
+            ]
+        );
+    }
+}
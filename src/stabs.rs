@@ -0,0 +1,257 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use goblin::elf::Elf;
+use goblin::mach::Mach;
+use symbolic::debuginfo::Object;
+
+const N_UNDF: u8 = 0x00;
+const N_FUN: u8 = 0x24;
+const N_SLINE: u8 = 0x44;
+const N_SO: u8 = 0x64;
+const N_OSO: u8 = 0x66;
+const N_SOL: u8 = 0x84;
+
+/// The bits of the type of a Mach-O symbol table entry which is a STABS one
+const N_STAB: u8 = 0xe0;
+
+/// A STABS entry
+#[derive(Debug)]
+struct Stab<'a> {
+    typ: u8,
+    desc: u16,
+    value: u64,
+    name: &'a str,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StabsLine {
+    pub rva: u32,
+    pub num: u32,
+    /// The path of the source file, the one of the unit or of an included file (N_SOL)
+    pub file: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StabsFunction<'a> {
+    /// The name without the type after the colon, it can be mangled
+    pub name: &'a str,
+    pub rva: u32,
+    pub len: u32,
+    pub lines: Vec<StabsLine>,
+}
+
+/// Get the functions of the STABS debug info of the old toolchains: the functions
+/// (N_FUN) and their lines (N_SLINE) are in the .stab section of the ELF files or
+/// in the symbol table of the Mach-O files.
+pub fn get_functions<'a>(object: &Object<'a>) -> Vec<StabsFunction<'a>> {
+    let load_address = object.load_address();
+    let stabs = match object {
+        Object::Elf(_) => get_elf_stabs(object.data()),
+        Object::MachO(_) => get_macho_stabs(object.data()),
+        _ => None,
+    };
+    stabs.map_or_else(Vec::new, |stabs| parse_stabs(stabs, load_address))
+}
+
+/// The .stab section is made of the entries of the units, each one is preceded
+/// by a header (N_UNDF) with the size of its strings in .stabstr.
+fn get_elf_stabs(data: &[u8]) -> Option<Vec<Stab<'_>>> {
+    let elf = Elf::parse(data).ok()?;
+    let section = |name| {
+        let header = elf
+            .section_headers
+            .iter()
+            .find(|h| elf.shdr_strtab.get_at(h.sh_name) == Some(name))?;
+        let start = header.sh_offset as usize;
+        data.get(start..start.checked_add(header.sh_size as usize)?)
+    };
+    let (stab, stabstr) = (section(".stab")?, section(".stabstr")?);
+    let u16_at = |b: &[u8], i: usize| {
+        let b = [b[i], b[i + 1]];
+        if elf.little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        }
+    };
+    let u32_at = |b: &[u8], i: usize| {
+        let b = [b[i], b[i + 1], b[i + 2], b[i + 3]];
+        if elf.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        }
+    };
+
+    let mut stabs = Vec::new();
+    let (mut unit_base, mut next_base) = (0usize, 0usize);
+    for entry in stab.chunks_exact(12) {
+        let (strx, typ, desc, value) = (
+            u32_at(entry, 0),
+            entry[4],
+            u16_at(entry, 6),
+            u32_at(entry, 8),
+        );
+        if typ == N_UNDF {
+            unit_base = next_base;
+            next_base += value as usize;
+            continue;
+        }
+        let name = stabstr
+            .get(unit_base + strx as usize..)
+            .and_then(|s| s.split(|b| *b == 0).next())
+            .and_then(|s| std::str::from_utf8(s).ok())
+            .unwrap_or("");
+        stabs.push(Stab {
+            typ,
+            desc,
+            value: value as u64,
+            name,
+        });
+    }
+    Some(stabs)
+}
+
+fn get_macho_stabs(data: &[u8]) -> Option<Vec<Stab<'_>>> {
+    let macho = match Mach::parse(data).ok()? {
+        Mach::Binary(macho) => macho,
+        Mach::Fat(_) => return None,
+    };
+    let symbols = macho.symbols.as_ref()?;
+    let stabs = symbols
+        .iter()
+        .filter_map(Result::ok)
+        .filter(|(_, nlist)| nlist.n_type & N_STAB != 0)
+        .map(|(name, nlist)| Stab {
+            typ: nlist.n_type,
+            desc: nlist.n_desc,
+            value: nlist.n_value,
+            name,
+        })
+        .collect();
+    Some(stabs)
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() || name.starts_with('/') {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir.trim_end_matches('/'), name)
+    }
+}
+
+/// End the current function, at `end` when its size isn't known, else after
+/// its last line
+fn close<'a>(
+    current: &mut Option<StabsFunction<'a>>,
+    end: Option<u32>,
+    functions: &mut Vec<StabsFunction<'a>>,
+) {
+    if let Some(mut fun) = current.take() {
+        if fun.len == 0 {
+            let last_line = fun.lines.iter().map(|l| l.rva + 1).max();
+            fun.len = end
+                .or(last_line)
+                .map_or(0, |end| end.saturating_sub(fun.rva));
+        }
+        if fun.len != 0 {
+            functions.push(fun);
+        }
+    }
+}
+
+/// Like the Breakpad tools: the address of a line is relative to its function,
+/// which ends at the empty N_FUN giving its size, else at the next function or
+/// at the end of its unit (empty N_SO).
+///
+/// The units of the debug map of a Mach-O file (N_OSO) are skipped: their
+/// functions have no lines, the debug info is in the DWARF of the object files.
+fn parse_stabs(stabs: Vec<Stab<'_>>, load_address: u64) -> Vec<StabsFunction<'_>> {
+    let rva = |value: u64| value.saturating_sub(load_address) as u32;
+    let mut functions = Vec::new();
+    let mut current: Option<StabsFunction> = None;
+    let (mut dir, mut unit, mut file) = ("", String::new(), String::new());
+    let mut debug_map = false;
+
+    for stab in stabs {
+        match stab.typ {
+            N_SO if stab.name.is_empty() => {
+                close(&mut current, Some(rva(stab.value)), &mut functions);
+                dir = "";
+            }
+            N_SO if stab.name.ends_with('/') => dir = stab.name,
+            N_SO => {
+                close(&mut current, None, &mut functions);
+                unit = join_path(dir, stab.name);
+                file = unit.clone();
+                debug_map = false;
+            }
+            N_OSO => debug_map = true,
+            N_SOL => file = join_path(dir, stab.name),
+            N_FUN if stab.name.is_empty() => {
+                // The size of the function
+                if let Some(fun) = current.as_mut() {
+                    fun.len = stab.value as u32;
+                }
+                close(&mut current, None, &mut functions);
+            }
+            N_FUN if debug_map => {}
+            N_FUN => {
+                let start = rva(stab.value);
+                close(&mut current, Some(start), &mut functions);
+                // A function starts in the file of its unit
+                file = unit.clone();
+                current = Some(StabsFunction {
+                    name: stab.name.split(':').next().unwrap_or(stab.name),
+                    rva: start,
+                    len: 0,
+                    lines: Vec::new(),
+                });
+            }
+            N_SLINE => {
+                if let Some(fun) = current.as_mut() {
+                    fun.lines.push(StabsLine {
+                        rva: fun.rva.saturating_add(stab.value as u32),
+                        num: stab.desc as u32,
+                        file: file.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    close(&mut current, None, &mut functions);
+
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elf_stabs() {
+        let buf = std::fs::read("./test_data/linux/stabs.full").unwrap();
+        let object = Object::parse(&buf).unwrap();
+        let functions = get_functions(&object);
+        let names: Vec<_> = functions
+            .iter()
+            .map(|f| (f.name, f.rva, f.len, f.lines.len()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![("square", 0x1129, 0xf, 3), ("main", 0x1138, 0x24, 4)]
+        );
+        assert_eq!(
+            functions[1].lines[1],
+            StabsLine {
+                rva: 0x1147,
+                num: 8,
+                file: "stabs.c".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,517 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use symbolic::common::Arch;
+use symbolic::debuginfo::{Archive, Object};
+
+use crate::common;
+use crate::object_info::ObjectInfo;
+use crate::utils::FileLock;
+
+/// A cache for the generated symbol files.
+///
+/// The files are stored as `DEBUG_ID/FILENAME.KEY.sym` where the key is a digest
+/// of the options used to generate them, so that a file is only reused when it
+/// would have been generated in the same way.
+#[derive(Debug)]
+pub struct SymCache {
+    dir: PathBuf,
+}
+
+impl SymCache {
+    /// Use the given directory or `~/.cache/dump_syms` by default.
+    pub fn new(dir: Option<&Path>) -> common::Result<Self> {
+        let dir = match dir {
+            Some(dir) => dir.to_path_buf(),
+            None => match dirs::cache_dir() {
+                Some(dir) => dir.join("dump_syms"),
+                None => anyhow::bail!("Unable to find a cache directory"),
+            },
+        };
+
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, debug_id: &str, file_name: &str, key: &str) -> PathBuf {
+        self.dir
+            .join(debug_id)
+            .join(format!("{}.{}.sym", file_name, key))
+    }
+
+    fn object_path(&self, debug_id: &str, file_name: &str, key: &str) -> PathBuf {
+        self.dir
+            .join(debug_id)
+            .join(format!("{}.{}.bin", file_name, key))
+    }
+
+    /// Get the cached symbols if any.
+    ///
+    /// The file is only returned if its MODULE line matches the debug id.
+    pub fn get(&self, debug_id: &str, file_name: &str, key: &str) -> Option<Vec<u8>> {
+        let path = self.path(debug_id, file_name, key);
+        let data = fs::read(&path).ok()?;
+
+        if !has_debug_id(&data, debug_id) {
+            warn!("Invalid symbol file in the cache: {}", path.display());
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+
+        info!("Use cached symbols from {}", path.display());
+        touch(&path);
+        Some(data)
+    }
+
+    /// Put the symbols in the cache.
+    pub fn put(
+        &self,
+        debug_id: &str,
+        file_name: &str,
+        key: &str,
+        data: &[u8],
+    ) -> common::Result<()> {
+        let path = self.path(debug_id, file_name, key);
+        Self::write(&path, data)?;
+
+        info!("Cache symbols at {}", path.display());
+        Ok(())
+    }
+
+    /// Get the cached parsed object if any, so that it can be dumped with other
+    /// output options without parsing the debug info again.
+    pub fn get_object(&self, debug_id: &str, file_name: &str, key: &str) -> Option<ObjectInfo> {
+        let path = self.object_path(debug_id, file_name, key);
+        let data = fs::read(&path).ok()?;
+
+        match bincode::deserialize::<ObjectInfo>(&data) {
+            Ok(object_info) if object_info.get_debug_id() == debug_id => {
+                info!("Use cached object from {}", path.display());
+                touch(&path);
+                Some(object_info)
+            }
+            _ => {
+                warn!("Invalid object file in the cache: {}", path.display());
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Put the parsed object in the cache.
+    pub fn put_object(
+        &self,
+        debug_id: &str,
+        file_name: &str,
+        key: &str,
+        object_info: &ObjectInfo,
+    ) -> common::Result<()> {
+        let path = self.object_path(debug_id, file_name, key);
+        Self::write(&path, &bincode::serialize(object_info)?)?;
+
+        info!("Cache object at {}", path.display());
+        Ok(())
+    }
+
+    /// The file is written in a temporary file and then renamed, so an interrupted
+    /// dump never leaves a truncated file in the cache.
+    fn write(path: &Path, data: &[u8]) -> common::Result<()> {
+        let parent = path.parent().unwrap();
+        let _lock = FileLock::lock(path)?;
+
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(format!(".{}.tmp", std::process::id()));
+        let tmp_path = PathBuf::from(tmp_path);
+        debug_assert_eq!(tmp_path.parent(), Some(parent));
+
+        let mut tmp_file = fs::File::create(&tmp_path)?;
+        tmp_file.write_all(data)?;
+        tmp_file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Compute the key for a set of options: different options or a different
+    /// version of dump_syms give a different key.
+    pub fn get_key<I, S>(options: I) -> String
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        for option in options {
+            hasher.update([0]);
+            hasher.update(option.as_ref());
+        }
+        let digest = format!("{:x}", hasher.finalize());
+        digest[..16].to_string()
+    }
+}
+
+/// Get the debug id from a MODULE line
+fn get_module_debug_id(line: &str) -> Option<&str> {
+    let toks: Vec<_> = line.split_whitespace().collect();
+    if toks.len() >= 4 && toks[0] == "MODULE" {
+        Some(toks[3])
+    } else {
+        None
+    }
+}
+
+/// Get the debug id of an existing symbol file by only reading its MODULE line.
+pub fn read_module_debug_id(path: &Path) -> Option<String> {
+    let file = fs::File::open(path).ok()?;
+    let mut line = String::new();
+    BufReader::new(file).read_line(&mut line).ok()?;
+
+    get_module_debug_id(&line).map(str::to_string)
+}
+
+fn peek_object<T, F>(buf: &[u8], arch: Arch, f: F) -> Option<T>
+where
+    F: FnOnce(&Object) -> Option<T>,
+{
+    let archive = Archive::parse(buf).ok()?;
+    let object = if archive.object_count() == 1 {
+        archive.object_by_index(0).ok()?
+    } else {
+        archive
+            .objects()
+            .filter_map(|o| o.ok())
+            .find(|o| o.arch() == arch)
+    }?;
+
+    f(&object)
+}
+
+/// Get the debug id of the object which would be dumped, without parsing its debug info.
+pub fn peek_debug_id(buf: &[u8], arch: Arch) -> Option<String> {
+    peek_object(buf, arch, |object| {
+        let debug_id = object.debug_id();
+        if debug_id.is_nil() {
+            None
+        } else {
+            Some(debug_id.breakpad().to_string())
+        }
+    })
+}
+
+/// Get the name of the pdb file referenced by a PE file, this is the name used
+/// for the symbols in the symbol stores.
+pub fn peek_pdb_name(buf: &[u8], arch: Arch) -> Option<String> {
+    peek_object(buf, arch, |object| {
+        let name = match object {
+            Object::Pe(pe) => pe.debug_file_name()?,
+            _ => return None,
+        };
+        name.rsplit(['/', '\\']).next().map(str::to_string)
+    })
+}
+
+/// Check that the MODULE line of the symbols contains the debug id
+pub(crate) fn has_debug_id(data: &[u8], debug_id: &str) -> bool {
+    let first_line = match data.split(|c| *c == b'\n').next() {
+        Some(line) => line,
+        None => return false,
+    };
+
+    get_module_debug_id(&String::from_utf8_lossy(first_line)) == Some(debug_id)
+}
+
+/// The limits of a cache directory
+#[derive(Clone, Debug, Default)]
+pub struct CacheLimits {
+    pub max_size: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+impl CacheLimits {
+    pub fn is_empty(&self) -> bool {
+        self.max_size.is_none() && self.max_age.is_none()
+    }
+}
+
+/// Parse a size like 1024, 500K, 10M or 2G
+pub fn parse_size(s: &str) -> common::Result<u64> {
+    let s = s.trim();
+    let s = s
+        .strip_suffix("iB")
+        .or_else(|| s.strip_suffix('B'))
+        .unwrap_or(s);
+    let (num, factor) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let factor = match c.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                'T' => 1 << 40,
+                _ => anyhow::bail!("Invalid size unit: {}", c),
+            };
+            (&s[..i], factor)
+        }
+        _ => (s, 1),
+    };
+
+    match num.parse::<u64>().map(|num| num.checked_mul(factor)) {
+        Ok(Some(size)) => Ok(size),
+        Ok(None) => anyhow::bail!("Too big size: {}", s),
+        _ => anyhow::bail!("Invalid size: {}", s),
+    }
+}
+
+/// Parse a duration like 3600, 30m, 12h, 7d or 2w, the default unit is the second
+pub fn parse_age(s: &str) -> common::Result<Duration> {
+    let s = s.trim();
+    let (num, factor) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let factor = match c {
+                's' => 1,
+                'm' => 60,
+                'h' => 60 * 60,
+                'd' => 24 * 60 * 60,
+                'w' => 7 * 24 * 60 * 60,
+                _ => anyhow::bail!("Invalid duration unit: {}", c),
+            };
+            (&s[..i], factor)
+        }
+        _ => (s, 1),
+    };
+
+    match num.parse::<u64>().map(|num| num.checked_mul(factor)) {
+        Ok(Some(secs)) => Ok(Duration::from_secs(secs)),
+        Ok(None) => anyhow::bail!("Too long duration: {}", s),
+        _ => anyhow::bail!("Invalid duration: {}", s),
+    }
+}
+
+/// Update the modification time of a cached file when it's used, so that the
+/// least recently used files are evicted first.
+pub(crate) fn touch(path: &Path) {
+    if let Ok(file) = fs::File::options().write(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<(PathBuf, u64, SystemTime)>) -> common::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else if entry.path().extension().is_some_and(|e| e == "lock") {
+            // The lock files are removed with the locked file
+            continue;
+        } else {
+            files.push((entry.path(), metadata.len(), metadata.modified()?));
+        }
+    }
+    Ok(())
+}
+
+fn remove_empty_dirs(dir: &Path, root: bool) -> bool {
+    let mut empty = true;
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_dir() || !remove_empty_dirs(&path, false) {
+                empty = false;
+            }
+        }
+    }
+
+    empty && !root && fs::remove_dir(dir).is_ok()
+}
+
+/// Evict the files of a cache directory which are older than the max age, then
+/// the least recently used ones until the cache fits in the max size.
+/// Without limits, the cache is emptied.
+///
+/// Returns the number of removed files and their total size.
+pub fn clean_dir(dir: &Path, limits: &CacheLimits) -> common::Result<(usize, u64)> {
+    if !dir.is_dir() {
+        return Ok((0, 0));
+    }
+
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.sort_by_key(|f| std::cmp::Reverse(f.2));
+
+    let now = SystemTime::now();
+    let mut total = 0;
+    let mut removed = (0, 0);
+    for (path, size, modified) in files {
+        let too_old = limits.max_age.map_or(limits.max_size.is_none(), |max_age| {
+            now.duration_since(modified).unwrap_or_default() > max_age
+        });
+        let too_big = limits
+            .max_size
+            .is_some_and(|max_size| total + size > max_size);
+
+        if too_old || too_big {
+            // Don't remove a file which is being written by another process
+            let lock = FileLock::lock(&path)?;
+            fs::remove_file(&path)?;
+            lock.remove();
+            removed.0 += 1;
+            removed.1 += size;
+        } else {
+            total += size;
+        }
+    }
+    remove_empty_dirs(dir, true);
+
+    info!(
+        "Remove {} files ({} bytes) from the cache {}",
+        removed.0,
+        removed.1,
+        dir.display()
+    );
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_sym_cache() {
+        let tmp_dir = Builder::new().prefix("sym_cache").tempdir().unwrap();
+        let cache = SymCache::new(Some(tmp_dir.path())).unwrap();
+        let key = SymCache::get_key(["inlines"]);
+        let data = b"MODULE Linux x86_64 ABCDEF0 basic\nFUNC 1000 10 0 main\n";
+
+        assert!(cache.get("ABCDEF0", "basic", &key).is_none());
+        cache.put("ABCDEF0", "basic", &key, data).unwrap();
+        assert_eq!(cache.get("ABCDEF0", "basic", &key).unwrap(), data);
+
+        // Other options
+        let other_key = SymCache::get_key(["no-inlines"]);
+        assert_ne!(key, other_key);
+        assert!(cache.get("ABCDEF0", "basic", &other_key).is_none());
+
+        // A file with the wrong id is removed
+        cache.put("1234567", "basic", &key, data).unwrap();
+        assert!(cache.get("1234567", "basic", &key).is_none());
+        assert!(!cache.path("1234567", "basic", &key).exists());
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("500K").unwrap(), 500 * 1024);
+        assert_eq!(parse_size("10MB").unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size("2GiB").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert!(parse_size("12X").is_err());
+        assert!(parse_size("99999999999999999999G").is_err());
+        assert!(parse_size("17179869184G").is_err());
+
+        assert_eq!(parse_age("3600").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_age("12h").unwrap(), Duration::from_secs(12 * 3600));
+        assert_eq!(parse_age("7d").unwrap(), Duration::from_secs(7 * 24 * 3600));
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("30500568904944w").is_err());
+    }
+
+    #[test]
+    fn test_clean_dir() {
+        let tmp_dir = Builder::new().prefix("clean_cache").tempdir().unwrap();
+        let cache = SymCache::new(Some(tmp_dir.path())).unwrap();
+        let data = b"MODULE Linux x86_64 ABCDEF0 basic\n";
+        let now = SystemTime::now();
+
+        for (i, id) in ["ABCDEF0", "ABCDEF1", "ABCDEF2"].iter().enumerate() {
+            cache.put(id, "basic", "key", data).unwrap();
+            let file = fs::File::options()
+                .write(true)
+                .open(cache.path(id, "basic", "key"))
+                .unwrap();
+            file.set_modified(now - Duration::from_secs(3600 * i as u64))
+                .unwrap();
+        }
+
+        // The oldest one
+        let limits = CacheLimits {
+            max_age: Some(Duration::from_secs(5000)),
+            ..Default::default()
+        };
+        assert_eq!(clean_dir(tmp_dir.path(), &limits).unwrap(), (1, 34));
+        assert!(!tmp_dir.path().join("ABCDEF2").exists());
+
+        // The least recently used one
+        let limits = CacheLimits {
+            max_size: Some(40),
+            ..Default::default()
+        };
+        assert_eq!(clean_dir(tmp_dir.path(), &limits).unwrap(), (1, 34));
+        assert!(tmp_dir.path().join("ABCDEF0").exists());
+        assert!(!tmp_dir.path().join("ABCDEF1").exists());
+
+        // Everything
+        let limits = CacheLimits::default();
+        assert_eq!(clean_dir(tmp_dir.path(), &limits).unwrap(), (1, 34));
+        assert!(tmp_dir.path().exists());
+        assert_eq!(fs::read_dir(tmp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_remove_lock() {
+        let tmp_dir = Builder::new().prefix("remove_lock").tempdir().unwrap();
+        let path = tmp_dir.path().join("basic.sym");
+        let lock_path = tmp_dir.path().join("basic.sym.lock");
+
+        // The file is removed while another process is waiting for its lock:
+        // the waiter must lock the new lock file, not the removed one
+        let lock = FileLock::lock(&path).unwrap();
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                let _lock = FileLock::lock(&path).unwrap();
+                lock_path.exists()
+            });
+            std::thread::sleep(Duration::from_millis(100));
+            lock.remove();
+            assert!(waiter.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_read_module_debug_id() {
+        assert_eq!(
+            read_module_debug_id(Path::new("./test_data/linux/basic.full.sym")).unwrap(),
+            "20AD60B0B4C68177552708AA192E77390"
+        );
+        assert!(read_module_debug_id(Path::new("./test_data/linux/basic.full")).is_none());
+    }
+
+    #[test]
+    fn test_peek_pdb_name() {
+        let buf = std::fs::read("./test_data/windows/mozwer.dll").unwrap();
+        assert_eq!(peek_pdb_name(&buf, Arch::Amd64).unwrap(), "mozwer.pdb");
+
+        let buf = std::fs::read("./test_data/linux/basic.full").unwrap();
+        assert!(peek_pdb_name(&buf, Arch::Amd64).is_none());
+    }
+
+    #[test]
+    fn test_peek_debug_id() {
+        let buf = std::fs::read("./test_data/linux/basic.full").unwrap();
+        assert_eq!(
+            peek_debug_id(&buf, Arch::Amd64).unwrap(),
+            "20AD60B0B4C68177552708AA192E77390"
+        );
+    }
+}
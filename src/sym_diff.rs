@@ -0,0 +1,188 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use std::collections::BTreeMap;
+
+/// The key of a record: its kind, its address and the rest of the header for the
+/// records without an address, so the records are compared in the address order.
+type Key = (u8, u64, String);
+
+/// Split the symbols in blocks: a FUNC record and its lines, inlines and labels,
+/// a STACK CFI INIT record and its deltas, or a single record otherwise.
+/// The file and inline origin numbers are replaced by their names since they
+/// depend on the order of the collection.
+fn get_blocks(sym: &str) -> BTreeMap<Key, Vec<String>> {
+    let mut files = HashMap::new();
+    let mut origins = HashMap::new();
+    for line in sym.lines() {
+        if let Some(rest) = line.strip_prefix("FILE ") {
+            if let Some((id, name)) = rest.split_once(' ') {
+                files.insert(id, name);
+            }
+        } else if let Some(rest) = line.strip_prefix("INLINE_ORIGIN ") {
+            if let Some((id, name)) = rest.split_once(' ') {
+                origins.insert(id, name);
+            }
+        }
+    }
+    let file = |id: &str| files.get(id).copied().unwrap_or(id).to_string();
+    let origin = |id: &str| origins.get(id).copied().unwrap_or(id).to_string();
+
+    let mut blocks = BTreeMap::new();
+    let mut current: Option<Key> = None;
+    for line in sym.lines() {
+        let toks: Vec<_> = line.split(' ').collect();
+        let address = |i: usize| {
+            toks.get(i)
+                .and_then(|t| u64::from_str_radix(t, 16).ok())
+                .unwrap_or_default()
+        };
+        let base = if toks.get(1) == Some(&"m") { 2 } else { 1 };
+
+        let (key, line) = match toks[0] {
+            "FILE" | "INLINE_ORIGIN" => continue,
+            "FILE_GIT" if toks.len() >= 2 => {
+                let line = format!("FILE_GIT {} {}", file(toks[1]), toks[2..].join(" "));
+                ((7, 0, line.clone()), line)
+            }
+            "MODULE" => ((0, 0, String::new()), line.to_string()),
+            "INFO" => (
+                (1, 0, toks[..2.min(toks.len())].join(" ")),
+                line.to_string(),
+            ),
+            "FUNC" => ((2, address(base), String::new()), line.to_string()),
+            "PUBLIC" => ((3, address(base), String::new()), line.to_string()),
+            "DATA" => ((4, address(1), String::new()), line.to_string()),
+            "STACK" if toks.get(2) == Some(&"INIT") => {
+                ((5, address(3), String::new()), line.to_string())
+            }
+            "STACK" if toks.get(1) == Some(&"WIN") => (
+                (6, address(3), toks[..3.min(toks.len())].join(" ")),
+                line.to_string(),
+            ),
+            // The records of the current block
+            "STACK" | "LABEL" | "LANGUAGE" if current.is_some() => {
+                blocks
+                    .entry(current.clone().unwrap())
+                    .or_insert_with(Vec::new)
+                    .push(line.to_string());
+                continue;
+            }
+            "INLINE" if current.is_some() && toks.len() >= 5 => {
+                let mut toks: Vec<_> = toks.iter().map(|t| t.to_string()).collect();
+                toks[3] = file(&toks[3]);
+                toks[4] = origin(&toks[4]);
+                blocks
+                    .entry(current.clone().unwrap())
+                    .or_insert_with(Vec::new)
+                    .push(toks.join(" "));
+                continue;
+            }
+            _ if current.is_some() && toks.len() == 4 && address(0) != 0 => {
+                let line = format!("{} {} {} {}", toks[0], toks[1], toks[2], file(toks[3]));
+                blocks
+                    .entry(current.clone().unwrap())
+                    .or_insert_with(Vec::new)
+                    .push(line);
+                continue;
+            }
+            _ => ((7, 0, line.to_string()), line.to_string()),
+        };
+
+        current = match key.0 {
+            2 | 5 => Some(key.clone()),
+            _ => None,
+        };
+        blocks.entry(key).or_insert_with(Vec::new).push(line);
+    }
+
+    blocks
+}
+
+/// Compare two Breakpad symbol files regardless of the order of the records and
+/// of the numbering of the files and of the inline origins. The records only in
+/// the left file are prefixed by `-` and the ones only in the right file by `+`,
+/// the lines of a block which differs are all in the output.
+pub fn diff_sym(left: &str, right: &str) -> Vec<String> {
+    let left = get_blocks(left);
+    let mut right = get_blocks(right);
+    let mut out = Vec::new();
+
+    let mut keys: Vec<_> = left.keys().chain(right.keys()).cloned().collect();
+    keys.sort();
+    keys.dedup();
+
+    for key in keys {
+        let l = left.get(&key);
+        let r = right.remove(&key);
+        if l == r.as_ref() {
+            continue;
+        }
+        for line in l.into_iter().flatten() {
+            out.push(format!("- {}", line));
+        }
+        for line in r.into_iter().flatten() {
+            out.push(format!("+ {}", line));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_sym() {
+        let left = "MODULE Linux x86_64 ID basic.so
+FILE 0 a.cpp
+FILE 1 b.cpp
+FUNC 1000 10 0 foo
+1000 8 1 0
+1008 8 2 1
+PUBLIC 2000 0 bar
+STACK CFI INIT 1000 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^
+STACK CFI 1001 .cfa: $rsp 16 +
+";
+        // The same with other file numbers and another order
+        let right = "MODULE Linux x86_64 ID basic.so
+FILE 0 b.cpp
+FILE 1 a.cpp
+PUBLIC 2000 0 bar
+FUNC 1000 10 0 foo
+1000 8 1 1
+1008 8 2 0
+STACK CFI INIT 1000 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^
+STACK CFI 1001 .cfa: $rsp 16 +
+";
+        assert!(diff_sym(left, right).is_empty());
+
+        let right = "MODULE Linux x86_64 ID basic.so
+FILE 0 a.cpp
+FILE 1 b.cpp
+FUNC 1000 10 0 foo
+1000 8 1 0
+1008 8 3 1
+PUBLIC 2010 0 bar
+STACK CFI INIT 1000 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^
+STACK CFI 1001 .cfa: $rsp 16 +
+";
+        assert_eq!(
+            diff_sym(left, right),
+            vec![
+                "- FUNC 1000 10 0 foo",
+                "- 1000 8 1 a.cpp",
+                "- 1008 8 2 b.cpp",
+                "+ FUNC 1000 10 0 foo",
+                "+ 1000 8 1 a.cpp",
+                "+ 1008 8 3 b.cpp",
+                "- PUBLIC 2000 0 bar",
+                "+ PUBLIC 2010 0 bar",
+            ]
+        );
+    }
+}
@@ -3,14 +3,18 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use log::warn;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::ops::Bound::{Excluded, Included};
+use std::ops::Range;
 use symbolic::debuginfo::Object;
 
+use crate::collector::Sentinel;
 use crate::line::Lines;
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Symbol {
     pub name: String,
     pub is_public: bool,
@@ -20,10 +24,47 @@ pub struct Symbol {
     pub len: u32,
     pub parameter_size: u32,
     pub source: Lines,
+    /// The labels inside the function
+    pub labels: Vec<Label>,
+    /// The source language of the function, from its compiland or its unit
+    pub language: Option<String>,
+}
+
+/// A named location inside a function, e.g. a label in assembly code
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Label {
+    pub rva: u32,
+    pub name: String,
 }
 
 pub type Symbols = BTreeMap<u32, Symbol>;
 
+/// A global or a static variable
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DataSymbol {
+    pub name: String,
+    /// For a thread-local variable, it's the address in the TLS template
+    pub rva: u32,
+    pub len: u32,
+    pub is_tls: bool,
+}
+
+/// The TLS addresses are in another address space so they're in the key too
+pub type DataSymbols = BTreeMap<(u32, bool), DataSymbol>;
+
+impl Display for DataSymbol {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "DATA {}{:x} {:x} {}",
+            if self.is_tls { "t " } else { "" },
+            self.rva,
+            self.len,
+            self.name
+        )
+    }
+}
+
 pub trait ContainsSymbol {
     fn is_inside_symbol(&self, rva: u32) -> bool;
 }
@@ -31,7 +72,7 @@ pub trait ContainsSymbol {
 impl ContainsSymbol for Symbols {
     fn is_inside_symbol(&self, rva: u32) -> bool {
         let last = self.range((Included(0), Excluded(rva))).next_back();
-        last.map_or(false, |last| rva < (last.1.rva + last.1.len))
+        last.is_some_and(|last| rva < (last.1.rva + last.1.len))
     }
 }
 
@@ -58,6 +99,14 @@ impl Display for Symbol {
             )?;
 
             write!(f, "{}", self.source)?;
+
+            if let Some(language) = &self.language {
+                writeln!(f, "LANGUAGE {}", language)?;
+            }
+
+            for label in self.labels.iter() {
+                writeln!(f, "LABEL {:x} {}", label.rva, label.name)?;
+            }
         }
 
         Ok(())
@@ -115,6 +164,8 @@ pub(super) fn add_executable_section_symbols(
                     len: 0,
                     parameter_size: 0,
                     source: Lines::new(),
+                    labels: Vec::new(),
+                    language: None,
                 });
             }
         }
@@ -123,19 +174,141 @@ pub(super) fn add_executable_section_symbols(
     syms
 }
 
-pub(super) fn append_dummy_symbol(mut syms: Symbols, name: &str) -> Symbols {
+/// Based on the ranges of the FDEs of a stripped ELF file, add a synthetic function
+/// for every range which doesn't start in a known symbol, so the stack walkers get
+/// the frame boundaries. It's named after the nearest symbol before it (`name+0x40`),
+/// or `fn_<address>` when it's a section placeholder.
+// This runs after add_executable_section_symbols.
+pub(super) fn add_cfi_function_symbols(mut syms: Symbols, ranges: &[Range<u32>]) -> Symbols {
+    let mut functions = Vec::new();
+    for range in ranges.iter().filter(|r| r.start < r.end) {
+        // The function doesn't extend over the next symbol
+        let next = syms.range(range.start + 1..).next().map(|(rva, _)| *rva);
+        let len = next.map_or(range.end, |next| range.end.min(next)) - range.start;
+        if syms.contains_key(&range.start) || syms.is_inside_symbol(range.start) {
+            continue;
+        }
+
+        let name = match syms.range(..range.start).next_back() {
+            Some((rva, sym)) if !sym.is_synthetic => {
+                format!("{}+0x{:x}", sym.name, range.start - rva)
+            }
+            _ => format!("fn_{:x}", range.start),
+        };
+        functions.push((range.start, len, name));
+    }
+
+    for (rva, len, name) in functions {
+        syms.insert(
+            rva,
+            Symbol {
+                name,
+                is_public: false,
+                is_multiple: false,
+                is_synthetic: true,
+                rva,
+                len,
+                parameter_size: 0,
+                source: Lines::new(),
+                labels: Vec::new(),
+                language: None,
+            },
+        );
+    }
+
+    syms
+}
+
+/// Get the variables from the symbol tables of an ELF file
+#[cfg(feature = "elf")]
+pub(super) fn get_elf_data_symbols(object: &Object) -> Vec<DataSymbol> {
+    let load_address = object.load_address();
+    let mut data = Vec::new();
+
+    if let Ok(goblin::Object::Elf(elf)) = goblin::Object::parse(object.data()) {
+        // The value of a TLS symbol is an offset in the TLS template
+        let tls_address = elf
+            .program_headers
+            .iter()
+            .find(|h| h.p_type == goblin::elf::program_header::PT_TLS)
+            .map(|h| h.p_vaddr);
+
+        let syms = elf
+            .syms
+            .iter()
+            .map(|sym| (sym, &elf.strtab))
+            .chain(elf.dynsyms.iter().map(|sym| (sym, &elf.dynstrtab)));
+
+        for (sym, strtab) in syms {
+            // Skip the undefined, absolute and common symbols
+            if sym.st_shndx == goblin::elf::section_header::SHN_UNDEF as usize
+                || sym.st_shndx >= goblin::elf::section_header::SHN_LORESERVE as usize
+            {
+                continue;
+            }
+
+            let is_tls = match sym.st_type() {
+                goblin::elf::sym::STT_OBJECT => false,
+                goblin::elf::sym::STT_TLS => true,
+                _ => continue,
+            };
+            let address = match (is_tls, tls_address) {
+                (false, _) => sym.st_value,
+                (true, Some(tls_address)) => tls_address + sym.st_value,
+                (true, None) => continue,
+            };
+            if address < load_address {
+                continue;
+            }
+
+            let name = match strtab.get_at(sym.st_name) {
+                Some(name) if !name.is_empty() => name,
+                _ => continue,
+            };
+
+            data.push(DataSymbol {
+                name: name.to_string(),
+                rva: (address - load_address) as u32,
+                len: sym.st_size as u32,
+                is_tls,
+            });
+        }
+    }
+
+    data
+}
+
+/// The name of the synthetic symbols covering the code of a module without a symbol
+pub(super) fn get_unknown_name(module_name: &str) -> String {
+    if module_name.is_empty() {
+        String::from("<unknown>")
+    } else {
+        format!("<unknown in {}>", module_name)
+    }
+}
+
+/// Add a public symbol after the last one, so the addresses after the last
+/// function aren't attributed to it: just after it or at the end of its section.
+pub(super) fn append_dummy_symbol(
+    mut syms: Symbols,
+    name: String,
+    sentinel: Sentinel,
+    sections: &[Range<u32>],
+) -> Symbols {
     let (rva, len) = if let Some((_, last_sym)) = syms.iter().next_back() {
         (last_sym.rva, last_sym.len)
     } else {
         return syms;
     };
 
-    let rva = if len == 0 { rva + 1 } else { rva + len };
-
-    let name = if name.is_empty() {
-        String::from("<unknown>")
-    } else {
-        format!("<unknown in {}>", name)
+    let after_last = if len == 0 { rva + 1 } else { rva + len };
+    let rva = match sentinel {
+        Sentinel::Off => return syms,
+        Sentinel::AfterLast => after_last,
+        Sentinel::SectionEnd => sections
+            .iter()
+            .find(|s| s.contains(&rva))
+            .map_or(after_last, |s| s.end.max(after_last)),
     };
 
     syms.entry(rva).or_insert(Symbol {
@@ -147,13 +320,303 @@ pub(super) fn append_dummy_symbol(mut syms: Symbols, name: &str) -> Symbols {
         len: 0,
         parameter_size: 0,
         source: Lines::new(),
+        labels: Vec::new(),
+        language: None,
+    });
+
+    syms
+}
+
+/// A section of an object file
+pub(super) struct Section {
+    /// The address range, relative to the load address
+    pub range: Range<u32>,
+    pub is_code: bool,
+}
+
+/// Get the sections of an object, the file is parsed once for all the ranges
+/// needed. The headers come from the file so the ends are saturated.
+pub(super) fn get_sections(object: &Object) -> Vec<Section> {
+    let to_section = |start: u64, len: u64, is_code: bool| {
+        let start = start.saturating_sub(object.load_address()) as u32;
+        Section {
+            range: start..start.saturating_add(len as u32),
+            is_code,
+        }
+    };
+
+    if let Object::Pdb(pdb) = object {
+        let sections = pdb.inner().write().sections();
+        return match sections {
+            Ok(Some(sections)) => sections
+                .iter()
+                .map(|s| Section {
+                    range: s.virtual_address..s.virtual_address.saturating_add(s.virtual_size),
+                    is_code: s.characteristics.execute(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+    }
+
+    match goblin::Object::parse(object.data()) {
+        Ok(goblin::Object::PE(pe)) => pe
+            .sections
+            .iter()
+            .map(|s| Section {
+                range: s.virtual_address..s.virtual_address.saturating_add(s.virtual_size),
+                is_code: s.characteristics & goblin::pe::section_table::IMAGE_SCN_MEM_EXECUTE != 0,
+            })
+            .collect(),
+        Ok(goblin::Object::Elf(elf)) => elf
+            .section_headers
+            .iter()
+            .filter(|h| h.is_alloc())
+            .map(|h| to_section(h.sh_addr, h.sh_size, h.is_executable()))
+            .collect(),
+        Ok(goblin::Object::Mach(goblin::mach::Mach::Binary(macho))) => macho
+            .segments
+            .iter()
+            .filter_map(|segment| segment.sections().ok())
+            .flatten()
+            .map(|(section, _)| {
+                let is_code = section.flags
+                    & (goblin::mach::constants::S_ATTR_PURE_INSTRUCTIONS
+                        | goblin::mach::constants::S_ATTR_SOME_INSTRUCTIONS)
+                    != 0;
+                to_section(section.addr, section.size, is_code)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Get the address ranges of the sections, relative to the load address
+pub(super) fn get_section_ranges(sections: &[Section]) -> Vec<Range<u32>> {
+    sections.iter().map(|s| s.range.clone()).collect()
+}
+
+/// Get the address ranges of the executable sections, relative to the load address
+pub(super) fn get_executable_ranges(sections: &[Section]) -> Vec<Range<u32>> {
+    sections
+        .iter()
+        .filter(|s| s.is_code && !s.range.is_empty())
+        .map(|s| s.range.clone())
+        .collect()
+}
+
+/// Get the rva and the data in the file of the code sections of a PE file
+pub(super) fn get_pe_code<'a>(object: &Object<'a>) -> Vec<(u32, &'a [u8])> {
+    let data = object.data();
+    match goblin::Object::parse(data) {
+        Ok(goblin::Object::PE(pe)) => pe
+            .sections
+            .iter()
+            .filter(|s| s.characteristics & goblin::pe::section_table::IMAGE_SCN_MEM_EXECUTE != 0)
+            .filter_map(|s| {
+                let start = s.pointer_to_raw_data as usize;
+                let len = s.size_of_raw_data.min(s.virtual_size) as usize;
+                Some((s.virtual_address, data.get(start..start.checked_add(len)?)?))
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Cut the functions running over the end of their section (e.g. a wrong size
+/// in the exception data or in the section contributions) and their lines.
+pub(super) fn clamp_to_sections(syms: &mut Symbols, sections: &[Range<u32>]) {
+    for sym in syms.values_mut().filter(|sym| !sym.is_public) {
+        let end = match sections.iter().find(|s| s.contains(&sym.rva)) {
+            Some(section) => section.end,
+            None => continue,
+        };
+        if sym.rva.saturating_add(sym.len) > end {
+            warn!(
+                "The function {} at 0x{:x} runs over the end of its section at 0x{:x}",
+                sym.name, sym.rva, end
+            );
+            sym.len = end - sym.rva;
+            sym.source = sym.source.slice(&(sym.rva..end));
+        }
+    }
+}
+
+/// Remove the public symbols inside a function, whichever symbol is before them
+pub(super) fn drop_inner_publics(syms: &mut Symbols) {
+    let mut end = 0;
+    syms.retain(|rva, sym| {
+        if sym.is_public {
+            return *rva >= end;
+        }
+        end = end.max(rva.saturating_add(sym.len));
+        true
     });
+}
+
+/// Give a length to the symbols without one (the publics, the functions from
+/// the exception data, ...): they extend up to the next symbol or to the end of
+/// their section.
+pub(super) fn set_missing_lengths(syms: &mut Symbols, sections: &[Range<u32>]) {
+    let starts: Vec<u32> = syms.keys().copied().collect();
+    for (sym, next) in syms
+        .values_mut()
+        .zip(starts.iter().skip(1).map(Some).chain(std::iter::once(None)))
+    {
+        if sym.len != 0 {
+            continue;
+        }
+
+        let section_end = sections
+            .iter()
+            .find(|s| s.contains(&sym.rva))
+            .map(|s| s.end);
+        let end = match (next, section_end) {
+            (Some(next), Some(end)) => (*next).min(end),
+            (Some(next), None) => *next,
+            (None, Some(end)) => end,
+            (None, None) => continue,
+        };
+        sym.len = end - sym.rva;
+    }
+}
+
+/// Start an `<unknown in module>` public symbol at the end of the functions not
+/// followed by a symbol, so the code after them (padding, stripped functions,
+/// ...) isn't attributed to the previous public symbol by the processors.
+pub(super) fn add_unknown_symbols(
+    mut syms: Symbols,
+    sections: &[Range<u32>],
+    name: &str,
+) -> Symbols {
+    let name = get_unknown_name(name);
+
+    // The end of the functions covering the code up to a symbol after a gap
+    let mut ends = Vec::new();
+    let mut end: Option<u32> = None;
+    for sym in syms.values() {
+        if let Some(e) = end.filter(|e| sym.rva >= *e) {
+            if sym.rva > e {
+                ends.push(e);
+            }
+            end = None;
+        }
+        if !sym.is_public && sym.len != 0 {
+            let e = sym.rva.saturating_add(sym.len);
+            end = Some(end.map_or(e, |end| end.max(e)));
+        }
+    }
+    ends.extend(end);
+
+    for rva in ends {
+        if !sections.iter().any(|s| s.contains(&rva)) {
+            continue;
+        }
+        syms.insert(
+            rva,
+            Symbol {
+                name: name.clone(),
+                is_public: true,
+                is_multiple: false,
+                is_synthetic: true,
+                rva,
+                len: 0,
+                parameter_size: 0,
+                source: Lines::new(),
+                labels: Vec::new(),
+                language: None,
+            },
+        );
+    }
 
     syms
 }
 
+/// The gaps between the functions shorter than that are the alignment padding
+const MIN_GAP_LEN: u32 = 16;
+
+/// The section placeholders, the `<unknown>` symbols and the sentinel only fill
+/// the gaps between the symbols
+fn is_filler(sym: &Symbol) -> bool {
+    sym.is_public && sym.is_synthetic
+}
+
+/// Get the parts of the code which aren't covered by a FUNC or a PUBLIC record,
+/// sorted. The PUBLIC records cover the code up to the next symbol.
+// This runs after set_missing_lengths and before add_unknown_symbols.
+pub(super) fn get_gaps(syms: &Symbols, code: &[Range<u32>]) -> Vec<Range<u32>> {
+    let mut gaps = Vec::new();
+    for section in code {
+        let mut pos = section.start;
+        // A symbol before the section could run over it
+        let before = syms
+            .range(..section.start)
+            .rev()
+            .find(|(_, sym)| !is_filler(sym));
+        let inside = syms.range(section.clone());
+        for sym in before.into_iter().chain(inside).map(|(_, sym)| sym) {
+            if is_filler(sym) {
+                continue;
+            }
+            if sym.rva > pos {
+                gaps.push(pos..sym.rva);
+            }
+            pos = pos.max(sym.rva.saturating_add(sym.len));
+        }
+        if pos < section.end {
+            gaps.push(pos..section.end);
+        }
+    }
+    gaps.retain(|gap| gap.end - gap.start >= MIN_GAP_LEN);
+    gaps.sort_by_key(|gap| gap.start);
+
+    gaps
+}
+
+/// Log the code of the executable sections which isn't covered by a symbol, with
+/// the symbols around it, so the symbols dropped on the way can be spotted.
+pub(super) fn report_gaps(syms: &Symbols, code: &[Range<u32>]) {
+    let gaps = get_gaps(syms, code);
+    let mut total = 0;
+    for gap in &gaps {
+        let len = gap.end - gap.start;
+        total += len;
+        let before = syms
+            .range(..gap.start)
+            .rev()
+            .map(|(_, sym)| sym)
+            .find(|sym| !is_filler(sym));
+        let after = syms
+            .range(gap.end..)
+            .map(|(_, sym)| sym)
+            .find(|sym| !is_filler(sym));
+        let neighbors = match (before, after) {
+            (Some(before), Some(after)) => format!(" between {} and {}", before.name, after.name),
+            (Some(before), None) => format!(" after {}", before.name),
+            (None, Some(after)) => format!(" before {}", after.name),
+            (None, None) => String::new(),
+        };
+        warn!(
+            category = "gaps", rva = gap.start;
+            "Uncovered code at 0x{:x} (0x{:x} bytes){}",
+            gap.start,
+            len,
+            neighbors
+        );
+    }
+    if !gaps.is_empty() {
+        warn!(
+            category = "gaps";
+            "0x{:x} bytes of code in {} gaps aren't covered by a symbol",
+            total,
+            gaps.len()
+        );
+    }
+}
+
 // Get separated debugging information into .gnu_debugdata section.
 // See https://sourceware.org/gdb/onlinedocs/gdb/MiniDebugInfo.html.
+#[cfg(feature = "elf")]
 pub(super) fn get_compressed_minidebuginfo(object: &Object) -> Option<Vec<u8>> {
     let data = object.data();
     let object = goblin::Object::parse(data);
@@ -173,30 +636,32 @@ pub(super) fn get_compressed_minidebuginfo(object: &Object) -> Option<Vec<u8>> {
     None
 }
 
+/// A decorated name without its decorations, the name is borrowed from the
+/// symbol table which is mapped in memory, only the demangled one is allocated.
 #[derive(Clone, Debug)]
-pub struct ParsedWinFuncName {
-    pub name: String,
+pub struct ParsedWinFuncName<'a> {
+    pub name: &'a str,
     pub param_size: Option<u32>,
 }
 
-impl ParsedWinFuncName {
-    pub fn name_only(name: String) -> Self {
+impl<'a> ParsedWinFuncName<'a> {
+    pub fn name_only(name: &'a str) -> Self {
         Self {
             name,
             param_size: None,
         }
     }
 
-    pub fn parse_unknown(name: &str) -> Self {
-        if name.contains(|c| c == ':' || c == '(') {
-            Self::name_only(name.to_string())
+    pub fn parse_unknown(name: &'a str) -> Self {
+        if name.contains([':', '(']) {
+            Self::name_only(name)
         } else {
             Self::parse_c_decorated(name)
         }
     }
 
     /// Call this if c_decorated_name does not contain ':' or '('.
-    pub fn parse_c_decorated(c_decorated_name: &str) -> Self {
+    pub fn parse_c_decorated(c_decorated_name: &'a str) -> Self {
         // https://docs.microsoft.com/en-us/cpp/build/reference/decorated-names?view=vs-2019
         // __cdecl Leading underscore (_)
         // __stdcall Leading underscore (_) and a trailing at sign (@) followed by the number of bytes in the parameter list in decimal
@@ -208,7 +673,7 @@ impl ParsedWinFuncName {
         if let Some((name, param_size)) = c_decorated_name.rsplit_once("@@") {
             if let Ok(param_size) = param_size.parse::<u32>() {
                 return Self {
-                    name: name.to_string(),
+                    name,
                     param_size: Some(param_size),
                 };
             }
@@ -221,19 +686,19 @@ impl ParsedWinFuncName {
                     if let Ok(param_size) = param_size.parse::<u32>() {
                         // __stdcall or __fastcall
                         return Self {
-                            name: name.to_string(),
+                            name,
                             param_size: Some(param_size),
                         };
                     }
                 }
                 if &c_decorated_name[0..1] == "_" {
                     // __cdecl
-                    return Self::name_only(rest.to_string());
+                    return Self::name_only(rest);
                 }
             }
         }
 
-        Self::name_only(c_decorated_name.to_string())
+        Self::name_only(c_decorated_name)
     }
 }
 
@@ -252,3 +717,284 @@ fn is_constant_number(name: &str) -> bool {
 pub fn should_skip_symbol(name: &str) -> bool {
     is_constant_string(name) || is_constant_number(name)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_missing_lengths() {
+        let mut syms = Symbols::new();
+        for (rva, len) in [
+            (0x1000, 0),
+            (0x1010, 0x8),
+            (0x1020, 0),
+            (0x2000, 0),
+            (0x3000, 0),
+        ] {
+            syms.insert(
+                rva,
+                Symbol {
+                    rva,
+                    len,
+                    ..Default::default()
+                },
+            );
+        }
+
+        set_missing_lengths(&mut syms, &[0x1000..0x1100, 0x2000..0x2100]);
+        let lens: Vec<_> = syms.values().map(|s| (s.rva, s.len)).collect();
+        assert_eq!(
+            lens,
+            vec![
+                (0x1000, 0x10),
+                (0x1010, 0x8),
+                (0x1020, 0xe0),
+                (0x2000, 0x100),
+                (0x3000, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_sections() {
+        let pe = std::fs::read("./test_data/windows/basic64.dll").unwrap();
+        let pdb = std::fs::read("./test_data/windows/basic64.pdb").unwrap();
+        let pe_sections = get_sections(&Object::parse(&pe).unwrap());
+        let pdb_sections = get_sections(&Object::parse(&pdb).unwrap());
+
+        assert_eq!(get_section_ranges(&pe_sections).len(), 7);
+        assert_eq!(
+            get_section_ranges(&pe_sections),
+            get_section_ranges(&pdb_sections)
+        );
+        assert_eq!(get_executable_ranges(&pe_sections), vec![0x1000..0x6b033]);
+        assert_eq!(get_executable_ranges(&pdb_sections), vec![0x1000..0x6b033]);
+
+        // A corrupted virtual size doesn't overflow
+        let mut pe = pe;
+        let text = pe.windows(8).position(|w| w == b".text\0\0\0").unwrap();
+        pe[text + 8..text + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        let pe_sections = get_sections(&Object::parse(&pe).unwrap());
+        assert_eq!(get_executable_ranges(&pe_sections), vec![0x1000..u32::MAX]);
+    }
+
+    #[test]
+    fn test_clamp_to_sections() {
+        let mut syms = Symbols::new();
+        for (rva, len, is_public) in [
+            (0x1000, 0x10, false),
+            (0x10f0, 0x20, false),
+            (0x10f8, 0, true),
+            (0x3000, 0x20, false),
+        ] {
+            let mut source = Lines::new();
+            source.add_line(rva, 1, 0);
+            source.add_line(rva + 0x10, 2, 0);
+            source.finalize(rva, len);
+            syms.insert(
+                rva,
+                Symbol {
+                    rva,
+                    len,
+                    is_public,
+                    source,
+                    ..Default::default()
+                },
+            );
+        }
+
+        clamp_to_sections(&mut syms, &[0x1000..0x1100, 0x2000..0x2100]);
+        let lens: Vec<_> = syms.values().map(|s| (s.rva, s.len)).collect();
+        assert_eq!(
+            lens,
+            vec![(0x1000, 0x10), (0x10f0, 0x10), (0x10f8, 0), (0x3000, 0x20)]
+        );
+        // The line after the end of the section is gone
+        assert_eq!(syms[&0x10f0].source.to_string(), "10f0 10 1 0\n");
+    }
+
+    #[test]
+    fn test_drop_inner_publics() {
+        let mut syms = Symbols::new();
+        for (rva, len, is_public) in [
+            (0x1000, 0x40, false),
+            (0x1010, 0, true),
+            (0x1020, 0x8, false),
+            (0x1030, 0x4, true),
+            (0x1040, 0, true),
+            (0x1050, 0x8, false),
+        ] {
+            syms.insert(
+                rva,
+                Symbol {
+                    rva,
+                    len,
+                    is_public,
+                    ..Default::default()
+                },
+            );
+        }
+
+        drop_inner_publics(&mut syms);
+        // The public after the second function is still in the first one
+        let rvas: Vec<_> = syms.keys().copied().collect();
+        assert_eq!(rvas, vec![0x1000, 0x1020, 0x1040, 0x1050]);
+    }
+
+    #[test]
+    fn test_get_gaps() {
+        let mut syms = Symbols::new();
+        for (rva, len, is_public, is_synthetic) in [
+            // A section placeholder
+            (0x1000, 0x10, true, true),
+            (0x1010, 0x10, false, false),
+            (0x1040, 0x20, true, false),
+            (0x1070, 0x8, false, false),
+            (0x1080, 0x7c, false, false),
+            (0x1ff0, 0x20, false, false),
+            // An <unknown> symbol
+            (0x2040, 0xc0, true, true),
+        ] {
+            syms.insert(
+                rva,
+                Symbol {
+                    rva,
+                    len,
+                    is_public,
+                    is_synthetic,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let gaps = get_gaps(&syms, &[0x1000..0x1100, 0x2000..0x2100]);
+        // The padding before 0x1080 and at the end of the first section isn't a gap
+        assert_eq!(
+            gaps,
+            vec![
+                0x1000..0x1010,
+                0x1020..0x1040,
+                0x1060..0x1070,
+                0x2010..0x2100
+            ]
+        );
+    }
+
+    #[test]
+    fn test_append_dummy_symbol() {
+        let dummy = |sentinel| {
+            let mut syms = Symbols::new();
+            for (rva, len) in [(0x1000, 0x10), (0x1010, 0x8)] {
+                syms.insert(
+                    rva,
+                    Symbol {
+                        rva,
+                        len,
+                        ..Default::default()
+                    },
+                );
+            }
+            let syms = append_dummy_symbol(
+                syms,
+                get_unknown_name("foo.dll"),
+                sentinel,
+                &[0x1000..0x1100, 0x2000..0x2100],
+            );
+            syms.values()
+                .filter(|s| s.is_synthetic)
+                .map(|s| (s.rva, s.name.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        let name = String::from("<unknown in foo.dll>");
+        assert_eq!(dummy(Sentinel::AfterLast), vec![(0x1018, name.clone())]);
+        assert_eq!(dummy(Sentinel::SectionEnd), vec![(0x1100, name)]);
+        assert!(dummy(Sentinel::Off).is_empty());
+    }
+
+    #[test]
+    fn test_add_unknown_symbols() {
+        let mut syms = Symbols::new();
+        for (rva, len, is_public) in [
+            (0x1000, 0x10, false),
+            (0x1010, 0x8, false),
+            (0x1020, 0x4, false),
+            (0x1030, 0, true),
+            (0x1040, 0x20, false),
+            (0x1050, 0x4, false),
+            (0x10f0, 0x10, false),
+        ] {
+            syms.insert(
+                rva,
+                Symbol {
+                    rva,
+                    len,
+                    is_public,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let syms = add_unknown_symbols(syms, &[0x1000..0x1100, 0x2000..0x2100], "foo.dll");
+        let unknowns: Vec<_> = syms
+            .values()
+            .filter(|s| s.is_synthetic)
+            .map(|s| (s.rva, s.name.as_str()))
+            .collect();
+        // Nothing after a function followed by a symbol, inside another one or
+        // at the end of the section
+        assert_eq!(
+            unknowns,
+            vec![
+                (0x1018, "<unknown in foo.dll>"),
+                (0x1024, "<unknown in foo.dll>"),
+                (0x1060, "<unknown in foo.dll>")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_cfi_function_symbols() {
+        let mut syms = Symbols::new();
+        for (rva, len, name, is_synthetic) in [
+            (0x1000, 0, "<.text ELF section in foo>", true),
+            (0x1100, 0x20, "main", false),
+            (0x1200, 0, "bar", false),
+        ] {
+            syms.insert(
+                rva,
+                Symbol {
+                    name: name.to_string(),
+                    is_public: true,
+                    is_synthetic,
+                    rva,
+                    len,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let ranges = [
+            0x1000..0x1010,
+            0x1010..0x1020,
+            0x1108..0x1110,
+            0x1140..0x1180,
+            0x11f0..0x1210,
+        ];
+        let syms = add_cfi_function_symbols(syms, &ranges);
+        let funcs: Vec<_> = syms
+            .values()
+            .filter(|s| !s.is_public)
+            .map(|s| (s.rva, s.len, s.name.as_str()))
+            .collect();
+        assert_eq!(
+            funcs,
+            vec![
+                (0x1010, 0x10, "fn_1010"),
+                (0x1140, 0x40, "main+0x40"),
+                (0x11f0, 0x10, "main+0xf0"),
+            ]
+        );
+    }
+}
@@ -3,34 +3,41 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+#[cfg(feature = "pdb")]
 use cab::Cabinet;
+use fs2::FileExt;
 use std::fs::{self, File, Metadata};
-use std::io::{Cursor, Read};
+#[cfg(feature = "pdb")]
+use std::io::Cursor;
+use std::io::Read;
 use std::path::{Component, Path, PathBuf};
+use symbolic::common::ByteView;
 
 use crate::common;
 
-pub fn read_file<P: AsRef<Path>>(path: P) -> Vec<u8> {
-    let metadata = fs::metadata(&path).unwrap_or_else(|_| {
-        panic!(
-            "Unable to open the file {}",
-            path.as_ref().to_str().unwrap()
-        )
-    });
+/// The magic of the cabinet files
+const CAB_MAGIC: &[u8] = b"MSCF";
 
-    let (metadata, path) = get_mac_bundle(&metadata, &path)
-        .unwrap_or_else(|| (metadata, PathBuf::from(path.as_ref())));
+/// Map a file, or the DWARF file of a dSYM bundle, in memory: the PDBs of the big
+/// projects are several GiB so they aren't copied. The cabinet files are decompressed.
+pub fn read_file<P: AsRef<Path>>(path: P) -> common::Result<ByteView<'static>> {
+    let metadata = fs::metadata(&path).map_err(|e| {
+        anyhow::anyhow!("Unable to open the file {}: {}", path.as_ref().display(), e)
+    })?;
 
-    let file_size = metadata.len() as usize;
-    let mut file = File::open(&path)
-        .unwrap_or_else(|_| panic!("Unable to open the file {}", path.to_str().unwrap()));
+    let (_, path) = get_mac_bundle(&metadata, &path)
+        .unwrap_or_else(|| (metadata, PathBuf::from(path.as_ref())));
 
-    let mut buf = Vec::with_capacity(file_size + 1);
-    file.read_to_end(&mut buf)
-        .unwrap_or_else(|_| panic!("Unable to read the file {}", path.to_str().unwrap()));
+    let buf = ByteView::open(&path)
+        .map_err(|e| anyhow::anyhow!("Unable to read the file {}: {}", path.display(), e))?;
+    if !buf.starts_with(CAB_MAGIC) {
+        return Ok(buf);
+    }
 
-    read_cabinet(buf, path.clone())
-        .unwrap_or_else(|| panic!("Unable to read the cabinet file {}", path.to_str().unwrap()))
+    match read_cabinet(buf.to_vec(), path.clone()) {
+        Some(buf) => Ok(ByteView::from_vec(buf)),
+        None => anyhow::bail!("Unable to read the cabinet file {}", path.display()),
+    }
 }
 
 pub(crate) fn get_base(file_name: &str) -> PathBuf {
@@ -109,6 +116,7 @@ pub fn get_mac_bundle<P: AsRef<Path>>(metadata: &Metadata, path: P) -> Option<(M
     }
 }
 
+#[cfg(feature = "pdb")]
 pub fn read_cabinet(buf: Vec<u8>, path: PathBuf) -> Option<Vec<u8>> {
     // try to find a pdb in cabinet archive
     // if not a cabinet just return the buffer
@@ -138,6 +146,13 @@ pub fn read_cabinet(buf: Vec<u8>, path: PathBuf) -> Option<Vec<u8>> {
     }
 }
 
+/// The cabinet files are only decompressed with the pdb feature
+#[cfg(not(feature = "pdb"))]
+pub fn read_cabinet(buf: Vec<u8>, _path: PathBuf) -> Option<Vec<u8>> {
+    Some(buf)
+}
+
+#[cfg(feature = "pdb")]
 fn get_corrected_path(path: PathBuf) -> PathBuf {
     let e = path.extension().unwrap().to_str().unwrap();
     if e.starts_with("pd") {
@@ -153,6 +168,7 @@ fn get_corrected_path(path: PathBuf) -> PathBuf {
     }
 }
 
+#[cfg(feature = "pdb")]
 fn get_cabinet_files(cab: &Cabinet<Cursor<&Vec<u8>>>, path: PathBuf) -> Option<(String, usize)> {
     // Try to find in the cabinet the same path with pdb extension
     let path = get_corrected_path(path);
@@ -221,3 +237,68 @@ pub fn read<P: AsRef<Path>>(path: P) -> common::Result<Vec<u8>> {
 
     Ok(buf)
 }
+
+/// An exclusive lock on a file shared by several dump_syms processes, the lock
+/// is held on `FILE.lock` until it's dropped.
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl FileLock {
+    fn get_path(path: &Path) -> PathBuf {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        PathBuf::from(lock_path)
+    }
+
+    /// Wait until the lock on the given file is acquired
+    pub fn lock(path: &Path) -> common::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut path = Self::get_path(path);
+        loop {
+            let file = File::options()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&path)?;
+            file.lock_exclusive()?;
+
+            // The lock file may have been removed by its previous owner while
+            // we were waiting for it: the lock must be taken on the new one
+            let lock = Self { file, path };
+            if lock.is_linked() {
+                return Ok(lock);
+            }
+            path = lock.path;
+        }
+    }
+
+    #[cfg(unix)]
+    fn is_linked(&self) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        match (self.file.metadata(), fs::metadata(&self.path)) {
+            (Ok(locked), Ok(linked)) => {
+                locked.dev() == linked.dev() && locked.ino() == linked.ino()
+            }
+            _ => false,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn is_linked(&self) -> bool {
+        true
+    }
+
+    /// Remove the lock file, while the lock is held so that the other processes
+    /// don't take a lock on a removed file. The lock files are kept on the
+    /// platforms where they can't be checked once locked.
+    pub fn remove(self) {
+        if cfg!(unix) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
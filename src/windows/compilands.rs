@@ -0,0 +1,77 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashSet;
+use log::debug;
+use symbolic::debuginfo::pdb::pdb::{FallibleIterator, SymbolData};
+use symbolic::debuginfo::pdb::PdbObject;
+
+use crate::collector::CompilandFilter;
+use crate::common;
+
+/// Get the rvas of the functions of the modules (compilands) skipped by the
+/// filter, they're neither FUNC nor PUBLIC records.
+pub fn get_skipped_functions(
+    pdb: &PdbObject,
+    filter: &CompilandFilter,
+) -> common::Result<HashSet<u32>> {
+    let mut pdb = pdb.inner().write();
+    let address_map = pdb.address_map()?;
+    let dbi = pdb.debug_information()?;
+
+    let mut skipped = HashSet::new();
+    let mut modules = dbi.modules()?;
+    while let Some(module) = modules.next()? {
+        if !filter.should_skip(&module.module_name(), &module.object_file_name()) {
+            continue;
+        }
+        debug!("Skip the compiland {}", module.module_name());
+        let info = match pdb.module_info(&module)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let mut symbols = info.symbols()?;
+        while let Some(symbol) = symbols.next()? {
+            if let Ok(SymbolData::Procedure(proc)) = symbol.parse() {
+                if let Some(rva) = proc.offset.to_rva(&address_map) {
+                    skipped.insert(rva.0);
+                }
+            }
+        }
+    }
+
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symbolic::debuginfo::Object;
+
+    fn skipped(include: &[&str], exclude: &[&str]) -> usize {
+        let buf = std::fs::read("./test_data/windows/basic64.pdb").unwrap();
+        let filter = CompilandFilter::new(include, exclude).unwrap();
+        let object = Object::parse(&buf).unwrap();
+        match &object {
+            Object::Pdb(pdb) => get_skipped_functions(pdb, &filter).unwrap().len(),
+            _ => panic!("not a pdb"),
+        }
+    }
+
+    #[test]
+    fn test_skipped_functions() {
+        assert_eq!(skipped(&[], &[]), 0);
+        assert!(skipped(&[], &["*.lib"]) > 0);
+        // The only object of the project
+        assert_eq!(
+            skipped(&["basic64.obj"], &[]),
+            skipped(&[], &["*.lib", "* Linker *", "*.exp"])
+        );
+        assert_eq!(
+            skipped(&["basic64.obj"], &["**/test_data/**"]),
+            skipped(&["-"], &[])
+        );
+    }
+}
@@ -0,0 +1,116 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use goblin::pe::section_table::IMAGE_SCN_CNT_CODE;
+use hashbrown::{HashMap, HashSet};
+use std::ops::Range;
+use symbolic::debuginfo::pdb::pdb::{FallibleIterator, SymbolData};
+use symbolic::debuginfo::pdb::PdbObject;
+
+use super::symbols::{S_COMPILE2, S_COMPILE3};
+use crate::common;
+
+/// Whether the compile flags of a module (S_COMPILE2 or S_COMPILE3) have the
+/// Edit and Continue bit (fEC), it's the one after the language
+fn is_edit_and_continue(compile: &[u8]) -> bool {
+    compile.get(3).is_some_and(|flags| flags & 1 != 0)
+}
+
+/// Get the rvas of the old versions of the functions edited with Edit and
+/// Continue: the incremental link keeps them in the module streams but their
+/// code isn't in a section contribution of their module anymore, it's padding.
+///
+/// The modules built without /ZI are skipped, all their functions are live.
+pub fn get_superseded_functions(pdb: &PdbObject) -> common::Result<HashSet<u32>> {
+    let mut pdb = pdb.inner().write();
+    let address_map = pdb.address_map()?;
+    let dbi = pdb.debug_information()?;
+
+    let mut contributions: HashMap<usize, Vec<Range<u32>>> = HashMap::new();
+    let mut iter = dbi.section_contributions()?;
+    while let Some(contribution) = iter.next()? {
+        if contribution.characteristics.0 & IMAGE_SCN_CNT_CODE == 0 {
+            continue;
+        }
+        if let Some(rva) = contribution.offset.to_rva(&address_map) {
+            contributions
+                .entry(contribution.module)
+                .or_default()
+                .push(rva.0..rva.0.saturating_add(contribution.size));
+        }
+    }
+
+    let mut live = HashSet::new();
+    let mut superseded = HashSet::new();
+    let mut modules = dbi.modules()?;
+    let mut index = 0;
+    while let Some(module) = modules.next()? {
+        let module_contributions = contributions.get(&index);
+        index += 1;
+        let info = match pdb.module_info(&module)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let mut enc = false;
+        let mut symbols = info.symbols()?;
+        while let Some(symbol) = symbols.next()? {
+            match symbol.raw_kind() {
+                // The compile flags are at the start of the module
+                S_COMPILE2 | S_COMPILE3 => {
+                    enc = is_edit_and_continue(symbol.raw_bytes());
+                    if !enc {
+                        break;
+                    }
+                }
+                _ => {
+                    let rva = match symbol.parse() {
+                        Ok(SymbolData::Procedure(proc)) => match proc.offset.to_rva(&address_map) {
+                            Some(rva) => rva.0,
+                            None => continue,
+                        },
+                        _ => continue,
+                    };
+                    let is_live = match (enc, module_contributions) {
+                        (true, Some(ranges)) => ranges.iter().any(|r| r.contains(&rva)),
+                        _ => true,
+                    };
+                    if is_live {
+                        live.insert(rva);
+                    } else {
+                        superseded.insert(rva);
+                    }
+                }
+            }
+        }
+    }
+
+    // The code of an old version can be reused by a live function
+    superseded.retain(|rva| !live.contains(rva));
+    Ok(superseded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symbolic::debuginfo::Object;
+
+    #[test]
+    fn test_edit_and_continue_flag() {
+        // S_COMPILE3 for C++ with fEC
+        assert!(is_edit_and_continue(&[0x3c, 0x11, 0x01, 0x01, 0x00, 0x00]));
+        assert!(!is_edit_and_continue(&[0x3c, 0x11, 0x01, 0x40, 0x00, 0x00]));
+        assert!(!is_edit_and_continue(&[0x3c, 0x11]));
+    }
+
+    #[test]
+    fn test_no_superseded_functions() {
+        let buf = std::fs::read("./test_data/windows/basic64.pdb").unwrap();
+        let object = Object::parse(&buf).unwrap();
+        match object {
+            Object::Pdb(pdb) => assert!(get_superseded_functions(&pdb).unwrap().is_empty()),
+            _ => panic!("not a pdb"),
+        }
+    }
+}
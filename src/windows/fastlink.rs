@@ -0,0 +1,298 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use goblin::pe::section_table::IMAGE_SCN_CNT_CODE;
+use goblin::pe::symbol::{
+    IMAGE_SYM_CLASS_EXTERNAL, IMAGE_SYM_CLASS_STATIC, IMAGE_SYM_DTYPE_FUNCTION,
+};
+use goblin::pe::Coff;
+use hashbrown::HashMap;
+use log::warn;
+use std::path::Path;
+use symbolic::debuginfo::pdb::pdb::{FallibleIterator, StreamIndex};
+use symbolic::debuginfo::pdb::PdbObject;
+
+use crate::common;
+
+/// The feature code written in the PDB info stream by /DEBUG:FASTLINK
+const MINIMAL_DEBUG_INFO: u32 = 0x494e_494d;
+
+/// A function of an object file referenced by a /DEBUG:FASTLINK PDB
+#[derive(Debug, PartialEq, Eq)]
+pub struct ObjectFunction {
+    pub rva: u32,
+    pub len: u32,
+    /// The name in the COFF symbol table, it's mangled
+    pub name: String,
+}
+
+/// Whether a PDB has been written by a link with /DEBUG:FASTLINK: it only has the
+/// publics and the section contributions, the functions, their lines and the types
+/// stay in the object files.
+pub fn is_fastlink(pdb: &PdbObject) -> bool {
+    let mut pdb = pdb.inner().write();
+    match pdb.raw_stream(StreamIndex(1)) {
+        Ok(Some(stream)) => get_features(stream.as_slice()).contains(&MINIMAL_DEBUG_INFO),
+        _ => false,
+    }
+}
+
+/// Get the feature codes at the end of the PDB info stream: they follow the
+/// header and the hash table of the named streams.
+fn get_features(data: &[u8]) -> Vec<u32> {
+    let read = |pos: usize| -> Option<u32> {
+        let b = data.get(pos..pos + 4)?;
+        Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let features = || -> Option<Vec<u32>> {
+        // Version, signature, age and guid, then the names of the streams
+        let mut pos = 28;
+        pos += 4 + read(pos)? as usize;
+        // The size, the capacity and the bit vectors of the present and deleted
+        // entries of the hash table, then its entries and the next name index
+        let size = read(pos)? as usize;
+        pos += 8;
+        for _ in 0..2 {
+            pos += 4 + 4 * read(pos)? as usize;
+        }
+        pos += 8 * size + 4;
+
+        let mut features = Vec::new();
+        while let Some(feature) = read(pos) {
+            features.push(feature);
+            pos += 4;
+        }
+        Some(features)
+    };
+    features().unwrap_or_default()
+}
+
+/// A code section put in the binary by the linker
+#[derive(Clone, Copy, Debug)]
+struct Contribution {
+    rva: u32,
+    size: u32,
+}
+
+/// Get the functions of the object files referenced by the modules of a
+/// /DEBUG:FASTLINK PDB, when they're still on the disk.
+///
+/// The code sections of an object file are matched in order with the code
+/// contributions of its module which have the same size: the functions are
+/// in the COFF symbol table at an offset in their section.
+pub fn get_object_functions(pdb: &PdbObject) -> common::Result<Vec<ObjectFunction>> {
+    let mut pdb = pdb.inner().write();
+    let address_map = pdb.address_map()?;
+    let dbi = pdb.debug_information()?;
+
+    let mut contributions: HashMap<usize, Vec<Contribution>> = HashMap::new();
+    let mut iter = dbi.section_contributions()?;
+    while let Some(contribution) = iter.next()? {
+        if contribution.characteristics.0 & IMAGE_SCN_CNT_CODE == 0 {
+            continue;
+        }
+        if let Some(rva) = contribution.offset.to_rva(&address_map) {
+            contributions
+                .entry(contribution.module)
+                .or_default()
+                .push(Contribution {
+                    rva: rva.0,
+                    size: contribution.size,
+                });
+        }
+    }
+
+    let mut functions = Vec::new();
+    let mut missing = 0;
+    let mut modules = dbi.modules()?;
+    let mut index = 0;
+    while let Some(module) = modules.next()? {
+        let module_index = index;
+        index += 1;
+        let contributions = match contributions.get(&module_index) {
+            Some(contributions) => contributions,
+            None => continue,
+        };
+        let object_file = module.object_file_name();
+        let buf = match std::fs::read(Path::new(object_file.as_ref())) {
+            Ok(buf) => buf,
+            Err(_) => {
+                missing += 1;
+                continue;
+            }
+        };
+        match Coff::parse(&buf) {
+            Ok(coff) => functions.extend(get_coff_functions(&coff, contributions)),
+            Err(e) => warn!("Unable to read the object file {}: {}", object_file, e),
+        }
+    }
+    if missing != 0 {
+        warn!(
+            "{} object files referenced by the /DEBUG:FASTLINK PDB are missing",
+            missing
+        );
+    }
+
+    Ok(functions)
+}
+
+fn get_coff_functions(coff: &Coff, contributions: &[Contribution]) -> Vec<ObjectFunction> {
+    // The rva and the size of the code sections which are in the binary, the
+    // COMDATs discarded by the linker have no contribution
+    let mut used = vec![false; contributions.len()];
+    let sections: Vec<_> = coff
+        .sections
+        .iter()
+        .map(|section| {
+            if section.characteristics & IMAGE_SCN_CNT_CODE == 0 {
+                return None;
+            }
+            let i = (0..contributions.len())
+                .find(|i| !used[*i] && contributions[*i].size == section.size_of_raw_data)?;
+            used[i] = true;
+            Some(contributions[i])
+        })
+        .collect();
+
+    let mut functions: Vec<(usize, ObjectFunction)> = coff
+        .symbols
+        .iter()
+        .filter_map(|(_, name, symbol)| {
+            let is_function = symbol.derived_type() == IMAGE_SYM_DTYPE_FUNCTION
+                && (symbol.storage_class == IMAGE_SYM_CLASS_EXTERNAL
+                    || symbol.storage_class == IMAGE_SYM_CLASS_STATIC);
+            if !is_function || symbol.section_number <= 0 {
+                return None;
+            }
+            let section = symbol.section_number as usize - 1;
+            let contribution = (*sections.get(section)?)?;
+            let name = match name {
+                Some(name) => name,
+                None => symbol.name(&coff.strings).ok()?,
+            };
+            Some((
+                section,
+                ObjectFunction {
+                    rva: contribution.rva + symbol.value,
+                    // The end of its section for now
+                    len: contribution.size.saturating_sub(symbol.value),
+                    name: name.to_string(),
+                },
+            ))
+        })
+        .collect();
+
+    // A function ends at the next one in its section
+    functions.sort_by_key(|(section, fun)| (*section, fun.rva));
+    for i in 1..functions.len() {
+        let (section, next) = (functions[i].0, functions[i].1.rva);
+        let (prev_section, prev) = &mut functions[i - 1];
+        if *prev_section == section {
+            prev.len = prev.len.min(next - prev.rva);
+        }
+    }
+
+    functions
+        .into_iter()
+        .map(|(_, fun)| fun)
+        .filter(|fun| fun.len != 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symbolic::debuginfo::Object;
+
+    #[test]
+    fn test_get_features() {
+        let mut data = vec![0u8; 28];
+        // The names of the streams
+        data.extend(4u32.to_le_bytes());
+        data.extend(b"foo\0");
+        // One entry: size, capacity, the present bit and no deleted one
+        for v in [1u32, 2, 1, 1, 0, 0, 7, 0] {
+            data.extend(v.to_le_bytes());
+        }
+        for v in [20140508u32, MINIMAL_DEBUG_INFO] {
+            data.extend(v.to_le_bytes());
+        }
+        assert_eq!(get_features(&data), vec![20140508, MINIMAL_DEBUG_INFO]);
+
+        assert!(get_features(&data[..10]).is_empty());
+    }
+
+    /// A COFF object with a .text section of 0x30 bytes and its functions
+    fn make_coff() -> Vec<u8> {
+        let symbols: [(&[u8; 8], u32, u8); 3] = [
+            (b"main\0\0\0\0", 0x10, IMAGE_SYM_CLASS_EXTERNAL),
+            (b"square\0\0", 0, IMAGE_SYM_CLASS_STATIC),
+            // The long names are in the string table, after its size
+            (b"\0\0\0\0\x04\0\0\0", 0x20, IMAGE_SYM_CLASS_EXTERNAL),
+        ];
+        let mut data = Vec::new();
+        // The header: x64, one section, the symbol table after the code
+        data.extend(0x8664u16.to_le_bytes());
+        data.extend(1u16.to_le_bytes());
+        for v in [0u32, 20 + 40 + 0x30, symbols.len() as u32, 0] {
+            data.extend(v.to_le_bytes());
+        }
+        data.extend(b".text\0\0\0");
+        for v in [0u32, 0, 0x30, 20 + 40, 0, 0, 0] {
+            data.extend(v.to_le_bytes());
+        }
+        data.extend(0x6000_0020u32.to_le_bytes());
+        data.extend([0xcc; 0x30]);
+        for (name, value, class) in symbols {
+            data.extend(name);
+            data.extend(value.to_le_bytes());
+            data.extend(1i16.to_le_bytes());
+            data.extend((IMAGE_SYM_DTYPE_FUNCTION << 4).to_le_bytes());
+            data.extend([class, 0]);
+        }
+        data.extend(20u32.to_le_bytes());
+        data.extend(b"a_long_function\0");
+        data
+    }
+
+    #[test]
+    fn test_coff_functions() {
+        let data = make_coff();
+        let coff = Coff::parse(&data).unwrap();
+        let contributions = [
+            Contribution {
+                rva: 0x1000,
+                size: 0x10,
+            },
+            Contribution {
+                rva: 0x2000,
+                size: 0x30,
+            },
+        ];
+        let function = |rva, name: &str| ObjectFunction {
+            rva,
+            len: 0x10,
+            name: name.to_string(),
+        };
+        assert_eq!(
+            get_coff_functions(&coff, &contributions),
+            vec![
+                function(0x2000, "square"),
+                function(0x2010, "main"),
+                function(0x2020, "a_long_function"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_fastlink() {
+        let buf = std::fs::read("./test_data/windows/basic64.pdb").unwrap();
+        let object = Object::parse(&buf).unwrap();
+        match object {
+            Object::Pdb(pdb) => assert!(!is_fastlink(&pdb)),
+            _ => panic!("not a pdb"),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::path::Path;
+
+use crate::common;
+use crate::utils;
+
+/// A symbol found in a linker map file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapSymbol {
+    /// The decorated name of the symbol
+    pub name: String,
+    pub rva: u32,
+    /// The size of the symbol if the map contains it
+    pub len: Option<u32>,
+    pub is_function: bool,
+    /// Other symbols are at the same address, e.g. the functions folded by /OPT:ICF
+    pub is_multiple: bool,
+}
+
+/// The symbols of a map file generated by MSVC link.exe or lld-link with /MAP
+#[derive(Debug, Default)]
+pub struct LinkerMap {
+    symbols: Vec<MapSymbol>,
+}
+
+impl LinkerMap {
+    /// Parse a map file, `image_base` is used to convert the virtual addresses
+    /// into rvas when the map doesn't contain the preferred load address.
+    pub fn parse(data: &str, image_base: u64) -> Self {
+        let mut map = if data
+            .lines()
+            .any(|l| l.trim_start().starts_with("Address") && l.contains("Symbol"))
+        {
+            Self::parse_lld(data, image_base)
+        } else {
+            Self::parse_msvc(data, image_base)
+        };
+
+        map.symbols.sort_by_key(|s| s.rva);
+        map.symbols.dedup_by(|next, prev| {
+            if next.rva != prev.rva {
+                return false;
+            }
+            prev.is_multiple |= next.name != prev.name;
+            true
+        });
+        map
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P, image_base: u64) -> common::Result<Self> {
+        let data = utils::read(path)?;
+        Ok(Self::parse(&String::from_utf8_lossy(&data), image_base))
+    }
+
+    pub fn symbols(&self) -> &[MapSymbol] {
+        &self.symbols
+    }
+
+    // The MSVC format looks like:
+    //  Preferred load address is 0000000180000000
+    //  ...
+    //   Address         Publics by Value              Rva+Base               Lib:Object
+    //
+    //  0001:00000310       ?foo@@YAXXZ                0000000180001310 f   foo.obj
+    //  ...
+    //  Static symbols
+    //
+    //  0001:00000400       ?bar@@YAXXZ                0000000180001400 f   bar.obj
+    fn parse_msvc(data: &str, image_base: u64) -> Self {
+        let mut base = image_base;
+        let mut symbols = Vec::new();
+
+        for line in data.lines() {
+            let line = line.trim();
+            if let Some(address) = line.strip_prefix("Preferred load address is ") {
+                if let Ok(address) = u64::from_str_radix(address.trim(), 16) {
+                    base = address;
+                }
+                continue;
+            }
+
+            let toks: Vec<_> = line.split_whitespace().collect();
+            if toks.len() < 3 {
+                continue;
+            }
+
+            // The first token is section:offset, absolute symbols are in section 0.
+            let section = match toks[0].split_once(':') {
+                Some((section, offset))
+                    if section.len() == 4 && u32::from_str_radix(offset, 16).is_ok() =>
+                {
+                    section
+                }
+                _ => continue,
+            };
+            if section == "0000" {
+                continue;
+            }
+
+            let va = match u64::from_str_radix(toks[2], 16) {
+                Ok(va) => va,
+                _ => continue,
+            };
+            let rva = match va.checked_sub(base) {
+                Some(rva) if rva > 0 && rva <= u32::MAX as u64 => rva as u32,
+                _ => continue,
+            };
+            let is_function = toks.get(3) == Some(&"f");
+
+            symbols.push(MapSymbol {
+                name: toks[1].to_string(),
+                rva,
+                len: None,
+                is_function,
+                is_multiple: false,
+            });
+        }
+
+        Self { symbols }
+    }
+
+    // The lld-link format looks like:
+    //  Address          Size             Align Out     In      Symbol
+    //  0000000180001000 0000000000001234  4096 .text
+    //  0000000180001000 0000000000000045    16         foo.obj:(.text$mn)
+    //  0000000180001310 0000000000000000     0                 ?foo@@YAXXZ
+    fn parse_lld(data: &str, image_base: u64) -> Self {
+        let mut symbols = Vec::new();
+        let mut in_code = false;
+
+        for line in data.lines() {
+            let toks: Vec<_> = line.split_whitespace().collect();
+            if toks.len() < 4 {
+                continue;
+            }
+
+            let (va, size) = match (
+                u64::from_str_radix(toks[0], 16),
+                u64::from_str_radix(toks[1], 16),
+            ) {
+                (Ok(va), Ok(size)) => (va, size),
+                _ => continue,
+            };
+            if toks[2].parse::<u32>().is_err() {
+                continue;
+            }
+
+            let name = toks[3..].join(" ");
+            if name.starts_with('.') {
+                // An output section
+                in_code = name.starts_with(".text");
+                continue;
+            }
+            if name.contains(":(") {
+                // An input section
+                continue;
+            }
+
+            let rva = match va.checked_sub(image_base) {
+                Some(rva) if rva > 0 && rva <= u32::MAX as u64 => rva as u32,
+                _ => continue,
+            };
+
+            symbols.push(MapSymbol {
+                name,
+                rva,
+                len: if size == 0 { None } else { Some(size as u32) },
+                is_function: in_code,
+                is_multiple: false,
+            });
+        }
+
+        Self { symbols }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msvc_map() {
+        let data = r#"
+ mozwer
+
+ Timestamp is 5fdb6a28 (Thu Dec 17 15:15:04 2020)
+
+ Preferred load address is 0000000180000000
+
+ Start         Length     Name                   Class
+ 0001:00000000 00010a6cH .text$mn                CODE
+
+  Address         Publics by Value              Rva+Base               Lib:Object
+
+ 0000:00000000       __guard_fids_count         0000000000000000     <absolute>
+ 0001:00000310       ?foo@@YAXXZ                0000000180001310 f   foo.obj
+ 0001:00000310       ?baz@@YAXXZ                0000000180001310 f   baz.obj
+ 0002:00000000       ?data@@3HA                 0000000180012000     foo.obj
+
+ entry point at        0001:00000310
+
+ Static symbols
+
+ 0001:000003a0       _bar@8                     00000001800013a0 f   bar.obj
+"#;
+        let map = LinkerMap::parse(data, 0);
+        assert_eq!(
+            map.symbols(),
+            &[
+                MapSymbol {
+                    name: "?foo@@YAXXZ".to_string(),
+                    rva: 0x1310,
+                    len: None,
+                    is_function: true,
+                    is_multiple: true,
+                },
+                MapSymbol {
+                    name: "_bar@8".to_string(),
+                    rva: 0x13a0,
+                    len: None,
+                    is_function: true,
+                    is_multiple: false,
+                },
+                MapSymbol {
+                    name: "?data@@3HA".to_string(),
+                    rva: 0x12000,
+                    len: None,
+                    is_function: false,
+                    is_multiple: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lld_map() {
+        let data = r#"
+             Address              Size             Align Out     In      Symbol
+    0000000180001000 0000000000001234  4096 .text
+    0000000180001000 0000000000000045    16         foo.obj:(.text$mn)
+    0000000180001310 0000000000000000     0                 ?foo@@YAXXZ
+    0000000180003000 0000000000000100  4096 .data
+    0000000180003000 0000000000000004     0                 ?data@@3HA
+"#;
+        let map = LinkerMap::parse(data, 0x1_8000_0000);
+        assert_eq!(
+            map.symbols(),
+            &[
+                MapSymbol {
+                    name: "?foo@@YAXXZ".to_string(),
+                    rva: 0x1310,
+                    len: None,
+                    is_function: true,
+                    is_multiple: false,
+                },
+                MapSymbol {
+                    name: "?data@@3HA".to_string(),
+                    rva: 0x3000,
+                    len: Some(4),
+                    is_function: false,
+                    is_multiple: false,
+                },
+            ]
+        );
+    }
+}
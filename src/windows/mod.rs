@@ -3,5 +3,23 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+// The linker maps are plain text files, they're parsed without the pdb feature
+#[cfg(feature = "pdb")]
+pub mod compilands;
+#[cfg(feature = "pdb")]
+pub mod enc;
+#[cfg(feature = "pdb")]
+pub mod fastlink;
+pub mod map;
+#[cfg(feature = "pdb")]
+pub mod omap;
+#[cfg(feature = "pdb")]
+pub mod params;
+#[cfg(feature = "pdb")]
 pub mod pdb;
+#[cfg(feature = "pdb")]
+pub mod stubs;
+#[cfg(feature = "pdb")]
+pub mod symbols;
+#[cfg(feature = "pdb")]
 pub mod utils;
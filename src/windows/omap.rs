@@ -0,0 +1,102 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use std::ops::Range;
+use symbolic::debuginfo::pdb::pdb::{PdbInternalRva, SymbolData};
+use symbolic::debuginfo::pdb::PdbObject;
+
+use super::symbols::for_each_symbol;
+use crate::common;
+
+/// The address ranges of a function in the binary
+pub type Fragments = Vec<Range<u32>>;
+
+/// Get the fragments of the functions which have been moved around by a binary
+/// layout optimization (BBT, PGO, ...), by rva of the function.
+///
+/// The OMAP table maps the addresses in the PDB to the ones in the binary, so
+/// the code of a function can be split in several pieces, some of them removed
+/// and with a start which isn't the first piece.
+/// The functions which aren't split aren't in the map.
+pub fn get_function_fragments(pdb: &PdbObject) -> common::Result<HashMap<u32, Fragments>> {
+    let mut fragments = HashMap::new();
+    for_each_symbol(pdb, |symbol, address_map| {
+        let proc = match symbol {
+            SymbolData::Procedure(proc) if proc.len != 0 => proc,
+            _ => return,
+        };
+        // The procedures of a corrupted pdb can be out of the sections
+        let (rva, start, end) = match (
+            proc.offset.to_rva(address_map),
+            proc.offset.to_internal_rva(address_map),
+        ) {
+            (Some(rva), Some(start)) => match start.0.checked_add(proc.len) {
+                Some(end) => (rva.0, start, PdbInternalRva(end)),
+                None => return,
+            },
+            _ => return,
+        };
+
+        let ranges: Fragments = address_map
+            .rva_ranges(start..end)
+            .map(|range| range.start.0..range.end.0)
+            .filter(|range| !range.is_empty())
+            .collect();
+
+        if let Some(ranges) = normalize_fragments(rva, proc.len, ranges) {
+            fragments.insert(rva, ranges);
+        }
+    })?;
+
+    Ok(fragments)
+}
+
+/// Sort and merge the adjacent fragments, return None if the function is
+/// in one piece at its rva.
+fn normalize_fragments(rva: u32, len: u32, mut ranges: Fragments) -> Option<Fragments> {
+    ranges.sort_by_key(|range| range.start);
+    ranges.dedup_by(|next, current| {
+        if current.end >= next.start {
+            current.end = current.end.max(next.end);
+            true
+        } else {
+            false
+        }
+    });
+
+    if ranges.len() == 1 && ranges[0] == (rva..rva.saturating_add(len)) {
+        None
+    } else {
+        Some(ranges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_normalize_fragments() {
+        assert_eq!(
+            normalize_fragments(0x1000, 0x10, vec![0x1000..0x1010]),
+            None
+        );
+        assert_eq!(
+            normalize_fragments(0x1000, 0x10, vec![0x1000..0x1008, 0x1008..0x1010]),
+            None
+        );
+        // A backward mapping and an eliminated piece
+        assert_eq!(
+            normalize_fragments(0x2000, 0x20, vec![0x2000..0x2010, 0x1000..0x1008]),
+            Some(vec![0x1000..0x1008, 0x2000..0x2010])
+        );
+        assert_eq!(
+            normalize_fragments(0x2000, 0x10, vec![0x2000..0x2008]),
+            Some(vec![0x2000..0x2008])
+        );
+    }
+}
@@ -0,0 +1,351 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use symbolic::debuginfo::pdb::pdb::{
+    FallibleIterator, Indirection, PrimitiveKind, SymbolData, TypeData, TypeFinder, TypeIndex,
+};
+use symbolic::debuginfo::pdb::PdbObject;
+
+use super::symbols::for_each_raw_symbol;
+use crate::cfi::WinFrame;
+use crate::common;
+
+// CV_REG_ESP, CV_REG_EBP and CV_ALLREG_VFRAME
+const ESP: u16 = 21;
+const EBP: u16 = 22;
+const VFRAME: u16 = 30006;
+
+// S_FRAMEPROC isn't parsed by the pdb crate
+const S_FRAMEPROC: u16 = 0x1012;
+
+// The calling conventions where the callee pops the parameters pushed on the stack
+// (CV_CALL_NEAR_FAST and CV_CALL_NEAR_STD). Like in the Breakpad tools, the size
+// isn't emitted for the thiscall methods.
+const CALLEE_POP: [u8; 2] = [0x04, 0x07];
+
+/// The frame information of a procedure (S_FRAMEPROC)
+#[derive(Clone, Copy, Debug)]
+struct FrameProc {
+    /// The size of the locals
+    frame_size: u32,
+    /// The size of the callee-saved registers pushed in the prologue
+    saved_regs_size: u32,
+    flags: u32,
+}
+
+impl FrameProc {
+    // The record is:
+    //  u16 kind, u32 frame size, u32 pad size, u32 pad offset, u32 saved registers size,
+    //  u32 exception handler offset, u16 exception handler section, u32 flags
+    fn parse(data: &[u8]) -> Option<Self> {
+        let u32_at = |i: usize| {
+            data.get(i..i + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        Some(Self {
+            frame_size: u32_at(2)?,
+            saved_regs_size: u32_at(14)?,
+            flags: u32_at(24)?,
+        })
+    }
+
+    /// The register used to address the parameters, the frame pointer isn't
+    /// used when it isn't EBP.
+    /// The x86 encoding is 0: none (so ESP), 1: VFRAME, 2: EBP and 3: EBX.
+    fn param_base(&self) -> Option<u16> {
+        match (self.flags >> 16) & 0x3 {
+            0 => Some(ESP),
+            1 => Some(VFRAME),
+            2 => Some(EBP),
+            _ => None,
+        }
+    }
+
+    /// The frame of the STACK WIN records: with EBP the function sets up a frame
+    /// pointer and with ESP it's compiled with FPO. The other bases (the virtual
+    /// frame pointer and EBX) realign the stack so they don't have a frame.
+    fn win_frame(&self) -> Option<WinFrame> {
+        let has_frame_pointer = match self.param_base()? {
+            EBP => true,
+            ESP => false,
+            _ => return None,
+        };
+        Some(WinFrame {
+            locals_size: self.frame_size,
+            saved_regs_size: self.saved_regs_size,
+            has_frame_pointer,
+        })
+    }
+
+    /// The offset of the first parameter from the base register: the return address
+    /// and the saved EBP are below it with a frame pointer, and with ESP, the locals,
+    /// the saved registers and the return address.
+    fn first_param_offset(&self, base: u16) -> u32 {
+        if base == ESP {
+            self.frame_size
+                .saturating_add(self.saved_regs_size)
+                .saturating_add(4)
+        } else {
+            8
+        }
+    }
+}
+
+/// A register-relative variable (S_REGREL32)
+struct Var {
+    register: u16,
+    offset: i32,
+    type_index: TypeIndex,
+}
+
+/// A procedure (S_GPROC32 or S_LPROC32) and its register-relative variables
+struct Procedure {
+    rva: u32,
+    type_index: TypeIndex,
+    frame: Option<FrameProc>,
+    vars: Vec<Var>,
+}
+
+/// Get the size of the parameters pushed on the stack for the x86 functions
+/// which pop them (stdcall and fastcall), the others have a size of 0.
+///
+/// The size is the one in the frame data when the function has an entry in the FrameTable,
+/// else it's computed from the parameters relative to the frame pointer.
+pub fn get_parameter_sizes(pdb: &PdbObject) -> common::Result<HashMap<u32, u32>> {
+    let mut procs: Vec<Procedure> = Vec::new();
+    for_each_raw_symbol(pdb, |symbol, address_map| {
+        if symbol.raw_kind() == S_FRAMEPROC {
+            if let Some(proc) = procs.last_mut() {
+                proc.frame = FrameProc::parse(symbol.raw_bytes());
+            }
+            return;
+        }
+
+        match symbol.parse() {
+            Ok(SymbolData::Procedure(proc)) => {
+                if let Some(rva) = proc.offset.to_rva(address_map) {
+                    procs.push(Procedure {
+                        rva: rva.0,
+                        type_index: proc.type_index,
+                        frame: None,
+                        vars: Vec::new(),
+                    });
+                }
+            }
+            Ok(SymbolData::RegisterRelative(var)) => {
+                if let Some(proc) = procs.last_mut() {
+                    proc.vars.push(Var {
+                        register: var.register.0,
+                        offset: var.offset,
+                        type_index: var.type_index,
+                    });
+                }
+            }
+            _ => {}
+        }
+    })?;
+
+    let mut pdb = pdb.inner().write();
+    let address_map = pdb.address_map()?;
+
+    let frame_table = pdb.frame_table()?;
+    let mut frames = HashMap::new();
+    let mut iter = frame_table.iter();
+    while let Some(frame) = iter.next()? {
+        if let Some(rva) = frame.code_start.to_rva(&address_map) {
+            frames.entry(rva.0).or_insert(frame.params_size);
+        }
+    }
+
+    let type_info = pdb.type_information()?;
+    let mut finder = type_info.finder();
+    let mut iter = type_info.iter();
+    while iter.next()?.is_some() {
+        finder.update(&iter);
+    }
+
+    let mut sizes = HashMap::new();
+    for proc in procs {
+        if !is_callee_pop(&finder, proc.type_index) {
+            continue;
+        }
+        let size = frames
+            .get(&proc.rva)
+            .copied()
+            .unwrap_or_else(|| get_stack_parameter_size(&finder, &proc));
+        if size != 0 {
+            sizes.insert(proc.rva, size);
+        }
+    }
+
+    Ok(sizes)
+}
+
+/// Get the frames of the x86 procedures from their S_FRAMEPROC record, by rva, for
+/// the STACK WIN records of the PDBs without frame data.
+pub fn get_frames(pdb: &PdbObject) -> common::Result<HashMap<u32, WinFrame>> {
+    let mut frames = HashMap::new();
+    let mut rva = None;
+    for_each_raw_symbol(pdb, |symbol, address_map| {
+        if symbol.raw_kind() == S_FRAMEPROC {
+            let frame = FrameProc::parse(symbol.raw_bytes()).and_then(|f| f.win_frame());
+            if let (Some(rva), Some(frame)) = (rva.take(), frame) {
+                frames.insert(rva, frame);
+            }
+            return;
+        }
+
+        if let Ok(SymbolData::Procedure(proc)) = symbol.parse() {
+            rva = proc.offset.to_rva(address_map).map(|rva| rva.0);
+        }
+    })?;
+
+    Ok(frames)
+}
+
+fn is_callee_pop(finder: &TypeFinder, index: TypeIndex) -> bool {
+    let attributes = match finder.find(index).and_then(|t| t.parse()) {
+        Ok(TypeData::Procedure(t)) => t.attributes,
+        Ok(TypeData::MemberFunction(t)) => t.attributes,
+        _ => return false,
+    };
+    CALLEE_POP.contains(&attributes.calling_convention())
+}
+
+/// The register used for the parameters is in the S_FRAMEPROC record (EBP when there isn't one).
+/// With EBP and with the virtual frame pointer, the parameters are at [reg + 8] and above,
+/// for the FPO functions, they're above the frame at [esp + frame + saved registers + 4].
+/// The stack slots are 4 bytes long.
+fn get_stack_parameter_size(finder: &TypeFinder, proc: &Procedure) -> u32 {
+    let (base, first) = match proc.frame {
+        Some(frame) => match frame.param_base() {
+            Some(base) => (base, frame.first_param_offset(base)),
+            None => return 0,
+        },
+        None => (EBP, 8),
+    };
+
+    let params = proc
+        .vars
+        .iter()
+        .filter(|var| var.register == base && var.offset >= 0 && var.offset as u32 >= first);
+    let lowest = params.clone().map(|var| var.offset as u32).min();
+    let highest = params
+        .map(|var| {
+            (var.offset as u32).saturating_add(get_type_size(finder, var.type_index, 0).max(4))
+        })
+        .max();
+
+    match (lowest, highest) {
+        (Some(lowest), Some(highest)) => (highest.saturating_add(3) & !3).saturating_sub(lowest),
+        _ => 0,
+    }
+}
+
+/// Get the size of a type, 0 when it's unknown (e.g. a forward reference to a class)
+pub(super) fn get_type_size(finder: &TypeFinder, index: TypeIndex, depth: usize) -> u32 {
+    // Avoid to loop forever on a corrupted type stream
+    if depth > 16 {
+        return 0;
+    }
+
+    let data = match finder.find(index).and_then(|t| t.parse()) {
+        Ok(data) => data,
+        _ => return 0,
+    };
+
+    match data {
+        TypeData::Primitive(t) => match t.indirection {
+            Some(Indirection::Near16) => 2,
+            Some(Indirection::Far32) => 6,
+            Some(Indirection::Near64) => 8,
+            Some(Indirection::Near128) => 16,
+            Some(_) => 4,
+            None => get_primitive_size(t.kind),
+        },
+        TypeData::Pointer(t) => t.attributes.size() as u32,
+        TypeData::Class(t) => t.size as u32,
+        TypeData::Union(t) => t.size as u32,
+        TypeData::Enumeration(t) => get_type_size(finder, t.underlying_type, depth + 1),
+        TypeData::Modifier(t) => get_type_size(finder, t.underlying_type, depth + 1),
+        TypeData::Bitfield(t) => get_type_size(finder, t.underlying_type, depth + 1),
+        TypeData::Array(t) => t.dimensions.last().copied().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+fn get_primitive_size(kind: PrimitiveKind) -> u32 {
+    use PrimitiveKind::*;
+
+    match kind {
+        Char | UChar | RChar | I8 | U8 | Bool8 => 1,
+        WChar | RChar16 | Short | UShort | I16 | U16 | F16 | Bool16 => 2,
+        RChar32 | Long | ULong | I32 | U32 | F32 | F32PP | Bool32 | HRESULT => 4,
+        F48 => 6,
+        Quad | UQuad | I64 | U64 | F64 | Complex32 | Bool64 => 8,
+        F80 => 10,
+        Octa | UOcta | I128 | U128 | F128 | Complex64 => 16,
+        Complex80 => 20,
+        Complex128 => 32,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_frames() {
+        let buf = std::fs::read("./test_data/windows/basic-opt32.pdb").unwrap();
+        let pdb = PdbObject::parse(&buf).unwrap();
+        let frames = get_frames(&pdb).unwrap();
+
+        // The same as the frame data: test2 is compiled with FPO, operator delete
+        // sets up a frame pointer
+        assert!(!frames[&0x6b30].has_frame_pointer);
+        assert!(frames[&0x93f0].has_frame_pointer);
+    }
+
+    #[test]
+    fn test_frame_proc() {
+        let mut data = vec![0x12, 0x10];
+        // frame size, pad size, pad offset, saved registers size, exception handler offset
+        for x in &[0x20u32, 0, 0, 0xc, 0] {
+            data.extend_from_slice(&x.to_le_bytes());
+        }
+        // exception handler section
+        data.extend_from_slice(&[0, 0]);
+
+        let with_flags = |flags: u32| {
+            let mut data = data.clone();
+            data.extend_from_slice(&flags.to_le_bytes());
+            FrameProc::parse(&data).unwrap()
+        };
+
+        assert_eq!(with_flags(0x0002_a000).param_base(), Some(EBP));
+        assert_eq!(with_flags(0x0001_4000).param_base(), Some(VFRAME));
+        assert_eq!(with_flags(0x0003_c000).param_base(), None);
+
+        assert_eq!(
+            with_flags(0x0002_a000).win_frame(),
+            Some(WinFrame {
+                locals_size: 0x20,
+                saved_regs_size: 0xc,
+                has_frame_pointer: true,
+            })
+        );
+        assert!(with_flags(0x0001_4000).win_frame().is_none());
+
+        let frame = with_flags(0);
+        assert_eq!(frame.param_base(), Some(ESP));
+        assert!(!frame.win_frame().unwrap().has_frame_pointer);
+        assert_eq!(frame.first_param_offset(ESP), 0x30);
+        assert_eq!(frame.first_param_offset(EBP), 8);
+        assert!(FrameProc::parse(&data).is_none());
+    }
+}
@@ -6,10 +6,12 @@
 use std::sync::Arc;
 use symbolic::debuginfo::{pdb::PdbObject, pe::PeObject, Object};
 
+use crate::collector::CollectOptions;
 use crate::common;
 use crate::mapping::PathMappings;
 use crate::object_info::ObjectInfo;
 use crate::platform::Platform;
+use crate::windows::map::LinkerMap;
 
 impl ObjectInfo {
     pub fn from_pdb(
@@ -18,7 +20,7 @@ impl ObjectInfo {
         pe_name: Option<&str>,
         pe: Option<PeObject>,
         mapping: Option<Arc<PathMappings>>,
-        collect_inlines: bool,
+        options: CollectOptions,
     ) -> common::Result<Self> {
         let pdb = Object::Pdb(pdb);
         let pe = pe.map(Object::Pe);
@@ -28,13 +30,22 @@ impl ObjectInfo {
             pdb_name,
             pe.as_ref(),
             pe_name,
+            None,
             Platform::Win,
             mapping,
-            collect_inlines,
+            options,
+            None,
         )
     }
 
-    pub fn from_pe(pe_name: &str, pe: PeObject) -> common::Result<Self> {
+    /// Collect the symbols of a PE file without its PDB. If the linker map
+    /// is available, it is used to name the functions.
+    pub fn from_pe(
+        pe_name: &str,
+        pe: PeObject,
+        linker_map: Option<&LinkerMap>,
+        options: CollectOptions,
+    ) -> common::Result<Self> {
         let pdb_name = pe.debug_file_name().unwrap_or_default().to_string();
         let pe = Object::Pe(pe);
         let pdb_name = win_path_file_name(&pdb_name).to_string();
@@ -43,19 +54,23 @@ impl ObjectInfo {
             &pdb_name,
             None,
             Some(pe_name),
+            None,
             Platform::Win,
             None,
-            false,
+            options,
+            linker_map,
         )
     }
 }
 
 fn win_path_file_name(pdb_name: &str) -> &str {
-    let index = pdb_name.rfind('\\').map_or(0, |i| i + 1);
+    // The PDBs built with clang-cl on Linux or macOS have a POSIX path
+    let index = pdb_name.rfind(['/', '\\']).map_or(0, |i| i + 1);
     &pdb_name[index..]
 }
 
-#[cfg(test)]
+// The PE files are dumped with the PDB files from the symbol servers
+#[cfg(all(test, feature = "http"))]
 mod tests {
 
     use bitflags::bitflags;
@@ -64,6 +79,7 @@ mod tests {
     use std::fs::File;
     use std::io::{Cursor, Read};
     use std::path::PathBuf;
+    use symbolic::common::ByteView;
     use symbolic::debuginfo::breakpad::{
         BreakpadError, BreakpadFileMap, BreakpadFuncRecord, BreakpadLineRecord, BreakpadObject,
     };
@@ -79,6 +95,7 @@ mod tests {
         }
     }
 
+    #[allow(dead_code)]
     #[derive(Debug, PartialEq)]
     struct StackWin {
         typ: u32,
@@ -118,7 +135,15 @@ mod tests {
 
         let mut output = Vec::new();
         let cursor = Cursor::new(&mut output);
-        let pdb = ObjectInfo::from_pdb(pdb, &pdb_name, Some(name), Some(pe), None, false).unwrap();
+        let pdb = ObjectInfo::from_pdb(
+            pdb,
+            &pdb_name,
+            Some(name),
+            Some(pe),
+            None,
+            CollectOptions::default(),
+        )
+        .unwrap();
         pdb.dump(cursor).unwrap();
 
         let toks: Vec<_> = name.rsplitn(2, '.').collect();
@@ -134,25 +159,37 @@ mod tests {
             path.set_extension("exe");
         }
 
-        let pe_buf = crate::utils::read_file(&path);
+        let pe_buf = crate::utils::read_file(&path).unwrap();
         let (pe, pdb_buf, pdb_name) = crate::windows::utils::get_pe_pdb_buf(
             &path,
             &pe_buf,
             crate::cache::get_sym_servers(Some(&format!("SRV*~/symcache*{}", MS))).as_ref(),
         )
-        .unwrap_or_else(|| (PeObject::parse(&pe_buf).unwrap(), vec![], "".to_string()));
+        .unwrap_or_else(|| {
+            (
+                PeObject::parse(&pe_buf).unwrap(),
+                ByteView::from_slice(&[]),
+                "".to_string(),
+            )
+        });
 
         let mut output = Vec::new();
         let cursor = Cursor::new(&mut output);
 
         if pdb_buf.is_empty() {
-            let pe = ObjectInfo::from_pe(file_name, pe).unwrap();
+            let pe = ObjectInfo::from_pe(file_name, pe, None, CollectOptions::default()).unwrap();
             pe.dump(cursor).unwrap();
         } else {
             let pdb = PdbObject::parse(&pdb_buf).unwrap();
-            let pdb =
-                ObjectInfo::from_pdb(pdb, &pdb_name, Some(file_name), Some(pe), mapping, false)
-                    .unwrap();
+            let pdb = ObjectInfo::from_pdb(
+                pdb,
+                &pdb_name,
+                Some(file_name),
+                Some(pe),
+                mapping,
+                CollectOptions::default(),
+            )
+            .unwrap();
             pdb.dump(cursor).unwrap();
         }
 
@@ -361,7 +398,14 @@ mod tests {
         }
 
         let mut func_old: Vec<_> = old.func_records().collect();
-        let mut func_new: Vec<_> = new.func_records().collect();
+        // The jump stubs aren't in the Breakpad symbols
+        let mut func_new: Vec<_> = new
+            .func_records()
+            .filter(|f| {
+                let name = f.as_ref().unwrap().name;
+                !name.starts_with("jump_stub for ") && !name.starts_with("hotpatch_stub for ")
+            })
+            .collect();
         func_old.sort_by_key(|f| f.as_ref().unwrap().address);
         func_new.sort_by_key(|f| f.as_ref().unwrap().address);
 
@@ -427,6 +471,25 @@ mod tests {
         test_file("basic32", TestFlags::ALL);
     }
 
+    #[test]
+    fn test_jump_stubs() {
+        let out = get_new_bp("basic32.dll", None);
+        let out = String::from_utf8(out).unwrap();
+        let stubs: Vec<_> = out
+            .lines()
+            .filter(|l| l.starts_with("FUNC") && l.contains("_stub for "))
+            .collect();
+
+        // The incremental linking thunks
+        assert!(stubs.contains(&"FUNC 100a 5 0 jump_stub for pDNameNode::length() const"));
+        // The hot-patch padding before the functions starting with mov edi, edi
+        assert!(stubs.iter().any(|l| l.contains(" 5 0 hotpatch_stub for ")));
+
+        let out = get_new_bp("basic64.dll", None);
+        let out = String::from_utf8(out).unwrap();
+        assert!(!out.contains("hotpatch_stub"));
+    }
+
     #[test]
     fn test_basic32_min() {
         test_file("basic32-min", TestFlags::ALL);
@@ -437,6 +500,110 @@ mod tests {
         test_file("basic64", TestFlags::ALL);
     }
 
+    /// Rewrite a MSF file with another page size, the PDBs bigger than 4 GiB
+    /// are written with pages of 8 KiB or more (/pdbpagesize)
+    fn repage_msf(buf: &[u8], page_size: usize) -> Vec<u8> {
+        let u32_at = |data: &[u8], i: usize| {
+            u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]) as usize
+        };
+        let pages = |size: usize, ps: usize| size.div_ceil(ps);
+        let old_page_size = u32_at(buf, 32);
+        let page = |n: usize| &buf[n * old_page_size..(n + 1) * old_page_size];
+
+        // The directory is in the pages listed by the pages listed after the header
+        let dir_size = u32_at(buf, 44);
+        let dir_pages: Vec<_> = (0..pages(pages(dir_size, old_page_size) * 4, old_page_size))
+            .flat_map(|i| page(u32_at(buf, 52 + i * 4)).to_vec())
+            .collect();
+        let dir: Vec<u8> = (0..pages(dir_size, old_page_size))
+            .flat_map(|i| page(u32_at(&dir_pages, i * 4)).to_vec())
+            .collect();
+
+        let count = u32_at(&dir, 0);
+        let mut page_numbers = 4 + count * 4;
+        let streams: Vec<_> = (0..count)
+            .map(|i| {
+                let size = u32_at(&dir, 4 + i * 4);
+                if size == u32::MAX as usize {
+                    return None;
+                }
+                let data: Vec<u8> = (0..pages(size, old_page_size))
+                    .flat_map(|j| page(u32_at(&dir, page_numbers + j * 4)).to_vec())
+                    .take(size)
+                    .collect();
+                page_numbers += pages(size, old_page_size) * 4;
+                Some(data)
+            })
+            .collect();
+
+        // The header, the two pages of the free page map, the streams, the directory
+        // and the list of its pages
+        let mut out = vec![0; 3 * page_size];
+        let mut new_dir = (count as u32).to_le_bytes().to_vec();
+        for stream in &streams {
+            let size = stream.as_ref().map_or(u32::MAX, |s| s.len() as u32);
+            new_dir.extend_from_slice(&size.to_le_bytes());
+        }
+        let push = |out: &mut Vec<u8>, data: &[u8]| {
+            let first = out.len() / page_size;
+            out.extend_from_slice(data);
+            out.resize(pages(out.len(), page_size) * page_size, 0);
+            (first..out.len() / page_size).map(|n| n as u32)
+        };
+        for stream in streams.iter().flatten() {
+            for n in push(&mut out, stream) {
+                new_dir.extend_from_slice(&n.to_le_bytes());
+            }
+        }
+        let dir_list: Vec<u8> = push(&mut out, &new_dir)
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        let dir_list_pages: Vec<_> = push(&mut out, &dir_list).collect();
+
+        out[..32].copy_from_slice(&buf[..32]);
+        let header = [
+            page_size as u32,
+            1,
+            (out.len() / page_size) as u32,
+            new_dir.len() as u32,
+            0,
+        ];
+        for (i, x) in header.iter().chain(dir_list_pages.iter()).enumerate() {
+            out[32 + i * 4..36 + i * 4].copy_from_slice(&x.to_le_bytes());
+        }
+
+        out
+    }
+
+    #[test]
+    fn test_big_page_msf() {
+        let dump = |buf: &[u8]| {
+            let pdb = PdbObject::parse(buf).unwrap();
+            let pdb = ObjectInfo::from_pdb(
+                pdb,
+                "basic64.pdb",
+                None,
+                None,
+                None,
+                CollectOptions::default(),
+            )
+            .unwrap();
+            let mut output = Vec::new();
+            pdb.dump(Cursor::new(&mut output)).unwrap();
+            output
+        };
+
+        let buf = std::fs::read("./test_data/windows/basic64.pdb").unwrap();
+        let big = repage_msf(&buf, 8192);
+        assert_eq!(
+            u32::from_le_bytes([big[32], big[33], big[34], big[35]]),
+            8192
+        );
+        let expected = dump(&buf);
+        assert!(expected.windows(5).any(|w| w == b"FUNC "));
+        assert!(expected == dump(&big));
+    }
+
     #[test]
     fn test_basic_opt32() {
         test_file("basic-opt32", TestFlags::ALL);
@@ -452,6 +619,64 @@ mod tests {
         test_file("dump_syms_regtest64", TestFlags::ALL);
     }
 
+    #[test]
+    fn test_inlines() {
+        let buf = std::fs::read("./test_data/windows/dump_syms_regtest64.pdb").unwrap();
+        let pdb = PdbObject::parse(&buf).unwrap();
+        let pdb = ObjectInfo::from_pdb(
+            pdb,
+            "dump_syms_regtest64.pdb",
+            None,
+            None,
+            None,
+            CollectOptions {
+                inlines: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        pdb.dump(Cursor::new(&mut output)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+
+        let files = lines.iter().filter(|l| l.starts_with("FILE ")).count();
+        let origins = lines
+            .iter()
+            .filter(|l| l.starts_with("INLINE_ORIGIN "))
+            .count();
+        assert!(lines.contains(&"FILE 78 f:\\dd\\vctools\\crt\\crtw32\\startup\\crt0.c"));
+        assert!(lines.contains(&"INLINE_ORIGIN 17 check_managed_app()"));
+
+        // The inline sites of __tmainCRTStartup, from the S_INLINESITE symbols,
+        // are called in crt0.c
+        let start = lines
+            .iter()
+            .position(|l| *l == "FUNC 11bc 180 0 __tmainCRTStartup()")
+            .unwrap();
+        assert_eq!(
+            lines[start + 1..start + 4],
+            [
+                "INLINE 0 196 78 17 11d2 4e",
+                "INLINE 0 199 78 18 1229 22",
+                "INLINE 0 202 78 18 1254 22",
+            ]
+        );
+        // The leaf lines are the ones of the inlinee
+        assert_eq!(lines[start + 6], "11d2 e 352 78");
+
+        for inline in lines.iter().filter(|l| l.starts_with("INLINE ")) {
+            let fields: Vec<usize> = inline
+                .split(' ')
+                .skip(1)
+                .take(4)
+                .map(|f| f.parse().unwrap())
+                .collect();
+            assert!(fields[2] < files, "{}", inline);
+            assert!(fields[3] < origins, "{}", inline);
+        }
+    }
+
     #[test]
     fn test_mozwer() {
         test_file("mozwer", TestFlags::ALL);
@@ -490,15 +715,15 @@ mod tests {
         let files: Vec<_> = map.values().collect();
 
         assert_eq!(
-            files[6].replace('\\', "/"),
+            *files[6],
             "https://source/abcdef/vctools/crt/vcstartup/src/eh/i386/secchk.c"
         );
         assert_eq!(
-            files[7].replace('\\', "/"),
+            *files[7],
             "https://source/abcdef/vctools/crt/vcstartup/src/heap/delete_scalar_size.cpp"
         );
         assert_eq!(
-            files[files.len() - 1].replace('\\', "/"),
+            *files[files.len() - 1],
             "https://source/abcdef/vctools/crt/vcruntime/src/string/i386/memcmp.c"
         );
     }
@@ -0,0 +1,116 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use symbolic::debuginfo::pe::PeObject;
+
+// jmp rel32
+const JMP: u8 = 0xe9;
+pub const JMP_LEN: u32 = 5;
+
+// mov edi, edi: the two-byte nop at the start of the hot-patchable x86 functions
+const MOV_EDI_EDI: [u8; 2] = [0x8b, 0xff];
+// The padding before them is overwritten by a long jump when the function is patched
+pub const HOTPATCH_LEN: u32 = 5;
+
+/// The code of an executable section
+pub struct CodeSection<'a> {
+    pub rva: u32,
+    pub data: &'a [u8],
+}
+
+impl CodeSection<'_> {
+    pub fn end(&self) -> u32 {
+        self.rva + self.data.len() as u32
+    }
+
+    fn get(&self, rva: u32, len: u32) -> Option<&[u8]> {
+        let start = rva.checked_sub(self.rva)? as usize;
+        self.data.get(start..start.checked_add(len as usize)?)
+    }
+
+    /// Get the target of the `jmp rel32` at the given rva
+    pub fn get_jump_target(&self, rva: u32) -> Option<u32> {
+        match self.get(rva, JMP_LEN)? {
+            [JMP, rel @ ..] => {
+                let rel = i32::from_le_bytes([rel[0], rel[1], rel[2], rel[3]]);
+                (rva + JMP_LEN).checked_add_signed(rel)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the rva of the first byte in `start..end` which isn't a padding byte (int3 or nop)
+    pub fn skip_padding(&self, start: u32, end: u32) -> u32 {
+        (start..end)
+            .find(|rva| !matches!(self.get(*rva, 1), Some([0xcc]) | Some([0x90])))
+            .unwrap_or(end)
+    }
+
+    /// Check if the function at the given rva is hot-patchable: it starts with
+    /// `mov edi, edi` and there are 5 bytes of padding (int3 or nop) before it.
+    pub fn is_hotpatchable(&self, rva: u32) -> bool {
+        let padding = rva
+            .checked_sub(HOTPATCH_LEN)
+            .and_then(|start| self.get(start, HOTPATCH_LEN));
+        match (padding, self.get(rva, 2)) {
+            (Some(padding), Some(start)) => {
+                start == MOV_EDI_EDI
+                    && (padding.iter().all(|b| *b == 0xcc) || padding.iter().all(|b| *b == 0x90))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Get the executable sections of a PE file
+pub fn get_code_sections<'a>(pe: &PeObject<'a>) -> Vec<CodeSection<'a>> {
+    const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+
+    let data = pe.data();
+    pe.sections()
+        .iter()
+        .filter(|s| s.characteristics & IMAGE_SCN_MEM_EXECUTE != 0)
+        .filter_map(|s| {
+            let start = s.pointer_to_raw_data as usize;
+            let len = s.size_of_raw_data.min(s.virtual_size) as usize;
+            data.get(start..start.checked_add(len)?)
+                .map(|data| CodeSection {
+                    rva: s.virtual_address,
+                    data,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_section() {
+        let data = [
+            // An incremental linking thunk: jmp 0x1010
+            0xe9, 0x0b, 0x00, 0x00, 0x00, //
+            // Hot-patch padding and mov edi, edi
+            0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x8b, 0xff, 0x55, //
+            // Alignment padding and push ebp
+            0xcc, 0xcc, 0xcc, 0x55,
+        ];
+        let code = CodeSection {
+            rva: 0x1000,
+            data: &data,
+        };
+
+        assert_eq!(code.end(), 0x1011);
+        assert_eq!(code.get_jump_target(0x1000), Some(0x1010));
+        assert_eq!(code.get_jump_target(0x1005), None);
+        assert_eq!(code.get_jump_target(0x100e), None);
+        assert_eq!(code.skip_padding(0x1005, 0x1011), 0x100a);
+        assert_eq!(code.skip_padding(0x100d, 0x1010), 0x1010);
+        assert!(code.is_hotpatchable(0x100a));
+        assert!(!code.is_hotpatchable(0x1010));
+        assert!(!code.is_hotpatchable(0x1001));
+    }
+}
@@ -0,0 +1,323 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use symbolic::debuginfo::pdb::pdb::{
+    AddressMap, FallibleIterator, ImageSectionHeader, Symbol, SymbolData,
+};
+use symbolic::debuginfo::pdb::PdbObject;
+
+use hashbrown::HashMap;
+
+use super::params::get_type_size;
+use crate::common::{self, InputEncoding};
+use crate::symbol::{DataSymbol, Label};
+
+/// Call `f` on each symbol of the global symbol stream and of the module streams.
+///
+/// The functions and the publics are collected by symbolic, this is used for the
+/// symbols it doesn't expose.
+pub(super) fn for_each_symbol<F>(pdb: &PdbObject, mut f: F) -> common::Result<()>
+where
+    F: FnMut(&SymbolData, &AddressMap),
+{
+    for_each_raw_symbol(pdb, |symbol, address_map| {
+        if let Ok(data) = symbol.parse() {
+            f(&data, address_map);
+        }
+    })
+}
+
+/// Same as `for_each_symbol` but `f` gets the unparsed symbols, which is
+/// required for the ones unknown to the pdb crate.
+pub(super) fn for_each_raw_symbol<F>(pdb: &PdbObject, mut f: F) -> common::Result<()>
+where
+    F: FnMut(&Symbol, &AddressMap),
+{
+    let mut pdb = pdb.inner().write();
+    let address_map = pdb.address_map()?;
+
+    let globals = pdb.global_symbols()?;
+    let mut symbols = globals.iter();
+    while let Some(symbol) = symbols.next()? {
+        f(&symbol, &address_map);
+    }
+
+    let dbi = pdb.debug_information()?;
+    let mut modules = dbi.modules()?;
+    while let Some(module) = modules.next()? {
+        let info = match pdb.module_info(&module)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let mut symbols = info.symbols()?;
+        while let Some(symbol) = symbols.next()? {
+            f(&symbol, &address_map);
+        }
+    }
+
+    Ok(())
+}
+
+// The compile flags aren't exposed by the pdb crate for the languages it doesn't know
+const S_OBJNAME: u16 = 0x1101;
+pub(super) const S_COMPILE2: u16 = 0x1116;
+pub(super) const S_COMPILE3: u16 = 0x113c;
+
+/// The name of a CodeView language (CV_CFL_LANG), the same as the symbolic one when
+/// it exists, nothing for the tools (linker, resource converter, ...)
+fn get_language_name(language: u8) -> Option<&'static str> {
+    Some(match language {
+        0x00 => "c",
+        0x01 => "cpp",
+        0x02 => "fortran",
+        0x03 => "masm",
+        0x04 => "pascal",
+        0x05 => "basic",
+        0x06 => "cobol",
+        0x0a => "csharp",
+        0x0b => "visualbasic",
+        0x0c => "ilasm",
+        0x0d => "java",
+        0x0e => "jscript",
+        0x0f => "msil",
+        0x10 => "hlsl",
+        0x11 => "objc",
+        0x12 => "objcpp",
+        0x13 => "swift",
+        0x15 => "rust",
+        0x16 => "go",
+        0x44 => "d",
+        _ => return None,
+    })
+}
+
+/// Get the source languages of the procedures from the compile flags (S_COMPILE2
+/// or S_COMPILE3) of their module, by rva.
+pub fn get_languages(pdb: &PdbObject) -> common::Result<HashMap<u32, &'static str>> {
+    let mut languages = HashMap::new();
+    let mut language = None;
+    for_each_raw_symbol(pdb, |symbol, address_map| match symbol.raw_kind() {
+        // A module starts with its object name
+        S_OBJNAME => language = None,
+        S_COMPILE2 | S_COMPILE3 => {
+            language = symbol
+                .raw_bytes()
+                .get(2)
+                .and_then(|l| get_language_name(*l));
+        }
+        _ => {
+            if let (Some(language), Ok(SymbolData::Procedure(proc))) = (language, symbol.parse()) {
+                if let Some(rva) = proc.offset.to_rva(address_map) {
+                    languages.insert(rva.0, language);
+                }
+            }
+        }
+    })?;
+
+    Ok(languages)
+}
+
+/// Get the names of the procedures which aren't valid UTF-8, by rva: the lossy
+/// name given by symbolic and the name decoded with the input encoding.
+pub fn get_undecoded_procedure_names(
+    pdb: &PdbObject,
+    encoding: InputEncoding,
+) -> common::Result<HashMap<u32, (String, String)>> {
+    let mut names = HashMap::new();
+    for_each_symbol(pdb, |symbol, address_map| {
+        if let SymbolData::Procedure(proc) = symbol {
+            let name = proc.name.as_bytes();
+            if std::str::from_utf8(name).is_ok() {
+                return;
+            }
+            if let Some(rva) = proc.offset.to_rva(address_map) {
+                let lossy = String::from_utf8_lossy(name).into_owned();
+                names.insert(rva.0, (lossy, encoding.decode(name).into_owned()));
+            }
+        }
+    })?;
+
+    Ok(names)
+}
+
+/// Get the code labels (S_LABEL32), they're mainly used in assembly code.
+/// The `$LN` ones are generated by the compiler for each line so they're skipped.
+pub fn get_labels(pdb: &PdbObject, encoding: InputEncoding) -> common::Result<Vec<Label>> {
+    let mut labels = Vec::new();
+    for_each_symbol(pdb, |data, address_map| {
+        if let SymbolData::Label(label) = data {
+            if label.name.as_bytes().starts_with(b"$LN") {
+                return;
+            }
+            if let Some(rva) = label.offset.to_rva(address_map) {
+                labels.push(Label {
+                    rva: rva.0,
+                    name: encoding.decode(label.name.as_bytes()).into_owned(),
+                });
+            }
+        }
+    })?;
+
+    Ok(labels)
+}
+
+/// Get the global and static variables (S_GDATA32 and S_LDATA32) and the
+/// thread-local ones (S_GTHREAD32 and S_LTHREAD32), the address of the latter
+/// is in the TLS template.
+///
+/// The size of a variable is the size of its type. When it's unknown (e.g. a
+/// forward reference to a class), it's the distance to the next variable or to
+/// the end of the section.
+pub fn get_data_symbols(
+    pdb: &PdbObject,
+    encoding: InputEncoding,
+) -> common::Result<Vec<DataSymbol>> {
+    let mut data = Vec::new();
+    for_each_symbol(pdb, |symbol, address_map| {
+        let (offset, name, type_index, is_tls) = match symbol {
+            SymbolData::Data(symbol) => (symbol.offset, symbol.name, symbol.type_index, false),
+            SymbolData::ThreadStorage(symbol) => {
+                (symbol.offset, symbol.name, symbol.type_index, true)
+            }
+            _ => return,
+        };
+        if let Some(rva) = offset.to_rva(address_map) {
+            let name = encoding.decode(name.as_bytes()).into_owned();
+            data.push((
+                type_index,
+                RawDataSymbol {
+                    section: offset.section,
+                    rva: rva.0,
+                    name,
+                    size: 0,
+                    is_tls,
+                },
+            ));
+        }
+    })?;
+
+    let mut pdb = pdb.inner().write();
+    let type_info = pdb.type_information()?;
+    let mut finder = type_info.finder();
+    let mut iter = type_info.iter();
+    while iter.next()?.is_some() {
+        finder.update(&iter);
+    }
+    let mut data: Vec<_> = data
+        .into_iter()
+        .map(|(type_index, d)| RawDataSymbol {
+            size: get_type_size(&finder, type_index, 0),
+            ..d
+        })
+        .collect();
+
+    // The data in the code sections are mainly jump tables
+    let sections = pdb.sections()?.unwrap_or_default();
+    data.retain(|d| {
+        !d.name.is_empty()
+            && !sections
+                .get((d.section as usize).wrapping_sub(1))
+                .is_some_and(|s| s.characteristics.execute())
+    });
+
+    Ok(get_sized_data_symbols(data, &sections))
+}
+
+struct RawDataSymbol {
+    section: u16,
+    rva: u32,
+    name: String,
+    /// The size of the type, 0 when it's unknown
+    size: u32,
+    is_tls: bool,
+}
+
+fn get_sized_data_symbols(
+    mut data: Vec<RawDataSymbol>,
+    sections: &[ImageSectionHeader],
+) -> Vec<DataSymbol> {
+    // The same variable can be in several modules, the one with a known size is kept
+    data.sort_by_key(|d| (d.rva, d.size == 0));
+    data.dedup_by_key(|d| d.rva);
+
+    let ends: Vec<_> = data
+        .iter()
+        .enumerate()
+        .map(|(i, d)| match data.get(i + 1) {
+            Some(next) if next.section == d.section => Some(next.rva),
+            _ => sections
+                .get((d.section as usize).wrapping_sub(1))
+                .map(|s| s.virtual_address.saturating_add(s.virtual_size))
+                .filter(|end| *end > d.rva),
+        })
+        .collect();
+
+    data.into_iter()
+        .zip(ends)
+        .map(|(d, end)| DataSymbol {
+            len: match d.size {
+                0 => end.map_or(0, |end| end - d.rva),
+                size => size,
+            },
+            name: d.name,
+            rva: d.rva,
+            is_tls: d.is_tls,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sized_data_symbols() {
+        let text = ImageSectionHeader {
+            virtual_address: 0x1000,
+            virtual_size: 0x100,
+            ..Default::default()
+        };
+        let data = ImageSectionHeader {
+            virtual_address: 0x2000,
+            virtual_size: 0x100,
+            ..Default::default()
+        };
+
+        let syms = [
+            (2, 0x2010, "b", 0),
+            (1, 0x1010, "a", 0),
+            (2, 0x2000, "c", 0),
+            (2, 0x2010, "d", 0),
+            (3, 0x3000, "e", 0),
+            (2, 0x2080, "f", 0),
+            (2, 0x2080, "g", 8),
+        ];
+        let syms = syms
+            .iter()
+            .map(|(section, rva, name, size)| RawDataSymbol {
+                section: *section,
+                rva: *rva,
+                name: name.to_string(),
+                size: *size,
+                is_tls: false,
+            })
+            .collect();
+        let syms: Vec<_> = get_sized_data_symbols(syms, &[text, data])
+            .into_iter()
+            .map(|s| (s.rva, s.len, s.name))
+            .collect();
+
+        assert_eq!(
+            syms,
+            vec![
+                (0x1010, 0xf0, "a".to_string()),
+                (0x2000, 0x10, "c".to_string()),
+                (0x2010, 0x70, "b".to_string()),
+                (0x2080, 8, "g".to_string()),
+                (0x3000, 0, "e".to_string()),
+            ]
+        );
+    }
+}
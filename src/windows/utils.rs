@@ -3,22 +3,41 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::path::Path;
-use symbolic::{common::DebugId, debuginfo::pe::PeObject};
+use hashbrown::HashMap;
+use log::warn;
+use std::path::{Path, PathBuf};
+use symbolic::common::{ByteView, DebugId};
+use symbolic::debuginfo::pdb::{pdb, PdbObject};
+use symbolic::debuginfo::pe::PeObject;
+use symbolic::debuginfo::{peek, FileFormat};
 
 #[cfg(feature = "http")]
 use crate::cache::{self, SymbolServer};
+use crate::common;
+use crate::mapping::{self, SrcSrvFiles};
 use crate::utils;
 
-#[cfg(feature = "http")]
-fn try_to_find_pdb(path: &Path, pdb_filename: &str) -> Option<Vec<u8>> {
+/// Get the source files of the srcsrv stream written by the source indexing
+/// tools, none when the PDB isn't indexed.
+pub(crate) fn get_srcsrv(pdb: &PdbObject) -> common::Result<Option<SrcSrvFiles>> {
+    let mut pdb = pdb.inner().write();
+    let stream = match pdb.named_stream(b"srcsrv") {
+        Ok(stream) => stream,
+        Err(pdb::Error::StreamNameNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let data = String::from_utf8_lossy(stream.as_slice());
+    Ok(Some(mapping::parse_srcsrv(&data)))
+}
+
+fn try_to_find_pdb(path: &Path, pdb_filename: &str) -> Option<PathBuf> {
     // Just check that the file is in the same directory as the PE one
     let pdb = path.with_file_name(pdb_filename);
     let pdb_cab = pdb.with_extension("pd_");
 
     for pdb in vec![pdb, pdb_cab].into_iter() {
         if pdb.is_file() {
-            return Some(utils::read_file(pdb));
+            return Some(pdb);
         }
     }
 
@@ -26,14 +45,14 @@ fn try_to_find_pdb(path: &Path, pdb_filename: &str) -> Option<Vec<u8>> {
     let mut pdb = std::env::current_dir().expect("Unable to get the current working directory");
     pdb.set_file_name(pdb_filename);
     if pdb.is_file() {
-        Some(utils::read_file(pdb))
+        Some(pdb)
     } else {
         None
     }
 }
 
-#[cfg(feature = "http")]
-fn os_specific_try_to_find_pdb(path: &Path, pdb_filename: &str) -> (Option<Vec<u8>>, String) {
+/// Get the path of the pdb file of a PE file on the disk if any, and the pdb name
+pub(crate) fn get_pdb_path(path: &Path, pdb_filename: &str) -> (Option<PathBuf>, String) {
     // We may have gotten either an OS native path, or a Windows path.
     // On Windows, they're both the same. On Unix, they are different, and in that case,
     // we change backslashes to forward slashes for `file_name()` to do its job.
@@ -44,7 +63,7 @@ fn os_specific_try_to_find_pdb(path: &Path, pdb_filename: &str) -> (Option<Vec<u
     if let Some(file_name) = pdb_path.file_name() {
         let pdb_name = file_name.to_str().unwrap().to_string();
         if pdb_path.is_file() {
-            (Some(utils::read_file(pdb_path)), pdb_name)
+            (Some(pdb_path.to_path_buf()), pdb_name)
         } else {
             (try_to_find_pdb(path, &pdb_name), pdb_name)
         }
@@ -53,33 +72,51 @@ fn os_specific_try_to_find_pdb(path: &Path, pdb_filename: &str) -> (Option<Vec<u
     }
 }
 
+/// Get the PE, the pdb file if it's on the disk, and the pdb name
+fn find_local_pdb<'a>(
+    path: &Path,
+    buf: &'a [u8],
+) -> Option<(PeObject<'a>, Option<ByteView<'static>>, String)> {
+    // A corrupted PE file is reported when it's dumped without a pdb
+    let pe = PeObject::parse(buf).ok()?;
+    let pdb_filename = pe.debug_file_name()?.into_owned();
+    let (pdb, pdb_name) = get_pdb_path(path, &pdb_filename);
+    let pdb = pdb.and_then(|pdb| utils::read_file(pdb).ok());
+    if pdb_name.is_empty() {
+        log::warn!("Invalid pdb filename in PE file: \"{}\"", pdb_filename);
+        None
+    } else {
+        Some((pe, pdb, pdb_name))
+    }
+}
+
 #[cfg(feature = "http")]
 pub fn get_pe_pdb_buf<'a>(
     path: &Path,
     buf: &'a [u8],
     symbol_server: Option<&Vec<SymbolServer>>,
-) -> Option<(PeObject<'a>, Vec<u8>, String)> {
-    let pe = PeObject::parse(buf)
-        .unwrap_or_else(|_| panic!("Unable to parse the PE file {}", path.to_str().unwrap()));
-    if let Some(pdb_filename) = pe.debug_file_name() {
-        let pdb_filename = pdb_filename.into_owned();
-        let (pdb, pdb_name) = os_specific_try_to_find_pdb(path, &pdb_filename);
-        if pdb_name.is_empty() {
-            log::warn!("Invalid pdb filename in PE file: \"{}\"", pdb_filename);
-            None
-        } else if let Some(pdb_buf) = pdb {
-            Some((pe, pdb_buf, pdb_name))
-        } else {
-            // Not here so try symbol server (or cache)
-            let debug_id = pe.debug_id().breakpad().to_string();
-            let (pdb, pdb_name) = cache::search_file(pdb_name, &debug_id, symbol_server);
-            pdb.map(|pdb_buf| (pe, pdb_buf, pdb_name))
-        }
+) -> Option<(PeObject<'a>, ByteView<'static>, String)> {
+    let (pe, pdb, pdb_name) = find_local_pdb(path, buf)?;
+    if let Some(pdb_buf) = pdb {
+        Some((pe, pdb_buf, pdb_name))
     } else {
-        None
+        // Not here so try symbol server (or cache)
+        let debug_id = pe.debug_id().breakpad().to_string();
+        let (pdb, pdb_name) = cache::search_file(pdb_name, &debug_id, symbol_server);
+        pdb.map(|pdb_buf| (pe, pdb_buf, pdb_name))
     }
 }
 
+/// Without the http feature, the pdb file is only searched on the disk
+#[cfg(not(feature = "http"))]
+pub fn get_pe_pdb_buf<'a>(
+    path: &Path,
+    buf: &'a [u8],
+) -> Option<(PeObject<'a>, ByteView<'static>, String)> {
+    let (pe, pdb, pdb_name) = find_local_pdb(path, buf)?;
+    pdb.map(|pdb_buf| (pe, pdb_buf, pdb_name))
+}
+
 fn fix_extension(ext: &str) -> &str {
     match ext {
         "dl_" => "dll",
@@ -90,12 +127,18 @@ fn fix_extension(ext: &str) -> &str {
 
 /// Tries to find the PE object for a PDB file, by looking for dll/exe files
 /// in the same directory with a matching debug ID.
-pub(crate) fn find_pe_for_pdb(path: &Path, pdb_debug_id: &DebugId) -> Option<(String, Vec<u8>)> {
+pub(crate) fn find_pe_for_pdb(
+    path: &Path,
+    pdb_debug_id: &DebugId,
+) -> Option<(String, ByteView<'static>)> {
     let mut path = path.to_path_buf();
     for ext in vec!["dll", "dl_", "exe", "ex_"].drain(..) {
         path.set_extension(ext);
         if path.is_file() {
-            let buf = utils::read_file(&path);
+            let buf = match utils::read_file(&path) {
+                Ok(buf) => buf,
+                _ => continue,
+            };
             if let Ok(pe) = PeObject::parse(&buf) {
                 if ext.ends_with('_') {
                     path.set_extension(fix_extension(ext));
@@ -109,3 +152,96 @@ pub(crate) fn find_pe_for_pdb(path: &Path, pdb_debug_id: &DebugId) -> Option<(St
     }
     None
 }
+
+/// A PE or a pdb file of a batch
+struct WinFile<'a> {
+    file: &'a str,
+    debug_id: DebugId,
+    /// The lowercase name of the pdb file, the one of the debug directory for a PE file
+    pdb_name: Option<String>,
+    stem: String,
+}
+
+impl<'a> WinFile<'a> {
+    fn new(file: &'a str, debug_id: DebugId, pdb_name: Option<&str>) -> Self {
+        let lowercase = |name: &str| name.rsplit(['/', '\\']).next().unwrap().to_lowercase();
+        let stem = Path::new(file)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        Self {
+            file,
+            debug_id,
+            pdb_name: pdb_name.map(lowercase),
+            stem,
+        }
+    }
+}
+
+/// Pair the PE files and the pdb files of a batch, so each pdb file is dumped
+/// with its PE file wherever they are. They're paired with their debug ids, else
+/// with their names when only the age differs or when the PE file has no debug
+/// directory. The map goes from the pdb files to their PE file.
+pub fn pair_pe_pdb<'a>(files: &[&'a str]) -> HashMap<&'a str, &'a str> {
+    let mut pes = Vec::new();
+    let mut pdbs = Vec::new();
+    for file in files {
+        let path = Path::new(file);
+        if !path.is_file() {
+            continue;
+        }
+        let buf = match utils::read_file(path) {
+            Ok(buf) => buf,
+            _ => continue,
+        };
+        match peek(&buf, false) {
+            FileFormat::Pe => {
+                if let Ok(pe) = PeObject::parse(&buf) {
+                    let pdb_name = pe.debug_file_name();
+                    pes.push(WinFile::new(file, pe.debug_id(), pdb_name.as_deref()));
+                }
+            }
+            FileFormat::Pdb => {
+                if let Ok(pdb) = PdbObject::parse(&buf) {
+                    let pdb_name = utils::get_filename(path);
+                    pdbs.push(WinFile::new(file, pdb.debug_id(), Some(&pdb_name)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // From the most to the least reliable
+    let matchers: [fn(&WinFile, &WinFile) -> bool; 3] = [
+        |pe, pdb| !pe.debug_id.is_nil() && pe.debug_id == pdb.debug_id,
+        |pe, pdb| {
+            !pe.debug_id.is_nil()
+                && pe.debug_id.uuid() == pdb.debug_id.uuid()
+                && pe.pdb_name == pdb.pdb_name
+        },
+        |pe, pdb| pe.debug_id.is_nil() && pe.stem == pdb.stem,
+    ];
+
+    let mut pairs = HashMap::new();
+    for (i, matches) in matchers.iter().enumerate() {
+        pdbs.retain(|pdb| {
+            let pos = match pes.iter().position(|pe| matches(pe, pdb)) {
+                Some(pos) => pos,
+                None => return true,
+            };
+            let pe = pes.swap_remove(pos);
+            if i != 0 {
+                warn!(
+                    "{} and {} are paired by name: their debug ids are {} and {}",
+                    pe.file,
+                    pdb.file,
+                    pe.debug_id.breakpad(),
+                    pdb.debug_id.breakpad()
+                );
+            }
+            pairs.insert(pdb.file, pe.file);
+            false
+        });
+    }
+    pairs
+}
@@ -0,0 +1,10 @@
+#include <stdio.h>
+
+__attribute__((noinline)) int nolines(int x) {
+    return x * 3 + 1;
+}
+
+int main(int argc, char **argv) {
+    printf("%d\n", nolines(argc));
+    return 0;
+}
@@ -0,0 +1,45 @@
+	# A function whose declaration is in the supplementary file (DW_FORM_GNU_ref_alt)
+	.text
+	.globl _start
+_start:
+	nop
+	nop
+	ret
+.Lfunction_end:
+
+	.section .debug_abbrev,"",@progbits
+	.uleb128 1, 0x11	# DW_TAG_compile_unit
+	.byte 1
+	.uleb128 0x03, 0x08	# DW_AT_name, DW_FORM_string
+	.uleb128 0x11, 0x01	# DW_AT_low_pc, DW_FORM_addr
+	.uleb128 0x12, 0x06	# DW_AT_high_pc, DW_FORM_data4
+	.byte 0, 0
+	.uleb128 2, 0x2e	# DW_TAG_subprogram
+	.byte 0
+	.uleb128 0x47, 0x1f20	# DW_AT_specification, DW_FORM_GNU_ref_alt
+	.uleb128 0x11, 0x01	# DW_AT_low_pc, DW_FORM_addr
+	.uleb128 0x12, 0x06	# DW_AT_high_pc, DW_FORM_data4
+	.byte 0, 0
+	.byte 0
+
+	.section .debug_info,"",@progbits
+	.long .Lend - .Lstart
+.Lstart:
+	.short 4
+	.long 0
+	.byte 8
+	.uleb128 1
+	.asciz "dwz_main.s"
+	.quad _start
+	.long .Lfunction_end - _start
+	.uleb128 2
+	.long 0x11
+	.quad _start
+	.long .Lfunction_end - _start
+	.byte 0
+.Lend:
+
+	.section .gnu_debugaltlink,"",@progbits
+	.asciz "dwz.sup"
+	.byte 0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x01, 0x23
+	.byte 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67
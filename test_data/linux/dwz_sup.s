@@ -0,0 +1,37 @@
+	# The supplementary file: the declaration of dwz::function in a partial unit
+	.section .debug_abbrev,"",@progbits
+	.uleb128 1, 0x3c	# DW_TAG_partial_unit
+	.byte 1
+	.byte 0, 0
+	.uleb128 2, 0x39	# DW_TAG_namespace
+	.byte 1
+	.uleb128 0x03, 0x0e	# DW_AT_name, DW_FORM_strp
+	.byte 0, 0
+	.uleb128 3, 0x2e	# DW_TAG_subprogram
+	.byte 0
+	.uleb128 0x03, 0x0e	# DW_AT_name, DW_FORM_strp
+	.uleb128 0x3c, 0x19	# DW_AT_declaration, DW_FORM_flag_present
+	.byte 0, 0
+	.byte 0
+
+	.section .debug_info,"",@progbits
+	.long .Lend - .Lstart
+.Lstart:
+	.short 4
+	.long 0
+	.byte 8
+	.uleb128 1
+	.uleb128 2
+	.long .Lnamespace
+	# At offset 0x11
+	.uleb128 3
+	.long .Lfunction
+	.byte 0
+	.byte 0
+.Lend:
+
+	.section .debug_str,"MS",@progbits,1
+.Lnamespace:
+	.asciz "dwz"
+.Lfunction:
+	.asciz "function"
@@ -0,0 +1,19 @@
+#include <cstdio>
+namespace ns {
+struct B {
+    int v;
+    __attribute__((noinline)) int twice() const;
+};
+
+int B::twice() const
+{
+    std::printf("%d\n", v);
+    return v * 2;
+}
+
+extern "C" __attribute__((noinline)) int c_callme(int x)
+{
+    B b{x};
+    return b.twice() + 1;
+}
+}
@@ -0,0 +1,6 @@
+extern "C" int c_callme(int);
+
+int main(int argc, char ** argv)
+{
+    return c_callme(argc);
+}
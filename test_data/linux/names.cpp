@@ -0,0 +1,19 @@
+namespace outer {
+namespace {
+struct Local {
+    __attribute__((noinline)) static int method(int x) { return x + 1; }
+};
+}
+
+namespace inner {
+extern "C" __attribute__((noinline)) int c_function(int x)
+{
+    return Local::method(x) * 2;
+}
+}
+}
+
+int main(int argc, char ** argv)
+{
+    return outer::inner::c_function(argc);
+}
@@ -0,0 +1,42 @@
+// The sequences shared by several functions are outlined by the LLVM machine
+// outliner (-mllvm -enable-machine-outliner) into OUTLINED_FUNCTION_<N>, they're
+// called with bl or tail-called with b.
+	.text
+
+	.globl	foo
+	.type	foo,@function
+foo:
+	stp	x29, x30, [sp, #-16]!
+	bl	OUTLINED_FUNCTION_0
+	ldp	x29, x30, [sp], #16
+	ret
+	.size	foo, .-foo
+
+	.globl	bar
+	.type	bar,@function
+bar:
+	stp	x29, x30, [sp, #-16]!
+	mov	x0, #1
+	bl	OUTLINED_FUNCTION_0
+	ldp	x29, x30, [sp], #16
+	b	OUTLINED_FUNCTION_1
+	.size	bar, .-bar
+
+	.type	OUTLINED_FUNCTION_0,@function
+OUTLINED_FUNCTION_0:
+	add	x0, x0, #42
+	mul	x0, x0, x0
+	ret
+	.size	OUTLINED_FUNCTION_0, .-OUTLINED_FUNCTION_0
+
+	.type	OUTLINED_FUNCTION_1,@function
+OUTLINED_FUNCTION_1:
+	mov	x1, #2
+	ret
+	.size	OUTLINED_FUNCTION_1, .-OUTLINED_FUNCTION_1
+
+	.type	OUTLINED_FUNCTION_2,@function
+OUTLINED_FUNCTION_2:
+	mov	x2, #3
+	ret
+	.size	OUTLINED_FUNCTION_2, .-OUTLINED_FUNCTION_2
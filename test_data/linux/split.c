@@ -0,0 +1,37 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+__attribute__((cold, noinline)) void fail(const char *msg, int value) {
+    fprintf(stderr, "%s: %d\n", msg, value);
+}
+
+/* The error path is moved to lookup.cold */
+__attribute__((noinline)) int lookup(const int *table, int n, int key) {
+    for (int i = 0; i < n; i++) {
+        if (table[i] == key) {
+            return i;
+        }
+    }
+    fail("missing key", key);
+    fail("table size", n);
+    exit(1);
+}
+
+/* The slow path is outlined to compute.part.0, the fast one is inlined */
+int compute(int x) {
+    if (x < 16) {
+        return x;
+    }
+    int r = 0;
+    for (int i = 0; i < x; i++) {
+        r += i * x;
+        printf("%d %d\n", i, r);
+    }
+    printf("%d\n", r);
+    return r;
+}
+
+int main(int argc, char **argv) {
+    int table[4] = {1, 2, 3, argc};
+    return compute(argc + lookup(table, 4, argc)) + compute(atoi(argv[0]));
+}
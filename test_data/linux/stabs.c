@@ -0,0 +1,10 @@
+static int square(int x)
+{
+    return x * x;
+}
+
+int main(int argc, char **argv)
+{
+    int s = square(argc);
+    return s + 1;
+}
@@ -0,0 +1,8 @@
+thread_local int counter = 42;
+thread_local char buffer[64];
+int global = 1;
+
+int main() {
+    buffer[0] = 'a';
+    return counter + global;
+}
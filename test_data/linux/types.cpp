@@ -0,0 +1,27 @@
+namespace geometry {
+class Point {
+public:
+    Point(int x, int y) : x(x), y(y) {}
+    __attribute__((noinline)) int norm() const;
+    __attribute__((noinline)) static Point origin();
+
+private:
+    int x;
+    int y;
+};
+
+int Point::norm() const
+{
+    return x * x + y * y;
+}
+
+Point Point::origin()
+{
+    return Point(0, 0);
+}
+}
+
+int main(int argc, char ** argv)
+{
+    return geometry::Point(argc, 2).norm() + geometry::Point::origin().norm();
+}
@@ -0,0 +1,39 @@
+; leaf is inlined in mid which is inlined in caller: its DILocations are
+; "inlinedAt" the call in mid, itself "inlinedAt" the call in caller
+
+target triple = "x86_64-apple-macosx10.15.0"
+
+declare void @ext(i32)
+
+define void @first() !dbg !10 {
+  call void @ext(i32 0), !dbg !20
+  ret void, !dbg !20
+}
+
+define void @caller() !dbg !11 {
+  call void @ext(i32 1), !dbg !21
+  call void @ext(i32 2), !dbg !22
+  call void @ext(i32 3), !dbg !23
+  ret void, !dbg !24
+}
+
+!llvm.dbg.cu = !{!0}
+!llvm.module.flags = !{!1, !2}
+
+!0 = distinct !DICompileUnit(language: DW_LANG_C99, file: !3, producer: "llc", isOptimized: true, runtimeVersion: 0, emissionKind: FullDebug)
+!1 = !{i32 7, !"Dwarf Version", i32 4}
+!2 = !{i32 2, !"Debug Info Version", i32 3}
+!3 = !DIFile(filename: "inlines.c", directory: "/src")
+!4 = !DISubroutineType(types: !5)
+!5 = !{null}
+!10 = distinct !DISubprogram(name: "first", scope: !3, file: !3, line: 1, type: !4, scopeLine: 1, spFlags: DISPFlagDefinition | DISPFlagOptimized, unit: !0)
+!11 = distinct !DISubprogram(name: "caller", scope: !3, file: !3, line: 12, type: !4, scopeLine: 12, spFlags: DISPFlagDefinition | DISPFlagOptimized, unit: !0)
+!12 = distinct !DISubprogram(name: "leaf", scope: !3, file: !3, line: 4, type: !4, scopeLine: 4, spFlags: DISPFlagDefinition | DISPFlagOptimized, unit: !0)
+!13 = distinct !DISubprogram(name: "mid", scope: !3, file: !3, line: 8, type: !4, scopeLine: 8, spFlags: DISPFlagDefinition | DISPFlagOptimized, unit: !0)
+!20 = !DILocation(line: 2, column: 3, scope: !10)
+!21 = !DILocation(line: 5, column: 3, scope: !12, inlinedAt: !31)
+!22 = !DILocation(line: 10, column: 3, scope: !13, inlinedAt: !30)
+!23 = !DILocation(line: 14, column: 3, scope: !11)
+!24 = !DILocation(line: 15, column: 1, scope: !11)
+!30 = distinct !DILocation(line: 13, column: 3, scope: !11)
+!31 = distinct !DILocation(line: 9, column: 3, scope: !13, inlinedAt: !30)